@@ -1,128 +1,752 @@
-use std::io::{BufReader, BufWriter};
-use std::net::{TcpListener, TcpStream};
+use std::collections::HashSet;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
-use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use anyhow::{Context, bail};
 use dap::base_message::Sendable;
 use dap::errors::ServerError;
-use dap::prelude::{Event, Request, ResponseBody, Server};
+use dap::prelude::{Command, Event, Request, ResponseBody, Server};
+use dap::reverse_requests::{ReverseCommand, ReverseRequest};
 use dap::server::{ServerReader, ServerWriter};
-use tracing::trace;
+use dap::types::Message;
+use interprocess::local_socket::prelude::*;
+use interprocess::local_socket::{
+    GenericFilePath, GenericNamespaced, Listener as LocalSocketListener, ListenerNonblockingMode,
+    ListenerOptions,
+};
+use serde_json::{Value, json};
+use tracing::{trace, warn};
+
+use crate::connection::auth::{AuthToken, FirstRequestToken, TokenReadingStream};
+use crate::connection::protocol_trace::ProtocolTrace;
+
+mod auth;
+pub mod in_memory;
+pub mod protocol_trace;
+
+/// Version of the announcement format and of the custom requests supported by the debugger.
+/// Bumped on breaking changes, so that tools can detect incompatible debugger versions.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// How often the listener is checked for a new client.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the writer thread checks whether the session is closed, once the outbound
+/// channel is empty, and the I/O threads are checked whether they finished.
+const IO_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Time the I/O threads of a closed session are given to finish, after which they are
+/// left behind, e.g. the reader thread of a client that keeps the connection open.
+const IO_THREADS_JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Channel the debugger waits for a client on.
+pub enum Transport {
+    /// TCP socket bound to `address`, on a port assigned by the OS if `port` is `None`.
+    /// Addresses other than the loopback one expose the debugger to other machines,
+    /// so they should be used together with [`DebugServer::with_auth_token`].
+    Tcp { address: IpAddr, port: Option<u16> },
+    /// Unix domain socket at the given path, whose access can be restricted with file permissions.
+    UnixSocket(PathBuf),
+    /// Named pipe on Windows, or a socket in the abstract namespace on Linux.
+    NamedPipe(String),
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::Tcp { address: Ipv4Addr::LOCALHOST.into(), port: None }
+    }
+}
+
+/// Channel that clients connect to the debugger through, e.g. a socket listener bound
+/// for a [`Transport`] by [`DebugServer::bind`]. Custom implementations are used with
+/// [`DebugServer::with_listener`], e.g. [`in_memory::InMemoryListener`], which lets tests drive the whole
+/// request handling without sockets or an editor.
+pub trait ClientListener: Send + Sync {
+    /// Waits for a client until `deadline`, or forever if it is `None`.
+    fn accept(&self, deadline: Option<Instant>) -> Result<Option<ClientStreams>>;
+
+    /// Port that clients connect to, if the listener has one.
+    fn port(&self) -> Option<u16> {
+        None
+    }
+}
+
+/// Streams of a connected client: requests are read from `input`, while responses, events
+/// and reverse requests are written to `output`, both framed as required by DAP.
+pub struct ClientStreams {
+    pub input: Box<dyn Read + Send>,
+    pub output: Box<dyn Write + Send>,
+}
+
+/// Listener shared by debuggers of programs executed in parallel, e.g. tests run by snforge,
+/// so that each of them can be debugged by its own client without a port per program.
+/// Each client that connects is handed over to one of the debuggers waiting for a client.
+#[derive(Clone)]
+pub struct DebugServer {
+    listener: Arc<dyn ClientListener>,
+    auth_token: Option<Arc<AuthToken>>,
+    /// How long to wait for a client before giving up. Forever if `None`.
+    accept_timeout: Option<Duration>,
+    /// How many more times to wait for [`Self::accept_timeout`] before giving up.
+    accept_retries: u32,
+    protocol_trace: Option<Arc<ProtocolTrace>>,
+}
+
+impl DebugServer {
+    pub fn bind(transport: &Transport) -> Result<Self> {
+        Ok(Self::with_listener(Listener::bind(transport)?))
+    }
+
+    /// Serves clients of a custom listener instead of one bound for a [`Transport`].
+    pub fn with_listener(listener: impl ClientListener + 'static) -> Self {
+        Self {
+            listener: Arc::new(listener),
+            auth_token: None,
+            accept_timeout: None,
+            accept_retries: 0,
+            protocol_trace: None,
+        }
+    }
+
+    /// Fails instead of waiting forever if no client connects within `timeout`,
+    /// e.g. when a test run with debugging enabled in CI is never attached to.
+    #[must_use]
+    pub fn with_accept_timeout(mut self, timeout: Duration) -> Self {
+        self.accept_timeout = Some(timeout);
+        self
+    }
+
+    /// Waits for a client `retries` more times after [`Self::with_accept_timeout`] elapses,
+    /// warning each time that no client connected yet.
+    #[must_use]
+    pub fn with_accept_retries(mut self, retries: u32) -> Self {
+        self.accept_retries = retries;
+        self
+    }
+
+    /// Port assigned to the TCP listener, `None` for other transports.
+    pub fn port(&self) -> Option<u16> {
+        self.listener.port()
+    }
+
+    /// Requires clients to send `auth_token` in the `authToken` field of the arguments of their
+    /// first request, usually `initialize`, or be disconnected. The token is used up by the first
+    /// client that sends it, so that it cannot be replayed once it leaked.
+    #[must_use]
+    pub fn with_auth_token(mut self, auth_token: String) -> Self {
+        self.auth_token = Some(Arc::new(AuthToken::new(auth_token, false)));
+        self
+    }
+
+    /// Like [`Self::with_auth_token`], but lets any number of clients authenticate with the token,
+    /// e.g. the clients of the programs sharing the listener, or the next client of an attached
+    /// debuggee once the current one disconnects.
+    #[must_use]
+    pub fn with_reusable_auth_token(mut self, auth_token: String) -> Self {
+        self.auth_token = Some(Arc::new(AuthToken::new(auth_token, true)));
+        self
+    }
+
+    /// Captures every message exchanged with the clients to the file at `path`, see
+    /// [`protocol_trace`]. Auth tokens are left out.
+    pub fn with_protocol_trace(mut self, path: &Path) -> Result<Self> {
+        self.protocol_trace = Some(Arc::new(ProtocolTrace::create(path)?));
+        Ok(self)
+    }
+
+    fn accept(&self) -> Result<ClientSession> {
+        // Without a timeout, accepting returns only once a client connects.
+        let timeout_secs = self.accept_timeout.unwrap_or_default().as_secs_f64();
+        let attempts = self.accept_retries + 1;
+        for attempt in 1..=attempts {
+            let deadline = self.accept_timeout.map(|timeout| Instant::now() + timeout);
+            if let Some(mut streams) = self.listener.accept(deadline)? {
+                if let Some(protocol_trace) = &self.protocol_trace {
+                    streams = protocol_trace.capture(streams);
+                }
+                let first_request_token = FirstRequestToken::default();
+                let input =
+                    TokenReadingStream::new(streams.input, Arc::clone(&first_request_token));
+                return Ok(ClientSession::new(
+                    input,
+                    streams.output,
+                    first_request_token,
+                    self.auth_token.is_none(),
+                ));
+            }
+            if attempt < attempts {
+                warn!(
+                    "No client connected within {timeout_secs}s, waiting again ({attempt}/{attempts})"
+                );
+            }
+        }
+        bail!(
+            "No client connected to the debugger within {}s. \
+             Attach a client or run the program without debugging",
+            timeout_secs * f64::from(attempts)
+        )
+    }
+}
+
+/// Sends events to the current client from other threads.
+pub struct EventSender(mpsc::Sender<Sendable>);
+
+impl EventSender {
+    pub fn send(&self, event: Event) -> Result<()> {
+        self.0.send(Sendable::Event(event)).context("Sending event to outbound channel failed")
+    }
+}
+
+/// Called with each event sent to the client, e.g. by tools embedding the debugger
+/// to mirror stops in their own output.
+pub type EventListener = Box<dyn Fn(&Event) + Send>;
 
 pub struct Connection {
+    /// `None` once the client disconnected, until the next one connects.
+    session: Option<ClientSession>,
+    /// Kept open after the first client connects, so that the next one can connect
+    /// once the current one is gone. May be shared with other debuggers.
+    server: DebugServer,
+    event_listeners: Vec<EventListener>,
+}
+
+/// Connection with a single client.
+struct ClientSession {
     inbound_rx: mpsc::Receiver<Request>,
     outbound_tx: mpsc::Sender<Sendable>,
+    interrupts: Arc<Interrupts>,
+    io_threads: IoThreads,
+    first_request_token: FirstRequestToken,
+}
+
+/// Signals from the reader thread to the debugger, checked at each instruction.
+///
+/// Pause and terminate requests are answered by the reader thread as soon as they arrive while
+/// the VM runs, rather than once the debugger gets to the next instruction, which may take long,
+/// e.g. in a hint waiting for the forked network. The debugger acts on them at that instruction.
+#[derive(Default)]
+struct Interrupts {
+    /// Set once the client is authenticated, before which every request waits for the debugger,
+    /// which checks the first one, see [`Connection::authenticate`].
+    authenticated: AtomicBool,
+    /// Set while the VM runs, cleared while the debugger handles an instruction.
+    running: AtomicBool,
+    pause: AtomicBool,
+    terminate: AtomicBool,
+    /// Set once a request was forwarded to the debugger, until it polls the requests,
+    /// so that the debugger checks a flag instead of the channel.
+    requests: AtomicBool,
+    /// Set while the debugger must handle every instruction, and whenever a request arrives
+    /// or the execution is interrupted, see [`Connection::needs_attention`].
+    attention: AtomicBool,
+    /// Sequence numbers of requests cancelled by the client before the debugger handled them.
+    cancelled: Mutex<HashSet<i64>>,
+    /// Set once the session is closed, see [`ClientSession::close`].
+    closing: AtomicBool,
+}
 
-    // NOTE: The order of members matters here.
-    // I/O threads must be dropped after the channels.
-    _io_threads: IoThreads,
+/// Non-blocking, so that waiting for a client can time out.
+enum Listener {
+    Tcp(TcpListener),
+    LocalSocket(LocalSocketListener),
 }
 
 impl Connection {
-    pub fn new() -> Result<Self> {
-        let tcp_listener = TcpListener::bind("127.0.0.1:0").map_err(ServerError::IoError)?;
-        let os_assigned_port = tcp_listener.local_addr()?.port();
-        // Print it so that the client can read it.
-        println!("\nDEBUGGER PORT: {os_assigned_port}");
+    pub fn new(server: &DebugServer) -> Result<Self> {
+        let session = server.accept()?;
+        Ok(Self { session: Some(session), server: server.clone(), event_listeners: Vec::new() })
+    }
 
-        let (stream, _client_addr) = tcp_listener.accept().map_err(ServerError::IoError)?;
-        let input = BufReader::new(stream.try_clone()?);
-        let output = BufWriter::new(stream);
+    pub fn add_event_listener(&mut self, listener: EventListener) {
+        self.event_listeners.push(listener);
+    }
 
-        let (server_reader, server_writer) = Server::new(input, output).split_server();
+    /// See [`DebugServer::with_auth_token`].
+    pub fn auth_token(&self) -> Option<&str> {
+        self.server.auth_token.as_ref().map(|auth_token| auth_token.token())
+    }
 
-        let (inbound_tx, inbound_rx) = mpsc::channel::<Request>();
-        let (outbound_tx, outbound_rx) = mpsc::channel::<Sendable>();
+    /// See [`DebugServer::with_reusable_auth_token`].
+    pub fn is_auth_token_reusable(&self) -> bool {
+        self.server.auth_token.as_ref().is_some_and(|auth_token| auth_token.is_reusable())
+    }
 
-        Ok(Self {
-            inbound_rx,
-            outbound_tx,
-            _io_threads: IoThreads::spawn(server_reader, server_writer, inbound_tx, outbound_rx),
-        })
+    /// Checks the auth token the client sent in its first request, if the server requires one.
+    /// Must be called before the first request of each client is handled.
+    pub fn authenticate(&self) -> Result<()> {
+        let session = self.session()?;
+        if let Some(auth_token) = &self.server.auth_token {
+            // The first request is parsed only once it was read, so its token is known by now.
+            let client_token = session.first_request_token.get().and_then(Option::as_deref);
+            auth_token.authenticate(client_token)?;
+        }
+        session.interrupts.authenticated.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Waits for the next client, once the current one disconnected.
+    pub fn accept_next_client(&mut self) -> Result<()> {
+        self.close_session();
+        trace!("Waiting for the next client");
+        self.session = Some(self.server.accept()?);
+        Ok(())
+    }
+
+    /// Delivers all pending messages to the client and disconnects from it.
+    pub fn close_session(&mut self) {
+        if let Some(session) = self.session.take() {
+            session.close();
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.session.is_some()
+    }
+
+    fn session(&self) -> Result<&ClientSession> {
+        self.session.as_ref().context("No client is connected")
     }
 
     pub fn next_request(&self) -> Result<Request> {
-        self.inbound_rx.recv().context("Inbound connection closed")
+        self.session()?.inbound_rx.recv().context("Inbound connection closed")
+    }
+
+    /// Waits for the next request until `deadline`, returns `None` if none arrived by then.
+    pub fn next_request_until(&self, deadline: Instant) -> Result<Option<Request>> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.session()?.inbound_rx.recv_timeout(timeout) {
+            Ok(request) => Ok(Some(request)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => bail!("Inbound connection closed"),
+        }
     }
 
     pub fn try_next_request(&self) -> Result<Option<Request>> {
-        self.inbound_rx.try_recv().map(Some).or_else(|e| match e {
+        self.session()?.inbound_rx.try_recv().map(Some).or_else(|e| match e {
             TryRecvError::Empty => Ok(None),
             TryRecvError::Disconnected => bail!("Inbound connection closed"),
         })
     }
 
+    /// Lets the reader thread answer pause and terminate requests itself while the VM runs,
+    /// see [`Interrupts`]. Other requests wait for the debugger anyway.
+    pub fn set_running(&self, running: bool) {
+        if let Some(session) = &self.session {
+            session.interrupts.running.store(running, Ordering::SeqCst);
+        }
+    }
+
+    /// Whether the client asked to pause the execution while the VM was running,
+    /// since the last call.
+    pub fn take_pause_interrupt(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| session.interrupts.pause.swap(false, Ordering::SeqCst))
+    }
+
+    /// Whether the client asked to terminate the execution while the VM was running,
+    /// since the last call.
+    pub fn take_terminate_interrupt(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| session.interrupts.terminate.swap(false, Ordering::SeqCst))
+    }
+
+    /// Whether the client cancelled the request with the given sequence number. Cancelled requests
+    /// are still handed to the debugger, which must answer them with an error response.
+    pub fn take_cancellation(&self, seq: i64) -> bool {
+        self.session.as_ref().is_some_and(|session| {
+            session.interrupts.cancelled.lock().expect("Cancellations lock poisoned").remove(&seq)
+        })
+    }
+
+    /// Whether any request arrived since the last check, or the client is gone,
+    /// in which case the requests should be polled. Checked only at the steps the debugger
+    /// handles while the VM runs, which an arriving request makes it do, see
+    /// [`Self::needs_attention`]. Once stopped, the debugger waits for requests
+    /// with [`Self::next_request`] instead.
+    pub fn take_request_signal(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| session.interrupts.requests.swap(false, Ordering::SeqCst))
+    }
+
+    /// Whether the debugger must handle the next instruction, rather than let the VM run freely.
+    /// A single flag, so that free-running execution costs next to nothing per instruction.
+    pub fn needs_attention(&self) -> bool {
+        self.session
+            .as_ref()
+            .is_some_and(|session| session.interrupts.attention.load(Ordering::Relaxed))
+    }
+
+    /// Sets whether the debugger must handle every instruction. The flag stays set anyway
+    /// while any request or interrupt is pending.
+    pub fn set_attention(&self, needed: bool) {
+        let Some(session) = &self.session else {
+            return;
+        };
+        let interrupts = &session.interrupts;
+        interrupts.attention.store(needed, Ordering::SeqCst);
+        // The reader thread sets the flags of requests before raising the attention,
+        // so that none is missed if it arrived meanwhile.
+        if interrupts.requests.load(Ordering::SeqCst)
+            || interrupts.pause.load(Ordering::SeqCst)
+            || interrupts.terminate.load(Ordering::SeqCst)
+        {
+            interrupts.attention.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Events sent with the returned sender are dropped once the current client is gone.
+    pub fn event_sender(&self) -> Result<EventSender> {
+        Ok(EventSender(self.session()?.outbound_tx.clone()))
+    }
+
     pub fn send_event(&self, event: Event) -> Result<()> {
-        self.outbound_tx
+        for listener in &self.event_listeners {
+            listener(&event);
+        }
+        self.session()?
+            .outbound_tx
             .send(Sendable::Event(event))
             .context("Sending event to outbound channel failed")
     }
 
     pub fn send_success(&self, request: Request, body: ResponseBody) -> Result<()> {
-        self.outbound_tx
+        self.session()?
+            .outbound_tx
             .send(Sendable::Response(request.success(body)))
             .context("Sending success response to outbound channel failed")
     }
+
+    pub fn send_error(&self, request: Request, message: &str) -> Result<()> {
+        self.session()?
+            .outbound_tx
+            .send(Sendable::Response(request.error(message)))
+            .context("Sending error response to outbound channel failed")
+    }
+
+    /// Sends an error response with the structured details of the error, e.g. for the client
+    /// to show it to the user in a dialog linking to an explanation.
+    pub fn send_error_with_details(
+        &self,
+        request: Request,
+        message: &str,
+        details: Message,
+    ) -> Result<()> {
+        let mut response = request.error(message);
+        response.error = Some(details);
+        self.session()?
+            .outbound_tx
+            .send(Sendable::Response(response))
+            .context("Sending error response to outbound channel failed")
+    }
+
+    /// Sends a request to the client. Its response is not awaited, nor even read.
+    pub fn send_reverse_request(&self, command: ReverseCommand) -> Result<()> {
+        self.session()?
+            .outbound_tx
+            .send(Sendable::ReverseRequest(ReverseRequest { command }))
+            .context("Sending reverse request to outbound channel failed")
+    }
+}
+
+impl Listener {
+    fn bind(transport: &Transport) -> Result<Self> {
+        let (listener, address) = match transport {
+            Transport::Tcp { address, port } => {
+                let tcp_listener = TcpListener::bind((*address, port.unwrap_or(0)))
+                    .map_err(ServerError::IoError)?;
+                tcp_listener.set_nonblocking(true)?;
+                let local_addr = tcp_listener.local_addr()?;
+                let address = json!({ "address": local_addr.ip(), "port": local_addr.port() });
+                (Self::Tcp(tcp_listener), address)
+            }
+            Transport::UnixSocket(path) => {
+                let name = path.as_path().to_fs_name::<GenericFilePath>()?;
+                let listener = ListenerOptions::new()
+                    .name(name)
+                    .nonblocking(ListenerNonblockingMode::Accept)
+                    .create_sync()
+                    .with_context(|| format!("Failed to listen on socket {}", path.display()))?;
+                (Self::LocalSocket(listener), json!({ "socket": path }))
+            }
+            Transport::NamedPipe(pipe_name) => {
+                let name = pipe_name.as_str().to_ns_name::<GenericNamespaced>()?;
+                let listener = ListenerOptions::new()
+                    .name(name)
+                    .nonblocking(ListenerNonblockingMode::Accept)
+                    .create_sync()
+                    .with_context(|| format!("Failed to listen on pipe {pipe_name}"))?;
+                (Self::LocalSocket(listener), json!({ "pipe": pipe_name }))
+            }
+        };
+        announce(address);
+
+        Ok(listener)
+    }
+
+    fn try_accept(&self) -> std::io::Result<ClientStreams> {
+        // Accepted streams may inherit the non-blocking mode of the listener on some platforms.
+        match self {
+            Self::Tcp(tcp_listener) => {
+                let (stream, _client_addr) = tcp_listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(ClientStreams { input: Box::new(stream.try_clone()?), output: Box::new(stream) })
+            }
+            Self::LocalSocket(listener) => {
+                let stream = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                let (input, output) = stream.split();
+                Ok(ClientStreams { input: Box::new(input), output: Box::new(output) })
+            }
+        }
+    }
+}
+
+impl ClientListener for Listener {
+    fn accept(&self, deadline: Option<Instant>) -> Result<Option<ClientStreams>> {
+        loop {
+            match self.try_accept() {
+                Ok(streams) => return Ok(Some(streams)),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => return Err(ServerError::IoError(err).into()),
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+            thread::sleep(ACCEPT_POLL_INTERVAL);
+        }
+    }
+
+    fn port(&self) -> Option<u16> {
+        match self {
+            Self::Tcp(tcp_listener) => {
+                tcp_listener.local_addr().ok().map(|local_addr| local_addr.port())
+            }
+            Self::LocalSocket(_) => None,
+        }
+    }
+}
+
+impl ClientSession {
+    fn new<R, W>(
+        input: R,
+        output: W,
+        first_request_token: FirstRequestToken,
+        authenticated: bool,
+    ) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (server_reader, server_writer) =
+            Server::new(BufReader::new(input), BufWriter::new(output)).split_server();
+
+        let (inbound_tx, inbound_rx) = mpsc::channel::<Request>();
+        let (outbound_tx, outbound_rx) = mpsc::channel::<Sendable>();
+        let interrupts = Arc::new(Interrupts {
+            authenticated: AtomicBool::new(authenticated),
+            ..Default::default()
+        });
+
+        Self {
+            inbound_rx,
+            outbound_tx: outbound_tx.clone(),
+            interrupts: Arc::clone(&interrupts),
+            io_threads: IoThreads::spawn(
+                server_reader,
+                server_writer,
+                inbound_tx,
+                outbound_tx,
+                outbound_rx,
+                interrupts,
+            ),
+            first_request_token,
+        }
+    }
+
+    /// Delivers the messages sent so far and disconnects from the client. The writer thread
+    /// sends what is left in the outbound channel before it stops, which cannot wait for
+    /// the channel to be closed, as the reader thread keeps a sender to answer interrupts.
+    /// The reader thread stops once the client closes the connection, usually right after
+    /// its disconnect request is answered.
+    fn close(self) {
+        let Self { inbound_rx, outbound_tx, interrupts, io_threads, .. } = self;
+        interrupts.closing.store(true, Ordering::SeqCst);
+        drop(outbound_tx);
+        drop(inbound_rx);
+        io_threads.join(Instant::now() + IO_THREADS_JOIN_TIMEOUT);
+    }
+}
+
+/// Prints a single-line JSON announcement, so that tools wrapping the debuggee can tell the client
+/// where to connect to, e.g. `{"event":"debuggerListening","pid":42,"port":4000,"protocolVersion":1}`.
+///
+/// `address` is an object with the `address` and `port`, `socket` or `pipe` fields,
+/// depending on the transport.
+fn announce(address: Value) {
+    let mut announcement = json!({
+        "event": "debuggerListening",
+        "pid": process::id(),
+        "protocolVersion": PROTOCOL_VERSION,
+    });
+    if let (Some(announcement), Value::Object(address)) = (announcement.as_object_mut(), address) {
+        announcement.extend(address);
+    }
+    // Starts with a newline, so that it is not glued to any output of the debuggee.
+    println!("\n{announcement}");
 }
 
 struct IoThreads {
-    pub reader: Option<JoinHandle<()>>,
-    pub writer: Option<JoinHandle<()>>,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
 }
 
 impl IoThreads {
-    fn spawn(
-        server_reader: ServerReader<TcpStream>,
-        server_writer: ServerWriter<TcpStream>,
+    fn spawn<R, W>(
+        server_reader: ServerReader<R>,
+        server_writer: ServerWriter<W>,
         inbound_tx: mpsc::Sender<Request>,
+        outbound_tx: mpsc::Sender<Sendable>,
         outbound_rx: mpsc::Receiver<Sendable>,
-    ) -> Self {
+        interrupts: Arc<Interrupts>,
+    ) -> Self
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
         Self {
-            reader: Some(spawn_reader_thread(server_reader, inbound_tx)),
-            writer: Some(spawn_writer_thread(server_writer, outbound_rx)),
+            reader: spawn_reader_thread(
+                server_reader,
+                inbound_tx,
+                outbound_tx,
+                Arc::clone(&interrupts),
+            ),
+            writer: spawn_writer_thread(server_writer, outbound_rx, interrupts),
+        }
+    }
+
+    /// Waits for the writer thread, so that all messages are delivered, and then the reader
+    /// thread, each until `deadline` at the latest.
+    fn join(self, deadline: Instant) {
+        for (name, thread) in [("writer", self.writer), ("reader", self.reader)] {
+            while !thread.is_finished() && Instant::now() < deadline {
+                thread::sleep(IO_POLL_INTERVAL);
+            }
+            if thread.is_finished() {
+                let _ = thread.join();
+            } else {
+                trace!("Left the {name} thread of the closed session behind");
+            }
         }
     }
 }
 
-impl Drop for IoThreads {
-    fn drop(&mut self) {
-        self.reader.take().map(|h| h.join());
-        self.writer.take().map(|h| h.join());
+impl Interrupts {
+    /// Flags the request and returns its response if it is answered right away.
+    fn answer(&self, request: &Request) -> Option<ResponseBody> {
+        if !self.authenticated.load(Ordering::SeqCst) {
+            return None;
+        }
+        // Cancelled requests are still in the channel, so the cancellation must bypass it.
+        if let Command::Cancel(args) = &request.command {
+            if let Some(request_id) = args.request_id {
+                self.cancelled.lock().expect("Cancellations lock poisoned").insert(request_id);
+            }
+            return Some(ResponseBody::Cancel);
+        }
+        if !self.running.load(Ordering::SeqCst) {
+            return None;
+        }
+        match request.command {
+            Command::Pause(_) => {
+                self.pause.store(true, Ordering::SeqCst);
+                self.attention.store(true, Ordering::SeqCst);
+                Some(ResponseBody::Pause)
+            }
+            Command::Terminate(_) => {
+                self.terminate.store(true, Ordering::SeqCst);
+                self.attention.store(true, Ordering::SeqCst);
+                Some(ResponseBody::Terminate)
+            }
+            _ => None,
+        }
     }
 }
 
-fn spawn_reader_thread(
-    mut server_reader: ServerReader<TcpStream>,
+fn spawn_reader_thread<R: Read + Send + 'static>(
+    mut server_reader: ServerReader<R>,
     inbound_tx: mpsc::Sender<Request>,
+    outbound_tx: mpsc::Sender<Sendable>,
+    interrupts: Arc<Interrupts>,
 ) -> JoinHandle<()> {
     thread::spawn(move || {
-        while let Ok(Some(request)) = server_reader.poll_request() {
+        loop {
+            let request = match server_reader.poll_request() {
+                Ok(Some(request)) => request,
+                Ok(None) | Err(ServerError::IoError(_)) => break,
+                // Responses to reverse requests cannot be parsed as requests. They are not needed,
+                // so they are skipped, as is any other malformed message.
+                Err(err) => {
+                    trace!("Skipping a message from the client: {err}");
+                    continue;
+                }
+            };
+            if let Some(body) = interrupts.answer(&request) {
+                if outbound_tx.send(Sendable::Response(request.success(body))).is_err() {
+                    trace!("Outbound channel closed");
+                    break;
+                }
+                continue;
+            }
             if inbound_tx.send(request).is_err() {
                 trace!("Inbound channel closed");
                 break;
             }
+            interrupts.requests.store(true, Ordering::SeqCst);
+            interrupts.attention.store(true, Ordering::SeqCst);
         }
+        // The debugger finds out that the client is gone once it polls the closed channel.
+        drop(inbound_tx);
+        interrupts.requests.store(true, Ordering::SeqCst);
+        interrupts.attention.store(true, Ordering::SeqCst);
     })
 }
 
-fn spawn_writer_thread(
-    mut server_writer: ServerWriter<TcpStream>,
+fn spawn_writer_thread<W: Write + Send + 'static>(
+    mut server_writer: ServerWriter<W>,
     outbound_rx: mpsc::Receiver<Sendable>,
+    interrupts: Arc<Interrupts>,
 ) -> JoinHandle<()> {
     thread::spawn(move || {
-        while let Ok(msg) = outbound_rx.recv() {
-            match msg {
-                Sendable::Response(response) => {
-                    server_writer.respond(response).expect("Failed to send response")
+        loop {
+            let msg = match outbound_rx.recv_timeout(IO_POLL_INTERVAL) {
+                Ok(msg) => msg,
+                // Messages sent before the session was closed are all delivered by now.
+                Err(RecvTimeoutError::Timeout) if interrupts.closing.load(Ordering::SeqCst) => {
+                    break;
                 }
-                Sendable::Event(event) => {
-                    server_writer.send_event(event).expect("Failed to send event")
-                }
-                Sendable::ReverseRequest(_) => unreachable!("Reverse requests are not supported"),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            let result = match msg {
+                Sendable::Response(response) => server_writer.respond(response),
+                Sendable::Event(event) => server_writer.send_event(event),
+                request @ Sendable::ReverseRequest(_) => server_writer.send(request),
+            };
+            // The client is gone, which the reader thread notices as well.
+            if let Err(err) = result {
+                trace!("Failed to send a message to the client: {err}");
+                break;
             }
         }
     })
@@ -0,0 +1,98 @@
+//! Authentication of clients with the token set by [`crate::DebugServer::with_auth_token`].
+//!
+//! The token is sent in the `authToken` field of the arguments of the first request, usually
+//! `initialize`. Since DAP has no such field, the token is read from the first message of the
+//! client before it is parsed, and only that message is checked, so that a client cannot send
+//! any request before it is authenticated.
+
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Result, bail, ensure};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::connection::protocol_trace::Frames;
+
+/// Field of the arguments of requests the token is sent in.
+pub const AUTH_TOKEN_FIELD: &str = "authToken";
+
+/// Token shared by all clients of a [`crate::DebugServer`].
+pub struct AuthToken {
+    token: String,
+    /// Whether more than one client can authenticate with the token,
+    /// see [`crate::DebugServer::with_reusable_auth_token`].
+    reusable: bool,
+    /// Set once a client authenticated with a token that is not reusable.
+    used: AtomicBool,
+}
+
+impl AuthToken {
+    pub fn new(token: String, reusable: bool) -> Self {
+        Self { token, reusable, used: AtomicBool::new(false) }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn is_reusable(&self) -> bool {
+        self.reusable
+    }
+
+    /// Checks the token the client sent in its first request, and uses it up unless reusable.
+    pub fn authenticate(&self, client_token: Option<&str>) -> Result<()> {
+        let Some(client_token) = client_token else {
+            bail!(
+                "The client must send the auth token in the `{AUTH_TOKEN_FIELD}` field of its first request"
+            );
+        };
+        ensure!(constant_time_eq(client_token, &self.token), "Invalid auth token");
+        // Checked after the token, so that it is not revealed whether a guess was used up.
+        if !self.reusable {
+            ensure!(!self.used.swap(true, Ordering::SeqCst), "The auth token was already used");
+        }
+        Ok(())
+    }
+}
+
+/// Compares the digests rather than the tokens, so that the time taken depends
+/// on neither the content nor the length of the tokens.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter().zip(b.iter()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Token sent by a client in its first request, `None` inside if it sent none.
+pub type FirstRequestToken = Arc<OnceLock<Option<String>>>;
+
+/// Input of a client that records the token of its first message in [`FirstRequestToken`].
+pub struct TokenReadingStream<R> {
+    inner: R,
+    /// `None` once the first message was read.
+    frames: Option<Frames>,
+    token: FirstRequestToken,
+}
+
+impl<R> TokenReadingStream<R> {
+    pub fn new(inner: R, token: FirstRequestToken) -> Self {
+        Self { inner, frames: Some(Frames::default()), token }
+    }
+}
+
+impl<R: Read> Read for TokenReadingStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(frames) = &mut self.frames
+            && let Some(content) = frames.push(&buf[..read]).into_iter().next()
+        {
+            let token = serde_json::from_slice::<Value>(&content).ok().and_then(|message| {
+                Some(message.get("arguments")?.get(AUTH_TOKEN_FIELD)?.as_str()?.to_string())
+            });
+            let _ = self.token.set(token);
+            self.frames = None;
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,193 @@
+//! Listener whose clients are connected through in-memory pipes instead of sockets,
+//! so that tests can drive the debugger, e.g. set breakpoints and check the events
+//! sent when they are hit, without real sockets or an editor.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Instant;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+
+use crate::connection::{ClientListener, ClientStreams};
+
+/// Hands the server ends of the pipes created by [`InMemoryListener::connect`]
+/// to the debugger waiting for a client.
+pub struct InMemoryListener {
+    clients_tx: Sender<ClientStreams>,
+    clients_rx: Mutex<Receiver<ClientStreams>>,
+}
+
+impl InMemoryListener {
+    pub fn new() -> Self {
+        let (clients_tx, clients_rx) = mpsc::channel();
+        Self { clients_tx, clients_rx: Mutex::new(clients_rx) }
+    }
+
+    /// Connects a new client. The debugger accepts it once it waits for a client,
+    /// which may be before or after this call.
+    pub fn connect(&self) -> InMemoryClient {
+        let (to_debugger, from_client) = pipe();
+        let (to_client, from_debugger) = pipe();
+        // The receiver lives as long as the listener, which outlives this call.
+        let _ = self
+            .clients_tx
+            .send(ClientStreams { input: Box::new(from_client), output: Box::new(to_client) });
+        InMemoryClient { to_debugger, from_debugger: BufReader::new(from_debugger), next_seq: 1 }
+    }
+}
+
+impl Default for InMemoryListener {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientListener for InMemoryListener {
+    fn accept(&self, deadline: Option<Instant>) -> Result<Option<ClientStreams>> {
+        let clients_rx = self.clients_rx.lock().expect("In-memory listener lock poisoned");
+        let accepted = match deadline {
+            Some(deadline) => {
+                clients_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => clients_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match accepted {
+            Ok(streams) => Ok(Some(streams)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            // The listener keeps a sender itself, so the channel is never closed.
+            Err(RecvTimeoutError::Disconnected) => unreachable!("In-memory listener closed"),
+        }
+    }
+}
+
+/// Client end of an in-memory connection, speaking DAP with the debugger.
+/// The debugger notices that the client is gone once it is dropped.
+pub struct InMemoryClient {
+    to_debugger: PipeWriter,
+    from_debugger: BufReader<PipeReader>,
+    next_seq: i64,
+}
+
+impl InMemoryClient {
+    /// Sends a request, e.g. `client.send_request("setBreakpoints", json!({ ... }))`,
+    /// and returns its sequence number, which the response refers to as `request_seq`.
+    pub fn send_request(&mut self, command: &str, arguments: Value) -> Result<i64> {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.send_message(
+            &json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments }),
+        )?;
+        Ok(seq)
+    }
+
+    /// Sends a message as it is, e.g. a request with its own sequence number.
+    pub fn send_message(&mut self, message: &Value) -> Result<()> {
+        let content = serde_json::to_vec(message)?;
+        write!(self.to_debugger, "Content-Length: {}\r\n\r\n", content.len())?;
+        self.to_debugger.write_all(&content)?;
+        Ok(())
+    }
+
+    /// Waits for the next message from the debugger: a response, an event or a reverse request.
+    pub fn next_message(&mut self) -> Result<Value> {
+        let mut content_length = None;
+        loop {
+            let mut line = String::new();
+            if self.from_debugger.read_line(&mut line)? == 0 {
+                bail!("The debugger closed the connection");
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(length) = line.strip_prefix("Content-Length: ") {
+                content_length = Some(length.parse::<usize>()?);
+            }
+        }
+
+        let content_length =
+            content_length.ok_or_else(|| anyhow!("Message without the Content-Length header"))?;
+        let mut content = vec![0; content_length];
+        self.from_debugger.read_exact(&mut content)?;
+        serde_json::from_slice(&content).context("Failed to parse a message from the debugger")
+    }
+
+    /// Waits for the response to the request with the given sequence number, skipping
+    /// the messages sent before it, and returns its body if the request succeeded.
+    pub fn response_to(&mut self, seq: i64) -> Result<Value> {
+        let response = self.wait_for_response(seq)?;
+        if response["success"] != true {
+            bail!("Request {seq} failed: {}", response["message"]);
+        }
+        Ok(response.get("body").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Waits for the response to the request with the given sequence number, skipping
+    /// the messages sent before it, and returns it whether the request succeeded or not.
+    pub fn wait_for_response(&mut self, seq: i64) -> Result<Value> {
+        loop {
+            let message = self.next_message()?;
+            if message["type"] == "response" && message["request_seq"] == seq {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Waits for the event of the given name, e.g. `stopped`, skipping the messages
+    /// sent before it, and returns its body.
+    pub fn wait_for_event(&mut self, event: &str) -> Result<Value> {
+        loop {
+            let message = self.next_message()?;
+            if message["type"] == "event" && message["event"] == event {
+                return Ok(message.get("body").cloned().unwrap_or(Value::Null));
+            }
+        }
+    }
+}
+
+/// One direction of an in-memory connection.
+fn pipe() -> (PipeWriter, PipeReader) {
+    let (tx, rx) = mpsc::channel();
+    (PipeWriter(tx), PipeReader { rx, chunk: Vec::new(), position: 0 })
+}
+
+struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf.to_vec()).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct PipeReader {
+    rx: Receiver<Vec<u8>>,
+    /// Chunk written last, read from `position` on.
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                // The writing end is dropped, which is the end of the stream.
+                Err(_) => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
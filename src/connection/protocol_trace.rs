@@ -0,0 +1,228 @@
+//! Capture of the messages exchanged with clients, and replay of the requests of a capture,
+//! so that bugs specific to a client can be reproduced from the capture sent by its user.
+//!
+//! The capture is a JSON Lines file, with a line for each message, e.g.
+//! `{"time":0.52,"from":"client","message":{"seq":3,"type":"request","command":"next",...}}`,
+//! where `time` is the number of seconds since the capture started.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+use crate::connection::ClientStreams;
+use crate::connection::auth::AUTH_TOKEN_FIELD;
+use crate::connection::in_memory::InMemoryListener;
+
+#[derive(Clone, Copy)]
+enum Sender {
+    Client,
+    Debugger,
+}
+
+impl Sender {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Debugger => "debugger",
+        }
+    }
+}
+
+/// File the messages of all clients of a [`crate::DebugServer`] are captured to.
+pub struct ProtocolTrace {
+    file: Mutex<BufWriter<File>>,
+    started: Instant,
+}
+
+impl ProtocolTrace {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create protocol trace {}", path.display()))?;
+        Ok(Self { file: Mutex::new(BufWriter::new(file)), started: Instant::now() })
+    }
+
+    /// Wraps the streams of a client, so that the messages passing through them are captured.
+    pub fn capture(self: &Arc<Self>, streams: ClientStreams) -> ClientStreams {
+        ClientStreams {
+            input: Box::new(CapturedStream {
+                inner: streams.input,
+                frames: Frames::default(),
+                sender: Sender::Client,
+                trace: Arc::clone(self),
+            }),
+            output: Box::new(CapturedStream {
+                inner: streams.output,
+                frames: Frames::default(),
+                sender: Sender::Debugger,
+                trace: Arc::clone(self),
+            }),
+        }
+    }
+
+    /// Capturing is best effort, so that a full disk does not break the session.
+    fn record(&self, sender: Sender, content: &[u8]) {
+        let mut message = serde_json::from_slice(content)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(content).into_owned()));
+        if let Some(arguments) = message.get_mut("arguments").and_then(Value::as_object_mut) {
+            arguments.remove(AUTH_TOKEN_FIELD);
+        }
+        let line = json!({
+            "time": self.started.elapsed().as_secs_f64(),
+            "from": sender.name(),
+            "message": message,
+        });
+
+        let mut file = self.file.lock().expect("Protocol trace lock poisoned");
+        if let Err(err) = writeln!(file, "{line}").and_then(|()| file.flush()) {
+            debug!("Failed to capture a message: {err}");
+        }
+    }
+}
+
+/// Stream of a client whose messages are captured as they are read or written.
+struct CapturedStream<S> {
+    inner: S,
+    frames: Frames,
+    sender: Sender,
+    trace: Arc<ProtocolTrace>,
+}
+
+impl<S> CapturedStream<S> {
+    fn capture(&mut self, bytes: &[u8]) {
+        for content in self.frames.push(bytes) {
+            self.trace.record(self.sender, &content);
+        }
+    }
+}
+
+impl<S: Read> Read for CapturedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.capture(&buf[..read]);
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for CapturedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.capture(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Splits a stream of bytes into the contents of DAP messages, each preceded by
+/// the `Content-Length` header.
+#[derive(Default)]
+pub(crate) struct Frames {
+    buffer: Vec<u8>,
+}
+
+impl Frames {
+    /// Returns the contents of the messages completed by `bytes`.
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut contents = Vec::new();
+        while let Some(header_end) = self.buffer.windows(4).position(|window| window == b"\r\n\r\n")
+        {
+            let content_start = header_end + 4;
+            let Some(content_length) = content_length(&self.buffer[..header_end]) else {
+                // Headers other than the length carry nothing to capture.
+                self.buffer.drain(..content_start);
+                continue;
+            };
+            let content_end = content_start + content_length;
+            if self.buffer.len() < content_end {
+                break;
+            }
+            contents.push(self.buffer[content_start..content_end].to_vec());
+            self.buffer.drain(..content_end);
+        }
+        contents
+    }
+}
+
+fn content_length(header: &[u8]) -> Option<usize> {
+    String::from_utf8_lossy(header)
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Length:"))
+        .and_then(|length| length.trim().parse().ok())
+}
+
+/// Listener of a single client that sends the requests captured in the trace, each once
+/// the previous one is answered and the execution stopped as many times as it had before
+/// the request was captured, so that they are handled in the captured order relative
+/// to the execution. The time between the requests is not reproduced.
+///
+/// The responses and events of the debugger can be captured with [`ProtocolTrace`] in turn,
+/// to compare them with the captured ones.
+pub fn replay_protocol_trace(trace_path: &Path) -> Result<InMemoryListener> {
+    let file = File::open(trace_path)
+        .with_context(|| format!("Failed to open protocol trace {}", trace_path.display()))?;
+    let entries = BufReader::new(file)
+        .lines()
+        .map(|line| Ok(serde_json::from_str::<Value>(&line?)?))
+        .collect::<Result<Vec<Value>>>()
+        .with_context(|| format!("Invalid protocol trace {}", trace_path.display()))?;
+    // Each request with the number of stops captured before it.
+    let mut requests = Vec::new();
+    let mut stops = 0;
+    for mut entry in entries {
+        let message = entry["message"].take();
+        if entry["from"] == Sender::Client.name() && message["type"] == "request" {
+            requests.push((message, stops));
+        } else if is_stop(&message) {
+            stops += 1;
+        }
+    }
+
+    let listener = InMemoryListener::new();
+    let mut client = listener.connect();
+    thread::spawn(move || {
+        let mut stops = 0;
+        for (request, stops_before) in requests {
+            let replayed = request["seq"]
+                .as_i64()
+                .ok_or_else(|| anyhow!("Request without a sequence number: {request}"))
+                .and_then(|seq| {
+                    // Should the replayed execution stop fewer times, the wait ends
+                    // when the debugger closes the connection at its end.
+                    while stops < stops_before {
+                        if is_stop(&client.next_message()?) {
+                            stops += 1;
+                        }
+                    }
+                    client.send_message(&request)?;
+                    loop {
+                        let message = client.next_message()?;
+                        if is_stop(&message) {
+                            stops += 1;
+                        } else if message["type"] == "response" && message["request_seq"] == seq {
+                            return Ok(());
+                        }
+                    }
+                });
+            if let Err(err) = replayed {
+                warn!("Stopped replaying the protocol trace: {err:#}");
+                break;
+            }
+        }
+        // Dropping the client disconnects it, like the captured client once it was done.
+    });
+    Ok(listener)
+}
+
+fn is_stop(message: &Value) -> bool {
+    message["type"] == "event" && message["event"] == "stopped"
+}
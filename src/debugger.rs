@@ -1,23 +1,90 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::mem;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::{Result, anyhow};
-use cairo_vm::vm::vm_core::VirtualMachine;
-use dap::events::{Event, ExitedEventBody, StoppedEventBody};
+use anyhow::{Context as _, Result, anyhow, bail};
+use cairo_annotations::annotations::coverage::{CodeLocation, SourceFileFullPath};
+use dap::events::{
+    BreakpointEventBody, CapabilitiesEventBody, Event, ExitedEventBody, LoadedSourceEventBody,
+    ModuleEventBody, OutputEventBody, StoppedEventBody, ThreadEventBody,
+};
 use dap::prelude::Event::{Exited, Terminated};
-use dap::prelude::{Request, ResponseBody};
-use dap::types::StoppedEventReason;
-use tracing::error;
+use dap::prelude::{Command, Request, ResponseBody};
+use dap::reverse_requests::{
+    ReverseCommand, RunInTerminalRequestArguments, StartDebuggingRequestArguments,
+};
+use dap::types::{
+    Breakpoint, BreakpointEventReason, Capabilities, LoadedSourceEventReason, ModuleEventReason,
+    ModuleId, OutputEventCategory, RunInTerminalRequestArgumentsKind, Source,
+    StartDebuggingRequestKind, StoppedEventReason, ThreadEventReason, Variable,
+    VariablePresentationHint, VariablePresentationHintKind,
+};
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+use tracing::{debug, error, info_span, warn};
 
-use crate::connection::Connection;
+use crate::connection::{Connection, DebugServer, EventListener, Transport};
+use crate::debugger::abi::ContractAbi;
+use crate::debugger::artifact_watch::ArtifactWatcher;
+use crate::debugger::call_trace::{CallResult, ContractCall};
+use crate::debugger::context::registry::ContextRegistry;
 use crate::debugger::context::{CasmDebugInfo, Context, Line};
+use crate::debugger::deployments::{DECLARE_CHEATCODE, DeployedContract, PendingDeclaration};
+use crate::debugger::errors::DebuggerError;
+use crate::debugger::execution_info::ExecutionInfo;
+use crate::debugger::fork::{ForkOrigin, ForkRead};
+use crate::debugger::fuzzer::FuzzIteration;
 use crate::debugger::handler::StepAction;
+use crate::debugger::hints::HintInfo;
+use crate::debugger::launch_args::{Console, DisconnectAction, ProgramArg};
+use crate::debugger::panics::{self, ExpectedPanic, Panic};
+use crate::debugger::post_mortem::TraceEntry;
+use crate::debugger::recording::ExecutionRecording;
+use crate::debugger::replay_metadata::ReplayMetadata;
 use crate::debugger::state::State;
+use crate::debugger::storage::StorageReader;
+use crate::debugger::syscalls::Syscall;
+use crate::debugger::view_call::ViewCaller;
+use crate::debugger::vm_access::VmAccess;
+use crate::debugger::watchdog::{Activity, Watchdog};
 
+mod abi;
+mod artifact_watch;
 mod call_stack;
+pub mod call_trace;
+mod cheatcodes;
 pub mod context;
+mod custom_request;
+pub mod deployments;
+pub mod errors;
+pub mod execution_info;
+pub mod fork;
+pub mod fuzzer;
 mod handler;
+mod hints;
+pub mod launch_args;
+mod lifecycle;
+pub mod memory;
+mod metrics;
+mod middleware;
+pub mod panics;
+mod pc_set;
+pub mod post_mortem;
+mod recording;
+pub mod replay_metadata;
+mod resources;
+pub mod settings;
 mod state;
+pub mod storage;
+mod syscall_trace;
+mod syscalls;
+pub mod view_call;
 mod vm;
+pub mod vm_access;
+mod watchdog;
 
 /// According to [object references](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references).
 const MAX_OBJECT_REFERENCE: i64 = (1 << 31) - 1;
@@ -25,82 +92,701 @@ const MAX_OBJECT_REFERENCE: i64 = (1 << 31) - 1;
 /// According to [object references](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references).
 const MIN_OBJECT_REFERENCE: i64 = 1;
 
+/// Exit code reported when the execution was aborted with a terminate request.
+/// Same as the one of processes interrupted with Ctrl+C.
+const TERMINATED_EXIT_CODE: i64 = 130;
+
+/// Time the client is given to send the disconnect request once the session terminated.
+const DISCONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
 pub struct CairoDebugger {
     connection: Connection,
-    ctx: Context,
+    contexts: ContextRegistry,
     state: State,
+    /// Started once the client configured the session, if it asked for it.
+    watchdog: Option<Watchdog>,
+    /// Watches the compiled programs, to reload them once they are rebuilt.
+    artifact_watcher: ArtifactWatcher,
 }
 
 impl CairoDebugger {
-    pub fn connect_and_initialize(
+    /// Waits for a client on a listener that may be shared with debuggers of other programs.
+    pub fn connect_and_initialize_with_server(
         sierra_path: &Path,
         casm_debug_info: CasmDebugInfo,
+        server: &DebugServer,
     ) -> Result<Self> {
-        let connection = Connection::new()?;
+        let connection = Connection::new(server)?;
         let ctx = Context::new(sierra_path, casm_debug_info)?;
 
-        let mut debugger = Self { connection, ctx, state: State::new() };
+        let artifact_watcher = ArtifactWatcher::start();
+        artifact_watcher.watch(&ctx.sierra_path);
+        let mut debugger = Self {
+            connection,
+            contexts: ContextRegistry::new(ctx),
+            state: State::new(),
+            watchdog: None,
+            artifact_watcher,
+        };
         debugger.initialize()?;
 
         Ok(debugger)
     }
 
+    /// See [`crate::Debugger::start_child_session`].
+    pub fn start_child_session(
+        &self,
+        contract_name: &str,
+        sierra_path: &Path,
+        casm_debug_info: CasmDebugInfo,
+    ) -> Result<Option<Self>> {
+        if !self.state.debug_contract_calls {
+            return Ok(None);
+        }
+        if !self.state.supports_start_debugging {
+            warn!("The client does not support child sessions, {contract_name} is not debugged");
+            return Ok(None);
+        }
+
+        // The child session is a separate connection, so that the client can tell it apart
+        // from this one. Its listener is announced, like any other.
+        let mut server = DebugServer::bind(&Transport::default())?;
+        // The client sends the token of this session to the child one too.
+        if let Some(auth_token) = self.connection.auth_token() {
+            let auth_token = auth_token.to_string();
+            server = if self.connection.is_auth_token_reusable() {
+                server.with_reusable_auth_token(auth_token)
+            } else {
+                server.with_auth_token(auth_token)
+            };
+        }
+        let port = server.port().context("Child session is not listening on a TCP port")?;
+
+        let mut configuration = self.state.configuration.clone();
+        // Arguments of the program do not apply to the contract.
+        configuration.remove("args");
+        configuration.remove("calldata");
+        configuration.insert("name".to_string(), json!(contract_name));
+        configuration.insert("testName".to_string(), json!(contract_name));
+        configuration.insert("program".to_string(), json!(sierra_path));
+        configuration.insert("request".to_string(), json!("attach"));
+        // Makes the client connect to the port instead of starting a new adapter.
+        configuration.insert("debugServer".to_string(), json!(port));
+
+        self.connection.send_reverse_request(ReverseCommand::StartDebugging(
+            StartDebuggingRequestArguments {
+                configuration: configuration.into_iter().collect(),
+                request: StartDebuggingRequestKind::Attach,
+            },
+        ))?;
+
+        Self::connect_and_initialize_with_server(sierra_path, casm_debug_info, &server).map(Some)
+    }
+
+    /// See [`crate::Debugger::register_class`].
+    pub fn register_class(
+        &mut self,
+        class_hash: Felt,
+        sierra_path: &Path,
+        casm_debug_info: CasmDebugInfo,
+        abi: Option<&Value>,
+    ) -> Result<()> {
+        let mut ctx = Context::new(sierra_path, casm_debug_info)?;
+        if let Some(abi) = abi {
+            ctx.abi = Some(ContractAbi::parse(abi)?);
+        }
+        // Packages of the class are only known once the project settings are applied.
+        self.contexts.apply_launch_settings(&mut ctx);
+        self.artifact_watcher.watch(sierra_path);
+
+        // Announces only what is new, since classes often share packages and files.
+        let known_packages: HashSet<&str> = self
+            .contexts
+            .iter()
+            .flat_map(|ctx| &ctx.packages)
+            .map(|package| package.id.as_str())
+            .collect();
+        let known_sources: HashSet<&Arc<str>> =
+            self.contexts.iter().flat_map(|ctx| ctx.source_files.paths()).collect();
+        let mut events: Vec<Event> = handler::build_modules(&ctx)
+            .into_iter()
+            .filter(|module| {
+                !matches!(&module.id, ModuleId::String(id) if known_packages.contains(id.as_str()))
+            })
+            .map(|module| Event::Module(ModuleEventBody { reason: ModuleEventReason::New, module }))
+            .collect();
+        events.extend(
+            ctx.source_files.paths().iter().filter(|path| !known_sources.contains(path)).map(
+                |path| {
+                    Event::LoadedSource(LoadedSourceEventBody {
+                        reason: LoadedSourceEventReason::New,
+                        source: ctx.source_files.source(path),
+                    })
+                },
+            ),
+        );
+
+        self.report_stale_files(&ctx)?;
+        self.contexts.register(class_hash, ctx);
+
+        // Breakpoints in the files of the contract, set before its class was known.
+        for breakpoint in self.state.verify_pending_breakpoints(&self.contexts) {
+            events.push(Event::Breakpoint(BreakpointEventBody {
+                reason: BreakpointEventReason::Changed,
+                breakpoint,
+            }));
+        }
+
+        if self.connection.is_connected() {
+            for event in events {
+                self.connection.send_event(event)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_class_registered(&self, class_hash: &Felt) -> bool {
+        self.contexts.is_registered(class_hash)
+    }
+
+    /// Sets a breakpoint requested by the execution tool rather than the client, e.g. from
+    /// its command line. The client learns about it from a breakpoint event.
+    /// `line` is 1-indexed, like in editors.
+    pub(crate) fn set_breakpoint(&mut self, source: &Path, line: usize) -> Result<()> {
+        let source = source.to_string_lossy().into_owned();
+        let line = Line::new(line.saturating_sub(1));
+        let (id, verified) =
+            self.state.verify_and_set_breakpoint(source.clone(), line, &self.contexts);
+        if !self.connection.is_connected() {
+            return Ok(());
+        }
+        self.connection.send_event(Event::Breakpoint(BreakpointEventBody {
+            reason: BreakpointEventReason::New,
+            breakpoint: Breakpoint {
+                id: Some(id),
+                verified,
+                message: (!verified).then(|| {
+                    DebuggerError::UnmappedBreakpoint {
+                        source: source.clone(),
+                        line: line.ui_number() as usize,
+                    }
+                    .to_string()
+                }),
+                source: Some(Source { path: Some(source), ..Default::default() }),
+                line: Some(line.ui_number()),
+                ..Default::default()
+            },
+        }))
+    }
+
+    pub(crate) fn add_event_listener(&mut self, listener: EventListener) {
+        self.connection.add_event_listener(listener);
+    }
+
+    /// See [`crate::Debugger::enter_class`].
+    pub fn enter_class(&mut self, call: ContractCall) -> Result<()> {
+        self.contexts.enter(call.class_hash)?;
+        // Tools that do not report deployments still let the called contracts be listed.
+        if !self.state.deployments.is_known(&call.contract_address) {
+            self.state.deployments.record(DeployedContract {
+                contract_address: call.contract_address,
+                class_hash: call.class_hash,
+                name: None,
+            });
+        }
+        self.state.enter_program(self.contexts.active());
+        self.state.call_trace.enter(call);
+        self.request_attention();
+        Ok(())
+    }
+
+    /// See [`crate::Debugger::exit_class`].
+    pub fn exit_class(&mut self, result: CallResult) -> Result<()> {
+        let return_value =
+            result.retdata.as_deref().and_then(|retdata| self.decode_return_value(retdata));
+        let entrypoint = self
+            .state
+            .call_stack
+            .entrypoint(self.state.current_statement_idx, self.contexts.active())
+            .map(|entrypoint| entrypoint.name.clone());
+        let failure = result.retdata.is_none().then(|| {
+            let callee = entrypoint.clone().unwrap_or_else(|| {
+                self.state.call_trace.active_call().map_or_else(
+                    || "Contract call".to_string(),
+                    |call| call.entry_point_selector.to_hex_string(),
+                )
+            });
+            (callee, panics::decode_panic_data(&result.panic_data))
+        });
+
+        self.contexts.exit()?;
+        self.state.exit_program(self.contexts.active());
+        self.request_attention();
+        self.state.syscall_trace.record_call_steps(result.resources.steps);
+        self.state.call_trace.exit(
+            result,
+            entrypoint,
+            return_value.as_ref().map(|(_, variable)| variable.value.clone()),
+        );
+
+        if let Some((entrypoint, variable)) = return_value {
+            if self.connection.is_connected() && !self.state.no_debug {
+                self.connection.send_event(Event::Output(OutputEventBody {
+                    category: Some(OutputEventCategory::Console),
+                    output: format!("{entrypoint} returned {}\n", variable.value),
+                    group: None,
+                    variables_reference: None,
+                    source: None,
+                    line: None,
+                    column: None,
+                    data: Some(json!({
+                        "event": "entrypointReturn",
+                        "entrypoint": entrypoint,
+                        "value": variable.value,
+                    })),
+                }))?;
+            }
+            self.state.call_stack.return_value = Some(variable);
+        }
+        if let Some((callee, message)) = failure
+            && !self.state.no_debug
+        {
+            self.send_output(
+                OutputEventCategory::Console,
+                format!("{callee} failed with {message}\n"),
+                json!({ "event": "callFailed", "entrypoint": callee, "panicMessage": message }),
+            )?;
+            self.state.failed_call_break_pending = self.state.break_on_failed_call;
+        }
+        Ok(())
+    }
+
+    /// Decodes the retdata of the entrypoint of the active contract, if its ABI is known.
+    /// Returns the name of the entrypoint and the pseudo-variable with the value.
+    fn decode_return_value(&self, retdata: &[Felt]) -> Option<(String, Variable)> {
+        let ctx = self.contexts.active();
+        let entrypoint = self.state.call_stack.entrypoint(self.state.current_statement_idx, ctx)?;
+        let value = ctx.abi.as_ref()?.decode_outputs(
+            entrypoint,
+            retdata,
+            self.state.call_stack.value_format,
+        )?;
+        let variable = Variable {
+            name: "Return value".to_string(),
+            value,
+            type_field: Some(entrypoint.outputs.join(", ")),
+            presentation_hint: Some(VariablePresentationHint {
+                kind: Some(VariablePresentationHintKind::Virtual),
+                ..Default::default()
+            }),
+            variables_reference: 0,
+            ..Default::default()
+        };
+        Some((entrypoint.name.clone(), variable))
+    }
+}
+
+/// Setup of the standalone runners, which execution tools embedding the debugger do not use.
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+impl CairoDebugger {
+    /// Test chosen with the `testFilter` launch argument, to be run instead of the function
+    /// given on the command line.
+    pub(crate) fn selected_test(&self) -> Option<&str> {
+        self.state.selected_test.as_deref()
+    }
+
+    /// See [`Context::offset_pcs`].
+    pub(crate) fn offset_program(&mut self, offset: usize) {
+        self.contexts.root_mut().offset_pcs(offset);
+        self.state.index_breakpoints(self.contexts.active());
+    }
+
+    /// Arguments of the run function, set in the launch configuration.
+    pub(crate) fn program_args(&self) -> &[ProgramArg] {
+        &self.state.program_args
+    }
+
+    /// Whether the output of the program should be shown in a terminal of the client,
+    /// see [`Self::run_in_terminal`].
+    pub(crate) fn uses_terminal(&self) -> bool {
+        if self.state.console == Console::InternalConsole {
+            return false;
+        }
+        if !self.state.supports_run_in_terminal {
+            warn!("The client cannot run commands in a terminal, printing the output here instead");
+            return false;
+        }
+        true
+    }
+
+    /// Asks the client to run `args` in the terminal chosen in the launch configuration.
+    /// The client does not wait for the command to finish, and neither does the debugger.
+    pub(crate) fn run_in_terminal(&self, args: Vec<String>) -> Result<()> {
+        let kind = match self.state.console {
+            Console::ExternalTerminal => RunInTerminalRequestArgumentsKind::External,
+            Console::IntegratedTerminal | Console::InternalConsole => {
+                RunInTerminalRequestArgumentsKind::Integrated
+            }
+        };
+        let cwd = self.state.cwd.as_ref().map(|cwd| cwd.display().to_string()).unwrap_or_default();
+
+        self.connection.send_reverse_request(ReverseCommand::RunInTerminal(
+            RunInTerminalRequestArguments {
+                kind: Some(kind),
+                title: self.state.test_name.clone(),
+                cwd,
+                args,
+                env: None,
+                args_can_be_interpreted_by_shell: None,
+            },
+        ))
+    }
+
+    /// Lets the client navigate an execution that already finished, whose memory is loaded
+    /// into `vm`, starting from the first step of `trace`. Nothing is executed, the session ends
+    /// once the client continues past the last recorded breakpoint. See [`post_mortem`].
+    /// The inputs of the execution, if they were saved with the trace, are shown in the output.
+    pub(crate) fn debug_post_mortem(
+        mut self,
+        trace: &[TraceEntry],
+        mut vm: impl VmAccess,
+        metadata: Option<ReplayMetadata>,
+    ) -> Result<()> {
+        let recording = ExecutionRecording::from_trace(trace)?;
+        self.state.start_post_mortem(recording, &mut vm, self.contexts.active())?;
+
+        if !self.state.no_debug {
+            if let Some(metadata) = metadata {
+                self.send_output(
+                    OutputEventCategory::Console,
+                    format!("{}\n", metadata.describe()),
+                    json!({ "event": "replayMetadata", "metadata": metadata.to_json() }),
+                )?;
+            }
+            self.pause_and_process_requests(StoppedEventReason::Entry, &mut vm)?;
+        }
+        self.shut_down()
+    }
+}
+
+impl CairoDebugger {
     fn initialize(&mut self) -> Result<()> {
         while !self.state.is_configuration_done() {
             // TODO(#35)
-            let request = self.connection.next_request()?;
-            self.process_request(request)?;
+            let Some(request) = self.next_request()? else {
+                break;
+            };
+            self.process_request(request, None)?;
+        }
+        for ctx in self.contexts.iter() {
+            self.report_stale_files(ctx)?;
+        }
+
+        self.watchdog = match self.state.watchdog_timeout {
+            Some(timeout) if self.connection.is_connected() && !self.state.no_debug => {
+                Some(Watchdog::start(
+                    timeout,
+                    self.state.pause_on_watchdog_timeout,
+                    self.connection.event_sender()?,
+                ))
+            }
+            _ => None,
+        };
+
+        self.ensure_not_terminated()
+    }
+
+    fn record_activity(&self, activity: Activity) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.record(activity);
+        }
+    }
+
+    /// Waits for the next client once the current one is gone, keeping the loaded program
+    /// and the state of the execution, so that the debuggee does not have to be restarted.
+    fn start_new_session(&mut self) -> Result<()> {
+        self.connection.accept_next_client()?;
+        self.state.reset_session();
+        self.initialize()
+    }
+
+    /// Returns `None` once the client is gone, unless the debuggee waits for the next one.
+    fn next_request(&mut self) -> Result<Option<Request>> {
+        self.record_activity(Activity::Idle);
+        while self.connection.is_connected() {
+            match self.connection.next_request() {
+                Ok(request) => return Ok(Some(request)),
+                Err(err) => self.handle_client_gone(&err)?,
+            }
+        }
+        Ok(None)
+    }
+
+    fn try_next_request(&mut self) -> Result<Option<Request>> {
+        if !self.connection.is_connected() {
+            return Ok(None);
+        }
+        match self.connection.try_next_request() {
+            Ok(request) => Ok(request),
+            Err(err) => {
+                self.handle_client_gone(&err)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Handles a client that is gone without a disconnect request, according to
+    /// [`State::disconnect_action`]. Unless the debuggee waits for the next client,
+    /// the execution is resumed, so that it is not stuck waiting for requests.
+    fn handle_client_gone(&mut self, err: &anyhow::Error) -> Result<()> {
+        let action = self.state.disconnect_action();
+        debug!("Client is gone: {err:#}");
+        self.connection.close_session();
+        // Nobody would receive its reports.
+        self.watchdog = None;
+
+        match action {
+            DisconnectAction::Terminate => self.state.request_termination(),
+            DisconnectAction::RunToCompletion => {
+                // The rest of the execution is the same as without debugging.
+                self.state.no_debug = true;
+                self.state.step_action = None;
+                self.state.resume_execution();
+            }
+            DisconnectAction::WaitForClient => self.start_new_session()?,
         }
 
         Ok(())
     }
 
-    fn sync_with_vm(&mut self, vm: &VirtualMachine) -> Result<()> {
-        self.state.update_state(vm, &self.ctx);
+    fn sync_with_vm(&mut self, vm: &mut dyn VmAccess, hints_data: &[Box<dyn Any>]) -> Result<()> {
+        // Free-running execution skips all the work below, see [`State::needs_every_step`].
+        if !self.connection.needs_attention() && !self.is_in_panic_function(vm) {
+            self.state.steps_skipped = true;
+            return Ok(());
+        }
 
-        self.maybe_handle_breakpoint_hit()?;
-        self.maybe_handle_step_action()?;
+        let sync_started = self.state.metrics.start_sync(self.state.steps_skipped);
+        // Requests answered by the connection while the VM was running take effect here.
+        self.connection.set_running(false);
+        if self.connection.take_terminate_interrupt() {
+            self.state.request_termination();
+        }
+        self.ensure_not_terminated()?;
 
-        while let Some(request) = self.connection.try_next_request()? {
-            self.process_request(request)?;
+        // Requests are not even polled, so that running without debugging costs as little
+        // as possible. The client is notified about the end of the execution anyway.
+        if self.state.no_debug {
+            self.refresh_attention();
+            return Ok(());
+        }
 
-            if self.state.is_execution_stopped() {
-                self.process_until_resume()?;
+        // Must happen before the state is updated, so that the growth is attributed
+        // to the statement whose instruction was just executed.
+        self.maybe_alert_segment_growth(vm)?;
+        if self.state.steps_skipped {
+            self.state.catch_up(vm, self.contexts.active());
+        }
+        self.state.update_state(vm, self.contexts.active());
+        if let Some(recording) = &mut self.state.recording {
+            recording.record_step(vm);
+        }
+        let stop_on_panic = match self.state.detect_panic(vm, self.contexts.active()) {
+            Some(panic) => self.report_panic(panic)?,
+            None => false,
+        };
+        self.state.hints = HintInfo::collect(vm, hints_data);
+        if let Some(recording) = &mut self.state.recording {
+            recording.record_hint_writes(&self.state.hints);
+        }
+        let stop_on_hint = self.state.hints.iter().any(|hint| {
+            self.state.hint_breakpoints.iter().any(|breakpoint| breakpoint.matches(hint))
+        });
+
+        if self.state.stop_on_entry {
+            // A breakpoint at the first statement would stop the execution for the second time.
+            self.state.stop_on_entry = false;
+            self.pause_and_process_requests(StoppedEventReason::Entry, vm)?;
+        } else if self.connection.take_pause_interrupt()
+            || self.watchdog.as_ref().is_some_and(Watchdog::take_pause_request)
+        {
+            self.pause_and_process_requests(StoppedEventReason::Pause, vm)?;
+        } else if mem::take(&mut self.state.fuzz_replay_stop_pending) {
+            self.pause_and_process_requests(StoppedEventReason::Entry, vm)?;
+        } else if let Some(description) = self.state.deployment_break_pending.take() {
+            self.pause_with_text(StoppedEventReason::Exception, Some(description), vm)?;
+        } else if mem::take(&mut self.state.fork_read_break_pending)
+            || mem::take(&mut self.state.failed_call_break_pending)
+            || stop_on_panic
+            || stop_on_hint
+        {
+            self.pause_and_process_requests(StoppedEventReason::Exception, vm)?;
+        } else if self.state.move_to_uncaught_panic(vm, self.contexts.active())? {
+            let description = self
+                .state
+                .panic
+                .as_ref()
+                .map(|panic| format!("Uncaught panic: {}", panic.describe()));
+            self.pause_with_text(StoppedEventReason::Exception, description, vm)?;
+        } else {
+            self.maybe_handle_breakpoint_hit(vm)?;
+        }
+        self.maybe_handle_step_action(vm)?;
+
+        // Checking the flag costs next to nothing, unlike polling the channel.
+        if self.connection.take_request_signal() {
+            while let Some(request) = self.try_next_request()? {
+                self.process_request(request, Some(&mut *vm))?;
+
+                if self.state.is_execution_stopped() {
+                    self.process_until_resume(vm)?;
+                }
             }
         }
 
+        // The value returned by a contract is relevant only until the caller moves on.
+        self.state.call_stack.return_value = None;
+
+        self.record_activity(Activity::Running);
+        self.refresh_attention();
+        self.connection.set_running(self.state.authenticated);
+        self.state.metrics.finish_sync(sync_started);
+
+        // Aborts the execution before the current instruction if the client requested it
+        // while the execution was stopped.
+        self.ensure_not_terminated()
+    }
+
+    /// Whether the next instruction belongs to a panic function, which the debugger must handle
+    /// to notice the panic, even if the VM runs freely otherwise.
+    fn is_in_panic_function(&self, vm: &dyn VmAccess) -> bool {
+        let pc = vm.pc();
+        pc.segment_index == 0 && self.contexts.active().is_panic_pc(pc.offset)
+    }
+
+    /// Lets the VM run freely unless the debugger must handle every instruction,
+    /// see [`Connection::needs_attention`].
+    fn refresh_attention(&self) {
+        self.connection.set_attention(self.state.needs_every_step());
+    }
+
+    /// Makes the debugger handle the next instruction, e.g. once the state changed between
+    /// instructions, which then decides whether it handles the following ones too.
+    fn request_attention(&self) {
+        self.connection.set_attention(true);
+    }
+
+    /// Returning an error from the hook is the only way to abort the run of the VM.
+    fn ensure_not_terminated(&self) -> Result<()> {
+        if self.state.is_termination_requested() {
+            bail!("Execution terminated by the client");
+        }
+        if self.state.is_rerun_requested() {
+            bail!("Re-run of the test requested by the client");
+        }
         Ok(())
     }
 
-    fn process_until_resume(&mut self) -> Result<()> {
+    fn process_until_resume(&mut self, vm: &mut dyn VmAccess) -> Result<()> {
+        self.state.metrics.record_stop();
+        self.state.call_stack.repair_from_vm(vm, self.contexts.active());
+
         while self.state.is_execution_stopped() {
-            let request = self.connection.next_request()?;
-            self.process_request(request)?;
+            let Some(request) = self.next_request()? else {
+                break;
+            };
+            self.process_request(request, Some(&mut *vm))?;
         }
+        // The execution resumes from where it actually stopped.
+        self.state.return_to_present(vm);
 
         Ok(())
     }
 
-    fn process_request(&mut self, request: Request) -> Result<()> {
-        let response = handler::handle_request(&request, &mut self.state, &self.ctx)?;
-        let disconnected = matches!(response.response_body, ResponseBody::Disconnect);
+    /// `vm` is `None` if the request is processed before the execution has started.
+    fn process_request(&mut self, request: Request, vm: Option<&mut dyn VmAccess>) -> Result<()> {
+        // Logs of the handling of the request, however deep, tell which request they are about.
+        let _span =
+            info_span!("request", seq = request.seq, command = command_name(&request.command))
+                .entered();
+        if !self.state.authenticated
+            && let Err(err) = self.authenticate()
+        {
+            error!("Rejected a client: {err:#}");
+            self.connection.send_error(request, &format!("{err:#}"))?;
+            // Do not let the client try again with another token.
+            return self.start_new_session();
+        }
+
+        self.reload_rebuilt_programs()?;
+        self.record_activity(Activity::Request(command_name(&request.command)));
+        let cancelled = self.connection.take_cancellation(request.seq);
+        let response = match middleware::handle_request(
+            &request,
+            cancelled,
+            &mut self.state,
+            &mut self.contexts,
+            vm,
+        ) {
+            Ok(response) => response,
+            Err(err) => return self.send_request_error(request, &err),
+        };
+        let terminate_debuggee = match &request.command {
+            // Unless requested otherwise, a launched debuggee is stopped together with the session,
+            // while an attached one keeps running.
+            Command::Disconnect(args) => {
+                Some(args.terminate_debuggee.unwrap_or(!self.state.attached))
+            }
+            _ => None,
+        };
 
-        if let Some(event) = response.event {
+        for event in response.events {
             self.connection.send_event(event)?;
         }
         self.connection.send_success(request, response.response_body)?;
 
-        if disconnected {
-            // Returning an error is the easiest way to get the process to exit.
-            return Err(anyhow!("Disconnect request received"));
+        match terminate_debuggee {
+            Some(true) => {
+                self.connection.close_session();
+                // Returning an error is the easiest way to get the process to exit.
+                return Err(anyhow!("Disconnect request received"));
+            }
+            Some(false) => self.start_new_session()?,
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Fails the request, with the details of the error for the client to show to the user
+    /// if it is a [`DebuggerError`].
+    fn send_request_error(&self, request: Request, err: &anyhow::Error) -> Result<()> {
+        let message = format!("{err:#}");
+        match err.downcast_ref::<DebuggerError>() {
+            Some(error) => {
+                self.connection.send_error_with_details(request, &message, error.to_message())
+            }
+            None => self.connection.send_error(request, &message),
         }
+    }
 
+    /// Checks the auth token of the client at its first request, whatever it is,
+    /// see [`DebugServer::with_auth_token`].
+    fn authenticate(&mut self) -> Result<()> {
+        self.connection.authenticate()?;
+        self.state.authenticated = true;
         Ok(())
     }
 
-    fn maybe_handle_step_action(&mut self) -> Result<()> {
-        let current_line =
-            Line::create_from_statement_idx(self.state.current_statement_idx, &self.ctx);
+    fn maybe_handle_step_action(&mut self, vm: &mut dyn VmAccess) -> Result<()> {
+        // Free-running execution does not need to know the current line.
+        if self.state.step_action.is_none() {
+            return Ok(());
+        }
+        let current_line = Line::create_from_statement_idx(
+            self.state.current_statement_idx,
+            self.contexts.active(),
+        );
 
         let stop = match &self.state.step_action {
             Some(StepAction::StepIn { prev_line }) if *prev_line != current_line => true,
@@ -113,48 +799,646 @@ impl CairoDebugger {
             _ => false,
         };
 
-        if stop {
+        // Keep stepping until the execution gets back to the user code.
+        let skip =
+            self.state.skips_statement(self.state.current_statement_idx, self.contexts.active());
+
+        if stop && !skip {
             self.state.step_action = None;
-            self.pause_and_process_requests(StoppedEventReason::Step)?;
+            self.pause_and_process_requests(StoppedEventReason::Step, vm)?;
+        }
+
+        Ok(())
+    }
+
+    fn maybe_handle_breakpoint_hit(&mut self, vm: &mut dyn VmAccess) -> Result<()> {
+        if self.state.was_breakpoint_hit(vm.pc().offset, self.contexts.active()) {
+            self.pause_and_process_requests(StoppedEventReason::Breakpoint, vm)?;
+        }
+
+        Ok(())
+    }
+
+    /// See [`crate::Debugger::start_test`].
+    pub fn start_test(&mut self, test_path: &str) -> Result<()> {
+        let (exited, started) = self.state.start_test(test_path.to_string());
+        if let Some(exited) = exited {
+            self.send_thread_event(ThreadEventReason::Exited, exited)?;
+        }
+        self.send_thread_event(ThreadEventReason::Started, started)
+    }
+
+    /// See [`crate::Debugger::finish_test`].
+    pub fn finish_test(&mut self) -> Result<()> {
+        self.report_outcome()?;
+        match self.state.finish_test() {
+            Some(exited) => self.send_thread_event(ThreadEventReason::Exited, exited),
+            None => Ok(()),
+        }
+    }
+
+    /// See [`crate::Debugger::enable_test_rerun`].
+    pub fn enable_test_rerun(&mut self) -> Result<()> {
+        self.state.supports_test_rerun = true;
+        if !self.connection.is_connected() {
+            return Ok(());
+        }
+        // The capabilities were sent before the tool could enable re-runs.
+        self.connection.send_event(Event::Capabilities(CapabilitiesEventBody {
+            capabilities: Capabilities {
+                supports_restart_request: Some(true),
+                ..Default::default()
+            },
+        }))
+    }
+
+    /// See [`crate::Debugger::take_rerun_request`].
+    pub fn take_rerun_request(&mut self) -> Result<bool> {
+        if !self.state.is_rerun_requested() {
+            return Ok(false);
+        }
+        self.contexts.exit_all();
+        self.state.restart_test(self.contexts.root());
+        let test = self.state.thread_name();
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("Re-running {test}\n"),
+            json!({ "event": "testRerun", "test": test }),
+        )?;
+        Ok(true)
+    }
+
+    /// See [`crate::Debugger::expect_panic`].
+    pub fn expect_panic(&mut self, expected: ExpectedPanic) {
+        self.state.expected_panic = Some(expected);
+    }
+
+    /// See [`crate::Debugger::start_fuzz_iteration`].
+    pub fn start_fuzz_iteration(&mut self, iteration: FuzzIteration) -> Result<()> {
+        if iteration.is_failure_replay && !self.state.no_debug {
+            self.send_output(
+                OutputEventCategory::Console,
+                format!(
+                    "Replaying failing fuzz iteration {} (seed {})\n",
+                    iteration.number, iteration.seed
+                ),
+                json!({
+                    "event": "fuzzFailureReplay",
+                    "iteration": iteration.number,
+                    "seed": iteration.seed,
+                }),
+            )?;
+        }
+        self.state.start_fuzz_iteration(iteration);
+        self.request_attention();
+        Ok(())
+    }
+
+    fn send_thread_event(&self, reason: ThreadEventReason, thread_id: i64) -> Result<()> {
+        if !self.connection.is_connected() {
+            return Ok(());
+        }
+        self.connection.send_event(Event::Thread(ThreadEventBody { reason, thread_id }))
+    }
+
+    /// See [`crate::Debugger::set_storage_reader`].
+    pub fn set_storage_reader(&mut self, reader: impl StorageReader + 'static) {
+        self.state.storage.reader = Some(Box::new(reader));
+    }
+
+    /// See [`crate::Debugger::set_view_caller`].
+    pub fn set_view_caller(&mut self, caller: impl ViewCaller + 'static) {
+        self.state.view_caller = Some(Box::new(caller));
+    }
+
+    /// See [`crate::Debugger::report_fork_origin`].
+    pub fn report_fork_origin(&mut self, origin: ForkOrigin) {
+        self.state.fork_origin = Some(origin);
+    }
+
+    /// See [`crate::Debugger::report_fork_read`].
+    pub fn report_fork_read(&mut self, read: ForkRead) -> Result<()> {
+        self.state.fork_reads += 1;
+        if self.state.no_debug {
+            return Ok(());
+        }
+
+        // Reads are served while a syscall or a cheatcode is executed, hence the execution
+        // can only stop at the next instruction.
+        if self.state.fork_reads == 1 && self.state.break_on_fork_read {
+            self.state.fork_read_break_pending = true;
+        }
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("{}\n", read.describe()),
+            json!({ "event": "forkRead", "read": read.to_json() }),
+        )
+    }
+
+    /// See [`crate::Debugger::report_execution_info`].
+    pub fn report_execution_info(&mut self, info: ExecutionInfo) {
+        self.state.call_trace.record_execution_info(info);
+    }
+
+    /// See [`crate::Debugger::report_deployment`].
+    pub fn report_deployment(&mut self, contract: DeployedContract) -> Result<()> {
+        self.state.deployments.record(contract.clone());
+        if self.state.no_debug {
+            return Ok(());
+        }
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("{}\n", contract.describe()),
+            json!({
+                "event": "contractDeployed",
+                "address": contract.contract_address.to_hex_string(),
+                "classHash": contract.class_hash.to_hex_string(),
+                "name": contract.name,
+            }),
+        )
+    }
+
+    /// Inspects the hints of the next instruction for cheatcodes and syscalls worth reporting.
+    /// The hints are executed once the execution moves on.
+    fn inspect_hints(&mut self, vm: &dyn VmAccess, hints_data: &[Box<dyn Any>]) -> Result<()> {
+        if self.state.no_debug {
+            return Ok(());
+        }
+
+        self.report_cheatcodes(vm, hints_data)?;
+
+        let Some(syscall) = Syscall::find(vm, hints_data) else {
+            return Ok(());
+        };
+        let contract_address =
+            self.state.call_trace.active_call().map(|call| call.contract_address);
+        if let Some(contract_address) = contract_address {
+            self.state.storage.record_syscall(vm, &syscall, contract_address);
+        }
+        let depth = self.state.program_depth();
+        self.state.syscall_trace.start(vm, &syscall, contract_address, depth);
+        if syscall.is("EmitEvent") {
+            self.report_event(vm, &syscall)?;
+        }
+        if syscall.is("Deploy") {
+            self.report_deploy(vm, &syscall)?;
+        }
+        if syscall.is("GetExecutionInfo") {
+            self.state.pending_execution_info = syscall.execution_info_address();
+        }
+        Ok(())
+    }
+
+    /// Reports the deployment in the output, with the constructor calldata decoded
+    /// with the ABI of the class if it is registered.
+    fn report_deploy(&mut self, vm: &dyn VmAccess, syscall: &Syscall) -> Result<()> {
+        let Some((class_hash, calldata)) = syscall.read_deploy(vm) else {
+            return Ok(());
+        };
+        let abi = self.contexts.class(&class_hash).and_then(|ctx| ctx.abi.as_ref());
+        let description = deployments::describe_deploy(
+            class_hash,
+            &calldata,
+            abi,
+            self.state.call_stack.value_format,
+        );
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("{description}\n"),
+            json!({
+                "event": "deploy",
+                "classHash": class_hash.to_hex_string(),
+                "calldata": calldata.iter().map(Felt::to_hex_string).collect::<Vec<_>>(),
+            }),
+        )?;
+        if self.state.break_on_deploy {
+            self.state.deployment_break_pending = Some(description);
+        }
+        Ok(())
+    }
+
+    /// Records the execution info returned by the syscall of the last instruction, if any.
+    fn finish_execution_info(&mut self, vm: &dyn VmAccess) {
+        let Some(address) = self.state.pending_execution_info.take() else {
+            return;
+        };
+        if let Some(info_address) = vm.read_pointer(address)
+            && let Some(info) = ExecutionInfo::read(vm, info_address)
+        {
+            self.state.call_trace.record_execution_info(info);
+        }
+    }
+
+    /// Reports the class declared by the last instruction, if any, once its hash is known.
+    fn finish_declaration(&mut self, vm: &dyn VmAccess) -> Result<()> {
+        let Some(declaration) = self.state.pending_declaration.take() else {
+            return Ok(());
+        };
+        let class_hash = cheatcodes::read_output(vm, declaration.output_cells)
+            .and_then(|output| deployments::declared_class_hash(&output));
+        let description = match class_hash {
+            Some(class_hash) => {
+                format!("Declared {} as class {}", declaration.name, class_hash.to_hex_string())
+            }
+            None => format!("Declaring {} failed", declaration.name),
+        };
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("{description}\n"),
+            json!({
+                "event": "declare",
+                "name": declaration.name,
+                "classHash": class_hash.as_ref().map(Felt::to_hex_string),
+            }),
+        )?;
+        if self.state.break_on_declare {
+            self.state.deployment_break_pending = Some(description);
+        }
+        Ok(())
+    }
+
+    /// Reports the panic in the output. Returns whether the execution should stop there.
+    fn report_panic(&mut self, panic: Panic) -> Result<bool> {
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("{}\n", panic.describe()),
+            json!({ "event": "panic", "panic": panic.to_json() }),
+        )?;
+        let stop = self.state.break_on_panic && !panic.expected;
+        if !self.state.is_in_nested_program() && self.state.panic.is_none() {
+            self.state.panic_step =
+                self.state.recording.as_ref().and_then(ExecutionRecording::last_step);
+            self.state.panic = Some(panic);
+        }
+        Ok(stop)
+    }
+
+    /// Reports whether the execution succeeded, taking the expected panics into account.
+    fn report_outcome(&self) -> Result<()> {
+        let outcome = self.state.outcome();
+        self.send_output(
+            OutputEventCategory::Console,
+            format!("{}\n", outcome.describe()),
+            json!({ "event": "outcome", "outcome": outcome.name() }),
+        )
+    }
+
+    /// Completes the syscall executed by the last instruction, if any, and reports it
+    /// in the output if requested.
+    fn finish_syscall(&mut self, vm: &dyn VmAccess) -> Result<()> {
+        let depth = self.state.program_depth();
+        let Some(record) = self.state.syscall_trace.finish(vm, depth) else {
+            return Ok(());
+        };
+        if !self.state.stream_syscalls {
+            return Ok(());
+        }
+        let output = format!("Syscall {}\n", record.describe());
+        let data = json!({ "event": "syscall", "syscall": record.to_json() });
+        self.send_output(OutputEventCategory::Console, output, data)
+    }
+
+    /// Reports the cheatcodes in the output and records them in the call trace.
+    fn report_cheatcodes(&mut self, vm: &dyn VmAccess, hints_data: &[Box<dyn Any>]) -> Result<()> {
+        for cheatcode in cheatcodes::find_cheatcodes(vm, hints_data) {
+            if cheatcode.name == DECLARE_CHEATCODE
+                && let Some(output_cells) = cheatcode.output_cells
+                && let Some(name) = abi::decode_byte_array(&mut cheatcode.input.iter())
+            {
+                self.state.pending_declaration = Some(PendingDeclaration { name, output_cells });
+            }
+            self.send_output(
+                OutputEventCategory::Console,
+                format!("Cheatcode {}\n", cheatcode.describe()),
+                json!({ "event": "cheatcode", "cheatcode": cheatcode.to_json() }),
+            )?;
+            self.state.call_trace.record_cheatcode(cheatcode);
         }
+        Ok(())
+    }
+
+    /// Reports the emitted event in the output, decoded with the ABI of the contract if known.
+    fn report_event(&self, vm: &dyn VmAccess, syscall: &Syscall) -> Result<()> {
+        let Some((keys, data)) = syscall.read_event(vm) else {
+            return Ok(());
+        };
+        let event = self
+            .contexts
+            .active()
+            .abi
+            .as_ref()
+            .and_then(|abi| abi.decode_event(&keys, &data, self.state.call_stack.value_format));
+
+        let to_hex = |felts: &[Felt]| felts.iter().map(Felt::to_hex_string).collect::<Vec<_>>();
+        let output = match &event {
+            Some(event) => {
+                let fields: Vec<String> =
+                    event.fields.iter().map(|(name, value)| format!("{name}: {value}")).collect();
+                format!("Event {} {{ {} }}\n", event.name, fields.join(", "))
+            }
+            None => format!(
+                "Event with keys [{}] and data [{}]\n",
+                to_hex(&keys).join(", "),
+                to_hex(&data).join(", ")
+            ),
+        };
+        let fields: Option<serde_json::Map<String, Value>> = event.as_ref().map(|event| {
+            event.fields.iter().map(|(name, value)| (name.clone(), json!(value))).collect()
+        });
+        let contract_address =
+            self.state.call_trace.active_call().map(|call| call.contract_address.to_hex_string());
 
+        self.send_output(
+            OutputEventCategory::Stdout,
+            output,
+            json!({
+                "event": "contractEvent",
+                "contractAddress": contract_address,
+                "name": event.as_ref().map(|event| &event.name),
+                "fields": fields,
+                "keys": to_hex(&keys),
+                "data": to_hex(&data),
+            }),
+        )
+    }
+
+    /// Reloads the programs rebuilt since the last check, e.g. by `scarb build` run during
+    /// the session, and tells the client about the breakpoints and modules that changed.
+    /// A program whose code changed is not reloaded, since the execution still runs the
+    /// previous code, and the user is told to restart the session instead.
+    fn reload_rebuilt_programs(&mut self) -> Result<()> {
+        for path in self.artifact_watcher.take_changed() {
+            match self.contexts.reload(&path) {
+                Ok(true) => {
+                    debug!("Reloaded {}", path.display());
+                    let mut events: Vec<Event> = self
+                        .state
+                        .reverify_breakpoints(&self.contexts)
+                        .into_iter()
+                        .map(|breakpoint| {
+                            Event::Breakpoint(BreakpointEventBody {
+                                reason: BreakpointEventReason::Changed,
+                                breakpoint,
+                            })
+                        })
+                        .collect();
+                    let modules = self
+                        .contexts
+                        .iter()
+                        .filter(|ctx| ctx.sierra_path == path)
+                        .flat_map(handler::build_modules);
+                    events.extend(modules.map(|module| {
+                        Event::Module(ModuleEventBody {
+                            reason: ModuleEventReason::Changed,
+                            module,
+                        })
+                    }));
+                    if self.connection.is_connected() {
+                        for event in events {
+                            self.connection.send_event(event)?;
+                        }
+                    }
+                    for ctx in self.contexts.iter().filter(|ctx| ctx.sierra_path == path) {
+                        self.report_stale_files(ctx)?;
+                    }
+                }
+                Ok(false) => self.send_output(
+                    OutputEventCategory::Important,
+                    format!(
+                        "{} was rebuilt with changes to the code, which the running execution \
+                         does not have. Restart the session to debug the new build.\n",
+                        path.display()
+                    ),
+                    json!({ "event": "programRebuilt", "path": path }),
+                )?,
+                // E.g. the build failed halfway, and the next one is reloaded.
+                Err(err) => warn!("Reloading {} failed: {err:#}", path.display()),
+            }
+        }
         Ok(())
     }
 
-    fn maybe_handle_breakpoint_hit(&mut self) -> Result<()> {
-        if self.state.was_breakpoint_hit(&self.ctx) {
-            self.pause_and_process_requests(StoppedEventReason::Breakpoint)?;
+    /// Tells the user about the files modified since the program was compiled, for which
+    /// breakpoints and the stopped line are likely off.
+    fn report_stale_files(&self, ctx: &Context) -> Result<()> {
+        let stale_files = ctx.source_files.stale_files();
+        if stale_files.is_empty() || !self.connection.is_connected() {
+            return Ok(());
+        }
+        let files: String =
+            stale_files.iter().map(|path| format!("  {}\n", path.display())).collect();
+        self.connection.send_event(Event::Output(OutputEventBody {
+            category: Some(OutputEventCategory::Important),
+            output: format!(
+                "These files were modified after {} was compiled, so their lines may not match \
+                 the execution. Rebuild the program to debug them.\n{files}",
+                ctx.sierra_path.display()
+            ),
+            group: None,
+            variables_reference: None,
+            source: None,
+            line: None,
+            column: None,
+            data: Some(json!({
+                "event": "staleFiles",
+                "program": ctx.sierra_path,
+                "paths": stale_files,
+            })),
+        }))
+    }
+
+    /// Sends the output to the client, if there is one, pointing at the current statement.
+    fn send_output(
+        &self,
+        category: OutputEventCategory,
+        output: String,
+        data: Value,
+    ) -> Result<()> {
+        if !self.connection.is_connected() {
+            return Ok(());
+        }
+        let (source, line, column) = self.current_location();
+        self.connection.send_event(Event::Output(OutputEventBody {
+            category: Some(category),
+            output,
+            group: None,
+            variables_reference: None,
+            source,
+            line,
+            column,
+            data: Some(data),
+        }))
+    }
+
+    /// Source, line and column of the current statement, as expected in events.
+    fn current_location(&self) -> (Option<Source>, Option<i64>, Option<i64>) {
+        let ctx = self.contexts.active();
+        match ctx.code_location_for_statement_idx(self.state.current_statement_idx) {
+            Some(CodeLocation(SourceFileFullPath(path), code_span, _)) => (
+                Some(ctx.source_files.source(path)),
+                // UI expects 1-indexed lines and columns.
+                Some((code_span.start.line.0 + 1) as i64),
+                Some((code_span.start.col.0 + 1) as i64),
+            ),
+            None => (None, None, None),
+        }
+    }
+
+    fn maybe_alert_segment_growth(&mut self, vm: &mut dyn VmAccess) -> Result<()> {
+        // Alerts are meant for free-running execution only.
+        if self.state.step_action.is_some() {
+            return Ok(());
+        }
+        let Some(monitor) = &mut self.state.segment_growth_monitor else {
+            return Ok(());
+        };
+
+        let limit = monitor.limit();
+        for (segment_index, size) in monitor.check(vm) {
+            let (source, line, column) = self.current_location();
+
+            self.connection.send_event(Event::Output(OutputEventBody {
+                category: Some(OutputEventCategory::Stderr),
+                output: format!(
+                    "Warning: segment {segment_index} grew to {size} cells, exceeding the limit of {limit} cells\n"
+                ),
+                group: None,
+                variables_reference: None,
+                source,
+                line,
+                column,
+                data: Some(json!({
+                    "event": "segmentGrowth",
+                    "segmentIndex": segment_index,
+                    "size": size,
+                    "limit": limit,
+                    "statementIdx": self.state.current_statement_idx.0,
+                })),
+            }))?;
         }
 
         Ok(())
     }
 
-    fn pause_and_process_requests(&mut self, reason: StoppedEventReason) -> Result<()> {
+    fn pause_and_process_requests(
+        &mut self,
+        reason: StoppedEventReason,
+        vm: &mut dyn VmAccess,
+    ) -> Result<()> {
+        self.pause_with_text(reason, None, vm)
+    }
+
+    /// Like [`Self::pause_and_process_requests`], with `text` describing the stop to the user.
+    fn pause_with_text(
+        &mut self,
+        reason: StoppedEventReason,
+        text: Option<String>,
+        vm: &mut dyn VmAccess,
+    ) -> Result<()> {
         self.state.stop_execution();
-        self.connection.send_event(Event::Stopped(StoppedEventBody {
+        // The client fetches the stack trace of the stop right away.
+        self.reload_rebuilt_programs()?;
+        // A pause the client asked for in the meantime is served by this stop.
+        self.connection.take_pause_interrupt();
+        let stopped = self.connection.send_event(Event::Stopped(StoppedEventBody {
             reason,
-            thread_id: Some(MAX_OBJECT_REFERENCE),
+            thread_id: Some(self.state.thread_id()),
             all_threads_stopped: Some(true),
-            // Breakpoint IDs are not set in `SetBreakpointsResponse`, hence we set them to `None` also here.
+            // Statements of breakpoints are not mapped back to their IDs.
             // This would matter if we supported multiple breakpoints per line, but currently we don't.
             hit_breakpoint_ids: None,
             description: None,
             preserve_focus_hint: None,
-            text: None,
-        }))?;
-        self.process_until_resume()
+            text,
+        }));
+        // The client may be gone before the reader thread noticed it.
+        if let Err(err) = stopped {
+            self.handle_client_gone(&err)?;
+        }
+        self.process_until_resume(vm)
+    }
+
+    /// See [`crate::Debugger::shut_down`].
+    pub fn shut_down(mut self) -> Result<()> {
+        let ended = self.end_session();
+        self.connection.close_session();
+        ended
+    }
+
+    fn end_session(&mut self) -> Result<()> {
+        // The client that disconnected does not expect any more messages.
+        if !self.connection.is_connected() {
+            return Ok(());
+        }
+        // The client may have terminated the execution after its last instruction.
+        if self.connection.take_terminate_interrupt() {
+            self.state.request_termination();
+        }
+
+        // Outcomes of the tests of a session covering multiple tests were reported
+        // once each of them finished.
+        if !self.state.is_termination_requested() && self.state.thread.is_some() {
+            self.report_outcome().context("Sending outcome failed")?;
+        }
+        self.connection.send_event(Terminated(None)).context("Sending terminated event failed")?;
+
+        let exit_code = if self.state.is_termination_requested() {
+            TERMINATED_EXIT_CODE
+        } else {
+            self.state.outcome().exit_code()
+        };
+        self.connection
+            .send_event(Exited(ExitedEventBody { exit_code }))
+            .context("Sending exit event failed")?;
+
+        self.answer_disconnect();
+        Ok(())
+    }
+
+    /// Waits a while for the disconnect request that clients send once the session terminated,
+    /// and answers it. Other requests sent meanwhile fail, as there is nothing left to debug.
+    fn answer_disconnect(&mut self) {
+        let deadline = Instant::now() + DISCONNECT_TIMEOUT;
+        loop {
+            let request = match self.connection.next_request_until(deadline) {
+                Ok(Some(request)) => request,
+                Ok(None) => {
+                    debug!("The client did not disconnect in time");
+                    return;
+                }
+                // The client is gone already.
+                Err(_) => return,
+            };
+            let disconnect = matches!(request.command, Command::Disconnect(_));
+            let answered = if disconnect {
+                self.connection.send_success(request, ResponseBody::Disconnect)
+            } else {
+                self.connection.send_error(request, "The execution has ended")
+            };
+            if let Err(err) = answered {
+                error!("Answering a request after the execution ended failed: {err:#}");
+                return;
+            }
+            if disconnect {
+                return;
+            }
+        }
     }
 }
 
+/// Name of the command as it appears in the DAP, e.g. `StackTrace` for `stackTrace`.
+fn command_name(command: &Command) -> String {
+    let debug = format!("{command:?}");
+    debug.split(['(', ' ', '{']).next().unwrap_or_default().to_string()
+}
+
+/// Fallback for execution tools that let the VM owning the debugger as its step hooks drop it,
+/// instead of calling [`CairoDebugger::shut_down`].
 impl Drop for CairoDebugger {
     fn drop(&mut self) {
-        if let Err(err) = self.connection.send_event(Terminated(None)) {
-            error!("Sending terminated event failed: {}", err);
-        }
-
-        // TODO(#34): Send correct exit code
-        if let Err(err) = self.connection.send_event(Exited(ExitedEventBody { exit_code: 0 })) {
-            error!("Sending exit event failed: {}", err);
+        if let Err(err) = self.end_session() {
+            error!("Ending the session failed: {err:#}");
         }
+        self.connection.close_session();
     }
 }
@@ -0,0 +1,431 @@
+//! ABI of a contract class, used to decode the raw felts passed to and returned from
+//! its entrypoints into typed values.
+
+use std::collections::HashMap;
+use std::slice::Iter;
+
+use anyhow::{Context as AnyhowContext, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::launch_args::ValueFormat;
+
+/// Prefix of the names of Sierra functions wrapping contract entrypoints,
+/// e.g. `hello::HelloStarknet::__wrapper__HelloStarknetImpl__increase_balance`.
+const WRAPPER_PREFIX: &str = "__wrapper__";
+
+const BYTE_ARRAY_TYPE: &str = "core::byte_array::ByteArray";
+
+/// Number of bytes stored in a single word of a `ByteArray`.
+const BYTES_IN_WORD: usize = 31;
+
+pub struct ContractAbi {
+    /// Entrypoints by name, including the ones of interfaces.
+    functions: HashMap<String, AbiFunction>,
+    structs: HashMap<String, Vec<AbiMember>>,
+    enums: HashMap<String, Vec<AbiMember>>,
+    events: HashMap<String, AbiEvent>,
+}
+
+pub struct AbiFunction {
+    pub name: String,
+    pub inputs: Vec<AbiMember>,
+    /// Types of the returned values.
+    pub outputs: Vec<String>,
+    pub kind: EntrypointKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EntrypointKind {
+    External,
+    Constructor,
+    /// Handler of messages sent from L1, whose first input is the address of the sender.
+    L1Handler,
+}
+
+/// Parameter of a function, member of a struct or variant of an enum.
+#[derive(Deserialize)]
+pub struct AbiMember {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// Member of a struct event or variant of an enum event.
+#[derive(Deserialize)]
+struct AbiEventMember {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    /// `key` or `data` for members, `nested` or `flat` for variants.
+    kind: String,
+}
+
+enum AbiEvent {
+    Struct(Vec<AbiEventMember>),
+    Enum(Vec<AbiEventMember>),
+}
+
+/// Event emitted by a contract, with its fields decoded.
+pub struct DecodedEvent {
+    pub name: String,
+    pub fields: Vec<(String, String)>,
+}
+
+#[derive(Deserialize)]
+struct AbiOutput {
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AbiItem {
+    Function {
+        name: String,
+        inputs: Vec<AbiMember>,
+        outputs: Vec<AbiOutput>,
+    },
+    Constructor {
+        name: String,
+        inputs: Vec<AbiMember>,
+    },
+    L1Handler {
+        name: String,
+        inputs: Vec<AbiMember>,
+        outputs: Vec<AbiOutput>,
+    },
+    Interface {
+        items: Vec<AbiItem>,
+    },
+    Struct {
+        name: String,
+        members: Vec<AbiMember>,
+    },
+    Enum {
+        name: String,
+        variants: Vec<AbiMember>,
+    },
+    Event {
+        name: String,
+        kind: String,
+        #[serde(default)]
+        members: Vec<AbiEventMember>,
+        #[serde(default)]
+        variants: Vec<AbiEventMember>,
+    },
+    /// Impls do not affect the encoding of values.
+    #[serde(other)]
+    Other,
+}
+
+impl ContractAbi {
+    /// Parses the ABI of a contract class, given either as a JSON array, like in Scarb artifacts,
+    /// or as a string with its JSON, like in classes returned by Starknet nodes.
+    pub fn parse(abi: &Value) -> Result<Self> {
+        let items: Vec<AbiItem> = match abi {
+            Value::String(abi) => serde_json::from_str(abi),
+            abi => serde_json::from_value(abi.clone()),
+        }
+        .context("Invalid contract ABI")?;
+
+        let mut contract_abi = Self {
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            events: HashMap::new(),
+        };
+        contract_abi.add_items(items);
+        Ok(contract_abi)
+    }
+
+    fn add_items(&mut self, items: Vec<AbiItem>) {
+        for item in items {
+            match item {
+                AbiItem::Function { name, inputs, outputs } => {
+                    self.add_function(name, inputs, outputs, EntrypointKind::External);
+                }
+                AbiItem::L1Handler { name, inputs, outputs } => {
+                    self.add_function(name, inputs, outputs, EntrypointKind::L1Handler);
+                }
+                AbiItem::Constructor { name, inputs } => {
+                    self.add_function(name, inputs, Vec::new(), EntrypointKind::Constructor);
+                }
+                AbiItem::Interface { items } => self.add_items(items),
+                AbiItem::Struct { name, members } => {
+                    self.structs.insert(name, members);
+                }
+                AbiItem::Enum { name, variants } => {
+                    self.enums.insert(name, variants);
+                }
+                AbiItem::Event { name, kind, members, variants } => {
+                    let event = if kind == "enum" {
+                        AbiEvent::Enum(variants)
+                    } else {
+                        AbiEvent::Struct(members)
+                    };
+                    self.events.insert(name, event);
+                }
+                AbiItem::Other => {}
+            }
+        }
+    }
+
+    fn add_function(
+        &mut self,
+        name: String,
+        inputs: Vec<AbiMember>,
+        outputs: Vec<AbiOutput>,
+        kind: EntrypointKind,
+    ) {
+        let outputs = outputs.into_iter().map(|output| output.ty).collect();
+        self.functions.insert(name.clone(), AbiFunction { name, inputs, outputs, kind });
+    }
+
+    /// Returns the entrypoint wrapped by the Sierra function, if it is an entrypoint wrapper.
+    pub fn function(&self, name: &str) -> Option<&AbiFunction> {
+        self.functions.get(name)
+    }
+
+    pub fn entrypoint(&self, sierra_function_name: &str) -> Option<&AbiFunction> {
+        self.functions.get(wrapped_function_name(sierra_function_name)?)
+    }
+
+    /// Decodes a value of type `ty` from `felts`, in the Serde encoding used for calldata
+    /// and return data. Returns `None` if the type is unknown or `felts` end too early,
+    /// in which case the following values cannot be decoded either.
+    pub fn decode(
+        &self,
+        ty: &str,
+        felts: &mut Iter<'_, Felt>,
+        value_format: ValueFormat,
+    ) -> Option<String> {
+        let ty = ty.trim();
+        if ty == "()" {
+            return Some("()".to_string());
+        }
+        if let Some(items) = ty.strip_prefix('(').and_then(|ty| ty.strip_suffix(')')) {
+            let values = split_top_level(items)
+                .into_iter()
+                .map(|item| self.decode(item, felts, value_format))
+                .collect::<Option<Vec<_>>>()?;
+            return Some(format!("({})", values.join(", ")));
+        }
+        if let Some(element) = generic_argument(ty, "core::array::Array")
+            .or_else(|| generic_argument(ty, "core::array::Span"))
+        {
+            let len = felt_to_usize(felts.next()?)?;
+            // Guards against a garbage length, which would otherwise allocate a lot.
+            if len > felts.len() {
+                return None;
+            }
+            let elements = (0..len)
+                .map(|_| self.decode(element, felts, value_format))
+                .collect::<Option<Vec<_>>>()?;
+            return Some(format!("[{}]", elements.join(", ")));
+        }
+        if ty == BYTE_ARRAY_TYPE {
+            return decode_byte_array(felts);
+        }
+        if let Some(members) = self.structs.get(ty) {
+            let members = members
+                .iter()
+                .map(|member| {
+                    let value = self.decode(&member.ty, felts, value_format)?;
+                    Some(format!("{}: {value}", member.name))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            return Some(format!("{} {{ {} }}", short_type_name(ty), members.join(", ")));
+        }
+        if let Some(variants) = self.enums.get(ty) {
+            let variant = variants.get(felt_to_usize(felts.next()?)?)?;
+            let name = format!("{}::{}", short_type_name(ty), variant.name);
+            return Some(match self.decode(&variant.ty, felts, value_format)?.as_str() {
+                "()" => name,
+                payload => format!("{name}({payload})"),
+            });
+        }
+
+        // All other types, e.g. integers and addresses, consist of a single felt.
+        Some(display_felt(ty, felts.next()?, value_format))
+    }
+
+    /// Decodes an event emitted by the contract. Events are variants of the `Event` enum
+    /// of the contract, identified by the first key, which is the selector of the variant.
+    pub fn decode_event(
+        &self,
+        keys: &[Felt],
+        data: &[Felt],
+        value_format: ValueFormat,
+    ) -> Option<DecodedEvent> {
+        self.events.iter().filter(|(name, _)| name.ends_with("::Event")).find_map(|(_, event)| {
+            let AbiEvent::Enum(variants) = event else {
+                return None;
+            };
+            self.decode_event_variant(variants, keys, data, value_format)
+        })
+    }
+
+    fn decode_event_variant(
+        &self,
+        variants: &[AbiEventMember],
+        keys: &[Felt],
+        data: &[Felt],
+        value_format: ValueFormat,
+    ) -> Option<DecodedEvent> {
+        variants.iter().find_map(|variant| {
+            // Variants of flattened enums are identified by their own selectors.
+            if variant.kind == "flat" {
+                let Some(AbiEvent::Enum(nested)) = self.events.get(&variant.ty) else {
+                    return None;
+                };
+                return self.decode_event_variant(nested, keys, data, value_format);
+            }
+            if keys.first() != Some(&starknet_keccak(&variant.name)) {
+                return None;
+            }
+
+            match self.events.get(&variant.ty)? {
+                AbiEvent::Struct(members) => {
+                    let mut keys = keys[1..].iter();
+                    let mut data = data.iter();
+                    let fields = members
+                        .iter()
+                        .map(|member| {
+                            let felts = if member.kind == "key" { &mut keys } else { &mut data };
+                            let value = self.decode(&member.ty, felts, value_format)?;
+                            Some((member.name.clone(), value))
+                        })
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(DecodedEvent { name: variant.name.clone(), fields })
+                }
+                AbiEvent::Enum(nested) => {
+                    let event =
+                        self.decode_event_variant(nested, &keys[1..], data, value_format)?;
+                    Some(DecodedEvent {
+                        name: format!("{}::{}", variant.name, event.name),
+                        ..event
+                    })
+                }
+            }
+        })
+    }
+
+    /// Decodes the values returned by the entrypoint from its retdata, as a tuple
+    /// unless it returns a single value.
+    pub fn decode_outputs(
+        &self,
+        entrypoint: &AbiFunction,
+        retdata: &[Felt],
+        value_format: ValueFormat,
+    ) -> Option<String> {
+        let mut felts = retdata.iter();
+        let values = entrypoint
+            .outputs
+            .iter()
+            .map(|ty| self.decode(ty, &mut felts, value_format))
+            .collect::<Option<Vec<_>>>()?;
+        match values.as_slice() {
+            [value] => Some(value.clone()),
+            values => Some(format!("({})", values.join(", "))),
+        }
+    }
+}
+
+/// Name of the function wrapped by an entrypoint wrapper, e.g. `increase_balance`
+/// for `hello::HelloStarknet::__wrapper__HelloStarknetImpl__increase_balance`.
+pub fn wrapped_function_name(sierra_function_name: &str) -> Option<&str> {
+    let wrapped = sierra_function_name.rsplit("::").next()?.strip_prefix(WRAPPER_PREFIX)?;
+    // Wrappers of functions in impls are prefixed with the name of the impl.
+    wrapped.rsplit("__").next()
+}
+
+/// Keccak of the name truncated to 250 bits, used e.g. for selectors of events
+/// and base addresses of storage variables.
+pub fn starknet_keccak(name: &str) -> Felt {
+    let mut hash: [u8; 32] = Keccak256::digest(name.as_bytes()).into();
+    hash[0] &= 0b11;
+    Felt::from_bytes_be(&hash)
+}
+
+fn display_felt(ty: &str, felt: &Felt, value_format: ValueFormat) -> String {
+    let is_signed =
+        ty.strip_prefix("core::integer::i").is_some_and(|bits| bits.parse::<u32>().is_ok());
+    let is_address = ty.starts_with("core::starknet::");
+
+    if is_signed && felt.to_biguint() > Felt::MAX.to_biguint() / 2_u32 {
+        format!("-{}", -felt)
+    } else if is_address || (value_format.hex && ty == "core::felt252") {
+        felt.to_hex_string()
+    } else {
+        felt.to_string()
+    }
+}
+
+/// Decodes the bytes of a `ByteArray`: an array of full words, the pending word
+/// and the number of bytes in it.
+pub fn decode_byte_array(felts: &mut Iter<'_, Felt>) -> Option<String> {
+    let words_count = felt_to_usize(felts.next()?)?;
+    if words_count > felts.len() {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    for _ in 0..words_count {
+        bytes.extend_from_slice(&word_bytes(felts.next()?, BYTES_IN_WORD)?);
+    }
+    let pending_word = felts.next()?;
+    let pending_word_len = felt_to_usize(felts.next()?)?;
+    bytes.extend_from_slice(&word_bytes(pending_word, pending_word_len)?);
+
+    Some(format!("{:?}", String::from_utf8_lossy(&bytes)))
+}
+
+/// Returns the last `len` big-endian bytes of the word.
+fn word_bytes(word: &Felt, len: usize) -> Option<Vec<u8>> {
+    let bytes = word.to_bytes_be();
+    Some(bytes.get(bytes.len().checked_sub(len)?..)?.to_vec())
+}
+
+fn felt_to_usize(felt: &Felt) -> Option<usize> {
+    felt.to_biguint().try_into().ok()
+}
+
+/// Returns `T` of `base::<T>`.
+fn generic_argument<'a>(ty: &'a str, base: &str) -> Option<&'a str> {
+    ty.strip_prefix(base)?.strip_prefix("::<")?.strip_suffix('>')
+}
+
+/// Splits the items of a tuple, skipping the commas nested in generic arguments and tuples.
+fn split_top_level(items: &str) -> Vec<&str> {
+    let mut depth = 0_usize;
+    let mut start = 0;
+    let mut result = Vec::new();
+    for (i, c) in items.char_indices() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                result.push(items[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let last = items[start..].trim();
+    if !last.is_empty() {
+        result.push(last);
+    }
+    result
+}
+
+/// Drops the module path of a type, e.g. `Option::<core::felt252>` for
+/// `core::option::Option::<core::felt252>`.
+fn short_type_name(ty: &str) -> &str {
+    let path_end = ty.find("::<").unwrap_or(ty.len());
+    match ty[..path_end].rfind("::") {
+        Some(position) => &ty[position + 2..],
+        None => ty,
+    }
+}
@@ -0,0 +1,100 @@
+//! Watching of the compiled programs being debugged, so that a rebuild, e.g. by `scarb build`
+//! run during the session, is noticed rather than leaving the session mapping the execution
+//! to stale code.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the modification times of the files are checked.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reports files rewritten since they were loaded. Stops checking once dropped.
+pub struct ArtifactWatcher {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    files: Mutex<HashMap<PathBuf, WatchedFile>>,
+    dropped: AtomicBool,
+}
+
+struct WatchedFile {
+    /// Modification time of the version of the file that is loaded or reported as changed.
+    loaded: Option<SystemTime>,
+    /// Modification time seen by the previous check.
+    seen: Option<SystemTime>,
+    changed: bool,
+}
+
+impl ArtifactWatcher {
+    pub fn start() -> Self {
+        let shared =
+            Arc::new(Shared { files: Mutex::new(HashMap::new()), dropped: AtomicBool::new(false) });
+
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            while !thread_shared.dropped.load(Ordering::Relaxed) {
+                thread::sleep(CHECK_INTERVAL);
+                thread_shared.check();
+            }
+        });
+
+        Self { shared }
+    }
+
+    /// Starts watching the file, unless it is watched already.
+    pub fn watch(&self, path: &Path) {
+        let modified = modification_time(path);
+        self.shared
+            .files
+            .lock()
+            .expect("Artifact watcher lock poisoned")
+            .entry(path.to_path_buf())
+            .or_insert(WatchedFile { loaded: modified, seen: modified, changed: false });
+    }
+
+    /// Files rewritten since they were loaded or returned last time.
+    pub fn take_changed(&self) -> Vec<PathBuf> {
+        let mut files = self.shared.files.lock().expect("Artifact watcher lock poisoned");
+        files
+            .iter_mut()
+            .filter(|(_, file)| file.changed)
+            .map(|(path, file)| {
+                file.changed = false;
+                path.clone()
+            })
+            .collect()
+    }
+}
+
+impl Drop for ArtifactWatcher {
+    fn drop(&mut self) {
+        self.shared.dropped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Shared {
+    /// Build tools write the files in chunks, so a file counts as changed only once
+    /// its modification time stayed the same for a whole interval.
+    fn check(&self) {
+        let mut files = self.files.lock().expect("Artifact watcher lock poisoned");
+        for (path, file) in files.iter_mut() {
+            let modified = modification_time(path);
+            if modified.is_some() && modified == file.seen && modified != file.loaded {
+                file.loaded = modified;
+                file.changed = true;
+            }
+            file.seen = modified;
+        }
+    }
+}
+
+/// `None` while the file does not exist, e.g. once it was removed by `scarb clean`.
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
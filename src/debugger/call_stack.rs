@@ -1,24 +1,35 @@
+use std::cmp::Reverse;
 use std::iter::once;
+use std::ops::Range;
 use std::path::Path;
 
 use cairo_annotations::annotations::coverage::{CodeLocation, SourceFileFullPath};
 use cairo_annotations::annotations::profiler::FunctionName;
 use cairo_lang_sierra::program::StatementIdx;
-use dap::types::{Scope, ScopePresentationhint, StackFrame, Variable};
-use dap::types::{Source, StackFramePresentationhint};
+use dap::types::StackFramePresentationhint;
+use dap::types::{Scope, ScopePresentationhint, StackFrame, StackFrameFormat, Variable};
+use starknet_types_core::felt::Felt;
+use tracing::{debug, warn};
 
 use crate::debugger::MIN_OBJECT_REFERENCE;
-use crate::debugger::context::Context;
+use crate::debugger::abi::{self, AbiFunction, EntrypointKind};
+use crate::debugger::context::{Context, FunctionParam, StatementKind};
+use crate::debugger::launch_args::ValueFormat;
+use crate::debugger::resources::{FrameResources, ResourceSnapshot};
+use crate::debugger::vm_access::{Address, Value, VmAccess};
 
 #[derive(Default)]
 pub struct CallStack {
-    /// Stack of indexes of sierra statements that are function calls and values of variables in frames corresponding to these functions.
+    /// Stack of indexes of sierra statements that are function calls, frame pointers of the called
+    /// functions and values of variables in frames corresponding to these functions.
     /// Does ***not*** contain a current function frame.
     ///
     /// [Object references](https://microsoft.github.io/debug-adapter-protocol/overview#lifetime-of-objects-references):
     /// object reference for each stack frame is equal to its `1 + 2 * index` where `index` is its
     /// position in this vector. For variables, it is `2 + 2 * index`.
-    call_ids: Vec<(StatementIdx, FunctionVariables)>,
+    /// Frames of functions inlined into the frame at `index` use the same scheme,
+    /// with `index` shifted by multiples of [`INLINED_FRAME_INDEX_STRIDE`], see [`frame_id`].
+    call_ids: Vec<CallFrame>,
 
     /// Modification that should be applied to the stack when a new sierra statement is reached.
     ///
@@ -26,18 +37,79 @@ pub struct CallStack {
     /// statement maps to a function call or a return statement.
     /// The stack should be modified ***after*** such a statement is executed.
     action_on_new_statement: Option<Action>,
+
+    /// Maximal number of levels of the same recursion that are displayed, a level being a frame
+    /// of a recursive function or a cycle of frames of mutually recursive ones.
+    /// The remaining ones are collapsed into a single label frame.
+    pub recursion_frame_limit: Option<usize>,
+
+    /// Whether frames of macro expansions should be folded into their parent frames.
+    pub fold_macro_frames: bool,
+
+    pub value_format: ValueFormat,
+
+    /// Whether the resources used by the frames should be appended to their names.
+    pub show_frame_resources: bool,
+
+    /// Resources used by the execution when the outermost frame was entered.
+    entry_resources: Option<ResourceSnapshot>,
+
+    /// Value returned by the contract called from the current statement, shown among
+    /// the locals of the current frame right after the call.
+    pub return_value: Option<Variable>,
 }
 
+/// Physical frame indexes must be lower than this value for the frame ids to be unique.
+const INLINED_FRAME_INDEX_STRIDE: i64 = 1 << 20;
+
+/// Longest cycle of mutually recursive functions detected as a recursion.
+const MAX_RECURSION_CYCLE_LENGTH: usize = 8;
+
+/// Set in the variables reference of the outermost frame to refer to the decoded arguments
+/// of the contract entrypoint, instead of the locals. Higher than the references of any frames
+/// but the ones inlined extremely deep.
+const ENTRYPOINT_ARGUMENTS_FLAG: i64 = 1 << 30;
+
 enum Action {
     Push(StatementIdx),
     Pop,
 }
 
 impl CallStack {
+    /// Resets the options set up by the client, keeping the tracked frames.
+    pub fn reset_session(&mut self) {
+        self.recursion_frame_limit = None;
+        self.fold_macro_frames = false;
+        self.value_format = ValueFormat::default();
+        self.show_frame_resources = false;
+    }
+
+    /// Empty call stack of a program executed from the one of this call stack,
+    /// with the same options.
+    pub fn for_nested_program(&self) -> Self {
+        Self {
+            recursion_frame_limit: self.recursion_frame_limit,
+            fold_macro_frames: self.fold_macro_frames,
+            value_format: self.value_format,
+            show_frame_resources: self.show_frame_resources,
+            ..Self::default()
+        }
+    }
+
     pub fn depth(&self) -> usize {
         self.call_ids.len()
     }
-    pub fn update(&mut self, statement_idx: StatementIdx, ctx: &Context) {
+    /// `vm` is in the state in which `statement_idx` is reached.
+    pub fn update(&mut self, statement_idx: StatementIdx, vm: &dyn VmAccess, ctx: &Context) {
+        let fp = vm.fp();
+        let entry_resources = || {
+            let params = ctx.function_params_for_statement_idx(statement_idx).unwrap_or_default();
+            ResourceSnapshot::at_function_entry(vm, fp, params)
+        };
+        if self.entry_resources.is_none() {
+            self.entry_resources = Some(entry_resources());
+        }
+
         // We can be sure that the `statement_idx` is different from the one which was the arg when
         // `action_on_new_statement` was set.
         // The reason is that both function call and return in sierra compile to one CASM instruction each.
@@ -45,8 +117,14 @@ impl CallStack {
         // https://github.com/starkware-libs/cairo/blob/d52acf845fc234f1746f814de7c64b535563d479/crates/cairo-lang-sierra-to-casm/src/compiler.rs#L533
         match self.action_on_new_statement.take() {
             Some(Action::Push(statement)) => {
+                // The call has just been executed, so fp points to the frame of the called function.
                 // TODO(#16)
-                self.call_ids.push((statement, FunctionVariables {}));
+                self.call_ids.push(CallFrame {
+                    call_statement_idx: statement,
+                    callee_fp: fp,
+                    entry_resources: Some(entry_resources()),
+                    variables: FunctionVariables {},
+                });
             }
             Some(Action::Pop) => {
                 self.call_ids.pop();
@@ -54,105 +132,774 @@ impl CallStack {
             None => {}
         }
 
-        if ctx.is_function_call_statement(statement_idx) {
-            self.action_on_new_statement = Some(Action::Push(statement_idx));
-        } else if ctx.is_return_statement(statement_idx) {
-            self.action_on_new_statement = Some(Action::Pop);
+        match ctx.statement_kind(statement_idx) {
+            StatementKind::FunctionCall => {
+                self.action_on_new_statement = Some(Action::Push(statement_idx));
+            }
+            StatementKind::Return => {
+                self.action_on_new_statement = Some(Action::Pop);
+            }
+            // The frame is reused by the target function, so the depth stays the same.
+            // The frames are named after the function of the current statement, hence they
+            // will show the target function once it is reached.
+            StatementKind::CrossFunctionJump => {
+                debug!("Statement {} jumps to another function", statement_idx.0);
+            }
+            StatementKind::Other => {}
         }
     }
 
-    pub fn get_frames(&self, statement_idx: StatementIdx, ctx: &Context) -> Vec<StackFrame> {
-        // DAP expects frames to start from the most nested element.
+    /// Validates the tracked stack against the one derived from the fp chain in VM memory,
+    /// and replaces the tracked stack with the derived one if they diverged.
+    ///
+    /// Tracking based on statement classification is cheap but fragile: a single missed push or pop
+    /// (e.g. after errors or unusual control flow) would silently corrupt every following trace,
+    /// while the fp chain always reflects the actual state of the VM.
+    /// Comparing frame pointers, and not only call statements, catches divergences that leave
+    /// the call statements intact, e.g. a missed push and pop of a recursive call.
+    pub fn repair_from_vm(&mut self, vm: &dyn VmAccess, ctx: &Context) {
+        if vm.pc().segment_index != 0 {
+            // Same as in `State::update_state` - the pc cannot be mapped to a statement.
+            return;
+        }
+
+        let FpChain { call_statements, frame_pointers } = FpChain::build(vm, ctx);
+        // The first frame pointer belongs to the outermost frame, which was not created by any
+        // of the tracked calls.
+        let callee_fps = &frame_pointers[1..];
+        let tracked_frames =
+            self.call_ids.iter().map(|frame| (frame.call_statement_idx, frame.callee_fp));
+        if tracked_frames.eq(call_statements.iter().copied().zip(callee_fps.iter().copied())) {
+            return;
+        }
+
+        warn!(
+            "Tracked call stack diverged from the VM (tracked depth: {}, actual depth: {}), \
+             rebuilding it from memory. Tracked calls: {:?}, actual calls: {:?}",
+            self.call_ids.len(),
+            call_statements.len(),
+            self.call_ids.iter().map(|frame| frame.call_statement_idx).collect::<Vec<_>>(),
+            call_statements
+        );
+        // TODO(#16): Variables of the replaced frames are lost here.
+        // Resources used by the replaced frames are unknown from now on.
+        self.call_ids = frames_from_fp_chain(call_statements, callee_fps);
+    }
+
+    /// Brings the tracked stack up to date with the fp chain after instructions were executed
+    /// without tracking it, keeping the frames that are still there.
+    pub fn catch_up(&mut self, vm: &dyn VmAccess, ctx: &Context) {
+        // The pending action belongs to a statement executed long ago.
+        self.action_on_new_statement = None;
+        if vm.pc().segment_index != 0 {
+            return;
+        }
+
+        let FpChain { mut call_statements, frame_pointers } = FpChain::build(vm, ctx);
+        let callee_fps = &frame_pointers[1..];
+        let kept = self
+            .call_ids
+            .iter()
+            .zip(call_statements.iter().zip(callee_fps))
+            .take_while(|(frame, (call_statement_idx, callee_fp))| {
+                frame.call_statement_idx == **call_statement_idx && frame.callee_fp == **callee_fp
+            })
+            .count();
+        self.call_ids.truncate(kept);
+        self.call_ids
+            .extend(frames_from_fp_chain(call_statements.split_off(kept), &callee_fps[kept..]));
+    }
+
+    /// Call stack of the past step `vm` was moved back to, with the same options,
+    /// rebuilt from the fp chain like in [`Self::repair_from_vm`].
+    pub fn at_past_step(&self, vm: &dyn VmAccess, ctx: &Context) -> Self {
+        let mut call_stack = self.for_nested_program();
+        if vm.pc().segment_index == 0 {
+            let FpChain { call_statements, frame_pointers } = FpChain::build(vm, ctx);
+            call_stack.call_ids = frames_from_fp_chain(call_statements, &frame_pointers[1..]);
+        }
+        call_stack
+    }
+
+    /// Formats names of `frames`, built by [`Self::get_frames`], according to `format`.
+    /// Argument values are read from memory, hence they are available only if `vm` is set.
+    pub fn format_frames(
+        &self,
+        frames: &mut [StackFrame],
+        format: &StackFrameFormat,
+        statement_idx: StatementIdx,
+        vm: Option<&dyn VmAccess>,
+        ctx: &Context,
+    ) {
+        let statements: Vec<StatementIdx> = self
+            .call_ids
+            .iter()
+            .map(|frame| frame.call_statement_idx)
+            .chain(once(statement_idx))
+            .collect();
+        let frame_pointers = vm.map(|vm| FpChain::build(vm, ctx).frame_pointers);
+
+        for frame in frames {
+            if frame.presentation_hint == Some(StackFramePresentationhint::Label) {
+                continue;
+            }
+
+            let (index, inline_depth) = frame_index_and_inline_depth(frame.id);
+            // Inlined functions have no frame of their own to read arguments from.
+            if inline_depth == 0
+                && format.parameters.unwrap_or(true)
+                && let Some(params) = statements
+                    .get(index)
+                    .and_then(|statement_idx| ctx.function_params_for_statement_idx(*statement_idx))
+            {
+                let fp = frame_pointers.as_ref().and_then(|fps| fps.get(index)).copied();
+                frame.name =
+                    name_with_arguments(&frame.name, params, fp, format, self.value_format, vm);
+            }
+
+            if format.module.unwrap_or(false)
+                && let Some(package) = frame
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.path.as_ref())
+                    .and_then(|path| ctx.package_for_path(Path::new(path)))
+            {
+                frame.name = format!("{} [{}]", frame.name, package.name);
+            }
+            if format.line.unwrap_or(false) {
+                frame.name = format!("{} Line {}", frame.name, frame.line);
+            }
+        }
+    }
+
+    /// Resources used so far by the frame of the given id, including the functions it called.
+    /// Frames of inlined functions share the resources of the physical frame. Sierra gas is known
+    /// only until the call of the next frame, as the remaining gas is kept in memory only when
+    /// it is passed to a function, hence it is unknown for the innermost frame.
+    pub fn frame_resources(&self, frame_id: i64, vm: &dyn VmAccess) -> Option<FrameResources> {
+        let (index, _) = frame_index_and_inline_depth(frame_id);
+        let entries: Vec<Option<&ResourceSnapshot>> = once(self.entry_resources.as_ref())
+            .chain(self.call_ids.iter().map(|frame| frame.entry_resources.as_ref()))
+            .collect();
+        let entry = (*entries.get(index)?)?;
+        let next_frame_gas = entries.get(index + 1).copied().flatten().and_then(|next| next.gas());
+        let now = ResourceSnapshot::take(vm, next_frame_gas);
+        Some(entry.usage_until(&now))
+    }
+
+    /// Appends the resources used by the physical frames to their names, see [`Self::frame_resources`].
+    pub fn append_frame_resources(&self, frames: &mut [StackFrame], vm: &dyn VmAccess) {
+        for frame in frames {
+            if frame.presentation_hint == Some(StackFramePresentationhint::Label)
+                || frame_index_and_inline_depth(frame.id).1 > 0
+            {
+                continue;
+            }
+            if let Some(resources) = self.frame_resources(frame.id, vm) {
+                frame.name = format!("{} [{}]", frame.name, resources.describe());
+            }
+        }
+    }
+
+    /// Statements that the physical frames are at, from the outermost one, which determine
+    /// the frames built by [`Self::get_frames`].
+    pub fn frame_statements(&self, statement_idx: StatementIdx) -> Vec<StatementIdx> {
         self.call_ids
             .iter()
-            .map(|(call_statement_idx, _)| call_statement_idx)
-            .cloned()
+            .map(|frame| frame.call_statement_idx)
             .chain(once(statement_idx))
-            .rev()
-            .flat_map(|statement_idx| self.build_stack_frames(ctx, statement_idx))
             .collect()
     }
 
-    pub fn get_scopes_for_frame(&self, frame_id: i64) -> Vec<Scope> {
-        let scope = Scope {
+    /// Builds the frames at the positions in `window`, counting from the most nested frame,
+    /// and returns them with the number of all frames. Frames outside of the window are only
+    /// counted, so that clients loading deep stacks in pages do not pay for the whole stack.
+    ///
+    /// If `just_my_code` is set, frames of code outside of the user's workspace are deemphasized.
+    pub fn get_frames(
+        &self,
+        statement_idx: StatementIdx,
+        just_my_code: bool,
+        ctx: &Context,
+        window: Range<usize>,
+    ) -> (Vec<StackFrame>, usize) {
+        // DAP expects frames to start from the most nested element.
+        let physical_frames: Vec<(usize, StatementIdx)> =
+            self.frame_statements(statement_idx).into_iter().enumerate().rev().collect();
+
+        // Frames are told apart by their functions, rather than the statements that called them,
+        // e.g. `fib(n - 1) + fib(n - 2)` recurses from two of them.
+        let functions: Vec<Option<StatementIdx>> = physical_frames
+            .iter()
+            .map(|&(_, statement_idx)| ctx.function_entry_point_for_statement_idx(statement_idx))
+            .collect();
+        let mut frames = Vec::new();
+        let mut total_frames = 0;
+        for Recursion { frames: range, cycle_length } in find_recursions(&functions) {
+            let run = &physical_frames[range];
+            let shown = self
+                .recursion_frame_limit
+                .map_or(run.len(), |limit| limit.saturating_mul(cycle_length).min(run.len()));
+            for &(index, statement_idx) in &run[..shown] {
+                let count = self.count_stack_frames(ctx, statement_idx);
+                if total_frames < window.end && window.start < total_frames + count {
+                    let built = self.build_stack_frames(ctx, statement_idx, index, just_my_code);
+                    frames.extend(
+                        built
+                            .into_iter()
+                            .zip(total_frames..)
+                            .filter(|(_, position)| window.contains(position))
+                            .map(|(frame, _)| frame),
+                    );
+                }
+                total_frames += count;
+            }
+            if let Some(&(first_collapsed_index, _)) = run.get(shown) {
+                if window.contains(&total_frames) {
+                    frames.push(collapsed_frames_label(run.len() - shown, first_collapsed_index));
+                }
+                total_frames += 1;
+            }
+        }
+        (frames, total_frames)
+    }
+
+    pub fn get_scopes_for_frame(
+        &self,
+        frame_id: i64,
+        statement_idx: StatementIdx,
+        ctx: &Context,
+    ) -> Vec<Scope> {
+        let mut scopes = Vec::new();
+        if self.entrypoint_for_frame(frame_id, statement_idx, ctx).is_some() {
+            scopes.push(Scope {
+                name: "Arguments".to_string(),
+                variables_reference: (frame_id + 1) | ENTRYPOINT_ARGUMENTS_FLAG,
+                presentation_hint: Some(ScopePresentationhint::Arguments),
+                ..Default::default()
+            });
+        }
+        scopes.push(Scope {
             name: "Locals".to_string(),
             variables_reference: frame_id + 1,
             presentation_hint: Some(ScopePresentationhint::Locals),
             ..Default::default()
-        };
-        vec![scope]
+        });
+        scopes
     }
 
-    pub fn get_variables(&self, variables_reference: i64) -> Vec<Variable> {
-        let index = variables_reference / 2 - 1;
+    /// Values are read from memory, hence the ones that need it are available only if `vm` is set.
+    pub fn get_variables(
+        &self,
+        variables_reference: i64,
+        statement_idx: StatementIdx,
+        vm: Option<&dyn VmAccess>,
+        ctx: &Context,
+    ) -> Vec<Variable> {
+        if variables_reference & ENTRYPOINT_ARGUMENTS_FLAG != 0 {
+            let frame_id = (variables_reference & !ENTRYPOINT_ARGUMENTS_FLAG) - 1;
+            return vm
+                .and_then(|vm| self.entrypoint_arguments(frame_id, statement_idx, vm, ctx))
+                .unwrap_or_default();
+        }
+
+        // Frames inlined into a physical frame share its variables.
+        let index = (variables_reference / 2 - 1) % INLINED_FRAME_INDEX_STRIDE;
         let &FunctionVariables {} = if index == self.call_ids.len() as i64 {
             // TODO(#16)
             //  Build them on demand.
             &FunctionVariables {}
         } else {
-            &self.call_ids[index as usize].1
+            &self.call_ids[index as usize].variables
         };
 
+        if index == self.call_ids.len() as i64 {
+            return self.return_value.iter().cloned().collect();
+        }
         vec![]
     }
 
+    /// Returns the entrypoint if the frame is the one of an entrypoint wrapper of a contract.
+    /// Such a frame is the outermost one, since contracts are called by the OS.
+    fn entrypoint_for_frame<'a>(
+        &self,
+        frame_id: i64,
+        statement_idx: StatementIdx,
+        ctx: &'a Context,
+    ) -> Option<&'a AbiFunction> {
+        if frame_index_and_inline_depth(frame_id) != (0, 0) {
+            return None;
+        }
+        self.entrypoint(statement_idx, ctx)
+    }
+
+    /// Returns the entrypoint being executed, if the program is a contract with a known ABI.
+    pub fn entrypoint<'a>(
+        &self,
+        statement_idx: StatementIdx,
+        ctx: &'a Context,
+    ) -> Option<&'a AbiFunction> {
+        let entry_statement_idx = self.outermost_statement_idx(statement_idx);
+        ctx.abi
+            .as_ref()?
+            .entrypoint(ctx.sierra_function_name_for_statement_idx(entry_statement_idx)?)
+    }
+
+    /// Statement of the outermost frame, which is executed when `statement_idx` is the current one.
+    fn outermost_statement_idx(&self, statement_idx: StatementIdx) -> StatementIdx {
+        self.call_ids.first().map_or(statement_idx, |frame| frame.call_statement_idx)
+    }
+
+    /// Decodes the calldata of the entrypoint into its named arguments.
+    fn entrypoint_arguments(
+        &self,
+        frame_id: i64,
+        statement_idx: StatementIdx,
+        vm: &dyn VmAccess,
+        ctx: &Context,
+    ) -> Option<Vec<Variable>> {
+        let entrypoint = self.entrypoint_for_frame(frame_id, statement_idx, ctx)?;
+        let abi = ctx.abi.as_ref()?;
+        let params =
+            ctx.function_params_for_statement_idx(self.outermost_statement_idx(statement_idx))?;
+        let fp = *FpChain::build(vm, ctx).frame_pointers.first()?;
+        let calldata = read_entrypoint_calldata(vm, fp, params)?;
+
+        let mut felts = calldata.iter();
+        let mut decodable = true;
+        let variables = entrypoint
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(position, input)| {
+                // The first input of an L1 handler is the Ethereum address of the sender.
+                let value_format = if entrypoint.kind == EntrypointKind::L1Handler && position == 0
+                {
+                    ValueFormat { hex: true }
+                } else {
+                    self.value_format
+                };
+                // Once a value cannot be decoded, it is unknown where the next one starts.
+                let value =
+                    decodable.then(|| abi.decode(&input.ty, &mut felts, value_format)).flatten();
+                decodable = value.is_some();
+                Variable {
+                    name: input.name.clone(),
+                    value: value.unwrap_or_else(|| "?".to_string()),
+                    type_field: Some(input.ty.clone()),
+                    variables_reference: 0,
+                    ..Default::default()
+                }
+            })
+            .collect();
+        Some(variables)
+    }
+
     /// Builds a vector of stack frames, ordered from the most nested (innermost) to the least nested (outermost) element.
-    fn build_stack_frames(&self, ctx: &Context, statement_idx: StatementIdx) -> Vec<StackFrame> {
+    /// All but the last frame are frames of inlined functions, and they are labeled as such.
+    fn build_stack_frames(
+        &self,
+        ctx: &Context,
+        statement_idx: StatementIdx,
+        index: usize,
+        just_my_code: bool,
+    ) -> Vec<StackFrame> {
         let Some(code_locations) = ctx.code_locations_for_statement_idx(statement_idx) else {
             return vec![unknown_frame()];
         };
 
-        let default_function_names = vec![FunctionName("test".to_string())];
-        let function_names =
-            ctx.function_names_for_statement_idx(statement_idx).unwrap_or(&default_function_names);
+        let mut function_names: Vec<String> = ctx
+            .function_names_for_statement_idx(statement_idx)
+            .map(|names| names.iter().map(|FunctionName(name)| name.clone()).collect())
+            .unwrap_or_default();
 
-        code_locations
-            .iter()
-            .zip(function_names)
-            .map(|(code_location, function_name)| {
-                self.build_stack_frame(code_location, function_name, ctx)
+        // The outermost function is the one that the statement physically belongs to.
+        // Its Sierra debug name contains generic arguments, unlike the profiler annotations.
+        if let Some(sierra_function_name) =
+            ctx.sierra_function_name_for_statement_idx(statement_idx)
+        {
+            let name = l1_handler_frame_name(sierra_function_name, ctx)
+                .unwrap_or_else(|| sierra_function_name.to_string());
+            match function_names.last_mut() {
+                Some(last) => *last = name,
+                None => function_names.push(name),
+            }
+        }
+        if function_names.is_empty() {
+            function_names.push("Unknown".to_string());
+        }
+
+        let frames: Vec<_> = code_locations.iter().zip(function_names).collect();
+        let physical_frame_position = frames.len().saturating_sub(1);
+        frames
+            .into_iter()
+            .enumerate()
+            .filter(|(position, (code_location, _))| {
+                !self.is_folded(code_location, *position, physical_frame_position)
+            })
+            .map(|(position, (code_location, function_name))| {
+                let inline_depth = physical_frame_position - position;
+                build_stack_frame(
+                    code_location,
+                    &function_name,
+                    index,
+                    inline_depth,
+                    just_my_code,
+                    ctx,
+                )
             })
             .collect()
     }
 
-    fn build_stack_frame(
+    /// Number of the frames built by [`Self::build_stack_frames`], without building them.
+    fn count_stack_frames(&self, ctx: &Context, statement_idx: StatementIdx) -> usize {
+        let Some(code_locations) = ctx.code_locations_for_statement_idx(statement_idx) else {
+            return 1;
+        };
+        // A function name is always there, even if unknown.
+        let function_names =
+            ctx.function_names_for_statement_idx(statement_idx).map_or(0, Vec::len).max(1);
+        let frames = code_locations.len().min(function_names);
+        let physical_frame_position = frames.saturating_sub(1);
+        code_locations[..frames]
+            .iter()
+            .enumerate()
+            .filter(|(position, code_location)| {
+                !self.is_folded(code_location, *position, physical_frame_position)
+            })
+            .count()
+    }
+
+    /// Whether the frame of a macro expansion is folded into its parent frame.
+    fn is_folded(
         &self,
-        CodeLocation(SourceFileFullPath(source_file), code_span, _): &CodeLocation,
-        FunctionName(function_name): &FunctionName,
-        ctx: &Context,
-    ) -> StackFrame {
-        let id = MIN_OBJECT_REFERENCE + 2 * self.call_ids.len() as i64;
-        let file_path = Path::new(&source_file);
-        let name = function_name.clone();
+        CodeLocation(SourceFileFullPath(source_file), _, _): &CodeLocation,
+        position: usize,
+        physical_frame_position: usize,
+    ) -> bool {
+        // The physical frame has no parent frame to be folded into.
+        self.fold_macro_frames
+            && position != physical_frame_position
+            && macro_name(source_file).is_some()
+    }
+}
 
-        let is_user_code = file_path.starts_with(&ctx.root_path);
-        let presentation_hint = Some(if is_user_code {
+/// L1 handlers are not called by other functions, but invoked by tests through their wrappers.
+/// Frames of the wrappers are named after the handlers, e.g. `hello::HelloStarknet::handle_deposit`
+/// instead of `hello::HelloStarknet::__wrapper__handle_deposit`.
+fn l1_handler_frame_name(sierra_function_name: &str, ctx: &Context) -> Option<String> {
+    let entrypoint = ctx.abi.as_ref()?.entrypoint(sierra_function_name)?;
+    if entrypoint.kind != EntrypointKind::L1Handler {
+        return None;
+    }
+    let handler = abi::wrapped_function_name(sierra_function_name)?;
+    let module = sierra_function_name.rsplit_once("::").map(|(module, _)| module);
+    Some(match module {
+        Some(module) => format!("{module}::{handler} [L1 handler]"),
+        None => format!("{handler} [L1 handler]"),
+    })
+}
+
+/// `inline_depth` is 0 for the physical frame and increases for each level of inlining.
+fn build_stack_frame(
+    CodeLocation(SourceFileFullPath(source_file), code_span, _): &CodeLocation,
+    function_name: &str,
+    index: usize,
+    inline_depth: usize,
+    just_my_code: bool,
+    ctx: &Context,
+) -> StackFrame {
+    let id = frame_id(index, inline_depth);
+    let file_path = Path::new(&source_file);
+    let is_inlined = inline_depth > 0;
+    let macro_name = macro_name(source_file);
+    let name = match (&macro_name, is_inlined) {
+        (Some(macro_name), _) => {
+            format!("{} [macro: {macro_name}]", display_function_name(function_name))
+        }
+        (None, true) => format!("{} [inlined]", display_function_name(function_name)),
+        (None, false) => display_function_name(function_name),
+    };
+
+    let presentation_hint =
+        Some(if macro_name.is_none() && (!just_my_code || ctx.is_user_code(file_path)) {
             StackFramePresentationhint::Normal
         } else {
             StackFramePresentationhint::Subtle
         });
 
-        // Annotations from debug info are 0-indexed.
-        // UI expects 1-indexed, hence +1 below.
-        let line = (code_span.start.line.0 + 1) as i64;
-        let column = (code_span.start.col.0 + 1) as i64;
-
-        StackFrame {
-            id,
-            name,
-            source: Some(Source {
-                name: None,
-                path: Some(source_file.clone()),
-                ..Default::default()
-            }),
-            line,
-            column,
-            presentation_hint,
-            ..Default::default()
+    // Annotations from debug info are 0-indexed.
+    // UI expects 1-indexed, hence +1 below.
+    let line = (code_span.start.line.0 + 1) as i64;
+    let column = (code_span.start.col.0 + 1) as i64;
+    let end_line = (code_span.end.line.0 + 1) as i64;
+    let end_column = (code_span.end.col.0 + 1) as i64;
+
+    StackFrame {
+        id,
+        name,
+        source: Some(ctx.source_files.source(source_file)),
+        line,
+        column,
+        end_line: Some(end_line),
+        end_column: Some(end_column),
+        presentation_hint,
+        // Inlined frames have no frame of their own in the VM to restart.
+        can_restart: is_inlined.then_some(false),
+        ..Default::default()
+    }
+}
+
+/// Synthetic frame that replaces `count` collapsed frames, starting from the frame at `index`.
+fn collapsed_frames_label(count: usize, index: usize) -> StackFrame {
+    StackFrame {
+        id: frame_id(index, 0),
+        name: format!("{count} recursive frames collapsed"),
+        presentation_hint: Some(StackFramePresentationhint::Label),
+        can_restart: Some(false),
+        ..Default::default()
+    }
+}
+
+/// Consecutive frames of a recursion, or a single frame outside of any.
+#[derive(Debug, PartialEq)]
+struct Recursion {
+    frames: Range<usize>,
+    /// Number of frames of a single level of the recursion, more than one for mutual recursion.
+    cycle_length: usize,
+}
+
+/// Splits the frames, given by the functions they execute, into recursions, i.e. runs of frames
+/// repeating a cycle of functions at least twice. The longest run starting at a frame is taken,
+/// with the shortest cycle it repeats. Frames of unknown functions are not a part of any.
+fn find_recursions(functions: &[Option<StatementIdx>]) -> Vec<Recursion> {
+    let mut recursions = Vec::new();
+    let mut start = 0;
+    while start < functions.len() {
+        let (len, cycle_length) = (1..=MAX_RECURSION_CYCLE_LENGTH)
+            .filter_map(|cycle_length| {
+                let repeated = functions
+                    .get(start + cycle_length..)?
+                    .iter()
+                    .zip(&functions[start..])
+                    .take_while(|(function, cycle_function)| {
+                        function.is_some() && function == cycle_function
+                    })
+                    .count();
+                let len = cycle_length + repeated;
+                (repeated >= cycle_length).then_some((len, cycle_length))
+            })
+            .min_by_key(|&(len, cycle_length)| (Reverse(len), cycle_length))
+            .unwrap_or((1, 1));
+        recursions.push(Recursion { frames: start..start + len, cycle_length });
+        start += len;
+    }
+    recursions
+}
+
+/// Inverse of [`frame_id`].
+fn frame_index_and_inline_depth(frame_id: i64) -> (usize, usize) {
+    let shifted_index = (frame_id - MIN_OBJECT_REFERENCE) / 2;
+    (
+        (shifted_index % INLINED_FRAME_INDEX_STRIDE) as usize,
+        (shifted_index / INLINED_FRAME_INDEX_STRIDE) as usize,
+    )
+}
+
+fn frame_id(index: usize, inline_depth: usize) -> i64 {
+    MIN_OBJECT_REFERENCE + 2 * (index as i64 + inline_depth as i64 * INLINED_FRAME_INDEX_STRIDE)
+}
+
+/// Frames derived from the fp chain in VM memory, ordered from the outermost to the innermost frame.
+struct FpChain {
+    /// Indexes of function call statements of all frames below the current one.
+    call_statements: Vec<StatementIdx>,
+    /// Frame pointers of all frames, including the current one.
+    frame_pointers: Vec<Address>,
+}
+
+impl FpChain {
+    /// Walks the fp chain in VM memory.
+    ///
+    /// Each `call` instruction stores the caller's fp at `[fp - 2]` and the return pc at `[fp - 1]`
+    /// of the callee's frame. The chain ends when these cells do not hold a valid frame anymore,
+    /// e.g. at the frame of the entrypoint, whose return pc points outside the program segment.
+    fn build(vm: &dyn VmAccess, ctx: &Context) -> Self {
+        let mut call_statements = Vec::new();
+        let mut fp = vm.fp();
+        let mut frame_pointers = vec![fp];
+
+        while let (Some(prev_fp_addr), Some(return_pc_addr)) = (fp - 2, fp - 1) {
+            let (Some(prev_fp), Some(return_pc)) =
+                (vm.read_pointer(prev_fp_addr), vm.read_pointer(return_pc_addr))
+            else {
+                break;
+            };
+            if return_pc.segment_index != 0
+                || prev_fp.segment_index != fp.segment_index
+                || prev_fp.offset >= fp.offset
+            {
+                break;
+            }
+
+            // The return pc points right after the `call` instruction, which is the only
+            // instruction a function call statement compiles to.
+            let call_statement_idx = ctx.statement_idx_for_pc(return_pc.offset - 1);
+            if ctx.is_function_call_statement(call_statement_idx) {
+                call_statements.push(call_statement_idx);
+                frame_pointers.push(prev_fp);
+            } else if let Some(callee_fp) = frame_pointers.last_mut() {
+                // The callee frame was not created by a Sierra function call,
+                // so it is a part of the caller's frame from the user's point of view.
+                *callee_fp = prev_fp;
+            }
+            fp = prev_fp;
         }
+
+        call_statements.reverse();
+        frame_pointers.reverse();
+        Self { call_statements, frame_pointers }
     }
 }
 
+/// Reads the calldata passed to an entrypoint wrapper as its last parameter, a `Span<felt252>`,
+/// which consists of the start and end pointers of the calldata.
+fn read_entrypoint_calldata(
+    vm: &dyn VmAccess,
+    fp: Address,
+    params: &[FunctionParam],
+) -> Option<Vec<Felt>> {
+    if params.last()?.size != 2 {
+        return None;
+    }
+    // Cells of the arguments are followed by the caller's fp and the return pc.
+    let span_address = (fp - 4)?;
+    let start = vm.read_pointer(span_address)?;
+    let end = vm.read_pointer((span_address + 1)?)?;
+    vm.read_felts(start, (end - start)?)
+}
+
+/// Builds `name(arg1, arg2)` where the arguments are read from memory just below `fp`,
+/// as a caller stores them there right before the `call` instruction.
+fn name_with_arguments(
+    name: &str,
+    params: &[FunctionParam],
+    fp: Option<Address>,
+    format: &StackFrameFormat,
+    value_format: ValueFormat,
+    vm: Option<&dyn VmAccess>,
+) -> String {
+    // Cells of the arguments are followed by the caller's fp and the return pc.
+    let arguments_size: usize = params.iter().map(|param| param.size).sum();
+    let mut address = fp.and_then(|fp| fp - (arguments_size + 2));
+
+    let mut arguments = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        let param_address = address;
+        address = address.and_then(|address| address + param.size);
+        if param.is_builtin {
+            continue;
+        }
+
+        let mut argument = String::new();
+        if format.parameter_names.unwrap_or(true) {
+            argument.push_str(&param.name.clone().unwrap_or_else(|| format!("arg{i}")));
+        }
+        if format.parameter_types.unwrap_or(false) {
+            if !argument.is_empty() {
+                argument.push_str(": ");
+            }
+            argument.push_str(&display_function_name(&param.type_name));
+        }
+        if format.parameter_values.unwrap_or(true)
+            && let (Some(vm), Some(param_address)) = (vm, param_address)
+        {
+            let value = display_value(vm, param_address, param.size, value_format);
+            if !argument.is_empty() {
+                argument.push('=');
+            }
+            argument.push_str(&value);
+        }
+        arguments.push(argument);
+    }
+
+    format!("{name}({})", arguments.join(", "))
+}
+
+fn display_value(
+    vm: &dyn VmAccess,
+    address: Address,
+    size: usize,
+    value_format: ValueFormat,
+) -> String {
+    let cells: Vec<String> = vm
+        .read_range(address, size)
+        .into_iter()
+        .map(|cell| match cell {
+            None => "?".to_string(),
+            Some(Value::Felt(felt)) if value_format.hex => felt.to_hex_string(),
+            Some(value) => value.to_string(),
+        })
+        .collect();
+
+    match cells.as_slice() {
+        [cell] => cell.clone(),
+        cells => format!("[{}]", cells.join(", ")),
+    }
+}
+
+/// Returns the name of the macro that generated the file, as displayed to the user.
+///
+/// Code generated by inline macros and attribute expansions lives in virtual files, whose paths
+/// consist of the path of the file with the macro call and the name of the generated file,
+/// e.g. `src/lib.cairo[assert_eq_inline_macro]`.
+fn macro_name(source_file: &str) -> Option<String> {
+    let generated_file_name = source_file.strip_suffix(']')?.rsplit_once('[')?.1;
+    Some(match generated_file_name.strip_suffix("_inline_macro") {
+        Some(inline_macro_name) => format!("{inline_macro_name}!"),
+        None => generated_file_name.to_string(),
+    })
+}
+
+/// Shortens paths of types in generic arguments to their last segment, so that e.g.
+/// `core::array::ArrayImpl::<core::option::Option::<core::integer::u8>>::append`
+/// is displayed as `core::array::ArrayImpl::<Option::<u8>>::append`.
+/// The path of the function itself is left intact, as it tells where the function comes from.
+fn display_function_name(function_name: &str) -> String {
+    let mut result = String::with_capacity(function_name.len());
+    let mut generics_depth = 0_usize;
+    // Position in `result` where the currently processed path segment starts.
+    let mut segment_start = 0;
+
+    let mut chars = function_name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ':' if generics_depth > 0 && chars.peek() == Some(&':') => {
+                chars.next();
+                if chars.peek() == Some(&'<') {
+                    // Generic arguments of a type in generic arguments, keep the separator.
+                    result.push_str("::");
+                } else {
+                    // Drop the module path segment.
+                    result.truncate(segment_start);
+                }
+            }
+            '<' | '>' | ',' | ' ' | '(' | ')' | '[' | ']' | '@' => {
+                if c == '<' {
+                    generics_depth += 1;
+                } else if c == '>' {
+                    generics_depth = generics_depth.saturating_sub(1);
+                }
+                result.push(c);
+                segment_start = result.len();
+            }
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
 fn unknown_frame() -> StackFrame {
     StackFrame {
         id: 1,
@@ -164,5 +911,145 @@ fn unknown_frame() -> StackFrame {
     }
 }
 
+/// Frames of the calls made by `call_statements`, whose callees use `callee_fps`.
+fn frames_from_fp_chain(
+    call_statements: Vec<StatementIdx>,
+    callee_fps: &[Address],
+) -> Vec<CallFrame> {
+    call_statements
+        .into_iter()
+        .zip(callee_fps.iter().copied())
+        .map(|(call_statement_idx, callee_fp)| CallFrame {
+            call_statement_idx,
+            callee_fp,
+            entry_resources: None,
+            variables: FunctionVariables {},
+        })
+        .collect()
+}
+
+struct CallFrame {
+    /// Function call statement that created the frame, located in the caller's frame.
+    call_statement_idx: StatementIdx,
+    /// Frame pointer of the called function, used to detect divergences from the VM state.
+    callee_fp: Address,
+    /// Unknown for frames rebuilt from the VM memory.
+    entry_resources: Option<ResourceSnapshot>,
+    variables: FunctionVariables,
+}
+
 // TODO(#16)
 struct FunctionVariables {}
+
+#[cfg(test)]
+mod tests {
+    use cairo_lang_sierra::program::StatementIdx;
+
+    use super::{
+        INLINED_FRAME_INDEX_STRIDE, Recursion, display_function_name, find_recursions, frame_id,
+        frame_index_and_inline_depth,
+    };
+
+    /// Functions of frames from the innermost one, each named by a letter, `?` if unknown.
+    fn functions(frames: &str) -> Vec<Option<StatementIdx>> {
+        frames.chars().map(|name| (name != '?').then_some(StatementIdx(name as usize))).collect()
+    }
+
+    #[test]
+    fn function_names_without_generic_arguments() {
+        assert_eq!(display_function_name("hello::fib"), "hello::fib");
+    }
+
+    #[test]
+    fn module_paths_of_generic_arguments_are_dropped() {
+        assert_eq!(
+            display_function_name("core::array::ArrayImpl::<core::felt252>::append"),
+            "core::array::ArrayImpl::<felt252>::append"
+        );
+        assert_eq!(
+            display_function_name("core::array::SpanImpl::<@core::felt252>::len"),
+            "core::array::SpanImpl::<@felt252>::len"
+        );
+        assert_eq!(
+            display_function_name("hello::swap::<(core::felt252, core::integer::u32)>"),
+            "hello::swap::<(felt252, u32)>"
+        );
+    }
+
+    #[test]
+    fn nested_generic_arguments() {
+        assert_eq!(
+            display_function_name(
+                "core::option::OptionTraitImpl::<core::array::Array::<core::integer::u8>>::unwrap"
+            ),
+            "core::option::OptionTraitImpl::<Array::<u8>>::unwrap"
+        );
+    }
+
+    #[test]
+    fn frame_ids_of_inlined_frames() {
+        let last_index = INLINED_FRAME_INDEX_STRIDE as usize - 1;
+        for (index, inline_depth) in
+            [(0, 0), (3, 0), (3, 2), (last_index, 0), (0, 1), (last_index, 1)]
+        {
+            assert_eq!(
+                frame_index_and_inline_depth(frame_id(index, inline_depth)),
+                (index, inline_depth)
+            );
+        }
+    }
+
+    #[test]
+    fn frame_ids_at_stride_boundary() {
+        let last_index = INLINED_FRAME_INDEX_STRIDE as usize - 1;
+        // The first inlined frame of the first physical frame comes right after
+        // the last physical frame that can be told apart.
+        assert_eq!(frame_id(last_index, 0) + 2, frame_id(0, 1));
+        assert_eq!(frame_index_and_inline_depth(frame_id(last_index, 0) + 2), (0, 1));
+    }
+
+    fn recursion(frames: std::ops::Range<usize>, cycle_length: usize) -> Recursion {
+        Recursion { frames, cycle_length }
+    }
+
+    #[test]
+    fn recursion_called_from_different_statements() {
+        // `fib(n - 1) + fib(n - 2)`: the frames of `fib` are called from either statement,
+        // but all of them run `fib`.
+        assert_eq!(
+            find_recursions(&functions("ffffm")),
+            vec![recursion(0..4, 1), recursion(4..5, 1)]
+        );
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        // `is_even` and `is_odd` calling each other, the innermost cycle left unfinished.
+        assert_eq!(
+            find_recursions(&functions("oeoeoem")),
+            vec![recursion(0..6, 2), recursion(6..7, 1)]
+        );
+        assert_eq!(
+            find_recursions(&functions("eoeoem")),
+            vec![recursion(0..5, 2), recursion(5..6, 1)]
+        );
+    }
+
+    #[test]
+    fn recursion_called_from_mutual_recursion() {
+        assert_eq!(
+            find_recursions(&functions("aaabcbcbcm")),
+            vec![recursion(0..3, 1), recursion(3..9, 2), recursion(9..10, 1)]
+        );
+    }
+
+    #[test]
+    fn frames_without_recursion() {
+        assert_eq!(
+            find_recursions(&functions("abca")),
+            vec![recursion(0..1, 1), recursion(1..2, 1), recursion(2..3, 1), recursion(3..4, 1)]
+        );
+        // Unknown functions are not assumed to be the same.
+        assert_eq!(find_recursions(&functions("??")), vec![recursion(0..1, 1), recursion(1..2, 1)]);
+    }
+}
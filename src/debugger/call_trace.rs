@@ -0,0 +1,213 @@
+//! Tree of the contract calls made during the execution, reported by execution tools.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::cheatcodes::CheatcodeInvocation;
+use crate::debugger::execution_info::ExecutionInfo;
+use crate::debugger::panics;
+
+/// Filter of the exception breakpoint stopping in the caller of a contract call that failed,
+/// where the failure surfaces.
+pub const FAILED_CALL_FILTER: &str = "failedCall";
+
+/// Call of a contract entrypoint, see [`crate::Debugger::enter_class`].
+pub struct ContractCall {
+    pub class_hash: Felt,
+    pub contract_address: Felt,
+    pub caller_address: Felt,
+    pub entry_point_selector: Felt,
+    pub calldata: Vec<Felt>,
+}
+
+/// Outcome of a contract call, see [`crate::Debugger::exit_class`].
+pub struct CallResult {
+    /// Data returned by the entrypoint, `None` if the call failed.
+    pub retdata: Option<Vec<Felt>>,
+    /// Data the call panicked with, if it failed. Empty if it succeeded or the data is unknown.
+    pub panic_data: Vec<Felt>,
+    pub resources: CallResources,
+}
+
+/// Resources used by a contract call, including its nested calls.
+#[derive(Default)]
+pub struct CallResources {
+    pub steps: usize,
+    pub gas_consumed: u64,
+    /// Number of invocations of each builtin, by the name of the builtin.
+    pub builtins: BTreeMap<String, usize>,
+}
+
+#[derive(Default)]
+pub struct CallTrace {
+    /// Calls made directly by the debugged program.
+    calls: Vec<CallNode>,
+    /// Cheatcodes invoked by the debugged program itself.
+    cheatcodes: Vec<CheatcodeInvocation>,
+    /// Indexes of the calls being executed at consecutive levels of the tree,
+    /// the innermost one last.
+    active_path: Vec<usize>,
+}
+
+struct CallNode {
+    call: ContractCall,
+    /// Name of the entrypoint, known once it was executed with the ABI of the class.
+    entrypoint: Option<String>,
+    result: Option<CallResult>,
+    return_value: Option<String>,
+    /// Cheatcodes invoked during the call, outside of its nested calls.
+    cheatcodes: Vec<CheatcodeInvocation>,
+    /// Block and transaction seen by the contract, once known.
+    execution_info: Option<ExecutionInfo>,
+    children: Vec<CallNode>,
+}
+
+impl CallTrace {
+    pub fn enter(&mut self, call: ContractCall) {
+        let siblings = match self.active_node_mut() {
+            Some(node) => &mut node.children,
+            None => &mut self.calls,
+        };
+        siblings.push(CallNode {
+            call,
+            entrypoint: None,
+            result: None,
+            return_value: None,
+            cheatcodes: Vec::new(),
+            execution_info: None,
+            children: Vec::new(),
+        });
+        let index = siblings.len() - 1;
+        self.active_path.push(index);
+    }
+
+    /// Records the result of the innermost call being executed, which finished.
+    /// `entrypoint` and `return_value` are the name of the entrypoint and the decoded retdata,
+    /// if the ABI of the class is known.
+    pub fn exit(
+        &mut self,
+        result: CallResult,
+        entrypoint: Option<String>,
+        return_value: Option<String>,
+    ) {
+        if let Some(node) = self.active_node_mut() {
+            node.result = Some(result);
+            node.entrypoint = entrypoint;
+            node.return_value = return_value;
+        }
+        self.active_path.pop();
+    }
+
+    /// Records the cheatcode in the call being executed.
+    pub fn record_cheatcode(&mut self, cheatcode: CheatcodeInvocation) {
+        match self.active_node_mut() {
+            Some(node) => node.cheatcodes.push(cheatcode),
+            None => self.cheatcodes.push(cheatcode),
+        }
+    }
+
+    /// Records the block and transaction seen by the call being executed, if there is one.
+    pub fn record_execution_info(&mut self, info: ExecutionInfo) {
+        if let Some(node) = self.active_node_mut() {
+            node.execution_info = Some(info);
+        }
+    }
+
+    /// Block and transaction seen by the call being executed, if known.
+    pub fn active_execution_info(&self) -> Option<&ExecutionInfo> {
+        self.active_node()?.execution_info.as_ref()
+    }
+
+    /// Serializes the tree, marking the calls which are being executed at the current stop.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "calls": nodes_to_json(&self.calls, Some(&self.active_path)),
+            "cheatcodes": cheatcodes_to_json(&self.cheatcodes),
+        })
+    }
+
+    /// Innermost call being executed.
+    pub fn active_call(&self) -> Option<&ContractCall> {
+        Some(&self.active_node()?.call)
+    }
+
+    fn active_node(&self) -> Option<&CallNode> {
+        let (&first, rest) = self.active_path.split_first()?;
+        let mut node = self.calls.get(first)?;
+        for &index in rest {
+            node = node.children.get(index)?;
+        }
+        Some(node)
+    }
+
+    fn active_node_mut(&mut self) -> Option<&mut CallNode> {
+        let (&first, rest) = self.active_path.split_first()?;
+        let mut node = self.calls.get_mut(first)?;
+        for &index in rest {
+            node = node.children.get_mut(index)?;
+        }
+        Some(node)
+    }
+}
+
+/// `active_path` is the path to the active call relative to `nodes`, if it goes through them.
+fn nodes_to_json(nodes: &[CallNode], active_path: Option<&[usize]>) -> Vec<Value> {
+    let active = active_path.and_then(<[usize]>::split_first);
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| {
+            let child_path = active.filter(|(&first, _)| first == index).map(|(_, rest)| rest);
+            node_to_json(node, child_path)
+        })
+        .collect()
+}
+
+fn node_to_json(node: &CallNode, active_path: Option<&[usize]>) -> Value {
+    let status = match &node.result {
+        None => "running",
+        Some(CallResult { retdata: Some(_), .. }) => "succeeded",
+        Some(CallResult { retdata: None, .. }) => "failed",
+    };
+    let result = node.result.as_ref().map(|result| {
+        json!({
+            "retdata": result.retdata.as_deref().map(felts_to_json),
+            "panicData": result.retdata.is_none().then(|| felts_to_json(&result.panic_data)),
+            "panicMessage": result
+                .retdata
+                .is_none()
+                .then(|| panics::decode_panic_data(&result.panic_data)),
+            "returnValue": node.return_value,
+            "resources": {
+                "steps": result.resources.steps,
+                "gasConsumed": result.resources.gas_consumed,
+                "builtins": result.resources.builtins,
+            },
+        })
+    });
+
+    json!({
+        "classHash": node.call.class_hash.to_hex_string(),
+        "contractAddress": node.call.contract_address.to_hex_string(),
+        "callerAddress": node.call.caller_address.to_hex_string(),
+        "selector": node.call.entry_point_selector.to_hex_string(),
+        "entrypoint": node.entrypoint,
+        "calldata": felts_to_json(&node.call.calldata),
+        "status": status,
+        "result": result,
+        // Whether the execution is currently stopped inside the call.
+        "active": active_path.is_some(),
+        "cheatcodes": cheatcodes_to_json(&node.cheatcodes),
+        "children": nodes_to_json(&node.children, active_path),
+    })
+}
+
+fn cheatcodes_to_json(cheatcodes: &[CheatcodeInvocation]) -> Vec<Value> {
+    cheatcodes.iter().map(CheatcodeInvocation::to_json).collect()
+}
+
+fn felts_to_json(felts: &[Felt]) -> Vec<String> {
+    felts.iter().map(Felt::to_hex_string).collect()
+}
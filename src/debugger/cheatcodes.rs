@@ -0,0 +1,99 @@
+//! Cheatcodes of Starknet Foundry, invoked by tests with the `cheatcode` hint,
+//! which change the behavior of the executed contracts.
+
+use std::any::Any;
+
+use cairo_lang_casm::hints::{Hint, StarknetHint};
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::memory;
+use crate::debugger::vm_access::{Address, VmAccess};
+
+/// Properties of the execution changed by the common cheatcodes, by the name of the cheatcode.
+/// Names of the cheatcodes that start and stop cheating are derived from these.
+const CHEATED_PROPERTIES: &[(&str, &str)] = &[
+    ("cheat_caller_address", "caller address"),
+    ("cheat_block_timestamp", "block timestamp"),
+    ("cheat_block_number", "block number"),
+    ("cheat_sequencer_address", "sequencer address"),
+    ("cheat_account_contract_address", "account contract address"),
+    ("cheat_transaction_hash", "transaction hash"),
+    ("cheat_execution_info", "execution info"),
+    ("mock_call", "data returned by calls"),
+    ("replace_bytecode", "class of a contract"),
+];
+
+pub struct CheatcodeInvocation {
+    pub name: String,
+    pub input: Vec<Felt>,
+    /// Addresses of the cells the cheatcode writes the start and end of its output to.
+    pub output_cells: Option<(Address, Address)>,
+}
+
+impl CheatcodeInvocation {
+    /// Human-readable summary, e.g. `stop_cheat_block_number restores the block number, input: [..]`.
+    pub fn describe(&self) -> String {
+        let input = self.input.iter().map(Felt::to_hex_string).collect::<Vec<_>>().join(", ");
+        match cheated_property(&self.name) {
+            Some(property) if self.name.starts_with("stop_") => {
+                format!("{} restores the {property}, input: [{input}]", self.name)
+            }
+            Some(property) => format!("{} changes the {property}, input: [{input}]", self.name),
+            None => format!("{} called, input: [{input}]", self.name),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "cheatedProperty": cheated_property(&self.name),
+            "input": self.input.iter().map(Felt::to_hex_string).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Reads the output of the cheatcode, once it was executed.
+pub fn read_output(vm: &dyn VmAccess, (start, end): (Address, Address)) -> Option<Vec<Felt>> {
+    let start = vm.read_pointer(start)?;
+    let end = vm.read_pointer(end)?;
+    vm.read_felts(start, (end - start)?)
+}
+
+/// Returns the cheatcodes invoked by the hints of the instruction about to be executed.
+pub fn find_cheatcodes(vm: &dyn VmAccess, hints_data: &[Box<dyn Any>]) -> Vec<CheatcodeInvocation> {
+    hints_data
+        .iter()
+        .filter_map(|hint_data| hint_data.downcast_ref::<Vec<Hint>>())
+        .flatten()
+        .filter_map(|hint| match hint {
+            Hint::Starknet(StarknetHint::Cheatcode {
+                selector,
+                input_start,
+                input_end,
+                output_start,
+                output_end,
+            }) => {
+                // Selectors are names of the cheatcodes, encoded as short strings.
+                let name = String::from_utf8_lossy(&selector.value.to_bytes_be().1).into_owned();
+                let input = memory::read_felts(vm, input_start, input_end).unwrap_or_default();
+                let output_cells = memory::cell_address(vm, output_start)
+                    .zip(memory::cell_address(vm, output_end));
+                Some(CheatcodeInvocation { name, input, output_cells })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn cheated_property(name: &str) -> Option<&'static str> {
+    let base = ["start_", "stop_"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+        .trim_end_matches("_global");
+    CHEATED_PROPERTIES
+        .iter()
+        .find(|(cheatcode, _)| *cheatcode == base)
+        .map(|(_, property)| *property)
+}
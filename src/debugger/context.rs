@@ -1,37 +1,148 @@
+use std::cell::{Cell, OnceCell};
 use std::collections::HashMap;
-use std::fs;
-use std::ops::Not;
+use std::ops::{Not, Range};
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use anyhow::{Context as AnyhowContext, Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use cairo_annotations::annotations::TryFromDebugInfo;
 use cairo_annotations::annotations::coverage::{
-    CodeLocation, CoverageAnnotationsV1 as SierraCodeLocations,
+    CodeLocation, CoverageAnnotationsV1 as SierraCodeLocations, SourceFileFullPath,
 };
 use cairo_annotations::annotations::profiler::{
     FunctionName, ProfilerAnnotationsV1 as SierraFunctionNames,
 };
-use cairo_lang_sierra::extensions::core::{CoreConcreteLibfunc, CoreLibfunc, CoreType};
-use cairo_lang_sierra::program::{Program, ProgramArtifact, Statement, StatementIdx};
+use cairo_lang_sierra::debug_info::DebugInfo;
+use cairo_lang_sierra::extensions::circuit::CircuitTypeConcrete;
+use cairo_lang_sierra::extensions::core::{
+    CoreConcreteLibfunc, CoreLibfunc, CoreType, CoreTypeConcrete,
+};
+use cairo_lang_sierra::extensions::starknet::StarkNetTypeConcrete;
+use cairo_lang_sierra::ids::ConcreteTypeId;
+use cairo_lang_sierra::program::{Program, Statement, StatementIdx};
 use cairo_lang_sierra::program_registry::ProgramRegistry;
-use scarb_metadata::MetadataCommand;
-
+use cairo_lang_sierra_type_size::get_type_size_map;
+use scarb_metadata::Metadata;
+use tracing::{debug, warn};
+
+use crate::debugger::abi::{self, ContractAbi};
+use crate::debugger::context::artifact::{ArtifactKind, LoadedProgram};
+use crate::debugger::context::cairo0::Cairo0Program;
+use crate::debugger::context::source_files::{self, SourceFiles};
+use crate::debugger::errors::DebuggerError;
+use crate::debugger::panics;
+use crate::debugger::storage::{self, StorageVariable};
+
+pub mod artifact;
+pub mod cairo0;
+mod project;
 #[cfg(feature = "dev")]
 mod readable_sierra_ids;
+pub mod registry;
+mod source_files;
 
 /// Struct that holds all the initial data needed for the debugger during execution.
 pub struct Context {
-    pub root_path: PathBuf,
+    /// Path to the compiled program: the Sierra program, an executable, a contract class
+    /// or a Cairo 0 program.
+    pub sierra_path: PathBuf,
+    /// Kind of the compiled artifact the program was loaded from.
+    pub artifact_kind: ArtifactKind,
+    /// Roots of the workspace member packages - code inside them is the user code.
+    /// Both the paths reported by Scarb and their canonical forms are kept.
+    user_code_roots: Vec<PathBuf>,
+    pub packages: Vec<Package>,
+    /// Whether the project layout is known, from cached metadata or the launch configuration,
+    /// or Scarb was queried for it already.
+    project_resolved: bool,
+    pub source_files: SourceFiles,
+    /// ABI of the contract class, if the program is one.
+    pub abi: Option<ContractAbi>,
+    /// Storage variables declared by the program, if it is a contract.
+    pub storage_variables: Vec<StorageVariable>,
+    /// Full paths of the tests of the program, e.g. `hello::tests::test_increase_balance`.
+    pub test_functions: Vec<String>,
     casm_debug_info: CasmDebugInfo,
+    /// Statement looked up last with the bounds of its pc interval, since consecutive
+    /// instructions usually belong to the same statement.
+    last_pc_lookup: Cell<Option<(usize, usize, StatementIdx)>>,
     code_locations: SierraCodeLocations,
+    /// Code location of each statement, not including inlined ones, indexed by the statement,
+    /// so that lookups made at each step and stop need no hashing.
+    statement_locations: Vec<Option<CodeLocation>>,
     function_names: SierraFunctionNames,
-    files_data: HashMap<PathBuf, FileCodeLocationsData>,
-    program: Program,
-    sierra_program_registry: ProgramRegistry<CoreType, CoreLibfunc>,
+    /// Sierra functions sorted by their entry points.
+    functions: Vec<SierraFunction>,
+    /// Sorted pc intervals of the statements of panic functions, including the inlined ones,
+    /// so that free-running execution notices panics without mapping each pc to a statement.
+    panic_pcs: Vec<Range<usize>>,
+    /// Lines of each file, needed only once a breakpoint is set, hence mapped only then.
+    files_data: OnceCell<HashMap<PathBuf, FileCodeLocationsData>>,
+    backend: Backend,
     #[cfg(feature = "dev")]
     labels: HashMap<usize, String>,
 }
 
+/// Scarb package whose code may be a part of the debugged program.
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub root: PathBuf,
+    pub is_workspace_member: bool,
+}
+
+/// Program that statements come from.
+enum Backend {
+    Sierra {
+        program: Program,
+        registry: ProgramRegistry<CoreType, CoreLibfunc>,
+    },
+    /// Cairo 0 programs have no statements other than the instructions themselves,
+    /// so only kinds of the instructions are kept, indexed by their pcs.
+    Cairo0 {
+        statement_kinds: Vec<StatementKind>,
+    },
+}
+
+/// Sierra function, as displayed to the user.
+struct SierraFunction {
+    entry_point: StatementIdx,
+    name: Option<String>,
+    params: Vec<FunctionParam>,
+}
+
+/// Parameter of a Sierra function.
+pub struct FunctionParam {
+    /// Sierra variable ids usually carry no debug names, in which case this is `None`.
+    pub name: Option<String>,
+    pub type_name: String,
+    /// Number of memory cells the parameter takes.
+    pub size: usize,
+    /// Builtins are passed as implicit parameters, which the user never sees in the code.
+    pub is_builtin: bool,
+    /// Whether the parameter is the remaining Sierra gas.
+    pub is_gas: bool,
+}
+
+/// Names under which the compiler marks test functions as executables in the debug info,
+/// for Starknet Foundry and for `cairo-test`.
+const TEST_EXECUTABLE_KEYS: [&str; 2] = ["snforge_internal_test_executable", "test"];
+
+/// Kind of a Sierra statement, with regard to its effect on the call stack.
+#[derive(Clone, Copy)]
+pub enum StatementKind {
+    /// Creates a new frame for the called function.
+    FunctionCall,
+    /// Leaves the current frame and gets back to the caller.
+    Return,
+    /// Transfers control to a different function without creating a new frame, like a tail call.
+    /// The target function takes over the current frame and returns directly to its caller.
+    CrossFunctionJump,
+    Other,
+}
+
+#[derive(Clone)]
 pub struct CasmDebugInfo {
     /// Sierra statement index -> start CASM bytecode offset
     pub statement_to_pc: Vec<usize>,
@@ -52,6 +163,11 @@ impl Line {
         Self(line)
     }
 
+    /// Line number as displayed by the client, which is 1-indexed.
+    pub fn ui_number(self) -> i64 {
+        (self.0 + 1) as i64
+    }
+
     pub fn create_from_statement_idx(statement_idx: StatementIdx, ctx: &Context) -> Self {
         let CodeLocation(_, code_span, _) = ctx
             .code_location_for_statement_idx(statement_idx)
@@ -61,55 +177,306 @@ impl Line {
 }
 
 impl Context {
+    /// Creates the context of the program at `sierra_path`. For Cairo 0 programs,
+    /// `casm_debug_info` is ignored, since every pc is a statement of its own.
     pub fn new(sierra_path: &Path, casm_debug_info: CasmDebugInfo) -> Result<Self> {
-        let root_path = get_project_root_path(sierra_path)?;
+        if artifact::kind_of(sierra_path)? == ArtifactKind::Cairo0 {
+            return Self::new_cairo0(sierra_path);
+        }
 
-        let content = fs::read_to_string(sierra_path).expect("Failed to load sierra file");
-        let sierra_program: ProgramArtifact = serde_json::from_str(&content)?;
+        let started = Instant::now();
+        // Scarb is not queried here, since the launch configuration may tell the project
+        // layout instead, see [`Self::resolve_project`].
+        let metadata = project::cached_metadata(sierra_path);
+        let packages = metadata.as_ref().map(build_packages).unwrap_or_default();
+        let user_code_roots = metadata
+            .as_ref()
+            .map(|metadata| build_user_code_roots(metadata, &packages))
+            .unwrap_or_default();
+
+        let LoadedProgram { program: sierra_program, kind: artifact_kind, abi, .. } =
+            artifact::load_program(sierra_path)?;
         let program = sierra_program.program;
+        let debug_info = sierra_program.debug_info.ok_or_else(|| {
+            DebuggerError::MissingDebugInfo { artifact: sierra_path.to_path_buf() }
+        })?;
+
+        // The parts of the debug info independent of each other are decoded in parallel,
+        // which matters for big programs.
+        let ((sierra_program_registry, functions), (code_locations, function_names)) = rayon::join(
+            || {
+                let registry =
+                    ProgramRegistry::new(&program).expect("creating program registry failed");
+                let functions = build_functions(&program, &registry, &debug_info);
+                (registry, functions)
+            },
+            || {
+                rayon::join(
+                    || SierraCodeLocations::try_from_debug_info(&debug_info),
+                    || SierraFunctionNames::try_from_debug_info(&debug_info),
+                )
+            },
+        );
+        let (functions, code_locations, function_names) =
+            (functions?, code_locations?, function_names?);
+        let statement_locations = build_statement_locations(&casm_debug_info, &code_locations);
+        let test_functions = build_test_functions(&debug_info);
+        let mut source_files = SourceFiles::new(
+            &code_locations,
+            sierra_path,
+            &source_files::compiled_checksums(&debug_info),
+        );
+        source_files.detect_corelib_root(&packages);
+        let storage_variables = storage::storage_variables(
+            debug_info
+                .type_names
+                .values()
+                .chain(debug_info.user_func_names.values())
+                .map(|name| name.as_str()),
+        );
+        debug!("Loaded the debug info of {} in {:?}", sierra_path.display(), started.elapsed());
+
+        let mut ctx = Self {
+            #[cfg(feature = "dev")]
+            labels: readable_sierra_ids::extract_labels(&program),
+
+            sierra_path: sierra_path.to_path_buf(),
+            artifact_kind,
+            user_code_roots,
+            packages,
+            project_resolved: metadata.is_some(),
+            source_files,
+            abi: abi.as_ref().map(ContractAbi::parse).transpose()?,
+            storage_variables,
+            test_functions,
+            code_locations,
+            statement_locations,
+            function_names,
+            functions,
+            casm_debug_info,
+            last_pc_lookup: Cell::new(None),
+            panic_pcs: Vec::new(),
+            files_data: OnceCell::new(),
+            backend: Backend::Sierra { program, registry: sierra_program_registry },
+        };
+        ctx.panic_pcs = ctx.build_panic_pcs();
+        Ok(ctx)
+    }
 
-        let sierra_program_registry =
-            ProgramRegistry::new(&program).expect("creating program registry failed");
+    /// Loads the program again from [`Self::sierra_path`], e.g. once it was rebuilt. Returns `None`
+    /// if the code changed, rather than only its debug info, e.g. the lines it maps to, since
+    /// the execution still runs the previous code, which the new debug info does not describe.
+    /// Cairo 0 programs are never reloaded, since their debug info maps the pcs of their own code.
+    pub fn reload(&self) -> Result<Option<Self>> {
+        let Backend::Sierra { program, .. } = &self.backend else {
+            return Ok(None);
+        };
+        let reloaded = Self::new(&self.sierra_path, self.casm_debug_info.clone())?;
+        let same_code = matches!(
+            &reloaded.backend,
+            Backend::Sierra { program: reloaded_program, .. } if reloaded_program == program
+        );
+        Ok(same_code.then_some(reloaded))
+    }
 
-        let debug_info = sierra_program
-            .debug_info
-            .ok_or_else(|| anyhow!("debug_info must be present in compiled sierra"))?;
-        let code_locations = SierraCodeLocations::try_from_debug_info(&debug_info)?;
-        let function_names = SierraFunctionNames::try_from_debug_info(&debug_info)?;
-        let files_data = build_file_locations_map(&casm_debug_info, &code_locations);
+    /// Cairo 0 programs are not a part of a Scarb project, so, without roots of the user's code,
+    /// all of their code is considered the user code.
+    fn new_cairo0(program_path: &Path) -> Result<Self> {
+        let Cairo0Program {
+            casm_debug_info,
+            code_locations,
+            function_names,
+            functions,
+            statement_kinds,
+        } = cairo0::load(program_path)?;
+
+        let statement_locations = build_statement_locations(&casm_debug_info, &code_locations);
+        // Cairo 0 programs do not record the checksums of their files.
+        let source_files = SourceFiles::new(&code_locations, program_path, &HashMap::new());
+        let functions = functions
+            .into_iter()
+            .map(|(pc, name)| SierraFunction {
+                entry_point: StatementIdx(pc),
+                name: Some(name),
+                params: Vec::new(),
+            })
+            .collect();
 
         Ok(Self {
             #[cfg(feature = "dev")]
-            labels: readable_sierra_ids::extract_labels(&program),
-
-            root_path,
+            labels: HashMap::new(),
+
+            sierra_path: program_path.to_path_buf(),
+            artifact_kind: ArtifactKind::Cairo0,
+            user_code_roots: Vec::new(),
+            packages: Vec::new(),
+            project_resolved: true,
+            source_files,
+            abi: None,
+            storage_variables: Vec::new(),
+            test_functions: Vec::new(),
             code_locations,
+            statement_locations,
             function_names,
+            functions,
             casm_debug_info,
-            files_data,
-            program,
-            sierra_program_registry,
+            last_pc_lookup: Cell::new(None),
+            // Cairo 0 programs do not panic like Cairo programs.
+            panic_pcs: Vec::new(),
+            files_data: OnceCell::new(),
+            backend: Backend::Cairo0 { statement_kinds },
         })
     }
 
+    /// Finds the test matching the filter: the one whose full path is equal to it,
+    /// or else the only one whose full path contains it. Otherwise, the error lists
+    /// the candidates, one per line, so that the client can let the user choose.
+    pub fn find_test(&self, filter: &str) -> Result<&str> {
+        if let Some(test) = self.test_functions.iter().find(|test| *test == filter) {
+            return Ok(test);
+        }
+        let candidates: Vec<&str> = self
+            .test_functions
+            .iter()
+            .filter(|test| test.contains(filter))
+            .map(String::as_str)
+            .collect();
+        match candidates.as_slice() {
+            [test] => Ok(test),
+            [] if self.test_functions.is_empty() => bail!("The program contains no tests"),
+            [] => bail!(
+                "No test matches `{filter}`, available tests:\n{}",
+                self.test_functions.join("\n")
+            ),
+            _ => bail!("Multiple tests match `{filter}`:\n{}", candidates.join("\n")),
+        }
+    }
+
+    /// Moves the program by `offset` in the memory, e.g. once the code calling the function
+    /// to run was placed before it.
+    pub fn offset_pcs(&mut self, offset: usize) {
+        for pc in &mut self.casm_debug_info.statement_to_pc {
+            *pc += offset;
+        }
+        for pcs in &mut self.panic_pcs {
+            *pcs = pcs.start + offset..pcs.end.saturating_add(offset);
+        }
+        self.last_pc_lookup.set(None);
+    }
+
+    /// Queries Scarb for the packages of the project and the user's code among them,
+    /// unless they are known already. Outside of a Scarb project, all code is the user's code.
+    pub fn resolve_project(&mut self) {
+        if self.project_resolved {
+            return;
+        }
+        self.project_resolved = true;
+
+        match project::query_scarb(&self.sierra_path) {
+            Ok(metadata) => {
+                self.packages = build_packages(&metadata);
+                self.user_code_roots = build_user_code_roots(&metadata, &self.packages);
+                self.source_files.detect_corelib_root(&self.packages);
+            }
+            Err(err) => warn!(
+                "{err:#}. Set `workspaceRoot` in the launch configuration to tell the user's code"
+            ),
+        }
+    }
+
+    /// Sets the roots of the user's code given in the launch configuration, so that Scarb
+    /// is not queried for them.
+    pub fn set_user_code_roots(&mut self, roots: Vec<PathBuf>) {
+        self.user_code_roots = with_canonical_roots(roots);
+        self.project_resolved = true;
+    }
+
+    /// Whether the file belongs to the user's workspace, as opposed to e.g. corelib or dependencies.
+    /// All files do if the workspace is not known, so that `justMyCode` does not hide all code.
+    pub fn is_user_code(&self, path: &Path) -> bool {
+        self.user_code_roots.is_empty()
+            || self.user_code_roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// Whether the statement's code location (not including inlined ones) is in user code.
+    pub fn is_user_code_statement(&self, statement_idx: StatementIdx) -> bool {
+        self.code_location_for_statement_idx(statement_idx)
+            .is_some_and(|CodeLocation(path, _, _)| self.is_user_code(Path::new(&path.0)))
+    }
+
     pub fn statement_idx_for_pc(&self, pc: usize) -> StatementIdx {
-        StatementIdx(
-            self.casm_debug_info
-                .statement_to_pc
-                .partition_point(|&offset| offset <= pc)
-                .saturating_sub(1),
-        )
+        if let Some((start, end, statement_idx)) = self.last_pc_lookup.get()
+            && (start..end).contains(&pc)
+        {
+            return statement_idx;
+        }
+
+        let statement_to_pc = &self.casm_debug_info.statement_to_pc;
+        let next = statement_to_pc.partition_point(|&offset| offset <= pc);
+        let statement_idx = StatementIdx(next.saturating_sub(1));
+        // Pcs before the first statement, e.g. of the entry code, are mapped to it as well.
+        let start = next.checked_sub(1).map_or(0, |idx| statement_to_pc[idx]);
+        let end = statement_to_pc.get(next).copied().unwrap_or(usize::MAX);
+        self.last_pc_lookup.set(Some((start, end, statement_idx)));
+        statement_idx
+    }
+
+    /// Pcs mapped to the statement by [`Self::statement_idx_for_pc`], so the last statement
+    /// extends to the end of the program.
+    pub fn statement_pcs(&self, statement_idx: StatementIdx) -> Range<usize> {
+        let statement_to_pc = &self.casm_debug_info.statement_to_pc;
+        let Some(&start) = statement_to_pc.get(statement_idx.0) else {
+            return 0..0;
+        };
+        // Pcs before the first statement, e.g. of the entry code, are mapped to it as well.
+        let start = if statement_idx.0 == 0 { 0 } else { start };
+        let end = statement_to_pc.get(statement_idx.0 + 1).copied().unwrap_or(usize::MAX);
+        start..end
+    }
+
+    /// Start of the last statement of the program.
+    pub fn last_statement_pc(&self) -> usize {
+        self.casm_debug_info.statement_to_pc.last().copied().unwrap_or(0)
+    }
+
+    /// Whether the pc belongs to a panic function, see [`panics::panic_function`].
+    pub fn is_panic_pc(&self, pc: usize) -> bool {
+        let next = self.panic_pcs.partition_point(|pcs| pcs.start <= pc);
+        next.checked_sub(1).is_some_and(|idx| self.panic_pcs[idx].contains(&pc))
+    }
+
+    fn build_panic_pcs(&self) -> Vec<Range<usize>> {
+        let statement_to_pc = &self.casm_debug_info.statement_to_pc;
+        let mut panic_pcs: Vec<Range<usize>> = Vec::new();
+        for (idx, &start) in statement_to_pc.iter().enumerate() {
+            let end = statement_to_pc.get(idx + 1).copied().unwrap_or(usize::MAX);
+            if start == end || panics::panic_function(self, StatementIdx(idx)).is_none() {
+                continue;
+            }
+            match panic_pcs.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => panic_pcs.push(start..end),
+            }
+        }
+        panic_pcs
     }
 
     /// Return code location for the current statement, not including inlined code locations.
     pub fn code_location_for_statement_idx(
         &self,
         statement_idx: StatementIdx,
-    ) -> Option<CodeLocation> {
-        self.code_locations
-            .statements_code_locations
-            .get(&statement_idx)
-            .and_then(|locations| locations.first().cloned())
+    ) -> Option<&CodeLocation> {
+        self.statement_locations.get(statement_idx.0)?.as_ref()
+    }
+
+    /// Location of the statement as `path:line`, for messages to the user.
+    pub fn describe_location(&self, statement_idx: StatementIdx) -> String {
+        match self.code_location_for_statement_idx(statement_idx) {
+            Some(CodeLocation(SourceFileFullPath(path), code_span, _)) => {
+                format!("{path}:{}", code_span.start.line.0 + 1)
+            }
+            None => format!("statement {}", statement_idx.0),
+        }
     }
 
     /// Return code locations for the current statement, including inlined code locations.
@@ -130,38 +497,148 @@ impl Context {
         self.function_names.statements_functions.get(&statement_idx)
     }
 
+    /// Return the Sierra debug name of the function that the statement belongs to.
+    /// Unlike names from [`Self::function_names_for_statement_idx`], it includes generic arguments,
+    /// e.g. `core::array::ArrayImpl::<core::felt252>::append`.
+    pub fn sierra_function_name_for_statement_idx(
+        &self,
+        statement_idx: StatementIdx,
+    ) -> Option<&str> {
+        self.function_for_statement_idx(statement_idx)?.name.as_deref()
+    }
+
+    /// Return the entry point of the Sierra function that the statement belongs to,
+    /// which identifies the function.
+    pub fn function_entry_point_for_statement_idx(
+        &self,
+        statement_idx: StatementIdx,
+    ) -> Option<StatementIdx> {
+        Some(self.function_for_statement_idx(statement_idx)?.entry_point)
+    }
+
+    /// Return parameters of the Sierra function that the statement belongs to.
+    pub fn function_params_for_statement_idx(
+        &self,
+        statement_idx: StatementIdx,
+    ) -> Option<&[FunctionParam]> {
+        Some(&self.function_for_statement_idx(statement_idx)?.params)
+    }
+
+    /// Return the package that the file belongs to. Packages can be nested, e.g. a workspace
+    /// member in the workspace root, so the most nested one is chosen.
+    pub fn package_for_path(&self, path: &Path) -> Option<&Package> {
+        self.packages
+            .iter()
+            .filter(|package| path.starts_with(&package.root))
+            .max_by_key(|package| package.root.components().count())
+    }
+
+    fn function_for_statement_idx(&self, statement_idx: StatementIdx) -> Option<&SierraFunction> {
+        let functions_started =
+            self.functions.partition_point(|function| function.entry_point.0 <= statement_idx.0);
+        self.functions.get(functions_started.checked_sub(1)?)
+    }
+
     pub fn statement_idxs_for_breakpoint(
         &self,
         source: &Path,
         line: Line,
     ) -> Option<&Vec<StatementIdx>> {
-        self.files_data.get(source)?.lines.get(&line)
+        let original_path = self.source_files.original_path(source);
+        let source = original_path.as_deref().unwrap_or(source);
+        let file_data = self.files_data().get(source).or_else(|| {
+            // The client may refer to the file by a different path than the one in the debug info,
+            // e.g. through a symlinked package directory.
+            self.files_data().get(self.source_files.recorded_path(source)?)
+        })?;
+        file_data.lines.get(&line)
     }
 
-    pub fn is_return_statement(&self, statement_idx: StatementIdx) -> bool {
-        matches!(self.statement_idx_to_statement(statement_idx), Statement::Return(_))
+    fn files_data(&self) -> &HashMap<PathBuf, FileCodeLocationsData> {
+        self.files_data
+            .get_or_init(|| build_file_locations_map(&self.casm_debug_info, &self.code_locations))
     }
 
-    pub fn is_function_call_statement(&self, statement_idx: StatementIdx) -> bool {
-        match self.statement_idx_to_statement(statement_idx) {
-            Statement::Invocation(invocation) => {
-                matches!(
-                    self.sierra_program_registry.get_libfunc(&invocation.libfunc_id),
-                    Ok(CoreConcreteLibfunc::FunctionCall(_))
-                )
+    /// Return the first hittable statement of each function matching the name of a function
+    /// breakpoint: either its full path, e.g. `hello::HelloStarknet::handle_deposit`, or its last
+    /// segments, e.g. `handle_deposit`. Entrypoints, including L1 handlers, are usually inlined
+    /// into their wrappers, in which case the wrappers are matched instead.
+    pub fn statement_idxs_for_function_breakpoint(&self, name: &str) -> Vec<StatementIdx> {
+        let mut hittable: Vec<usize> = self
+            .files_data()
+            .values()
+            .flat_map(|file_data| file_data.lines.values().flatten())
+            .map(|statement_idx| statement_idx.0)
+            .collect();
+        hittable.sort_unstable();
+
+        let first_hittable = |position: usize| {
+            let function = &self.functions[position];
+            let end =
+                self.functions.get(position + 1).map_or(usize::MAX, |next| next.entry_point.0);
+            let first = hittable.partition_point(|idx| *idx < function.entry_point.0);
+            hittable.get(first).filter(|idx| **idx < end).map(|idx| StatementIdx(*idx))
+        };
+        let find = |matches: &dyn Fn(&str) -> bool| -> Vec<StatementIdx> {
+            (0..self.functions.len())
+                .filter(|&position| self.functions[position].name.as_deref().is_some_and(matches))
+                .filter_map(first_hittable)
+                .collect()
+        };
+
+        let statement_idxs = find(&|function_name| {
+            function_name == name
+                || function_name.strip_suffix(name).is_some_and(|path| path.ends_with("::"))
+        });
+        if !statement_idxs.is_empty() {
+            return statement_idxs;
+        }
+        find(&|function_name| abi::wrapped_function_name(function_name) == Some(name))
+    }
+
+    pub fn statement_kind(&self, statement_idx: StatementIdx) -> StatementKind {
+        let (program, registry) = match &self.backend {
+            Backend::Sierra { program, registry } => (program, registry),
+            Backend::Cairo0 { statement_kinds } => {
+                return statement_kinds
+                    .get(statement_idx.0)
+                    .copied()
+                    .unwrap_or(StatementKind::Other);
             }
-            Statement::Return(_) => false,
+        };
+        let invocation = match &program.statements[statement_idx.0] {
+            Statement::Return(_) => return StatementKind::Return,
+            Statement::Invocation(invocation) => invocation,
+        };
+
+        // Coupon calls compile to the same `call` instruction as regular function calls.
+        if matches!(
+            registry.get_libfunc(&invocation.libfunc_id),
+            Ok(CoreConcreteLibfunc::FunctionCall(_) | CoreConcreteLibfunc::CouponCall(_))
+        ) {
+            return StatementKind::FunctionCall;
         }
+
+        let function = self.function_for_statement_idx(statement_idx).map(|f| f.entry_point);
+        let leaves_function = invocation.branches.iter().any(|branch| {
+            let target = statement_idx.next(&branch.target);
+            self.function_for_statement_idx(target).map(|f| f.entry_point) != function
+        });
+        if leaves_function { StatementKind::CrossFunctionJump } else { StatementKind::Other }
     }
 
-    fn statement_idx_to_statement(&self, statement_idx: StatementIdx) -> &Statement {
-        &self.program.statements[statement_idx.0]
+    pub fn is_function_call_statement(&self, statement_idx: StatementIdx) -> bool {
+        matches!(self.statement_kind(statement_idx), StatementKind::FunctionCall)
     }
 
     #[cfg(feature = "dev")]
     #[allow(unused)]
     pub fn print_statement(&self, statement_idx: StatementIdx) {
-        let statement = self.statement_idx_to_statement(statement_idx);
+        let Backend::Sierra { program, .. } = &self.backend else {
+            eprintln!("pc {}", statement_idx.0);
+            return;
+        };
+        let statement = &program.statements[statement_idx.0];
         let with_labels = readable_sierra_ids::replace_statement_id(statement.clone(), |idx| {
             self.labels[&idx.0].clone()
         });
@@ -170,6 +647,19 @@ impl Context {
     }
 }
 
+fn build_statement_locations(
+    casm_debug_info: &CasmDebugInfo,
+    code_location_annotations: &SierraCodeLocations,
+) -> Vec<Option<CodeLocation>> {
+    let mut statement_locations = vec![None; casm_debug_info.statement_to_pc.len()];
+    for (statement_idx, locations) in &code_location_annotations.statements_code_locations {
+        if let Some(location) = statement_locations.get_mut(statement_idx.0) {
+            *location = locations.first().cloned();
+        }
+    }
+    statement_locations
+}
+
 fn build_file_locations_map(
     casm_debug_info: &CasmDebugInfo,
     code_location_annotations: &SierraCodeLocations,
@@ -221,14 +711,118 @@ fn build_file_locations_map(
     file_map
 }
 
-// TODO(#50)
-fn get_project_root_path(sierra_path: &Path) -> Result<PathBuf> {
-    Ok(MetadataCommand::new()
-        .current_dir(sierra_path.parent().expect("Compiled Sierra must be in target directory"))
-        .inherit_stderr()
-        .exec()
-        .context("Failed to get project metadata from Scarb")?
-        .workspace
-        .root
-        .into())
+fn build_functions(
+    program: &Program,
+    registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    debug_info: &DebugInfo,
+) -> Result<Vec<SierraFunction>> {
+    let type_sizes = get_type_size_map(program, registry)
+        .ok_or_else(|| anyhow!("Failed to compute sizes of Sierra types"))?;
+    let type_name = |ty: &ConcreteTypeId| {
+        debug_info
+            .type_names
+            .get(ty)
+            .or(ty.debug_name.as_ref())
+            .map_or_else(|| ty.to_string(), ToString::to_string)
+    };
+
+    let mut functions: Vec<_> = program
+        .funcs
+        .iter()
+        .map(|function| {
+            let name = debug_info
+                .user_func_names
+                .get(&function.id)
+                .or(function.id.debug_name.as_ref())
+                .map(ToString::to_string);
+            let params = function
+                .params
+                .iter()
+                .map(|param| FunctionParam {
+                    name: param.id.debug_name.as_ref().map(ToString::to_string),
+                    type_name: type_name(&param.ty),
+                    size: type_sizes.get(&param.ty).map_or(0, |&size| size as usize),
+                    is_builtin: registry.get_type(&param.ty).is_ok_and(is_builtin),
+                    is_gas: registry
+                        .get_type(&param.ty)
+                        .is_ok_and(|ty| matches!(ty, CoreTypeConcrete::GasBuiltin(_))),
+                })
+                .collect();
+            SierraFunction { entry_point: function.entry_point, name, params }
+        })
+        .collect();
+    functions.sort_by_key(|function| function.entry_point.0);
+    Ok(functions)
+}
+
+fn build_test_functions(debug_info: &DebugInfo) -> Vec<String> {
+    let mut tests: Vec<String> = TEST_EXECUTABLE_KEYS
+        .iter()
+        .filter_map(|key| debug_info.executables.get(*key))
+        .flatten()
+        .map(|function| {
+            debug_info
+                .user_func_names
+                .get(function)
+                .or(function.debug_name.as_ref())
+                .map_or_else(|| function.to_string(), ToString::to_string)
+        })
+        .collect();
+    tests.sort();
+    tests.dedup();
+    tests
+}
+
+fn is_builtin(ty: &CoreTypeConcrete) -> bool {
+    matches!(
+        ty,
+        CoreTypeConcrete::RangeCheck(_)
+            | CoreTypeConcrete::RangeCheck96(_)
+            | CoreTypeConcrete::GasBuiltin(_)
+            | CoreTypeConcrete::BuiltinCosts(_)
+            | CoreTypeConcrete::Bitwise(_)
+            | CoreTypeConcrete::EcOp(_)
+            | CoreTypeConcrete::Pedersen(_)
+            | CoreTypeConcrete::Poseidon(_)
+            | CoreTypeConcrete::SegmentArena(_)
+            | CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_))
+            | CoreTypeConcrete::Circuit(CircuitTypeConcrete::AddMod(_))
+            | CoreTypeConcrete::Circuit(CircuitTypeConcrete::MulMod(_))
+    )
+}
+
+fn build_user_code_roots(metadata: &Metadata, packages: &[Package]) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = packages
+        .iter()
+        .filter(|package| package.is_workspace_member)
+        .map(|package| package.root.clone())
+        .collect();
+    if roots.is_empty() {
+        roots.push(metadata.workspace.root.clone().into());
+    }
+    with_canonical_roots(roots)
+}
+
+/// Adds the canonical forms of the roots, since paths in the debug info may use either.
+fn with_canonical_roots(mut roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    let canonical_roots: Vec<PathBuf> =
+        roots.iter().filter_map(|root| root.canonicalize().ok()).collect();
+    roots.extend(canonical_roots);
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+fn build_packages(metadata: &Metadata) -> Vec<Package> {
+    metadata
+        .packages
+        .iter()
+        .map(|package| Package {
+            id: package.id.to_string(),
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            root: package.root.clone().into(),
+            is_workspace_member: metadata.workspace.members.contains(&package.id),
+        })
+        .collect()
 }
@@ -0,0 +1,179 @@
+//! Compiled programs accepted by the debugger: Sierra programs, e.g. of tests,
+//! executables built by Scarb for `scarb execute`, contract classes and Cairo 0 programs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use cairo_lang_sierra::program::ProgramArtifact;
+use cairo_lang_starknet_classes::contract_class::{ContractClass, ContractEntryPoints};
+use serde_json::Value;
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::cairo0;
+use crate::debugger::{abi, memory};
+
+/// Suffix of the executables built by Scarb, e.g. `target/dev/hello.executable.json`.
+const EXECUTABLE_SUFFIX: &str = ".executable.json";
+
+/// Suffix of the Sierra program of an executable, built next to it with `sierra = true`
+/// set for the executable target.
+const EXECUTABLE_SIERRA_SUFFIX: &str = ".executable.sierra.json";
+
+/// Prefix of the functions generated for `#[executable]` functions. They deserialize
+/// the arguments of the executable and serialize its result to the output.
+const EXECUTABLE_WRAPPER_PREFIX: &str = "__executable_wrapper__";
+
+/// Kind of the compiled artifact the program was loaded from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    Sierra,
+    /// Executable built by Scarb, run like with `scarb execute`.
+    Executable,
+    /// Contract class, e.g. `target/dev/hello_HelloStarknet.contract_class.json`,
+    /// whose entrypoints are called with calldata.
+    ContractClass,
+    /// Classic Cairo 0 program, which has no Sierra program. See [`cairo0`].
+    Cairo0,
+}
+
+/// Sierra program loaded from a compiled artifact.
+pub struct LoadedProgram {
+    pub program: ProgramArtifact,
+    pub kind: ArtifactKind,
+    /// ABI of the contract class, as stored in the artifact.
+    pub abi: Option<Value>,
+    /// Entrypoints of the contract class.
+    #[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+    pub entry_points: Option<ContractEntryPoints>,
+}
+
+/// Loads the Sierra program from `path`, which is the Sierra program itself, an executable
+/// or a contract class. Executables carry only CASM, so their Sierra program, with the debug info,
+/// is read from the file Scarb builds next to them.
+pub fn load_program(path: &Path) -> Result<LoadedProgram> {
+    let artifact = read_artifact(path)?;
+
+    match artifact_kind(&artifact) {
+        ArtifactKind::Sierra => {
+            let program = serde_json::from_value(artifact)
+                .with_context(|| format!("{} is not a Sierra program", path.display()))?;
+            Ok(LoadedProgram { program, kind: ArtifactKind::Sierra, abi: None, entry_points: None })
+        }
+        ArtifactKind::Executable => {
+            let sierra_path = executable_sierra_path(path);
+            if !sierra_path.is_file() {
+                bail!(
+                    "Sierra program of the executable not found at {}, \
+                     set `sierra = true` for the executable target in Scarb.toml",
+                    sierra_path.display()
+                );
+            }
+            let content = fs::read_to_string(&sierra_path)
+                .with_context(|| format!("Failed to read {}", sierra_path.display()))?;
+            let program = serde_json::from_str(&content)
+                .with_context(|| format!("{} is not a Sierra program", sierra_path.display()))?;
+            Ok(LoadedProgram {
+                program,
+                kind: ArtifactKind::Executable,
+                abi: None,
+                entry_points: None,
+            })
+        }
+        ArtifactKind::ContractClass => {
+            let abi = artifact.get("abi").cloned();
+            let class: ContractClass = serde_json::from_value(artifact)
+                .with_context(|| format!("{} is not a contract class", path.display()))?;
+            let mut program = class
+                .extract_sierra_program()
+                .map_err(|err| anyhow!("Failed to extract the Sierra program: {err}"))?;
+            // Names of functions and types are stored separately from the program in classes.
+            if let Some(debug_info) = &class.sierra_program_debug_info {
+                debug_info.populate(&mut program);
+            }
+            Ok(LoadedProgram {
+                program: ProgramArtifact { program, debug_info: class.sierra_program_debug_info },
+                kind: ArtifactKind::ContractClass,
+                abi,
+                entry_points: Some(class.entry_points_by_type),
+            })
+        }
+        ArtifactKind::Cairo0 => {
+            bail!("{} is a Cairo 0 program, which has no Sierra program", path.display())
+        }
+    }
+}
+
+/// Kind of the compiled artifact at `path`.
+pub fn kind_of(path: &Path) -> Result<ArtifactKind> {
+    Ok(artifact_kind(&read_artifact(path)?))
+}
+
+fn read_artifact(path: &Path) -> Result<Value> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a valid JSON", path.display()))
+}
+
+/// Name of the function run for `function` of an executable, e.g. `hello::main`.
+/// The function itself is called by its wrapper, which takes the arguments
+/// of the executable as a `Span<felt252>` and writes its result to an output array.
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub fn executable_wrapper_name(function: &str) -> String {
+    match function.rsplit_once("::") {
+        Some((_, name)) if name.starts_with(EXECUTABLE_WRAPPER_PREFIX) => function.to_string(),
+        Some((path, name)) => format!("{path}::{EXECUTABLE_WRAPPER_PREFIX}{name}"),
+        None => format!("{EXECUTABLE_WRAPPER_PREFIX}{function}"),
+    }
+}
+
+/// Executables built by Scarb consist of the assembled program and its entrypoints,
+/// and contract classes of the Sierra program encoded as felts.
+fn artifact_kind(artifact: &Value) -> ArtifactKind {
+    if cairo0::is_cairo0_program(artifact) {
+        ArtifactKind::Cairo0
+    } else if artifact.get("entrypoints").is_some() && artifact.get("program").is_some() {
+        ArtifactKind::Executable
+    } else if artifact.get("sierra_program").is_some() {
+        ArtifactKind::ContractClass
+    } else {
+        ArtifactKind::Sierra
+    }
+}
+
+/// `target/dev/hello.executable.json` -> `target/dev/hello.executable.sierra.json`.
+fn executable_sierra_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = file_name
+        .strip_suffix(EXECUTABLE_SUFFIX)
+        .or_else(|| file_name.strip_suffix(".json"))
+        .unwrap_or(&file_name);
+    path.with_file_name(format!("{stem}{EXECUTABLE_SIERRA_SUFFIX}"))
+}
+
+/// Finds the entrypoint of the contract class called with `entrypoint`, either its name,
+/// e.g. `increase_balance`, or its selector. Returns the selector and the index
+/// of the Sierra function implementing the entrypoint.
+#[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+pub fn find_entry_point(
+    entry_points: &ContractEntryPoints,
+    entrypoint: &str,
+) -> Result<(Felt, usize)> {
+    let selector = if entrypoint.starts_with("0x") || entrypoint.chars().all(|c| c.is_ascii_digit())
+    {
+        memory::parse_felt(entrypoint)?
+    } else {
+        abi::starknet_keccak(entrypoint)
+    };
+    entry_points
+        .external
+        .iter()
+        .chain(&entry_points.l1_handler)
+        .chain(&entry_points.constructor)
+        .find(|entry_point| {
+            Felt::from_bytes_be_slice(&entry_point.selector.value.to_bytes_be()) == selector
+        })
+        .map(|entry_point| (selector, entry_point.function_idx))
+        .ok_or_else(|| anyhow!("The contract class has no entrypoint {entrypoint}"))
+}
@@ -0,0 +1,169 @@
+//! Classic Cairo 0 programs, as compiled by `cairo-compile`, e.g. legacy contracts or OS code.
+//!
+//! They are executed directly, without Sierra, and carry the debug info mapping each instruction
+//! to the Cairo 0 code in `debug_info.instruction_locations`. The debugger treats every pc
+//! as a separate statement, so statement indexes are equal to pcs.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use cairo_annotations::annotations::coverage::{
+    CodeLocation, ColumnNumber, CoverageAnnotationsV1 as SierraCodeLocations, LineNumber,
+    SourceCodeLocation, SourceCodeSpan, SourceFileFullPath,
+};
+use cairo_annotations::annotations::profiler::{
+    FunctionName, ProfilerAnnotationsV1 as SierraFunctionNames,
+};
+use cairo_lang_sierra::program::StatementIdx;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::debugger::context::{CasmDebugInfo, StatementKind};
+
+/// Bits of the instruction flags, which take the highest 16 bits of an encoded instruction.
+const FLAGS_OFFSET: u32 = 48;
+const OPCODE_CALL_BIT: u32 = 12;
+const OPCODE_RET_BIT: u32 = 13;
+
+/// Cairo 0 program with everything the debugger needs to map pcs back to the code.
+pub struct Cairo0Program {
+    pub casm_debug_info: CasmDebugInfo,
+    pub code_locations: SierraCodeLocations,
+    pub function_names: SierraFunctionNames,
+    /// Functions of the program as pairs of their first pc and full name,
+    /// e.g. `(5, "__main__.fib")`, sorted by the pc.
+    pub functions: Vec<(usize, String)>,
+    /// Kind of the instruction at each pc. Immediate values are [`StatementKind::Other`].
+    pub statement_kinds: Vec<StatementKind>,
+}
+
+#[derive(Deserialize)]
+struct CompiledProgram {
+    data: Vec<String>,
+    debug_info: Option<DebugInfo>,
+    #[serde(default)]
+    identifiers: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct DebugInfo {
+    instruction_locations: HashMap<String, InstructionLocation>,
+}
+
+#[derive(Deserialize)]
+struct InstructionLocation {
+    /// Scopes accessible at the instruction, the innermost one being the function.
+    accessible_scopes: Vec<String>,
+    inst: Location,
+}
+
+/// Location in a Cairo 0 file. Lines and columns are 1-indexed.
+#[derive(Deserialize)]
+struct Location {
+    input_file: InputFile,
+    start_line: usize,
+    start_col: usize,
+    end_line: usize,
+    end_col: usize,
+}
+
+#[derive(Deserialize)]
+struct InputFile {
+    filename: Option<String>,
+}
+
+/// Compiled Cairo 0 programs consist of the encoded instructions and the identifiers
+/// declared in the program, unlike any artifact built from Sierra.
+pub fn is_cairo0_program(artifact: &Value) -> bool {
+    artifact.get("data").is_some_and(Value::is_array)
+        && artifact.get("identifiers").is_some()
+        && artifact.get("sierra_program").is_none()
+}
+
+/// Loads the Cairo 0 program from `path`. Paths of the files in the debug info are relative
+/// to the directory the program was compiled in, which is assumed to be the current one.
+pub fn load(path: &Path) -> Result<Cairo0Program> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let program: CompiledProgram = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a Cairo 0 program", path.display()))?;
+    let debug_info = program.debug_info.ok_or_else(|| {
+        anyhow!(
+            "debug_info must be present in the Cairo 0 program, \
+             compile it with --debug_info_with_source"
+        )
+    })?;
+    let cwd = env::current_dir().context("Failed to get the current directory")?;
+
+    let mut code_locations = HashMap::new();
+    let mut function_names = HashMap::new();
+    for (pc, location) in &debug_info.instruction_locations {
+        let pc: usize =
+            pc.parse().with_context(|| format!("Invalid pc in the debug info: {pc}"))?;
+        let Some(filename) = &location.inst.input_file.filename else {
+            continue;
+        };
+        let code_location = CodeLocation(
+            SourceFileFullPath(cwd.join(filename).display().to_string()),
+            SourceCodeSpan {
+                start: SourceCodeLocation {
+                    line: LineNumber(location.inst.start_line.saturating_sub(1)),
+                    col: ColumnNumber(location.inst.start_col.saturating_sub(1)),
+                },
+                end: SourceCodeLocation {
+                    line: LineNumber(location.inst.end_line.saturating_sub(1)),
+                    col: ColumnNumber(location.inst.end_col.saturating_sub(1)),
+                },
+            },
+            None,
+        );
+        code_locations.insert(StatementIdx(pc), vec![code_location]);
+        if let Some(scope) = location.accessible_scopes.last() {
+            function_names.insert(StatementIdx(pc), vec![FunctionName(scope.clone())]);
+        }
+    }
+
+    let mut functions: Vec<(usize, String)> = program
+        .identifiers
+        .iter()
+        .filter(|(_, identifier)| identifier["type"] == "function")
+        .filter_map(|(name, identifier)| Some((identifier["pc"].as_u64()? as usize, name.clone())))
+        .collect();
+    functions.sort();
+
+    let statement_kinds = (0..program.data.len())
+        .map(|pc| {
+            if debug_info.instruction_locations.contains_key(&pc.to_string()) {
+                instruction_kind(&program.data[pc])
+            } else {
+                StatementKind::Other
+            }
+        })
+        .collect();
+
+    Ok(Cairo0Program {
+        casm_debug_info: CasmDebugInfo { statement_to_pc: (0..program.data.len()).collect() },
+        code_locations: SierraCodeLocations { statements_code_locations: code_locations },
+        function_names: SierraFunctionNames { statements_functions: function_names },
+        functions,
+        statement_kinds,
+    })
+}
+
+/// Decodes the opcode of the instruction, e.g. `0x40780017fff7fff`.
+fn instruction_kind(encoded: &str) -> StatementKind {
+    let Ok(instruction) = u64::from_str_radix(encoded.trim_start_matches("0x"), 16) else {
+        return StatementKind::Other;
+    };
+    let flags = instruction >> FLAGS_OFFSET;
+    if (flags >> OPCODE_CALL_BIT) & 1 == 1 {
+        StatementKind::FunctionCall
+    } else if (flags >> OPCODE_RET_BIT) & 1 == 1 {
+        StatementKind::Return
+    } else {
+        StatementKind::Other
+    }
+}
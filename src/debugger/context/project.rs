@@ -0,0 +1,60 @@
+//! Scarb metadata of the project the program was built from, which tells its packages
+//! and which of them are the user's code.
+//!
+//! Running `scarb metadata` takes a while, so the metadata is cached next to the compiled
+//! program, and Scarb is only queried if neither the cache nor the launch configuration
+//! tells where the user's code is.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as AnyhowContext, Result};
+use scarb_metadata::{Metadata, MetadataCommand};
+use tracing::debug;
+
+/// Name of the file in the directory of the compiled program that caches its Scarb metadata.
+const METADATA_CACHE_FILE_NAME: &str = "cairo-debugger-metadata.json";
+
+/// Reads the metadata cached for the program, unless the program was rebuilt since,
+/// in which case the project may have changed as well.
+pub fn cached_metadata(sierra_path: &Path) -> Option<Metadata> {
+    let cache_path = cache_path(sierra_path)?;
+    let cached_at = fs::metadata(&cache_path).and_then(|file| file.modified()).ok()?;
+    let built_at = fs::metadata(sierra_path).and_then(|file| file.modified()).ok()?;
+    if cached_at < built_at {
+        debug!("Scarb metadata cached in {} is stale", cache_path.display());
+        return None;
+    }
+
+    let metadata =
+        fs::read(&cache_path).ok().and_then(|contents| serde_json::from_slice(&contents).ok());
+    if metadata.is_none() {
+        debug!("Scarb metadata cached in {} could not be read", cache_path.display());
+    }
+    metadata
+}
+
+/// Queries Scarb for the metadata of the project containing the program, and caches it
+/// for the next sessions.
+// TODO(#50)
+pub fn query_scarb(sierra_path: &Path) -> Result<Metadata> {
+    let metadata = MetadataCommand::new()
+        .current_dir(sierra_path.parent().expect("Compiled Sierra must be in target directory"))
+        .inherit_stderr()
+        .exec()
+        .context("Failed to get project metadata from Scarb")?;
+
+    // The cache only saves time, so failing to write it is not an error.
+    if let Some(cache_path) = cache_path(sierra_path)
+        && let Err(err) = serde_json::to_vec(&metadata)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(fs::write(&cache_path, contents)?))
+    {
+        debug!("Failed to cache Scarb metadata in {}: {err:#}", cache_path.display());
+    }
+    Ok(metadata)
+}
+
+fn cache_path(sierra_path: &Path) -> Option<PathBuf> {
+    Some(sierra_path.parent()?.join(METADATA_CACHE_FILE_NAME))
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as AnyhowContext, Result, bail};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::Context;
+
+/// Contexts of all programs executed during the debugged run, e.g. a test
+/// and the contracts it calls, of which the innermost one being executed is active.
+pub struct ContextRegistry {
+    /// Context of the program the debugger was started for.
+    root: Context,
+    /// Contexts of contract classes, by class hash.
+    classes: HashMap<Felt, Context>,
+    /// Classes of the contracts being executed, the innermost one last.
+    active_classes: Vec<Felt>,
+    /// Source settings from the launch configuration, applied to classes registered later too.
+    corelib_root: Option<PathBuf>,
+    source_map: HashMap<PathBuf, PathBuf>,
+    /// Roots of the user's code from the launch configuration. If empty, they are taken
+    /// from the Scarb metadata of each program.
+    user_code_roots: Vec<PathBuf>,
+}
+
+impl ContextRegistry {
+    pub fn new(root: Context) -> Self {
+        Self {
+            root,
+            classes: HashMap::new(),
+            active_classes: Vec::new(),
+            corelib_root: None,
+            source_map: HashMap::new(),
+            user_code_roots: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Context {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut Context {
+        &mut self.root
+    }
+
+    /// Context of the program currently being executed.
+    pub fn active(&self) -> &Context {
+        match self.active_classes.last() {
+            Some(class_hash) => &self.classes[class_hash],
+            None => &self.root,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Context> {
+        std::iter::once(&self.root).chain(self.classes.values())
+    }
+
+    pub fn class(&self, class_hash: &Felt) -> Option<&Context> {
+        self.classes.get(class_hash)
+    }
+
+    pub fn is_registered(&self, class_hash: &Felt) -> bool {
+        self.classes.contains_key(class_hash)
+    }
+
+    pub fn register(&mut self, class_hash: Felt, mut ctx: Context) {
+        self.apply_launch_settings(&mut ctx);
+        self.classes.insert(class_hash, ctx);
+    }
+
+    /// Applies the settings of the launch configuration to a context not registered yet.
+    pub fn apply_launch_settings(&self, ctx: &mut Context) {
+        apply_project(ctx, &self.user_code_roots);
+        apply_sources(ctx, self.corelib_root.as_ref(), &self.source_map);
+    }
+
+    /// Reloads the contexts of the programs loaded from `path`, e.g. once it was rebuilt,
+    /// keeping the settings of the launch configuration. Returns `false` if the code of the
+    /// program changed, in which case the contexts are kept, see [`Context::reload`].
+    /// Contexts are replaced only once all of them were reloaded, so that on an error none of
+    /// them describes a different build than the others.
+    pub fn reload(&mut self, path: &Path) -> Result<bool> {
+        let mut reloaded_contexts = Vec::new();
+        for ctx in self.iter().filter(|ctx| ctx.sierra_path == path) {
+            let Some(mut reloaded) = ctx.reload()? else {
+                return Ok(false);
+            };
+            apply_project(&mut reloaded, &self.user_code_roots);
+            apply_sources(&mut reloaded, self.corelib_root.as_ref(), &self.source_map);
+            reloaded_contexts.push(reloaded);
+        }
+
+        // Contexts are visited in the same order as above, since the registry did not change.
+        let contexts = std::iter::once(&mut self.root)
+            .chain(self.classes.values_mut())
+            .filter(|ctx| ctx.sierra_path == path);
+        for (ctx, mut reloaded) in contexts.zip(reloaded_contexts) {
+            // The ABI may have been passed by the execution tool rather than loaded.
+            if reloaded.abi.is_none() {
+                reloaded.abi = ctx.abi.take();
+            }
+            *ctx = reloaded;
+        }
+        Ok(true)
+    }
+
+    /// Makes the context of the class active, until [`Self::exit`] is called.
+    pub fn enter(&mut self, class_hash: Felt) -> Result<()> {
+        if !self.is_registered(&class_hash) {
+            bail!("Class {class_hash:#x} is not registered");
+        }
+        self.active_classes.push(class_hash);
+        Ok(())
+    }
+
+    /// Makes the context of the caller of the current contract active again.
+    pub fn exit(&mut self) -> Result<()> {
+        self.active_classes.pop().context("No contract is being executed")?;
+        Ok(())
+    }
+
+    /// Makes the root context active again, e.g. once the execution was aborted
+    /// inside a contract.
+    pub fn exit_all(&mut self) {
+        self.active_classes.clear();
+    }
+
+    /// Applies the source settings of the launch configuration to all contexts.
+    pub fn configure_sources(
+        &mut self,
+        corelib_root: Option<PathBuf>,
+        source_map: HashMap<PathBuf, PathBuf>,
+    ) {
+        self.corelib_root = corelib_root;
+        self.source_map = source_map;
+
+        let contexts = std::iter::once(&mut self.root).chain(self.classes.values_mut());
+        for ctx in contexts {
+            apply_sources(ctx, self.corelib_root.as_ref(), &self.source_map);
+        }
+    }
+
+    /// Applies the roots of the user's code from the launch configuration to all contexts.
+    /// Without them, Scarb is queried for the contexts whose metadata was not cached.
+    pub fn configure_project(&mut self, user_code_roots: Vec<PathBuf>) {
+        self.user_code_roots = user_code_roots;
+
+        let contexts = std::iter::once(&mut self.root).chain(self.classes.values_mut());
+        for ctx in contexts {
+            apply_project(ctx, &self.user_code_roots);
+        }
+    }
+}
+
+fn apply_project(ctx: &mut Context, user_code_roots: &[PathBuf]) {
+    if user_code_roots.is_empty() {
+        ctx.resolve_project();
+    } else {
+        ctx.set_user_code_roots(user_code_roots.to_vec());
+    }
+}
+
+fn apply_sources(
+    ctx: &mut Context,
+    corelib_root: Option<&PathBuf>,
+    source_map: &HashMap<PathBuf, PathBuf>,
+) {
+    if let Some(corelib_root) = corelib_root {
+        ctx.source_files.set_corelib_root(corelib_root.clone());
+    }
+    ctx.source_files.set_source_map(source_map.clone());
+}
@@ -0,0 +1,294 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use cairo_annotations::annotations::coverage::CoverageAnnotationsV1 as SierraCodeLocations;
+use cairo_lang_sierra::debug_info::DebugInfo;
+use dap::types::{Checksum, ChecksumAlgorithm, Source};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::debugger::context::Package;
+
+/// Name of the corelib package in Scarb metadata.
+const CORELIB_PACKAGE_NAME: &str = "core";
+
+/// Names of the corelib package directory, in the Scarb cache and in the compiler repository.
+const CORELIB_DIRECTORY_NAMES: [&str; 2] = ["core", "corelib"];
+
+/// Namespace of the annotations of the debug info with the SHA-256 checksums of the source files,
+/// by their paths, for build tools that record them when compiling the program.
+const SOURCE_CHECKSUMS_NAMESPACE: &str =
+    "github.com/software-mansion-labs/cairo-debugger/source-checksums";
+
+/// Source files referenced by the debug info.
+///
+/// Files that do not exist on disk (e.g. corelib paths from the machine the program was built on)
+/// are served to the client through [`dap::prelude::Command::Source`] requests instead,
+/// and identified by their `sourceReference`.
+pub struct SourceFiles {
+    /// All files referenced by the debug info, as recorded there.
+    paths: Vec<Arc<str>>,
+    /// [`Source`] of each file, built once rather than for every frame of every stack trace.
+    /// Rebuilt whenever the settings it depends on change.
+    sources: HashMap<Arc<str>, Source>,
+    /// Files missing on disk. Source reference of each file is equal to `1 + index`,
+    /// where `index` is its position in this vector.
+    missing_files: Vec<PathBuf>,
+    source_references: HashMap<PathBuf, i64>,
+    /// Root of the local corelib package, used to find corelib files missing on disk.
+    corelib_root: Option<PathBuf>,
+    /// Corelib files missing on disk, mapped to the corresponding files of the local corelib.
+    remapped_files: HashMap<PathBuf, PathBuf>,
+    /// SHA-256 checksums of the files the program was compiled from. Clients use them to detect
+    /// files that changed since, for which the line numbers are wrong.
+    checksums: HashMap<PathBuf, String>,
+    /// Files modified since the program was compiled, found when it was loaded.
+    stale_files: Vec<PathBuf>,
+    /// Canonical paths of the existing files, mapped to the paths recorded in the debug info.
+    canonical_paths: HashMap<PathBuf, PathBuf>,
+    /// Prefixes of paths recorded in the debug info, mapped to the local ones.
+    /// Sorted from the longest prefix, which takes precedence.
+    source_map: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SourceFiles {
+    /// `compiled_checksums` are the checksums recorded when the program was compiled, see
+    /// [`compiled_checksums`]. Other files are hashed only if they were not modified since,
+    /// so that the checksums describe the compiled code rather than the files on disk.
+    pub fn new(
+        code_locations: &SierraCodeLocations,
+        sierra_path: &Path,
+        compiled_checksums: &HashMap<PathBuf, String>,
+    ) -> Self {
+        let mut files: Vec<PathBuf> = code_locations
+            .statements_code_locations
+            .values()
+            .flatten()
+            .map(|location| PathBuf::from(&location.0.0))
+            .collect();
+        files.sort();
+        files.dedup();
+        let paths = files.iter().map(|path| Arc::from(path.display().to_string())).collect();
+
+        let (existing_files, missing_files): (Vec<_>, Vec<_>) =
+            files.into_iter().partition(|path| path.exists());
+        let compiled_at = modification_time(sierra_path);
+        // Reading and hashing every file of a big project takes a while, hence in parallel.
+        let files: Vec<_> = existing_files
+            .into_par_iter()
+            .map(|path| {
+                let (checksum, stale) = match compiled_checksums.get(&path) {
+                    Some(checksum) => {
+                        let stale =
+                            sha256_checksum(&path).is_some_and(|current| current != *checksum);
+                        (Some(checksum.clone()), stale)
+                    }
+                    // The content of a file modified since is not known anymore.
+                    None if is_modified_after(&path, compiled_at) => (None, true),
+                    None => (sha256_checksum(&path), false),
+                };
+                let canonical_path = path.canonicalize().ok();
+                (path, checksum, stale, canonical_path)
+            })
+            .collect();
+        let mut checksums = HashMap::new();
+        let mut stale_files = Vec::new();
+        let mut canonical_paths = HashMap::new();
+        for (path, checksum, stale, canonical_path) in files {
+            if let Some(canonical_path) = canonical_path {
+                canonical_paths.insert(canonical_path, path.clone());
+            }
+            if stale {
+                warn!("{} was modified after the program was compiled", path.display());
+                stale_files.push(path.clone());
+            }
+            if let Some(checksum) = checksum {
+                checksums.insert(path, checksum);
+            }
+        }
+
+        let source_references = missing_files
+            .iter()
+            .enumerate()
+            .map(|(index, path)| (path.clone(), 1 + index as i64))
+            .collect();
+        let mut source_files = Self {
+            paths,
+            sources: HashMap::new(),
+            missing_files,
+            source_references,
+            corelib_root: None,
+            remapped_files: HashMap::new(),
+            checksums,
+            stale_files,
+            canonical_paths,
+            source_map: Vec::new(),
+        };
+        source_files.build_sources();
+        source_files
+    }
+
+    /// Uses the corelib package of the project, if Scarb reported one.
+    pub fn detect_corelib_root(&mut self, packages: &[Package]) {
+        if let Some(corelib) = packages.iter().find(|package| package.name == CORELIB_PACKAGE_NAME)
+        {
+            self.set_corelib_root(corelib.root.clone());
+        }
+    }
+
+    /// Sets the root of the local corelib package, overriding the one detected with Scarb.
+    pub fn set_corelib_root(&mut self, corelib_root: PathBuf) {
+        self.corelib_root = Some(corelib_root);
+        self.remapped_files = self
+            .missing_files
+            .iter()
+            .filter_map(|path| {
+                let local_path = self.local_corelib_path(path)?;
+                local_path.exists().then(|| (path.clone(), local_path))
+            })
+            .collect();
+        self.build_sources();
+    }
+
+    pub fn set_source_map(&mut self, source_map: impl IntoIterator<Item = (PathBuf, PathBuf)>) {
+        self.source_map = source_map.into_iter().collect();
+        self.source_map.sort_by_key(|(from, _)| Reverse(from.components().count()));
+        self.build_sources();
+    }
+
+    /// Returns the path recorded in the debug info for a file remapped to the local corelib
+    /// or mapped with the source map.
+    pub fn original_path(&self, local_path: &Path) -> Option<PathBuf> {
+        self.remapped_files
+            .iter()
+            .find(|(_, remapped_path)| *remapped_path == local_path)
+            .map(|(path, _)| path.clone())
+            .or_else(|| {
+                self.source_map
+                    .iter()
+                    .find_map(|(from, to)| Some(from.join(local_path.strip_prefix(to).ok()?)))
+            })
+    }
+
+    /// Returns the path recorded in the debug info for a file the client refers to by
+    /// a different path, e.g. through a symlinked package directory.
+    pub fn recorded_path(&self, path: &Path) -> Option<&Path> {
+        self.canonical_paths.get(&path.canonicalize().ok()?).map(PathBuf::as_path)
+    }
+
+    /// Paths of all files referenced by the debug info, as recorded there.
+    pub fn paths(&self) -> &[Arc<str>] {
+        &self.paths
+    }
+
+    /// [`Source`] of a file recorded in the debug info.
+    pub fn source(&self, path: &str) -> Source {
+        self.sources.get(path).cloned().unwrap_or_else(|| self.build_source(path))
+    }
+
+    fn build_sources(&mut self) {
+        self.sources =
+            self.paths.iter().map(|path| (path.clone(), self.build_source(path))).collect();
+    }
+
+    fn build_source(&self, path: &str) -> Source {
+        if let Some(local_path) = self.remapped_files.get(Path::new(path)) {
+            return Source {
+                name: local_path.file_name().map(|name| name.to_string_lossy().into_owned()),
+                path: Some(local_path.display().to_string()),
+                ..Default::default()
+            };
+        }
+
+        if let Some(local_path) = self.source_mapped_path(Path::new(path)) {
+            return Source {
+                name: local_path.file_name().map(|name| name.to_string_lossy().into_owned()),
+                path: Some(local_path.display().to_string()),
+                ..Default::default()
+            };
+        }
+
+        Source {
+            name: Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned()),
+            path: Some(path.to_string()),
+            source_reference: self.source_references.get(Path::new(path)).copied(),
+            checksums: self.checksums.get(Path::new(path)).map(|checksum| {
+                vec![Checksum { algorithm: ChecksumAlgorithm::SHA256, checksum: checksum.clone() }]
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Whether checksums of any files are reported in [`Self::source`].
+    pub fn has_checksums(&self) -> bool {
+        !self.checksums.is_empty()
+    }
+
+    /// Files modified since the program was compiled, whose lines do not match the debug info.
+    pub fn stale_files(&self) -> &[PathBuf] {
+        &self.stale_files
+    }
+
+    /// Returns the content of a file missing on disk, if it can be found anywhere else.
+    pub fn content(&self, source_reference: i64) -> Option<String> {
+        let index = usize::try_from(source_reference - 1).ok()?;
+        let path = self.missing_files.get(index)?;
+
+        // The file could have been created since the debugger started.
+        fs::read_to_string(path).ok().or_else(|| {
+            let local_path = self.local_corelib_path(path)?;
+            fs::read_to_string(local_path).ok()
+        })
+    }
+
+    fn source_mapped_path(&self, path: &Path) -> Option<PathBuf> {
+        self.source_map.iter().find_map(|(from, to)| Some(to.join(path.strip_prefix(from).ok()?)))
+    }
+
+    /// Maps a path to a corelib file, e.g. `/build/machine/corelib/src/array.cairo`,
+    /// to the corresponding file of the local corelib.
+    fn local_corelib_path(&self, path: &Path) -> Option<PathBuf> {
+        let corelib_root = self.corelib_root.as_ref()?;
+        let components: Vec<_> = path.components().map(|component| component.as_os_str()).collect();
+        // Corelib sources are kept in the `src` directory of the corelib package.
+        let src_position = components.windows(2).rposition(|window| {
+            CORELIB_DIRECTORY_NAMES.iter().any(|name| window[0] == *name) && window[1] == "src"
+        })? + 1;
+        let relative_path: PathBuf = components[src_position..].iter().collect();
+
+        Some(corelib_root.join(relative_path))
+    }
+}
+
+/// Checksums of the source files recorded in the debug info by the build tool, if any.
+pub fn compiled_checksums(debug_info: &DebugInfo) -> HashMap<PathBuf, String> {
+    let Some(annotations) = debug_info.annotations.get(SOURCE_CHECKSUMS_NAMESPACE) else {
+        return HashMap::new();
+    };
+    serde_json::from_value(annotations.clone()).unwrap_or_else(|err| {
+        warn!("Ignoring malformed checksums of the source files in the debug info: {err}");
+        HashMap::new()
+    })
+}
+
+fn sha256_checksum(path: &Path) -> Option<String> {
+    let content = fs::read(path).ok()?;
+    let digest = Sha256::digest(content);
+    Some(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Files modified after the program was compiled do not match the debug info anymore.
+fn is_modified_after(path: &Path, compiled_at: Option<SystemTime>) -> bool {
+    compiled_at.is_some_and(|compiled_at| {
+        modification_time(path).is_some_and(|modified_at| modified_at > compiled_at)
+    })
+}
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,452 @@
+//! Requests that are not part of the DAP specification.
+//!
+//! The DAP server we build upon only understands commands defined by the specification,
+//! so custom requests are sent as [`dap::prelude::Command::Evaluate`] requests whose
+//! expression starts with [`CUSTOM_REQUEST_PREFIX`], e.g. `:dict 5:12`.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::registry::ContextRegistry;
+use crate::debugger::memory;
+use crate::debugger::metrics::RuntimeMetrics;
+use crate::debugger::post_mortem;
+use crate::debugger::recording::{TrackedCell, Writer};
+use crate::debugger::replay_metadata::ReplayMetadata;
+use crate::debugger::resources::ResourceMark;
+use crate::debugger::settings::SettingsUpdate;
+use crate::debugger::state::State;
+use crate::debugger::vm_access::{self, Address, VmAccess};
+
+pub const CUSTOM_REQUEST_PREFIX: char = ':';
+
+pub enum CustomRequest {
+    /// Dumps the full access log of the dict segment the pointer belongs to.
+    Dict { dict_ptr: Address },
+    /// Finds all addresses holding the value, in the whole memory or in a single segment.
+    Search { value: Felt, segment_index: Option<usize> },
+    /// Dumps the tree of contract calls made so far as JSON, for clients to render.
+    Calls,
+    /// Dumps the resources used so far by the frame as JSON.
+    Resources { frame_id: i64 },
+    /// Marks the current point of the execution, to compare the resources with at a later stop.
+    ResourcesMark,
+    /// Dumps the resources used since the mark as JSON.
+    ResourcesDiff,
+    /// Dumps the syscalls executed so far as JSON, with the gas used by each of them.
+    Syscalls,
+    /// Snapshots the tracked contract storage, to be compared with a later stop.
+    StorageSnapshot,
+    /// Lists storage keys changed since the snapshot, of all contracts or of a single one.
+    StorageDiff { contract_address: Option<Felt> },
+    /// Lists the contracts deployed so far as JSON, with their classes and names.
+    Contracts,
+    /// Lists the tests of the program, to be chosen from with the `testFilter` launch argument.
+    Tests,
+    /// Aborts the execution and asks the execution tool to execute the current test again,
+    /// keeping the session.
+    Rerun,
+    /// Moves the stopped execution to the recorded state after the given number of steps.
+    Goto { step: usize },
+    /// Writes the recorded steps, and optionally the memory, to files that can be debugged
+    /// post-mortem later, along with the inputs needed to replay the execution.
+    Export { trace_path: PathBuf, memory_path: Option<PathBuf> },
+    /// Dumps the values of the cell across the recorded steps as JSON, with the steps
+    /// they changed at.
+    History { cell: TrackedCell },
+    /// Finds the recorded step that wrote the cell, and moves the execution there if `goto` is set.
+    Writer { cell: Address, goto: bool },
+    /// Starts recording the steps, so that the memory the recording takes is used only
+    /// for the part of the execution of interest.
+    RecordStart,
+    /// Stops recording the steps, forgetting the recorded ones.
+    RecordStop,
+    /// Dumps the performance counters of the session as JSON, see [`RuntimeMetrics`].
+    Metrics,
+    /// Changes the settings of the session given as JSON, e.g. from toggles of the client.
+    Settings { update: SettingsUpdate },
+}
+
+impl CustomRequest {
+    /// Returns `None` if the expression is not a custom request.
+    pub fn parse(expression: &str) -> Option<Result<Self>> {
+        let request = expression.trim().strip_prefix(CUSTOM_REQUEST_PREFIX)?;
+        let (name, args) = request.split_once(char::is_whitespace).unwrap_or((request, ""));
+        let args = args.trim();
+
+        Some(match name {
+            "dict" => memory::parse_address(args).map(|dict_ptr| Self::Dict { dict_ptr }),
+            "search" => parse_search_args(args),
+            "calls" => Ok(Self::Calls),
+            "syscalls" => Ok(Self::Syscalls),
+            "resources" => parse_resources_args(args),
+            "storage" => parse_storage_args(args),
+            "contracts" => Ok(Self::Contracts),
+            "tests" => Ok(Self::Tests),
+            "rerun" => Ok(Self::Rerun),
+            "goto" => parse_goto_args(args),
+            "export" => parse_export_args(args),
+            "history" => parse_history_args(args),
+            "writer" => parse_writer_args(args),
+            "metrics" => Ok(Self::Metrics),
+            "settings" => SettingsUpdate::parse(args).map(|update| Self::Settings { update }),
+            "record" => match args {
+                "start" => Ok(Self::RecordStart),
+                "stop" => Ok(Self::RecordStop),
+                _ => Err(anyhow!("Usage: {CUSTOM_REQUEST_PREFIX}record start | stop")),
+            },
+            _ => Err(anyhow!("Unknown custom request: {name}")),
+        })
+    }
+
+    /// Whether the client has to be told that the execution stopped somewhere else.
+    pub fn moves_execution(&self) -> bool {
+        matches!(self, Self::Goto { .. } | Self::Writer { goto: true, .. })
+    }
+
+    /// Whether the client has to be told about breakpoints that became valid.
+    pub fn changes_settings(&self) -> bool {
+        matches!(self, Self::Settings { .. })
+    }
+
+    /// Whether the client has to be told that it can step back now.
+    pub fn starts_recording(&self) -> bool {
+        matches!(self, Self::RecordStart)
+    }
+
+    pub fn handle(
+        self,
+        state: &mut State,
+        contexts: &ContextRegistry,
+        vm: Option<&mut dyn VmAccess>,
+    ) -> Result<String> {
+        match self {
+            Self::Dict { dict_ptr } => {
+                let vm = started_vm(vm)?;
+                let accesses = memory::read_dict_accesses(vm, dict_ptr)?;
+
+                let mut result = format!(
+                    "Dict segment {} ({} accesses):",
+                    dict_ptr.segment_index,
+                    accesses.len()
+                );
+                for (i, access) in accesses.iter().enumerate() {
+                    write!(
+                        result,
+                        "\n#{i}: key={}, prev_value={}, new_value={}",
+                        display_cell(&access.key),
+                        display_cell(&access.prev_value),
+                        display_cell(&access.new_value),
+                    )?;
+                }
+                Ok(result)
+            }
+            Self::Search { value, segment_index } => {
+                let mut addresses = memory::search(started_vm(vm)?, value, segment_index)?;
+                // Values written after the past step the execution was moved back to
+                // did not exist yet there.
+                if let Some(recording) =
+                    state.recording.as_ref().filter(|recording| recording.is_in_past())
+                {
+                    addresses.retain(|address| !recording.is_written_later(*address));
+                }
+                if addresses.is_empty() {
+                    return Ok(format!("Value {value} not found"));
+                }
+
+                let addresses: Vec<String> = addresses.iter().map(ToString::to_string).collect();
+                Ok(format!(
+                    "Value {value} found at {} addresses:\n{}",
+                    addresses.len(),
+                    addresses.join("\n")
+                ))
+            }
+            Self::Calls => Ok(state.call_trace.to_json().to_string()),
+            Self::Syscalls => Ok(state.syscall_trace.to_json().to_string()),
+            Self::Resources { frame_id } => {
+                let resources = state
+                    .call_stack
+                    .frame_resources(frame_id, started_vm(vm)?)
+                    .ok_or_else(|| anyhow!("Resources of frame {frame_id} are unknown"))?;
+                Ok(resources.to_json().to_string())
+            }
+            Self::ResourcesMark => {
+                state.resource_mark =
+                    Some(ResourceMark::take(started_vm(vm)?, &state.syscall_trace));
+                Ok("Resources mark set".to_string())
+            }
+            Self::ResourcesDiff => {
+                let mark = state.resource_mark.as_ref().ok_or_else(|| {
+                    anyhow!("No mark set, use {CUSTOM_REQUEST_PREFIX}resources mark")
+                })?;
+                Ok(mark.diff(started_vm(vm)?, &state.syscall_trace).to_string())
+            }
+            Self::StorageSnapshot => {
+                state.storage.take_snapshot();
+                Ok("Storage snapshot taken".to_string())
+            }
+            Self::StorageDiff { contract_address } => {
+                let diffs = state.storage.diff(contract_address)?;
+                if diffs.is_empty() {
+                    return Ok("No storage changes since the snapshot".to_string());
+                }
+
+                // Any of the classes may declare the variable, since storage addresses
+                // depend only on the names of the variables.
+                let variable_name = |key: &Felt| {
+                    contexts
+                        .iter()
+                        .flat_map(|ctx| &ctx.storage_variables)
+                        .find(|variable| variable.address == *key)
+                        .map_or_else(|| key.to_hex_string(), |variable| variable.name.clone())
+                };
+                let mut result = String::from("Storage changes since the snapshot:");
+                for diff in diffs {
+                    write!(result, "\nContract {}:", diff.contract_address.to_hex_string())?;
+                    for change in diff.changes {
+                        let old = change.old.map_or_else(|| "?".to_string(), |old| old.to_string());
+                        write!(
+                            result,
+                            "\n  {}: {old} -> {}",
+                            variable_name(&change.key),
+                            change.new
+                        )?;
+                    }
+                }
+                Ok(result)
+            }
+            Self::Contracts => Ok(state.deployments.to_json(contexts).to_string()),
+            Self::Tests => Ok(serde_json::to_string(&contexts.root().test_functions)?),
+            Self::Rerun => {
+                state.request_rerun()?;
+                Ok("Re-running the test".to_string())
+            }
+            Self::Goto { step } => {
+                state.go_to_step(step, started_vm(vm)?, contexts.active())?;
+                Ok(format!("Moved to step {step}"))
+            }
+            Self::Export { trace_path, memory_path } => {
+                let recording = state.enabled_recording()?;
+                let steps = post_mortem::export(
+                    started_vm(vm)?,
+                    recording,
+                    &trace_path,
+                    memory_path.as_deref(),
+                )?;
+                ReplayMetadata::collect(state, contexts.active())?.write(&trace_path)?;
+                Ok(format!("Exported {steps} steps to {}", trace_path.display()))
+            }
+            Self::History { cell } => {
+                let history = state.enabled_recording()?.history(started_vm(vm)?, cell)?;
+                let history: Vec<_> = history
+                    .into_iter()
+                    .map(|(step, value)| {
+                        json!({ "step": step, "value": value.map(|value| value.to_string()) })
+                    })
+                    .collect();
+                Ok(Value::Array(history).to_string())
+            }
+            Self::Writer { cell, goto } => {
+                let recording = state.enabled_recording()?;
+                let (step, writer) = recording.last_write(cell).ok_or_else(|| {
+                    anyhow!("No recorded step wrote {cell}, it may have been written before them")
+                })?;
+                let ctx = contexts.active();
+                let location = ctx.describe_location(recording.statement_idx(step, ctx));
+                let writer = match writer {
+                    Writer::Instruction => "the instruction",
+                    Writer::Hint => "a hint",
+                };
+                if goto {
+                    state.go_to_step(step, started_vm(vm)?, ctx)?;
+                }
+                Ok(format!("{cell} was written at step {step} by {writer} at {location}"))
+            }
+            Self::RecordStart => {
+                state.start_recording(started_vm(vm)?)?;
+                Ok("Recording started".to_string())
+            }
+            Self::RecordStop => {
+                state.stop_recording(started_vm(vm)?)?;
+                Ok("Recording stopped".to_string())
+            }
+            Self::Metrics => Ok(state.metrics.to_json().to_string()),
+            Self::Settings { update } => {
+                let changed = update.apply(state)?;
+                Ok(format!("Changed settings: {}", changed.join(", ")))
+            }
+        }
+    }
+}
+
+/// Parses `<value> [segment_index]` - the value may be a short string containing spaces,
+/// hence the segment index is looked for at the end.
+fn parse_search_args(args: &str) -> Result<CustomRequest> {
+    if args.is_empty() {
+        bail!("Usage: {CUSTOM_REQUEST_PREFIX}search <value> [segment_index]");
+    }
+
+    let (value, segment_index) = match args.rsplit_once(char::is_whitespace) {
+        Some((value, segment)) if !segment.ends_with('\'') => {
+            let segment_index =
+                segment.parse().map_err(|_| anyhow!("Invalid segment index: {segment}"))?;
+            (value, Some(segment_index))
+        }
+        _ => (args, None),
+    };
+
+    Ok(CustomRequest::Search { value: memory::parse_felt(value)?, segment_index })
+}
+
+/// Parses `<frame_id>`, `mark` or `diff`.
+fn parse_resources_args(args: &str) -> Result<CustomRequest> {
+    match args {
+        "mark" => Ok(CustomRequest::ResourcesMark),
+        "diff" => Ok(CustomRequest::ResourcesDiff),
+        frame_id => {
+            frame_id.parse().map(|frame_id| CustomRequest::Resources { frame_id }).map_err(|_| {
+                anyhow!("Usage: {CUSTOM_REQUEST_PREFIX}resources <frame_id> | mark | diff")
+            })
+        }
+    }
+}
+
+/// Parses `snapshot` or `diff [contract_address]`.
+fn parse_storage_args(args: &str) -> Result<CustomRequest> {
+    let (command, contract_address) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    match (command, contract_address.trim()) {
+        ("snapshot", "") => Ok(CustomRequest::StorageSnapshot),
+        ("diff", "") => Ok(CustomRequest::StorageDiff { contract_address: None }),
+        ("diff", address) => {
+            Ok(CustomRequest::StorageDiff { contract_address: Some(memory::parse_felt(address)?) })
+        }
+        _ => bail!("Usage: {CUSTOM_REQUEST_PREFIX}storage snapshot | diff [contract_address]"),
+    }
+}
+
+/// Parses `<step>`, which may be grouped with spaces or underscores, e.g. `1 234 567`.
+fn parse_goto_args(args: &str) -> Result<CustomRequest> {
+    let digits: String = args.chars().filter(|c| !c.is_whitespace() && *c != '_').collect();
+    digits
+        .parse()
+        .map(|step| CustomRequest::Goto { step })
+        .map_err(|_| anyhow!("Usage: {CUSTOM_REQUEST_PREFIX}goto <step>"))
+}
+
+/// Parses `<trace_path> [memory_path]`.
+fn parse_export_args(args: &str) -> Result<CustomRequest> {
+    let mut paths = args.split_whitespace().map(PathBuf::from);
+    match (paths.next(), paths.next(), paths.next()) {
+        (Some(trace_path), memory_path, None) => {
+            Ok(CustomRequest::Export { trace_path, memory_path })
+        }
+        _ => bail!("Usage: {CUSTOM_REQUEST_PREFIX}export <trace_path> [memory_path]"),
+    }
+}
+
+/// Parses `<segment:offset>`, `[ap+<offset>]` or `[fp-<offset>]`.
+fn parse_history_args(args: &str) -> Result<CustomRequest> {
+    let usage =
+        || anyhow!("Usage: {CUSTOM_REQUEST_PREFIX}history <segment:offset> | [ap+k] | [fp-k]");
+    let cell = match args.strip_prefix('[').and_then(|cell| cell.strip_suffix(']')) {
+        Some(cell) => {
+            let cell: String = cell.chars().filter(|c| !c.is_whitespace()).collect();
+            let (register, offset) = cell.split_at_checked(2).ok_or_else(usage)?;
+            let offset = if offset.is_empty() {
+                0
+            } else {
+                offset.strip_prefix('+').unwrap_or(offset).parse().map_err(|_| usage())?
+            };
+            match register {
+                "ap" => TrackedCell::Ap(offset),
+                "fp" => TrackedCell::Fp(offset),
+                _ => return Err(usage()),
+            }
+        }
+        None if args.is_empty() => return Err(usage()),
+        None => TrackedCell::Fixed(memory::parse_address(args)?),
+    };
+    Ok(CustomRequest::History { cell })
+}
+
+/// Parses `<segment:offset> [goto]`.
+fn parse_writer_args(args: &str) -> Result<CustomRequest> {
+    let (cell, goto) = match args.strip_suffix("goto") {
+        Some(cell) => (cell.trim(), true),
+        None => (args, false),
+    };
+    if cell.is_empty() {
+        bail!("Usage: {CUSTOM_REQUEST_PREFIX}writer <segment:offset> [goto]");
+    }
+    Ok(CustomRequest::Writer { cell: memory::parse_address(cell)?, goto })
+}
+
+fn started_vm(vm: Option<&mut dyn VmAccess>) -> Result<&mut dyn VmAccess> {
+    vm.ok_or_else(|| anyhow!("This request is available only once the execution has started"))
+}
+
+fn display_cell(cell: &Option<vm_access::Value>) -> String {
+    cell.as_ref().map_or_else(|| "<unset>".to_string(), ToString::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet_types_core::felt::Felt;
+
+    use super::{CustomRequest, parse_search_args};
+
+    const PRIME: &str =
+        "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+
+    fn search(args: &str) -> (Felt, Option<usize>) {
+        match parse_search_args(args).unwrap() {
+            CustomRequest::Search { value, segment_index } => (value, segment_index),
+            _ => panic!("Expected a search request"),
+        }
+    }
+
+    #[test]
+    fn negative_values_are_counted_from_the_prime() {
+        assert_eq!(search("-1"), (Felt::MAX, None));
+        assert_eq!(search("-0x2"), (-Felt::TWO, None));
+        assert_eq!(search("-1 2"), (Felt::MAX, Some(2)));
+    }
+
+    #[test]
+    fn largest_felt_is_accepted() {
+        let largest =
+            "3618502788666131213697322783095070105623107215331596699973092056135872020480";
+        assert_eq!(search(largest), (Felt::MAX, None));
+        assert_eq!(
+            search("0x800000000000011000000000000000000000000000000000000000000000000").0,
+            Felt::MAX
+        );
+    }
+
+    #[test]
+    fn values_out_of_the_field_are_rejected() {
+        assert!(parse_search_args(PRIME).is_err());
+        assert!(parse_search_args(&format!("-{PRIME}")).is_err());
+        assert!(
+            parse_search_args("0x800000000000011000000000000000000000000000000000000000000000001")
+                .is_err()
+        );
+        assert!(
+            parse_search_args("0x0800000000000011000000000000000000000000000000000000000000000001")
+                .is_err()
+        );
+        assert!(parse_search_args(&format!("0x1{}", "0".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn short_strings_and_segments_are_parsed() {
+        assert_eq!(search("'a b'"), (Felt::from_bytes_be_slice(b"a b"), None));
+        assert_eq!(search("'ab' 3"), (Felt::from_bytes_be_slice(b"ab"), Some(3)));
+        assert!(parse_search_args("").is_err());
+        assert!(parse_search_args("1 x").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Contracts deployed during the execution, reported by execution tools or learned from
+//! the contract calls, see [`crate::Debugger::report_deployment`], and the classes declared
+//! and contracts deployed by the program itself, which the execution can stop at.
+
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::abi::ContractAbi;
+use crate::debugger::context::registry::ContextRegistry;
+use crate::debugger::launch_args::ValueFormat;
+use crate::debugger::vm_access::Address;
+
+/// Filter of the exception breakpoint stopping once a class is declared with the `declare`
+/// cheatcode of Starknet Foundry.
+pub const DECLARE_FILTER: &str = "declare";
+
+/// Filter of the exception breakpoint stopping at each `Deploy` syscall.
+pub const DEPLOY_FILTER: &str = "deploy";
+
+/// Name of the cheatcode declaring classes in Starknet Foundry.
+pub const DECLARE_CHEATCODE: &str = "declare";
+
+/// Name of the constructor in ABIs.
+const CONSTRUCTOR_NAME: &str = "constructor";
+
+/// Contract deployed during the execution.
+#[derive(Clone)]
+pub struct DeployedContract {
+    pub contract_address: Felt,
+    pub class_hash: Felt,
+    /// Name the class was declared with, e.g. `HelloStarknet`.
+    pub name: Option<String>,
+}
+
+/// Class being declared, whose hash is known once the cheatcode was executed.
+pub struct PendingDeclaration {
+    /// Name of the declared contract, e.g. `HelloStarknet`.
+    pub name: String,
+    /// See [`crate::debugger::cheatcodes::CheatcodeInvocation::output_cells`].
+    pub output_cells: (Address, Address),
+}
+
+#[derive(Default)]
+pub struct Deployments {
+    /// In the order of deployment.
+    contracts: Vec<DeployedContract>,
+}
+
+impl DeployedContract {
+    /// Human-readable summary, e.g. `Deployed HelloStarknet (class 0x1) at 0x2`.
+    pub fn describe(&self) -> String {
+        let name = self.name.as_deref().unwrap_or("contract");
+        format!(
+            "Deployed {name} (class {}) at {}",
+            self.class_hash.to_hex_string(),
+            self.contract_address.to_hex_string()
+        )
+    }
+}
+
+impl Deployments {
+    /// Records the contract, replacing the one previously deployed at the same address.
+    /// Names already known are kept if the new record has none.
+    pub fn record(&mut self, mut contract: DeployedContract) {
+        match self
+            .contracts
+            .iter_mut()
+            .find(|known| known.contract_address == contract.contract_address)
+        {
+            Some(known) => {
+                if known.class_hash == contract.class_hash {
+                    contract.name = contract.name.or_else(|| known.name.take());
+                }
+                *known = contract;
+            }
+            None => self.contracts.push(contract),
+        }
+    }
+
+    pub fn is_known(&self, contract_address: &Felt) -> bool {
+        self.contracts.iter().any(|contract| contract.contract_address == *contract_address)
+    }
+
+    pub fn clear(&mut self) {
+        self.contracts.clear();
+    }
+
+    /// Lists the contracts for clients to show, e.g. in a panel, and to name addresses after.
+    pub fn to_json(&self, contexts: &ContextRegistry) -> Value {
+        Value::Array(
+            self.contracts
+                .iter()
+                .map(|contract| {
+                    json!({
+                        "address": contract.contract_address.to_hex_string(),
+                        "classHash": contract.class_hash.to_hex_string(),
+                        "name": contract.name,
+                        "classRegistered": contexts.is_registered(&contract.class_hash),
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Hash of the class declared with the `declare` cheatcode, from its output: a `Result`
+/// whose `Ok` holds either of the `DeclareResult` variants, both carrying the class.
+pub fn declared_class_hash(output: &[Felt]) -> Option<Felt> {
+    match output {
+        [ok, _variant, class_hash, ..] if *ok == Felt::ZERO => Some(*class_hash),
+        _ => None,
+    }
+}
+
+/// Human-readable summary of a `Deploy` syscall, e.g.
+/// `Deploying class 0x1 with constructor(owner: 0x2, initial_balance: 100)`. The constructor
+/// calldata is decoded with the ABI of the class if it is registered, and shown raw otherwise.
+pub fn describe_deploy(
+    class_hash: Felt,
+    calldata: &[Felt],
+    abi: Option<&ContractAbi>,
+    value_format: ValueFormat,
+) -> String {
+    let class_hash = class_hash.to_hex_string();
+    let arguments = abi.and_then(|abi| {
+        let constructor = abi.function(CONSTRUCTOR_NAME)?;
+        let mut felts = calldata.iter();
+        let arguments = constructor
+            .inputs
+            .iter()
+            .map(|input| {
+                let value = abi.decode(&input.ty, &mut felts, value_format)?;
+                Some(format!("{}: {value}", input.name))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(arguments.join(", "))
+    });
+    match arguments {
+        Some(arguments) => format!("Deploying class {class_hash} with constructor({arguments})"),
+        None => {
+            let felts: Vec<String> = calldata.iter().map(Felt::to_hex_string).collect();
+            format!("Deploying class {class_hash} with constructor calldata [{}]", felts.join(", "))
+        }
+    }
+}
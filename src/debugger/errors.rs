@@ -0,0 +1,100 @@
+//! Errors that users can act on, sent to the client as the structured `Message` of DAP,
+//! so that it shows them in a dialog with a link to how to fix them, rather than as an opaque string.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use dap::types::Message;
+
+/// Section of the README explaining the errors.
+const ERRORS_HELP_URL: &str = "https://github.com/software-mansion-labs/cairo-debugger#errors";
+const ISSUES_URL: &str = "https://github.com/software-mansion-labs/cairo-debugger/issues";
+
+#[derive(Debug)]
+pub enum DebuggerError {
+    /// The program or a contract class was compiled without the debug info mapping it to the code.
+    MissingDebugInfo { artifact: PathBuf },
+    /// A breakpoint is set on a line that no statement of the program maps to.
+    UnmappedBreakpoint { source: String, line: usize },
+    /// The request is not supported, or not in the mode the session was launched in,
+    /// e.g. stepping back without a recorded execution.
+    Unsupported { feature: String, requirement: Option<String> },
+    /// A bug of the debugger, e.g. a panic of a request handler.
+    Internal { details: String },
+}
+
+impl DebuggerError {
+    /// Identifier of the kind of the error, which does not change between versions,
+    /// so that clients can tell the errors apart.
+    fn id(&self) -> i64 {
+        match self {
+            Self::MissingDebugInfo { .. } => 1,
+            Self::UnmappedBreakpoint { .. } => 2,
+            Self::Unsupported { .. } => 3,
+            Self::Internal { .. } => 4,
+        }
+    }
+
+    /// Template of the message, whose `{name}` placeholders are filled with [`Self::variables`].
+    fn format(&self) -> &'static str {
+        match self {
+            Self::MissingDebugInfo { .. } => "{artifact} was compiled without debug info",
+            Self::UnmappedBreakpoint { .. } => {
+                "No code of the program is at line {line} of {source}"
+            }
+            Self::Unsupported { requirement: Some(_), .. } => "{feature} requires {requirement}",
+            Self::Unsupported { requirement: None, .. } => "{feature} is not supported",
+            Self::Internal { .. } => "Internal error of the debugger: {details}",
+        }
+    }
+
+    fn variables(&self) -> HashMap<String, String> {
+        let variables = match self {
+            Self::MissingDebugInfo { artifact } => {
+                vec![("artifact", artifact.display().to_string())]
+            }
+            Self::UnmappedBreakpoint { source, line } => {
+                vec![("source", source.clone()), ("line", line.to_string())]
+            }
+            Self::Unsupported { feature, requirement } => {
+                let mut variables = vec![("feature", feature.clone())];
+                variables
+                    .extend(requirement.clone().map(|requirement| ("requirement", requirement)));
+                variables
+            }
+            Self::Internal { details } => vec![("details", details.clone())],
+        };
+        variables.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    /// Details of the error response, shown to the user, apart from unmapped breakpoints,
+    /// which the client shows next to the breakpoint anyway.
+    pub fn to_message(&self) -> Message {
+        let (url, url_label) = match self {
+            Self::Internal { .. } => (ISSUES_URL, "Report the bug"),
+            _ => (ERRORS_HELP_URL, "How to fix it"),
+        };
+        Message {
+            id: self.id(),
+            format: self.format().to_string(),
+            variables: Some(self.variables()),
+            send_telemetry: Some(false),
+            show_user: Some(!matches!(self, Self::UnmappedBreakpoint { .. })),
+            url: Some(url.to_string()),
+            url_label: Some(url_label.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DebuggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut message = self.format().to_string();
+        for (name, value) in self.variables() {
+            message = message.replace(&format!("{{{name}}}"), &value);
+        }
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for DebuggerError {}
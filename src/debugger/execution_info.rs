@@ -0,0 +1,116 @@
+//! Context the contract being executed runs in: who called it, at which address, and the block
+//! and transaction it sees, which may be changed by cheatcodes.
+
+use dap::types::{Scope, Variable};
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::call_trace::ContractCall;
+use crate::debugger::vm_access::{Address, VmAccess};
+
+/// Set in the variables reference of the Execution info scope.
+/// Lower than the flag of the Hints scope.
+const EXECUTION_INFO_REFERENCE_FLAG: i64 = 1 << 26;
+
+/// Block and transaction seen by a contract, as returned by the `GetExecutionInfo` syscall,
+/// see [`crate::Debugger::report_execution_info`].
+#[derive(Clone, Default)]
+pub struct ExecutionInfo {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub sequencer_address: Felt,
+    pub transaction_version: Felt,
+    pub account_contract_address: Felt,
+    pub transaction_hash: Felt,
+    pub chain_id: Felt,
+    pub nonce: Felt,
+}
+
+impl ExecutionInfo {
+    /// Reads the `ExecutionInfo` struct at `address`, which starts with the pointers to
+    /// the `BlockInfo` and `TxInfo` structs, followed by the caller, the contract address
+    /// and the selector, known from the call anyway.
+    pub fn read(vm: &dyn VmAccess, address: Address) -> Option<Self> {
+        let block_info = vm.read_pointer(address)?;
+        let tx_info = vm.read_pointer((address + 1_usize)?)?;
+        let read = |base: Address, offset: usize| vm.read_felt((base + offset)?);
+
+        Some(Self {
+            block_number: u64::try_from(read(block_info, 0)?).ok()?,
+            block_timestamp: u64::try_from(read(block_info, 1)?).ok()?,
+            sequencer_address: read(block_info, 2)?,
+            transaction_version: read(tx_info, 0)?,
+            account_contract_address: read(tx_info, 1)?,
+            // The max fee and the signature span come in between.
+            transaction_hash: read(tx_info, 5)?,
+            chain_id: read(tx_info, 6)?,
+            nonce: read(tx_info, 7)?,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "blockNumber": self.block_number,
+            "blockTimestamp": self.block_timestamp,
+            "sequencerAddress": self.sequencer_address.to_hex_string(),
+            "transactionVersion": self.transaction_version.to_hex_string(),
+            "accountContractAddress": self.account_contract_address.to_hex_string(),
+            "transactionHash": self.transaction_hash.to_hex_string(),
+            "chainId": self.chain_id.to_hex_string(),
+            "nonce": self.nonce.to_hex_string(),
+        })
+    }
+}
+
+pub fn scope() -> Scope {
+    Scope {
+        name: "Execution info".to_string(),
+        variables_reference: EXECUTION_INFO_REFERENCE_FLAG,
+        ..Default::default()
+    }
+}
+
+/// The call being executed, followed by the block and transaction info if it is known,
+/// i.e. once the contract asked for it or the execution tool reported it.
+pub fn variables(call: &ContractCall, info: Option<&ExecutionInfo>) -> Vec<Variable> {
+    let variable = |name: &str, value: String| Variable {
+        name: name.to_string(),
+        value,
+        variables_reference: 0,
+        ..Default::default()
+    };
+    let mut variables = vec![
+        variable("caller_address", call.caller_address.to_hex_string()),
+        variable("contract_address", call.contract_address.to_hex_string()),
+        variable("entry_point_selector", call.entry_point_selector.to_hex_string()),
+        variable("class_hash", call.class_hash.to_hex_string()),
+    ];
+    if let Some(info) = info {
+        variables.extend([
+            variable("block_number", info.block_number.to_string()),
+            variable("block_timestamp", info.block_timestamp.to_string()),
+            variable("sequencer_address", info.sequencer_address.to_hex_string()),
+            variable("transaction_version", info.transaction_version.to_string()),
+            variable("account_contract_address", info.account_contract_address.to_hex_string()),
+            variable("transaction_hash", info.transaction_hash.to_hex_string()),
+            variable("chain_id", display_short_string(&info.chain_id)),
+            variable("nonce", info.nonce.to_string()),
+        ]);
+    }
+    variables
+}
+
+pub fn is_execution_info_reference(variables_reference: i64) -> bool {
+    variables_reference & EXECUTION_INFO_REFERENCE_FLAG != 0
+}
+
+/// Chain ids are short strings, e.g. `SN_SEPOLIA`, shown next to their hex value.
+fn display_short_string(felt: &Felt) -> String {
+    let bytes = felt.to_bytes_be();
+    let text = String::from_utf8_lossy(&bytes).trim_start_matches('\0').to_string();
+    if !text.is_empty() && text.chars().all(|c| c.is_ascii_graphic()) {
+        format!("{} ('{text}')", felt.to_hex_string())
+    } else {
+        felt.to_hex_string()
+    }
+}
@@ -0,0 +1,87 @@
+//! Reads of the state served from the forked network in fork tests, reported by execution tools.
+
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+/// Filter of the exception breakpoint stopping at the first read from the forked network.
+pub const FORK_READ_FILTER: &str = "forkRead";
+
+/// Network a fork test runs against, see [`crate::Debugger::report_fork_origin`].
+#[derive(Clone)]
+pub struct ForkOrigin {
+    pub url: String,
+    /// Block the state is read at.
+    pub block_number: u64,
+}
+
+/// Read from the forked network, see [`crate::Debugger::report_fork_read`].
+pub struct ForkRead {
+    pub kind: ForkReadKind,
+    /// How long it took the network to respond.
+    pub duration: Duration,
+}
+
+pub enum ForkReadKind {
+    Storage { contract_address: Felt, key: Felt, value: Felt },
+    Nonce { contract_address: Felt },
+    ClassHashAt { contract_address: Felt },
+    Class { class_hash: Felt },
+    BlockInfo { block_number: u64 },
+}
+
+impl ForkRead {
+    /// Human-readable summary, e.g. `Fork read of the class 0x1 took 120ms`.
+    pub fn describe(&self) -> String {
+        let what = match &self.kind {
+            ForkReadKind::Storage { contract_address, key, value } => format!(
+                "storage key {} of contract {} = {}",
+                key.to_hex_string(),
+                contract_address.to_hex_string(),
+                value.to_hex_string()
+            ),
+            ForkReadKind::Nonce { contract_address } => {
+                format!("nonce of contract {}", contract_address.to_hex_string())
+            }
+            ForkReadKind::ClassHashAt { contract_address } => {
+                format!("class hash of contract {}", contract_address.to_hex_string())
+            }
+            ForkReadKind::Class { class_hash } => {
+                format!("the class {}", class_hash.to_hex_string())
+            }
+            ForkReadKind::BlockInfo { block_number } => format!("info of block {block_number}"),
+        };
+        format!("Fork read of {what} took {}ms", self.duration.as_millis())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (kind, details) = match &self.kind {
+            ForkReadKind::Storage { contract_address, key, value } => (
+                "storage",
+                json!({
+                    "contractAddress": contract_address.to_hex_string(),
+                    "key": key.to_hex_string(),
+                    "value": value.to_hex_string(),
+                }),
+            ),
+            ForkReadKind::Nonce { contract_address } => {
+                ("nonce", json!({ "contractAddress": contract_address.to_hex_string() }))
+            }
+            ForkReadKind::ClassHashAt { contract_address } => {
+                ("classHashAt", json!({ "contractAddress": contract_address.to_hex_string() }))
+            }
+            ForkReadKind::Class { class_hash } => {
+                ("class", json!({ "classHash": class_hash.to_hex_string() }))
+            }
+            ForkReadKind::BlockInfo { block_number } => {
+                ("blockInfo", json!({ "blockNumber": block_number }))
+            }
+        };
+        json!({
+            "kind": kind,
+            "details": details,
+            "durationMs": self.duration.as_secs_f64() * 1000.0,
+        })
+    }
+}
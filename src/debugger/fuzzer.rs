@@ -0,0 +1,71 @@
+//! Iterations of fuzz tests, which execution tools run many times with generated arguments.
+
+use dap::types::{Scope, Variable};
+use serde::Deserialize;
+
+/// Set in the variables reference of the Fuzzer scope. Lower than the flag of the Storage scope.
+const FUZZER_REFERENCE_FLAG: i64 = 1 << 28;
+
+/// Iteration of a fuzz test, see [`crate::Debugger::start_fuzz_iteration`].
+pub struct FuzzIteration {
+    /// Number of the iteration, starting from 1.
+    pub number: usize,
+    /// Seed of the fuzzer, with which the same arguments are generated in the next run.
+    pub seed: u64,
+    pub arguments: Vec<FuzzArgument>,
+    /// Whether the iteration replays a failing one, in which case the execution stops at its start.
+    pub is_failure_replay: bool,
+}
+
+/// Generated argument of a fuzz test.
+pub struct FuzzArgument {
+    pub name: String,
+    /// Value formatted by the execution tool, which knows the type of the parameter.
+    pub value: String,
+}
+
+/// Iteration in which breakpoints apply, set in the launch configuration.
+/// Breakpoints apply in all iterations matching the options that are set.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FuzzerOptions {
+    pub seed: Option<u64>,
+    pub iteration: Option<usize>,
+}
+
+impl FuzzIteration {
+    pub fn matches(&self, options: FuzzerOptions) -> bool {
+        options.seed.is_none_or(|seed| seed == self.seed)
+            && options.iteration.is_none_or(|iteration| iteration == self.number)
+    }
+
+    pub fn scope(&self) -> Scope {
+        Scope {
+            name: "Fuzzer".to_string(),
+            variables_reference: FUZZER_REFERENCE_FLAG,
+            ..Default::default()
+        }
+    }
+
+    /// The iteration number and the seed, followed by the generated arguments.
+    pub fn variables(&self) -> Vec<Variable> {
+        let variable = |name: &str, value: String| Variable {
+            name: name.to_string(),
+            value,
+            variables_reference: 0,
+            ..Default::default()
+        };
+        [variable("iteration", self.number.to_string()), variable("seed", self.seed.to_string())]
+            .into_iter()
+            .chain(
+                self.arguments
+                    .iter()
+                    .map(|argument| variable(&argument.name, argument.value.clone())),
+            )
+            .collect()
+    }
+}
+
+pub fn is_fuzzer_reference(variables_reference: i64) -> bool {
+    variables_reference & FUZZER_REFERENCE_FLAG != 0
+}
@@ -1,27 +1,56 @@
-use anyhow::{Result, anyhow, bail};
-use dap::events::{Event, StoppedEventBody};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use dap::events::{
+    BreakpointEventBody, CapabilitiesEventBody, Event, ModuleEventBody, StoppedEventBody,
+};
 use dap::prelude::{Command, Request, ResponseBody};
-use dap::requests::{NextArguments, StepInArguments};
-use dap::requests::{ScopesArguments, VariablesArguments};
+use dap::requests::{
+    EvaluateArguments, NextArguments, SetExceptionBreakpointsArguments, StepInArguments,
+};
+use dap::requests::{
+    ModulesArguments, ScopesArguments, SourceArguments, StackTraceArguments, VariablesArguments,
+};
 use dap::responses::{
-    ContinueResponse, EvaluateResponse, ScopesResponse, SetBreakpointsResponse,
-    SetExceptionBreakpointsResponse, StackTraceResponse, ThreadsResponse, VariablesResponse,
+    ContinueResponse, EvaluateResponse, ModulesResponse, ScopesResponse, SetBreakpointsResponse,
+    SetExceptionBreakpointsResponse, SetFunctionBreakpointsResponse, SourceResponse,
+    StackTraceResponse, ThreadsResponse, VariablesResponse,
+};
+use dap::types::{
+    Breakpoint, BreakpointEventReason, Capabilities, ChecksumAlgorithm, ExceptionBreakpointsFilter,
+    Module, ModuleEventReason, ModuleId, StoppedEventReason, Thread,
 };
-use dap::types::{Breakpoint, Capabilities, StoppedEventReason, Thread};
 use tracing::{error, trace};
 
-use crate::debugger::MAX_OBJECT_REFERENCE;
+use crate::debugger::call_trace::FAILED_CALL_FILTER;
+use crate::debugger::command_name;
+use crate::debugger::context::registry::ContextRegistry;
 use crate::debugger::context::{Context, Line};
+use crate::debugger::custom_request::CustomRequest;
+use crate::debugger::deployments::{DECLARE_FILTER, DEPLOY_FILTER};
+use crate::debugger::errors::DebuggerError;
+use crate::debugger::execution_info;
+use crate::debugger::fork::FORK_READ_FILTER;
+use crate::debugger::fuzzer::{self, FuzzIteration};
+use crate::debugger::hints::{self, HINT_FILTER, HintBreakpoint};
+use crate::debugger::launch_args::LaunchArguments;
+use crate::debugger::memory::SegmentGrowthMonitor;
+use crate::debugger::panics::PANIC_FILTER;
+use crate::debugger::recording::ExecutionRecording;
 use crate::debugger::state::State;
+use crate::debugger::storage;
+use crate::debugger::view_call::ViewCall;
+use crate::debugger::vm_access::VmAccess;
 
 pub struct HandlerResponse {
     pub response_body: ResponseBody,
-    pub event: Option<Event>,
+    pub events: Vec<Event>,
 }
 
 impl From<ResponseBody> for HandlerResponse {
     fn from(response_body: ResponseBody) -> Self {
-        Self { response_body, event: None }
+        Self { response_body, events: Vec::new() }
     }
 }
 
@@ -34,7 +63,13 @@ pub enum StepAction {
 impl HandlerResponse {
     #[must_use]
     pub fn with_event(mut self, event: Event) -> Self {
-        self.event = Some(event);
+        self.events.push(event);
+        self
+    }
+
+    #[must_use]
+    pub fn with_events(mut self, events: impl IntoIterator<Item = Event>) -> Self {
+        self.events.extend(events);
         self
     }
 }
@@ -42,16 +77,12 @@ impl HandlerResponse {
 pub fn handle_request(
     request: &Request,
     state: &mut State,
-    ctx: &Context,
+    contexts: &mut ContextRegistry,
+    vm: Option<&mut dyn VmAccess>,
 ) -> Result<HandlerResponse> {
     match &request.command {
         // We have not yet decided if we want to support these.
-        Command::Attach(_)
-        | Command::ReverseContinue(_)
-        | Command::StepBack(_)
-        | Command::SetFunctionBreakpoints(_)
-        | Command::BreakpointLocations(_)
-        | Command::Cancel(_)
+        Command::BreakpointLocations(_)
         | Command::Completions(_)
         | Command::DataBreakpointInfo(_)
         | Command::Disassemble(_)
@@ -59,13 +90,10 @@ pub fn handle_request(
         | Command::ExceptionInfo(_)
         | Command::GotoTargets(_)
         | Command::LoadedSources
-        | Command::Modules(_)
         | Command::ReadMemory(_)
         | Command::RestartFrame(_)
         | Command::SetDataBreakpoints(_)
-        | Command::Restart(_)
         | Command::TerminateThreads(_)
-        | Command::Terminate(_)
         | Command::StepInTargets(_)
         | Command::SetVariable(_)
         | Command::SetInstructionBreakpoints(_)
@@ -73,16 +101,42 @@ pub fn handle_request(
         | Command::WriteMemory(_) => {
             // If we receive these with current capabilities, it is the client's fault.
             error!("Received unsupported request: {request:?}");
-            bail!("Unsupported request");
+            Err(DebuggerError::Unsupported {
+                feature: format!("The `{}` request", command_name(&request.command)),
+                requirement: None,
+            }
+            .into())
         }
-        Command::SetExceptionBreakpoints(_) => {
-            // VS Code sometimes sends this request based on old user settings,
-            // even if we disabled the feature in our initialization.
-            //
-            // We reply with "success" to keep the debug session running smoothly,
-            // but we intentionally ignore the request and set no breakpoints.
-
-            trace!("Ignoring SetExceptionBreakpoints request (feature disabled)");
+        // Answered by the connection as soon as it arrives, see `Connection::take_cancellation`.
+        Command::Cancel(_) => Ok(ResponseBody::Cancel.into()),
+        Command::SetExceptionBreakpoints(SetExceptionBreakpointsArguments {
+            filters,
+            filter_options,
+            ..
+        }) => {
+            // Filters with options, e.g. a condition, are sent in `filter_options` instead.
+            let filter_options = filter_options.as_deref().unwrap_or_default();
+            let is_enabled = |id: &str| {
+                filters.iter().any(|filter| filter == id)
+                    || filter_options.iter().any(|options| options.filter_id == id)
+            };
+            // Unknown filters may come from old user settings, hence they are ignored.
+            state.break_on_fork_read = is_enabled(FORK_READ_FILTER);
+            state.break_on_panic = is_enabled(PANIC_FILTER);
+            state.break_on_failed_call = is_enabled(FAILED_CALL_FILTER);
+            state.break_on_declare = is_enabled(DECLARE_FILTER);
+            state.break_on_deploy = is_enabled(DEPLOY_FILTER);
+            state.hint_breakpoints = filters
+                .iter()
+                .filter(|filter| *filter == HINT_FILTER)
+                .map(|_| HintBreakpoint::Any)
+                .chain(
+                    filter_options
+                        .iter()
+                        .filter(|options| options.filter_id == HINT_FILTER)
+                        .map(|options| HintBreakpoint::parse(options.condition.as_deref())),
+                )
+                .collect();
             Ok(ResponseBody::SetExceptionBreakpoints(SetExceptionBreakpointsResponse {
                 breakpoints: None,
             })
@@ -92,37 +146,71 @@ pub fn handle_request(
         // Initialize flow requests.
         Command::Initialize(args) => {
             trace!("Initialized a client: {:?}", args.client_name);
-            Ok(HandlerResponse::from(ResponseBody::Initialize(Capabilities {
-                supports_configuration_done_request: Some(true),
-                ..Default::default()
-            }))
-            .with_event(Event::Initialized))
+            state.supports_run_in_terminal = args.supports_run_in_terminal_request.unwrap_or(false);
+            state.supports_start_debugging = args.supports_start_debugging_request.unwrap_or(false);
+            state.set_initialized();
+            Ok(HandlerResponse::from(ResponseBody::Initialize(build_capabilities(contexts.root())))
+                .with_event(Event::Initialized))
+        }
+        Command::Launch(args) => {
+            let launch_args =
+                LaunchArguments::parse(args.additional_data.as_ref(), contexts.root())?;
+            let had_modules = !contexts.root().packages.is_empty();
+            configure_session(launch_args, state, contexts);
+            state.no_debug = args.no_debug.unwrap_or(false);
+            Ok(HandlerResponse::from(ResponseBody::Launch)
+                .with_events(modules_capabilities_event(had_modules, contexts.root()))
+                .with_events(module_events(contexts.root()))
+                .with_events(recording_capabilities_event(state)))
+        }
+        // The debuggee is already running and waiting for a client on a known port,
+        // so attaching differs from launching only from the client's point of view.
+        Command::Attach(args) => {
+            let launch_args =
+                LaunchArguments::parse(args.additional_data.as_ref(), contexts.root())?;
+            let had_modules = !contexts.root().packages.is_empty();
+            configure_session(launch_args, state, contexts);
+            state.attached = true;
+            Ok(HandlerResponse::from(ResponseBody::Attach)
+                .with_events(modules_capabilities_event(had_modules, contexts.root()))
+                .with_events(module_events(contexts.root()))
+                .with_events(recording_capabilities_event(state)))
         }
-        Command::Launch(_) => Ok(ResponseBody::Launch.into()),
         Command::ConfigurationDone => {
             // Start running the Cairo program here.
             state.set_configuration_done();
             Ok(ResponseBody::ConfigurationDone.into())
         }
 
+        Command::Restart(_) => {
+            // Only advertised once the execution tool enabled re-runs of tests.
+            state.request_rerun()?;
+            Ok(ResponseBody::Restart.into())
+        }
+
+        Command::Terminate(_) => {
+            // The execution is aborted before the next instruction.
+            state.request_termination();
+            Ok(ResponseBody::Terminate.into())
+        }
+
         Command::Pause(_) => {
             state.stop_execution();
-            Ok(HandlerResponse::from(ResponseBody::Pause).with_event(Event::Stopped(
-                StoppedEventBody {
-                    reason: StoppedEventReason::Pause,
-                    thread_id: Some(MAX_OBJECT_REFERENCE),
-                    description: None,
-                    preserve_focus_hint: None,
-                    text: None,
-                    all_threads_stopped: Some(true),
-                    hit_breakpoint_ids: None,
-                },
-            )))
+            Ok(HandlerResponse::from(ResponseBody::Pause)
+                .with_event(stopped_event(state, StoppedEventReason::Pause)))
         }
         Command::Continue(_) => {
+            let response =
+                ResponseBody::Continue(ContinueResponse { all_threads_continued: Some(true) });
+            // Breakpoints hit in the recorded steps are stopped at before the execution resumes.
+            if let Some(vm) = vm
+                && state.continue_in_past(vm, contexts.active())?
+            {
+                return Ok(HandlerResponse::from(response)
+                    .with_event(stopped_event(state, StoppedEventReason::Breakpoint)));
+            }
             state.resume_execution();
-            Ok(ResponseBody::Continue(ContinueResponse { all_threads_continued: Some(true) })
-                .into())
+            Ok(response.into())
         }
 
         Command::SetBreakpoints(args) => {
@@ -134,17 +222,25 @@ pub fn handle_request(
                     .clone()
                     .ok_or_else(|| anyhow!("Source file path is missing"))?;
 
-                state.clear_breakpoints(&source_path);
+                state.clear_breakpoints(&source_path, contexts.active());
 
                 for bp in requested_bps {
-                    let is_valid = state.verify_and_set_breakpoint(
+                    let (id, is_valid) = state.verify_and_set_breakpoint(
                         source_path.clone(),
                         // UI sends line numbers as 1-indexed, hence we subtract 1 here.
                         Line::new((bp.line - 1) as usize),
-                        ctx,
+                        contexts,
                     );
                     response_bps.push(Breakpoint {
+                        id: Some(id),
                         verified: is_valid,
+                        message: (!is_valid).then(|| {
+                            DebuggerError::UnmappedBreakpoint {
+                                source: source_path.clone(),
+                                line: bp.line as usize,
+                            }
+                            .to_string()
+                        }),
                         source: Some(args.source.clone()),
                         line: Some(bp.line),
                         ..Default::default()
@@ -154,36 +250,121 @@ pub fn handle_request(
             Ok(ResponseBody::SetBreakpoints(SetBreakpointsResponse { breakpoints: response_bps })
                 .into())
         }
+        Command::SetFunctionBreakpoints(args) => {
+            let names = args.breakpoints.iter().map(|breakpoint| breakpoint.name.clone()).collect();
+            let breakpoints = state
+                .set_function_breakpoints(names, contexts)
+                .into_iter()
+                .map(|(id, verified)| Breakpoint { id: Some(id), verified, ..Default::default() })
+                .collect();
+            Ok(ResponseBody::SetFunctionBreakpoints(SetFunctionBreakpointsResponse { breakpoints })
+                .into())
+        }
 
         Command::Threads => {
             Ok(ResponseBody::Threads(ThreadsResponse {
-                // Return a single thread.
-                threads: vec![Thread { id: MAX_OBJECT_REFERENCE, name: "".to_string() }],
+                // Tests are executed one after another, so at most one thread exists at a time.
+                threads: state
+                    .thread
+                    .iter()
+                    .map(|thread| Thread { id: thread.id, name: state.thread_name() })
+                    .collect(),
             })
             .into())
         }
-        Command::StackTrace(_) => {
-            let stack_frames = state.call_stack.get_frames(state.current_statement_idx, ctx);
-            let total_frames = Some(stack_frames.len() as i64);
+        Command::StackTrace(StackTraceArguments { start_frame, levels, format, .. }) => {
+            let ctx = contexts.active();
+            // Omitted or zero `levels` means that all remaining frames should be returned.
+            let start_frame = start_frame.unwrap_or(0).max(0) as usize;
+            let levels = match levels {
+                Some(levels) if *levels > 0 => *levels as usize,
+                _ => usize::MAX,
+            };
+            let (mut stack_frames, total_frames) = state.call_stack.get_frames(
+                state.current_statement_idx,
+                state.just_my_code,
+                ctx,
+                start_frame..start_frame.saturating_add(levels),
+            );
+            let total_frames = Some(total_frames as i64);
+
+            if let Some(format) = format {
+                state.call_stack.format_frames(
+                    &mut stack_frames,
+                    format,
+                    state.current_statement_idx,
+                    vm.as_deref(),
+                    ctx,
+                );
+            }
+            if state.call_stack.show_frame_resources
+                && let Some(vm) = vm.as_deref()
+            {
+                state.call_stack.append_frame_resources(&mut stack_frames, vm);
+            }
+
             Ok(ResponseBody::StackTrace(StackTraceResponse { stack_frames, total_frames }).into())
         }
         Command::Scopes(ScopesArguments { frame_id }) => {
-            let scopes = state.call_stack.get_scopes_for_frame(*frame_id);
+            let mut scopes = state.call_stack.get_scopes_for_frame(
+                *frame_id,
+                state.current_statement_idx,
+                contexts.active(),
+            );
+            // Storage belongs to the contract, hence it is the same in all of its frames.
+            if state.call_trace.active_call().is_some() {
+                scopes.push(state.storage.scope());
+                scopes.push(execution_info::scope());
+            }
+            if let Some(iteration) = &state.fuzz_iteration {
+                scopes.push(iteration.scope());
+            }
+            if !state.hints.is_empty() {
+                scopes.push(hints::scope());
+            }
             Ok(ResponseBody::Scopes(ScopesResponse { scopes }).into())
         }
         Command::Variables(VariablesArguments { variables_reference, .. }) => {
-            let variables = state.call_stack.get_variables(*variables_reference);
+            let variables = match state.call_trace.active_call() {
+                _ if fuzzer::is_fuzzer_reference(*variables_reference) => {
+                    state.fuzz_iteration.as_ref().map(FuzzIteration::variables).unwrap_or_default()
+                }
+                _ if hints::is_hint_reference(*variables_reference) => {
+                    hints::variables(&state.hints, *variables_reference)
+                }
+                Some(call) if execution_info::is_execution_info_reference(*variables_reference) => {
+                    execution_info::variables(call, state.call_trace.active_execution_info())
+                }
+                Some(call) if storage::is_storage_reference(*variables_reference) => {
+                    state.storage.variables(
+                        *variables_reference,
+                        call.contract_address,
+                        &contexts.active().storage_variables,
+                        state.call_stack.value_format,
+                    )
+                }
+                _ => state.call_stack.get_variables(
+                    *variables_reference,
+                    state.current_statement_idx,
+                    vm.as_deref(),
+                    contexts.active(),
+                ),
+            };
             Ok(ResponseBody::Variables(VariablesResponse { variables }).into())
         }
 
         // `vs-code` currently doesn't support choosing granularity, see: https://github.com/microsoft/vscode/issues/102236.
         // We assume granularity of line.
         Command::Next(NextArguments { .. }) => {
+            if state.is_in_past() {
+                return step_forward_in_past(ResponseBody::Next, state, contexts, vm);
+            }
             // To handle a "step over" action, we set the step action to `Next`.
             // We record the current call stack depth. The debugger will resume execution
             // and only stop when it reaches a new line at the same or a shallower call stack depth.
             // This effectively "steps over" any function calls.
-            let line = Line::create_from_statement_idx(state.current_statement_idx, ctx);
+            let line =
+                Line::create_from_statement_idx(state.current_statement_idx, contexts.active());
 
             state.step_action =
                 Some(StepAction::Next { depth: state.call_stack.depth(), prev_line: line });
@@ -194,10 +375,14 @@ pub fn handle_request(
         // `vs-code` currently doesn't support choosing granularity, see: https://github.com/microsoft/vscode/issues/102236.
         // We assume granularity of line.
         Command::StepIn(StepInArguments { .. }) => {
+            if state.is_in_past() {
+                return step_forward_in_past(ResponseBody::StepIn, state, contexts, vm);
+            }
             // To handle a "step in" action, we set the step action to `StepIn`.
             // The debugger will resume execution and stop at the very next executable line,
             // which might be inside a function call.
-            let line = Line::create_from_statement_idx(state.current_statement_idx, ctx);
+            let line =
+                Line::create_from_statement_idx(state.current_statement_idx, contexts.active());
 
             state.step_action = Some(StepAction::StepIn { prev_line: line });
             state.resume_execution();
@@ -208,31 +393,347 @@ pub fn handle_request(
             // We record the current call stack depth. The debugger will resume execution
             // and only stop when it reaches a line in a shallower call stack depth, which
             // happens when the current function returns.
+            // Stepping out of the entrypoint of a called contract stops in its caller.
+            // Steps recorded in the past are not replayed, the execution steps out from
+            // where it actually stopped.
+            if let Some(vm) = vm {
+                state.return_to_present(vm);
+            }
             let depth = state.call_stack.depth();
-            if depth > 0 {
+            if depth > 0 || state.is_in_nested_program() {
                 state.step_action = Some(StepAction::StepOut { depth });
             }
             state.resume_execution();
             Ok(ResponseBody::StepOut.into())
         }
-        Command::Source(_) => {
-            todo!()
+        Command::StepBack(_) => {
+            let vm = vm.ok_or_else(|| anyhow!("The execution has not started yet"))?;
+            state.step_back(vm, contexts.active())?;
+            Ok(HandlerResponse::from(ResponseBody::StepBack)
+                .with_event(stopped_event(state, StoppedEventReason::Step)))
         }
+        Command::ReverseContinue(_) => {
+            let vm = vm.ok_or_else(|| anyhow!("The execution has not started yet"))?;
+            let reason = if state.reverse_continue(vm, contexts.active())? {
+                StoppedEventReason::Breakpoint
+            } else {
+                // No breakpoint was hit before, like when continuing to the end of the program.
+                StoppedEventReason::Entry
+            };
+            Ok(HandlerResponse::from(ResponseBody::ReverseContinue)
+                .with_event(stopped_event(state, reason)))
+        }
+        Command::Modules(ModulesArguments { start_module, module_count }) => {
+            // Modules of contract classes were announced once they were registered, too.
+            let mut known_ids = HashSet::new();
+            let all_modules: Vec<Module> = contexts
+                .iter()
+                .flat_map(build_modules)
+                .filter(|module| known_ids.insert(module_id_key(&module.id)))
+                .collect();
+            let total_modules = Some(all_modules.len() as i64);
+
+            // Omitted or zero `module_count` means that all remaining modules should be returned.
+            let start_module = start_module.unwrap_or(0).max(0) as usize;
+            let module_count = match module_count {
+                Some(count) if *count > 0 => *count as usize,
+                _ => usize::MAX,
+            };
+            let modules = all_modules.into_iter().skip(start_module).take(module_count).collect();
+
+            Ok(ResponseBody::Modules(ModulesResponse { modules, total_modules }).into())
+        }
+        Command::Source(SourceArguments { source, source_reference }) => {
+            let source_reference = source
+                .as_ref()
+                .and_then(|source| source.source_reference)
+                .unwrap_or(*source_reference);
+
+            // Reply with a placeholder instead of an error, so that the client can still display
+            // the frame. Source references come from stack traces of the active program.
+            let content =
+                contexts.active().source_files.content(source_reference).unwrap_or_else(|| {
+                    "// Source file is not available on this machine.".to_string()
+                });
+            Ok(ResponseBody::Source(SourceResponse { content, mime_type: None }).into())
+        }
+
+        Command::Evaluate(EvaluateArguments { expression, .. }) => {
+            let mut events = Vec::new();
+            let result = match CustomRequest::parse(expression) {
+                // Errors are displayed to the user instead of failing the whole session.
+                Some(custom_request) => custom_request
+                    .and_then(|custom_request| {
+                        let moves_execution = custom_request.moves_execution();
+                        let starts_recording = custom_request.starts_recording();
+                        let changes_settings = custom_request.changes_settings();
+                        let result = custom_request.handle(state, contexts, vm)?;
+                        if moves_execution {
+                            events.push(stopped_event(state, StoppedEventReason::Goto));
+                        }
+                        if starts_recording {
+                            events.extend(recording_capabilities_event(state));
+                        }
+                        if changes_settings {
+                            // Breakpoints outside of the user's code, if `justMyCode` was unset.
+                            events.extend(
+                                state.verify_pending_breakpoints(contexts).into_iter().map(
+                                    |breakpoint| {
+                                        Event::Breakpoint(BreakpointEventBody {
+                                            reason: BreakpointEventReason::Changed,
+                                            breakpoint,
+                                        })
+                                    },
+                                ),
+                            );
+                        }
+                        Ok(result)
+                    })
+                    .unwrap_or_else(|err| format!("Error: {err:#}")),
+                None => match ViewCall::parse(expression) {
+                    Some(view_call) => view_call
+                        .and_then(|view_call| view_call.execute(state, contexts))
+                        .unwrap_or_else(|err| format!("Error: {err:#}")),
+                    // Return whatever since we cannot opt out of supporting this request.
+                    None => "".to_string(),
+                },
+            };
 
-        Command::Evaluate(_) => {
-            Ok(ResponseBody::Evaluate(EvaluateResponse {
-                // Return whatever since we cannot opt out of supporting this request.
-                result: "".to_string(),
+            Ok(HandlerResponse::from(ResponseBody::Evaluate(EvaluateResponse {
+                result,
                 type_field: None,
                 presentation_hint: None,
                 variables_reference: 0,
                 named_variables: None,
                 indexed_variables: None,
                 memory_reference: None,
-            })
-            .into())
+            }))
+            .with_events(events))
         }
 
         Command::Disconnect(_) => Ok(ResponseBody::Disconnect.into()),
     }
 }
+
+/// Moves the execution that was moved back to a past step forward to the next recorded line,
+/// as both stepping over and stepping in do, since the recording has no notion of calls
+/// being stepped over.
+fn step_forward_in_past(
+    response_body: ResponseBody,
+    state: &mut State,
+    contexts: &ContextRegistry,
+    vm: Option<&mut dyn VmAccess>,
+) -> Result<HandlerResponse> {
+    let vm = vm.ok_or_else(|| anyhow!("The execution has not started yet"))?;
+    state.step_forward_in_past(vm, contexts.active())?;
+    Ok(HandlerResponse::from(response_body)
+        .with_event(stopped_event(state, StoppedEventReason::Step)))
+}
+
+fn stopped_event(state: &State, reason: StoppedEventReason) -> Event {
+    Event::Stopped(StoppedEventBody {
+        reason,
+        thread_id: Some(state.thread_id()),
+        description: None,
+        preserve_focus_hint: None,
+        text: None,
+        all_threads_stopped: Some(true),
+        hit_breakpoint_ids: None,
+    })
+}
+
+/// The capabilities were sent before the client could enable the recording of the execution,
+/// in the launch configuration or with `:record start`.
+fn recording_capabilities_event(state: &State) -> Option<Event> {
+    state.recording.as_ref().map(|_| {
+        Event::Capabilities(CapabilitiesEventBody {
+            capabilities: Capabilities { supports_step_back: Some(true), ..Default::default() },
+        })
+    })
+}
+
+/// The capabilities were sent before Scarb was queried for the packages, if their metadata
+/// was not cached.
+fn modules_capabilities_event(had_modules: bool, ctx: &Context) -> Option<Event> {
+    (!had_modules && !ctx.packages.is_empty()).then(|| {
+        Event::Capabilities(CapabilitiesEventBody {
+            capabilities: Capabilities {
+                supports_modules_request: Some(true),
+                ..Default::default()
+            },
+        })
+    })
+}
+
+/// Applies the configuration sent with the launch or attach request.
+fn configure_session(
+    launch_args: LaunchArguments,
+    state: &mut State,
+    contexts: &mut ContextRegistry,
+) {
+    if let Some(limit) = launch_args.segment_growth_limit {
+        trace!("Segment growth limit set to {limit} cells");
+        state.segment_growth_monitor = Some(SegmentGrowthMonitor::new(limit));
+    }
+    state.call_stack.recursion_frame_limit = launch_args.recursion_frame_limit;
+    state.call_stack.fold_macro_frames = launch_args.fold_macro_frames;
+    state.call_stack.show_frame_resources = launch_args.show_frame_resources;
+    state.call_stack.value_format = launch_args.value_format;
+    state.just_my_code = launch_args.just_my_code;
+    state.stop_on_entry = launch_args.stop_on_entry;
+    state.test_name = launch_args.test_name;
+    state.selected_test = launch_args.selected_test;
+    state.program_args = launch_args.program_args;
+    state.console = launch_args.console;
+    state.cwd = launch_args.cwd;
+    state.debug_contract_calls = launch_args.debug_contract_calls;
+    state.stream_syscalls = launch_args.stream_syscalls;
+    state.max_recorded_steps = launch_args.max_recorded_steps;
+    if launch_args.record_execution {
+        state.recording = Some(ExecutionRecording::new(launch_args.max_recorded_steps));
+    }
+    state.fuzzer_options = launch_args.fuzzer;
+    state.on_disconnect = launch_args.on_disconnect;
+    state.watchdog_timeout = launch_args.watchdog_timeout.map(Duration::from_secs_f64);
+    state.pause_on_watchdog_timeout = launch_args.pause_on_watchdog_timeout;
+    state.configuration = launch_args.configuration;
+
+    // Packages take precedence over the workspace, since they single out the user's code in it.
+    let user_code_roots = if launch_args.package_roots.is_empty() {
+        launch_args.workspace_root.into_iter().collect()
+    } else {
+        launch_args.package_roots
+    };
+    contexts.configure_project(user_code_roots);
+    if let Some(corelib_path) = &launch_args.corelib_path {
+        trace!("Using corelib from {}", corelib_path.display());
+    }
+    contexts.configure_sources(launch_args.corelib_path, launch_args.source_map);
+    state.set_launched();
+}
+
+/// Advertises only the features available for the debugged program,
+/// so that the client does not offer the user what would come out empty.
+fn build_capabilities(ctx: &Context) -> Capabilities {
+    Capabilities {
+        supports_configuration_done_request: Some(true),
+        supports_function_breakpoints: Some(true),
+        supports_exception_filter_options: Some(true),
+        supports_delayed_stack_trace_loading: Some(true),
+        supports_cancel_request: Some(true),
+        // Modules are built from Scarb packages, which are unknown outside of a Scarb project.
+        supports_modules_request: Some(!ctx.packages.is_empty()),
+        supports_value_formatting_option: Some(true),
+        // Lets the client choose between stopping the debuggee and only disconnecting,
+        // after which the next client can connect.
+        support_terminate_debuggee: Some(true),
+        supports_terminate_request: Some(true),
+        supported_checksum_algorithms: ctx
+            .source_files
+            .has_checksums()
+            .then(|| vec![ChecksumAlgorithm::SHA256]),
+        exception_breakpoint_filters: Some(vec![
+            ExceptionBreakpointsFilter {
+                filter: PANIC_FILTER.to_string(),
+                label: "Unexpected panics".to_string(),
+                description: Some(
+                    "Pause where the program starts panicking, unless the panic is expected \
+                     by a #[should_panic] test"
+                        .to_string(),
+                ),
+                default: Some(false),
+                supports_condition: None,
+                condition_description: None,
+            },
+            ExceptionBreakpointsFilter {
+                filter: FAILED_CALL_FILTER.to_string(),
+                label: "Failed contract calls".to_string(),
+                description: Some(
+                    "Pause in the caller of a contract call that failed, where the failure surfaces"
+                        .to_string(),
+                ),
+                default: Some(false),
+                supports_condition: None,
+                condition_description: None,
+            },
+            ExceptionBreakpointsFilter {
+                filter: HINT_FILTER.to_string(),
+                label: "Hints".to_string(),
+                description: Some("Pause before hints are executed".to_string()),
+                default: Some(false),
+                supports_condition: Some(true),
+                condition_description: Some(
+                    "Pc of the hint, e.g. 42, or a part of its name or code, e.g. AllocSegment"
+                        .to_string(),
+                ),
+            },
+            ExceptionBreakpointsFilter {
+                filter: FORK_READ_FILTER.to_string(),
+                label: "First fork read".to_string(),
+                description: Some(
+                    "Pause at the first read of the state served from the forked network"
+                        .to_string(),
+                ),
+                default: Some(false),
+                supports_condition: None,
+                condition_description: None,
+            },
+            ExceptionBreakpointsFilter {
+                filter: DECLARE_FILTER.to_string(),
+                label: "Class declarations".to_string(),
+                description: Some(
+                    "Pause once a class is declared, with the declared contract and its class hash"
+                        .to_string(),
+                ),
+                default: Some(false),
+                supports_condition: None,
+                condition_description: None,
+            },
+            ExceptionBreakpointsFilter {
+                filter: DEPLOY_FILTER.to_string(),
+                label: "Contract deployments".to_string(),
+                description: Some(
+                    "Pause at each deployment of a contract, with its decoded constructor calldata"
+                        .to_string(),
+                ),
+                default: Some(false),
+                supports_condition: None,
+                condition_description: None,
+            },
+        ]),
+        ..Default::default()
+    }
+}
+
+fn module_events(ctx: &Context) -> Vec<Event> {
+    build_modules(ctx)
+        .into_iter()
+        .map(|module| Event::Module(ModuleEventBody { reason: ModuleEventReason::New, module }))
+        .collect()
+}
+
+fn module_id_key(id: &ModuleId) -> String {
+    match id {
+        ModuleId::Number(number) => number.to_string(),
+        ModuleId::String(id) => id.clone(),
+    }
+}
+
+/// Builds a module for each Scarb package whose code may be a part of the debugged program.
+pub fn build_modules(ctx: &Context) -> Vec<Module> {
+    ctx.packages
+        .iter()
+        .map(|package| Module {
+            id: ModuleId::String(package.id.clone()),
+            name: package.name.clone(),
+            path: Some(package.root.display().to_string()),
+            is_optimized: None,
+            is_user_code: Some(package.is_workspace_member),
+            version: Some(package.version.clone()),
+            symbol_status: None,
+            symbol_file_path: None,
+            date_time_stamp: None,
+            address_range: None,
+        })
+        .collect()
+}
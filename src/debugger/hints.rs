@@ -0,0 +1,207 @@
+//! Hints of the instruction about to be executed, which can pause the execution
+//! and are shown in the Hints scope when it is stopped.
+
+use std::any::Any;
+
+use cairo_lang_casm::hints::{Hint, PythonicHint};
+use dap::types::{Scope, Variable};
+
+use crate::debugger::vm_access::{Address, ApTracking, Cairo0Hint, Value, VmAccess};
+
+/// Filter of the exception breakpoint stopping before hints are executed. Its condition
+/// narrows the hints down, see [`HintBreakpoint::parse`].
+pub const HINT_FILTER: &str = "hint";
+
+/// Set in the variables references of the Hints scope and of its hints.
+/// Lower than the flag of the Fuzzer scope.
+const HINT_REFERENCE_FLAG: i64 = 1 << 27;
+
+/// Wrapping variants of [`Hint`], which do not tell what the hint does.
+const HINT_WRAPPERS: [&str; 4] = ["Core", "Deprecated", "Starknet", "External"];
+
+/// Hints the execution stops at.
+pub enum HintBreakpoint {
+    Any,
+    /// Hints at the pc, relative to the start of the program.
+    Pc(usize),
+    /// Hints whose name or code contains the string.
+    Code(String),
+}
+
+/// Hint about to be executed.
+pub struct HintInfo {
+    pub pc: usize,
+    /// Variant of Cairo 1 hints, e.g. `AllocSegment`. Cairo 0 hints are known by their code only.
+    pub name: Option<String>,
+    /// Python code of the hint.
+    pub code: String,
+    /// Tracking of the `ap` register, used to find `ids` of Cairo 0 hints.
+    pub ap_tracking: Option<ApTracking>,
+    /// Cells the hint refers to: `ids` of Cairo 0 hints, or cells relative to the registers.
+    pub cells: Vec<HintCell>,
+}
+
+pub struct HintCell {
+    /// E.g. `ids.x` or `[ap + 1]`.
+    pub name: String,
+    pub address: Address,
+    /// Value before the hint is executed, unset for cells the hint is yet to write.
+    pub value: Option<Value>,
+}
+
+impl HintBreakpoint {
+    /// Parses the condition of the Hints filter: the pc of the hint, e.g. `42`, or a part
+    /// of its name or code, e.g. `AllocSegment`. Without a condition, all hints match.
+    pub fn parse(condition: Option<&str>) -> Self {
+        match condition.map(str::trim) {
+            None | Some("") => Self::Any,
+            Some(condition) => match condition.parse() {
+                Ok(pc) => Self::Pc(pc),
+                Err(_) => Self::Code(condition.to_string()),
+            },
+        }
+    }
+
+    pub fn matches(&self, hint: &HintInfo) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Pc(pc) => hint.pc == *pc,
+            Self::Code(code) => {
+                hint.code.contains(code.as_str())
+                    || hint.name.as_ref().is_some_and(|name| name.contains(code.as_str()))
+            }
+        }
+    }
+}
+
+impl HintInfo {
+    /// Returns the hints executed before the instruction at the current pc.
+    pub fn collect(vm: &dyn VmAccess, hints_data: &[Box<dyn Any>]) -> Vec<Self> {
+        let pc = vm.pc().offset;
+        hints_data
+            .iter()
+            .flat_map(|hint_data| {
+                if let Some(hints) = hint_data.downcast_ref::<Vec<Hint>>() {
+                    hints.iter().map(|hint| Self::from_cairo_1_hint(vm, pc, hint)).collect()
+                } else if let Some(hint) = vm.cairo_0_hint(&**hint_data) {
+                    vec![Self::from_cairo_0_hint(vm, pc, hint)]
+                } else {
+                    vec![]
+                }
+            })
+            .collect()
+    }
+
+    fn from_cairo_1_hint(vm: &dyn VmAccess, pc: usize, hint: &Hint) -> Self {
+        let debug = format!("{hint:?}");
+        let name = debug
+            .split(['(', ' ', '{'])
+            .find(|part| !part.is_empty() && !HINT_WRAPPERS.contains(part))
+            .map(ToString::to_string);
+        let code = hint.get_pythonic_hint();
+        let cells = register_cells(&code)
+            .into_iter()
+            .filter_map(|(name, register, offset)| {
+                let base = if register == "ap" { vm.ap() } else { vm.fp() };
+                let address = base.add_signed(offset)?;
+                Some(HintCell { name, address, value: vm.read(address) })
+            })
+            .collect();
+        Self { pc, name, code, ap_tracking: None, cells }
+    }
+
+    fn from_cairo_0_hint(vm: &dyn VmAccess, pc: usize, hint: Cairo0Hint) -> Self {
+        let cells = hint
+            .ids
+            .into_iter()
+            .map(|(name, address)| HintCell {
+                name: format!("ids.{name}"),
+                address,
+                value: vm.read(address),
+            })
+            .collect();
+        Self { pc, name: None, code: hint.code, ap_tracking: Some(hint.ap_tracking), cells }
+    }
+
+    fn variables(&self) -> Vec<Variable> {
+        let variable = |name: &str, value: String| Variable {
+            name: name.to_string(),
+            value,
+            variables_reference: 0,
+            ..Default::default()
+        };
+        let mut variables = vec![variable("pc", self.pc.to_string())];
+        if let Some(name) = &self.name {
+            variables.push(variable("name", name.clone()));
+        }
+        variables.push(variable("code", self.code.clone()));
+        if let Some(ap_tracking) = &self.ap_tracking {
+            variables.push(variable(
+                "ap tracking",
+                format!("group {}, offset {}", ap_tracking.group, ap_tracking.offset),
+            ));
+        }
+        variables.extend(self.cells.iter().map(|cell| {
+            let value = cell
+                .value
+                .as_ref()
+                .map_or_else(|| "<unset>".to_string(), |value| value.to_string());
+            variable(&cell.name, format!("{} ({})", value, cell.address))
+        }));
+        variables
+    }
+}
+
+pub fn scope() -> Scope {
+    Scope {
+        name: "Hints".to_string(),
+        variables_reference: HINT_REFERENCE_FLAG,
+        ..Default::default()
+    }
+}
+
+/// Hints of the current instruction, or the details of a single hint.
+pub fn variables(hints: &[HintInfo], variables_reference: i64) -> Vec<Variable> {
+    let index = variables_reference & !HINT_REFERENCE_FLAG;
+    if index > 0 {
+        return hints.get(index as usize - 1).map(HintInfo::variables).unwrap_or_default();
+    }
+    hints
+        .iter()
+        .enumerate()
+        .map(|(index, hint)| Variable {
+            name: hint.name.clone().unwrap_or_else(|| format!("hint {index}")),
+            value: hint.code.lines().next().unwrap_or_default().to_string(),
+            variables_reference: HINT_REFERENCE_FLAG | (index as i64 + 1),
+            ..Default::default()
+        })
+        .collect()
+}
+
+pub fn is_hint_reference(variables_reference: i64) -> bool {
+    variables_reference & HINT_REFERENCE_FLAG != 0
+}
+
+/// Cells relative to the registers in the code of a Cairo 1 hint, e.g. `memory[fp + -3]`,
+/// as the name, the register and the offset.
+fn register_cells(code: &str) -> Vec<(String, &str, isize)> {
+    let mut cells: Vec<(String, &str, isize)> = Vec::new();
+    for register in ["ap", "fp"] {
+        let pattern = format!("{register} + ");
+        for (start, _) in code.match_indices(&pattern) {
+            let rest = &code[start + pattern.len()..];
+            let end = rest
+                .char_indices()
+                .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-')))
+                .map_or(rest.len(), |(i, _)| i);
+            let Ok(offset) = rest[..end].parse::<isize>() else {
+                continue;
+            };
+            let name = format!("[{register} + {offset}]");
+            if !cells.iter().any(|(known, _, _)| *known == name) {
+                cells.push((name, register, offset));
+            }
+        }
+    }
+    cells
+}
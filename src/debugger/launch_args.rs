@@ -0,0 +1,320 @@
+//! Configuration of the debug session, sent by the client as the arguments of the launch
+//! or attach request.
+
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as AnyhowContext, Result, anyhow, bail};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::Context;
+use crate::debugger::context::artifact::ArtifactKind;
+use crate::debugger::fuzzer::FuzzerOptions;
+use crate::debugger::memory::parse_felt;
+use crate::debugger::recording::DEFAULT_MAX_RECORDED_STEPS;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LaunchArguments {
+    /// Path to the compiled Sierra program. If set, it must be the program being debugged.
+    pub program: Option<PathBuf>,
+    /// Name of the debugged test or function, displayed as the name of the thread.
+    pub test_name: Option<String>,
+    /// Test to run in the standalone mode, when the program contains multiple tests:
+    /// its full path or a part of it matching no other test.
+    pub test_filter: Option<String>,
+    /// Full path of the test matching [`Self::test_filter`].
+    #[serde(skip)]
+    pub selected_test: Option<String>,
+    /// Directory that relative paths are resolved against. Defaults to the current directory.
+    pub cwd: Option<PathBuf>,
+    /// Whether the execution should stop at the first statement of the program.
+    pub stop_on_entry: bool,
+    /// Whether code outside of the user's workspace should be skipped when debugging.
+    /// Off by default, as the user's code is not known outside of a Scarb project.
+    pub just_my_code: bool,
+    /// Prefixes of paths recorded in the debug info, mapped to the local ones.
+    /// Useful when the program was compiled on a different machine or in a container.
+    pub source_map: HashMap<PathBuf, PathBuf>,
+    pub value_format: ValueFormat,
+    /// Size in cells above which the segments are reported as growing suspiciously.
+    pub segment_growth_limit: Option<usize>,
+    /// Maximal number of consecutive frames of the same recursive call that are displayed.
+    pub recursion_frame_limit: Option<usize>,
+    /// Whether frames of macro expansions should be folded into their parent frames.
+    pub fold_macro_frames: bool,
+    /// Whether the steps, builtin applications and Sierra gas used by each frame
+    /// should be appended to its name in stack traces.
+    pub show_frame_resources: bool,
+    /// Root of the local corelib package, overriding the one detected with Scarb.
+    pub corelib_path: Option<PathBuf>,
+    /// Root of the Scarb workspace the program was built from, whose code is the user's code.
+    /// If set, Scarb is not queried for the project layout.
+    pub workspace_root: Option<PathBuf>,
+    /// Roots of the packages whose code is the user's code, when it is not the whole workspace.
+    /// If set, Scarb is not queried for the project layout.
+    pub package_roots: Vec<PathBuf>,
+    /// Where the output of the program is shown in the standalone mode.
+    pub console: Console,
+    /// What happens to the debuggee when the client is gone without a disconnect request,
+    /// e.g. when it crashed. By default, the same as for a disconnect request:
+    /// a launched debuggee is terminated, while an attached one waits for the next client.
+    pub on_disconnect: Option<DisconnectAction>,
+    /// Seconds after which the debugger reports that it has made no progress,
+    /// handling a request or running the program. Disabled if not set.
+    pub watchdog_timeout: Option<f64>,
+    /// Whether the execution should be paused when the program makes no progress
+    /// for [`Self::watchdog_timeout`].
+    pub pause_on_watchdog_timeout: bool,
+    /// Whether contracts called by the program should be debugged in child sessions,
+    /// if the execution tool supports it, see [`crate::Debugger::start_child_session`].
+    pub debug_contract_calls: bool,
+    /// Iteration of fuzz tests in which breakpoints apply. By default, they apply in all of them.
+    pub fuzzer: FuzzerOptions,
+    /// Whether each syscall executed by contracts should be reported in the debug console,
+    /// with the gas it used. The syscalls are recorded either way.
+    pub stream_syscalls: bool,
+    /// Whether the steps should be recorded, so that the client can step back through them.
+    pub record_execution: bool,
+    /// Most steps kept by the recording, the earliest ones being dropped once there are more.
+    pub max_recorded_steps: usize,
+    /// Arguments of the run function in the standalone mode, one for each of its parameters,
+    /// apart from the implicit ones. Each argument is a felt or an array of arguments,
+    /// e.g. `[1, ["0x2", "'abc'"]]`.
+    args: Vec<Value>,
+    /// Serialized arguments of an executable, passed as its only `Span<felt252>` parameter,
+    /// like with `scarb execute --arguments`. Cannot be used together with `args`.
+    /// For executables built by Scarb and contract classes, `args` are serialized into
+    /// the calldata instead, with arrays prefixed by their length.
+    calldata: Option<Vec<Value>>,
+    /// Parsed [`Self::args`] or [`Self::calldata`].
+    #[serde(skip)]
+    pub program_args: Vec<ProgramArg>,
+    /// All the arguments as sent by the client, including the ones of the client itself.
+    #[serde(skip)]
+    pub configuration: Map<String, Value>,
+}
+
+/// Argument of the function run in the standalone mode.
+#[derive(Clone)]
+pub enum ProgramArg {
+    Value(Felt),
+    Array(Vec<ProgramArg>),
+}
+
+/// Action taken when the client is gone without a disconnect request.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DisconnectAction {
+    /// Abort the execution.
+    Terminate,
+    /// Let the execution finish without debugging.
+    RunToCompletion,
+    /// Keep the debuggee as it is until the next client connects.
+    WaitForClient,
+}
+
+/// Destination of the output of the program run in the standalone mode.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Console {
+    /// Standard output of the debugger.
+    #[default]
+    InternalConsole,
+    /// Terminal of the client, opened with the `runInTerminal` reverse request,
+    /// so that the output is not mixed with the announcement of the debugger.
+    IntegratedTerminal,
+    /// Terminal window outside of the client, opened the same way.
+    ExternalTerminal,
+}
+
+/// Defaults for displaying values, used unless the client requests otherwise.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ValueFormat {
+    /// Whether felts should be displayed in hexadecimal.
+    pub hex: bool,
+}
+
+impl Default for LaunchArguments {
+    fn default() -> Self {
+        Self {
+            program: None,
+            test_name: None,
+            test_filter: None,
+            selected_test: None,
+            cwd: None,
+            stop_on_entry: false,
+            just_my_code: false,
+            source_map: HashMap::new(),
+            value_format: ValueFormat::default(),
+            segment_growth_limit: None,
+            recursion_frame_limit: None,
+            fold_macro_frames: false,
+            show_frame_resources: false,
+            corelib_path: None,
+            workspace_root: None,
+            package_roots: Vec::new(),
+            console: Console::default(),
+            on_disconnect: None,
+            watchdog_timeout: None,
+            pause_on_watchdog_timeout: false,
+            debug_contract_calls: false,
+            fuzzer: FuzzerOptions::default(),
+            stream_syscalls: false,
+            record_execution: false,
+            max_recorded_steps: DEFAULT_MAX_RECORDED_STEPS,
+            args: Vec::new(),
+            calldata: None,
+            program_args: Vec::new(),
+            configuration: Map::new(),
+        }
+    }
+}
+
+impl LaunchArguments {
+    /// Parses and validates the arguments. Relative paths are resolved against [`Self::cwd`].
+    pub fn parse(additional_data: Option<&Value>, ctx: &Context) -> Result<Self> {
+        let mut args: Self = match additional_data {
+            // Clients put their own fields (e.g. `type` or `name`) next to ours, so unknown
+            // fields are ignored rather than rejected.
+            Some(data) => serde_path_to_error::deserialize(data).map_err(|err| {
+                anyhow!("Invalid launch argument `{}`: {}", err.path(), err.inner())
+            })?,
+            None => Self::default(),
+        };
+        args.configuration =
+            additional_data.and_then(Value::as_object).cloned().unwrap_or_default();
+
+        let cwd = match args.cwd.take() {
+            Some(cwd) if !cwd.is_dir() => bail!("`cwd` is not a directory: {}", cwd.display()),
+            Some(cwd) => cwd,
+            None => env::current_dir().context("Failed to get the current directory")?,
+        };
+        args.program = args.program.map(|program| cwd.join(program));
+        args.corelib_path = args.corelib_path.map(|corelib_path| cwd.join(corelib_path));
+        args.workspace_root = args.workspace_root.map(|workspace_root| cwd.join(workspace_root));
+        args.package_roots = args.package_roots.iter().map(|root| cwd.join(root)).collect();
+        args.source_map =
+            args.source_map.into_iter().map(|(from, to)| (from, cwd.join(to))).collect();
+        args.cwd = Some(cwd);
+
+        args.program_args = match (&args.calldata, args.args.is_empty()) {
+            (Some(_), false) => bail!("`args` and `calldata` cannot be used together"),
+            (Some(calldata), true) => vec![ProgramArg::Array(
+                calldata
+                    .iter()
+                    .map(parse_program_arg)
+                    .collect::<Result<_>>()
+                    .context("Invalid `calldata`")?,
+            )],
+            (None, _)
+                if matches!(
+                    ctx.artifact_kind,
+                    ArtifactKind::Executable | ArtifactKind::ContractClass
+                ) =>
+            {
+                let program_args: Vec<ProgramArg> = args
+                    .args
+                    .iter()
+                    .map(parse_program_arg)
+                    .collect::<Result<_>>()
+                    .context("Invalid `args`")?;
+                let mut calldata = Vec::new();
+                serialize_program_args(&program_args, &mut calldata);
+                vec![ProgramArg::Array(calldata)]
+            }
+            (None, _) => args
+                .args
+                .iter()
+                .map(parse_program_arg)
+                .collect::<Result<_>>()
+                .context("Invalid `args`")?,
+        };
+        if ctx.artifact_kind == ArtifactKind::Executable {
+            // The wrapper of the executable function writes its result to an output array.
+            args.program_args.push(ProgramArg::Array(Vec::new()));
+        }
+
+        if let Some(test_filter) = &args.test_filter {
+            let test = ctx.find_test(test_filter).context("Invalid `testFilter`")?;
+            args.selected_test = Some(test.to_string());
+            args.test_name = args.test_name.or_else(|| Some(test.to_string()));
+        }
+
+        args.validate(ctx)?;
+        Ok(args)
+    }
+
+    fn validate(&self, ctx: &Context) -> Result<()> {
+        if let Some(program) = &self.program {
+            if !program.is_file() {
+                bail!("`program` does not exist: {}", program.display());
+            }
+            if !is_same_file(program, &ctx.sierra_path) {
+                bail!(
+                    "`program` is {}, but the debugged program is {}",
+                    program.display(),
+                    ctx.sierra_path.display()
+                );
+            }
+        }
+        if let Some(corelib_path) = &self.corelib_path
+            && !corelib_path.is_dir()
+        {
+            bail!("`corelibPath` is not a directory: {}", corelib_path.display());
+        }
+        if let Some(to) = self.source_map.values().find(|to| !to.is_dir()) {
+            bail!("`sourceMap` target is not a directory: {}", to.display());
+        }
+        if self.max_recorded_steps == 0 {
+            bail!("`maxRecordedSteps` must be positive");
+        }
+        if self.recursion_frame_limit == Some(0) {
+            bail!("`recursionFrameLimit` must be positive");
+        }
+        if let Some(watchdog_timeout) = self.watchdog_timeout
+            && !(watchdog_timeout.is_finite() && watchdog_timeout > 0.0)
+        {
+            bail!("`watchdogTimeout` must be a positive number of seconds");
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes the arguments like Serde does, with arrays prefixed by their length.
+fn serialize_program_args(args: &[ProgramArg], calldata: &mut Vec<ProgramArg>) {
+    for arg in args {
+        match arg {
+            ProgramArg::Value(_) => calldata.push(arg.clone()),
+            ProgramArg::Array(items) => {
+                calldata.push(ProgramArg::Value(Felt::from(items.len())));
+                serialize_program_args(items, calldata);
+            }
+        }
+    }
+}
+
+fn parse_program_arg(value: &Value) -> Result<ProgramArg> {
+    match value {
+        Value::Array(values) => {
+            values.iter().map(parse_program_arg).collect::<Result<_>>().map(ProgramArg::Array)
+        }
+        Value::Number(number) if number.is_u64() => {
+            parse_felt(&number.to_string()).map(ProgramArg::Value)
+        }
+        Value::String(string) => parse_felt(string).map(ProgramArg::Value),
+        _ => bail!("Expected a felt or an array, got {value}"),
+    }
+}
+
+fn is_same_file(lhs: &Path, rhs: &Path) -> bool {
+    match (lhs.canonicalize(), rhs.canonicalize()) {
+        (Ok(lhs), Ok(rhs)) => lhs == rhs,
+        _ => lhs == rhs,
+    }
+}
@@ -0,0 +1,77 @@
+//! Lifecycle of a debug session, as laid out by DAP: the client initializes the session,
+//! launches or attaches to the debuggee, configures it, e.g. sets breakpoints, and then
+//! the execution runs and stops until it ends or the client terminates it.
+
+use anyhow::{Result, bail};
+use dap::prelude::Command;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for the initialize request.
+    Uninitialized,
+    /// The capabilities were sent, waiting for the launch or attach request.
+    Initialized,
+    /// Launched or attached, the client configures the session until the configurationDone
+    /// request, after which the execution starts.
+    Configuring,
+    Running,
+    Stopped,
+    /// The execution is aborted on the client's request before its next instruction.
+    Terminating,
+}
+
+impl Phase {
+    /// Whether the execution may start, or has started already.
+    pub fn is_configuration_done(self) -> bool {
+        matches!(self, Self::Running | Self::Stopped | Self::Terminating)
+    }
+
+    /// Rejects requests that the client may not send in this phase.
+    pub fn check_request(self, command: &Command) -> Result<()> {
+        match (command, self) {
+            (Command::Disconnect(_) | Command::Terminate(_), _) => Ok(()),
+            (Command::Initialize(_), Self::Uninitialized) => Ok(()),
+            (Command::Initialize(_), _) => bail!("The session is already initialized"),
+            (_, Self::Uninitialized) => bail!("The initialize request must be sent first"),
+
+            (Command::Launch(_) | Command::Attach(_), Self::Initialized) => Ok(()),
+            (Command::Launch(_) | Command::Attach(_), _) => {
+                bail!("The debuggee is already launched")
+            }
+
+            // Clients may configure the session, e.g. set breakpoints, as soon as it is initialized,
+            // but the execution cannot start without the launch configuration.
+            (Command::ConfigurationDone, Self::Configuring) => Ok(()),
+            (Command::ConfigurationDone, Self::Initialized) => {
+                bail!("The launch or attach request must be sent first")
+            }
+            (Command::ConfigurationDone, _) => bail!("The configuration is already done"),
+
+            (Command::Pause(_) | Command::Continue(_), Self::Running | Self::Stopped) => Ok(()),
+            (
+                Command::Next(_)
+                | Command::StepIn(_)
+                | Command::StepOut(_)
+                | Command::StepBack(_)
+                | Command::ReverseContinue(_),
+                Self::Stopped,
+            ) => Ok(()),
+            (
+                Command::Pause(_)
+                | Command::Continue(_)
+                | Command::Next(_)
+                | Command::StepIn(_)
+                | Command::StepOut(_)
+                | Command::StepBack(_)
+                | Command::ReverseContinue(_),
+                _,
+            ) => match self {
+                Self::Running => bail!("The execution must be stopped first"),
+                Self::Terminating => bail!("The execution is being terminated"),
+                _ => bail!("The execution has not started yet"),
+            },
+
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, anyhow, bail, ensure};
+use cairo_lang_casm::operand::{
+    BinOpOperand, CellRef, DerefOrImmediate, Operation, Register, ResOperand,
+};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::FunctionParam;
+use crate::debugger::vm_access::{Address, Value, VmAccess};
+
+/// Maximal length of a Cairo short string.
+const SHORT_STRING_MAX_LEN: usize = 31;
+
+/// Prime of the field of felts, which parsed values must be lower than, rather than be silently
+/// reduced modulo it.
+const PRIME_DEC: &str =
+    "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+const PRIME_HEX: &str = "800000000000011000000000000000000000000000000000000000000000001";
+
+/// Number of cells of a single dict access entry: key, previous value and new value.
+const DICT_ACCESS_SIZE: usize = 3;
+
+/// Single entry of a dict access log, as written to the dict segment by `dict` libfuncs.
+pub struct DictAccess {
+    pub key: Option<Value>,
+    pub prev_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+/// Tracks segments that grew beyond a configured number of cells.
+pub struct SegmentGrowthMonitor {
+    limit: usize,
+    /// Segments that already exceeded the limit - each segment is reported only once.
+    reported_segments: HashSet<usize>,
+}
+
+impl SegmentGrowthMonitor {
+    pub fn new(limit: usize) -> Self {
+        Self { limit, reported_segments: HashSet::new() }
+    }
+
+    /// Returns `(segment_index, size)` pairs of segments that exceeded the limit since the last check.
+    pub fn check(&mut self, vm: &mut dyn VmAccess) -> Vec<(usize, usize)> {
+        vm.segment_sizes()
+            .into_iter()
+            .enumerate()
+            .filter(|&(segment_index, size)| {
+                size > self.limit && self.reported_segments.insert(segment_index)
+            })
+            .collect()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+/// Returns the number of cells never accessed below the current sizes of the segments,
+/// apart from the ones of builtins, like the VM counts them at the end of the run.
+pub fn memory_holes(vm: &mut dyn VmAccess) -> usize {
+    let builtin_segments = vm.builtin_segments();
+    vm.segment_sizes()
+        .into_iter()
+        .enumerate()
+        .filter(|(segment_index, _)| !builtin_segments.contains(segment_index))
+        .map(|(segment_index, size)| size.saturating_sub(vm.accessed_cells(segment_index)))
+        .sum()
+}
+
+/// Parses an address in the `segment:offset` format.
+/// A bare `segment` is accepted too, and is equal to `segment:0`.
+pub fn parse_address(address: &str) -> Result<Address> {
+    let (segment, offset) = address.trim().split_once(':').unwrap_or((address.trim(), "0"));
+    let segment_index =
+        segment.trim().parse().map_err(|_| anyhow!("Invalid segment index: {segment}"))?;
+    let offset = offset.trim().parse().map_err(|_| anyhow!("Invalid offset: {offset}"))?;
+
+    Ok(Address { segment_index, offset })
+}
+
+/// Reads the full access log of the dict whose segment contains `dict_ptr`.
+///
+/// If `dict_ptr` points at the start of the segment, the whole segment is read - otherwise
+/// only the accesses written before `dict_ptr` are returned, which is what a dict pointer
+/// held by the program at a given point of the execution refers to.
+pub fn read_dict_accesses(vm: &mut dyn VmAccess, dict_ptr: Address) -> Result<Vec<DictAccess>> {
+    let Ok(segment_index) = usize::try_from(dict_ptr.segment_index) else {
+        bail!("Temporary segments cannot back a dict: {dict_ptr}");
+    };
+    let Some(&segment_size) = vm.segment_sizes().get(segment_index) else {
+        bail!("Segment {segment_index} does not exist");
+    };
+
+    let end = if dict_ptr.offset == 0 { segment_size } else { dict_ptr.offset };
+    if !end.is_multiple_of(DICT_ACCESS_SIZE) {
+        bail!("{dict_ptr} is not aligned to dict accesses of size {DICT_ACCESS_SIZE}");
+    }
+
+    let segment_start = Address { segment_index: dict_ptr.segment_index, offset: 0 };
+    let accesses = vm
+        .read_range(segment_start, end)
+        .chunks(DICT_ACCESS_SIZE)
+        .map(|access| {
+            let cell = |i: usize| access.get(i).cloned().flatten();
+            DictAccess { key: cell(0), prev_value: cell(1), new_value: cell(2) }
+        })
+        .collect();
+
+    Ok(accesses)
+}
+
+/// Parses a felt given as a decimal number, a `0x`-prefixed hex number or a short string
+/// in single quotes, e.g. `'hello'`.
+pub fn parse_felt(value: &str) -> Result<Felt> {
+    let value = value.trim();
+
+    if let Some(short_string) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        if !short_string.is_ascii() || short_string.len() > SHORT_STRING_MAX_LEN {
+            bail!("Short string must consist of at most {SHORT_STRING_MAX_LEN} ASCII characters");
+        }
+        return Ok(Felt::from_bytes_be_slice(short_string.as_bytes()));
+    }
+
+    // Negative values are counted from the prime, e.g. `-1` is the largest felt.
+    let (negative, magnitude) = match value.strip_prefix('-') {
+        Some(magnitude) => (true, magnitude),
+        None => (false, value),
+    };
+    let felt = match magnitude.strip_prefix("0x") {
+        Some(digits) => {
+            let felt =
+                Felt::from_hex(magnitude).map_err(|_| anyhow!("Invalid hex felt: {value}"))?;
+            ensure!(
+                is_below_prime(&digits.to_ascii_lowercase(), PRIME_HEX),
+                "{value} is out of the range of felts"
+            );
+            felt
+        }
+        None => {
+            let felt =
+                Felt::from_dec_str(magnitude).map_err(|_| anyhow!("Invalid felt: {value}"))?;
+            ensure!(is_below_prime(magnitude, PRIME_DEC), "{value} is out of the range of felts");
+            felt
+        }
+    };
+    Ok(if negative { -felt } else { felt })
+}
+
+/// Compares valid digits of a number with the digits of the prime in the same base.
+fn is_below_prime(digits: &str, prime: &str) -> bool {
+    let digits = digits.trim_start_matches('0');
+    digits.len() < prime.len() || (digits.len() == prime.len() && digits < prime)
+}
+
+/// Returns addresses of all cells that hold `value`.
+/// If `segment_index` is `None`, the whole memory is searched.
+pub fn search(
+    vm: &mut dyn VmAccess,
+    value: Felt,
+    segment_index: Option<usize>,
+) -> Result<Vec<Address>> {
+    let sizes = vm.segment_sizes();
+    let segments: Vec<(usize, usize)> = match segment_index {
+        Some(segment_index) => {
+            let Some(&size) = sizes.get(segment_index) else {
+                bail!("Segment {segment_index} does not exist");
+            };
+            vec![(segment_index, size)]
+        }
+        None => sizes.into_iter().enumerate().collect(),
+    };
+
+    let needle = Value::Felt(value);
+    let mut matches = Vec::new();
+    for (segment_index, size) in segments {
+        let segment_start = Address { segment_index: segment_index as isize, offset: 0 };
+        let segment_matches = vm
+            .read_range(segment_start, size)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.as_ref() == Some(&needle))
+            .map(|(offset, _)| Address { segment_index: segment_index as isize, offset });
+        matches.extend(segment_matches);
+    }
+
+    Ok(matches)
+}
+
+/// Returns the address of the cell, relative to the current registers.
+pub fn cell_address(vm: &dyn VmAccess, cell: &CellRef) -> Option<Address> {
+    let base = match cell.register {
+        Register::AP => vm.ap(),
+        Register::FP => vm.fp(),
+    };
+    base.add_signed(isize::from(cell.offset))
+}
+
+/// Reads a pointer passed to a hint, either as a cell or as a cell shifted by a constant.
+pub fn pointer_operand(vm: &dyn VmAccess, operand: &ResOperand) -> Option<Address> {
+    match operand {
+        ResOperand::Deref(cell) => vm.read_pointer(cell_address(vm, cell)?),
+        ResOperand::BinOp(BinOpOperand {
+            op: Operation::Add,
+            a,
+            b: DerefOrImmediate::Immediate(offset),
+        }) => {
+            let pointer = vm.read_pointer(cell_address(vm, a)?)?;
+            pointer + usize::try_from(&offset.value).ok()?
+        }
+        _ => None,
+    }
+}
+
+/// Reads the felts between the pointers passed to a hint, e.g. the input of a cheatcode.
+pub fn read_felts(vm: &dyn VmAccess, start: &ResOperand, end: &ResOperand) -> Option<Vec<Felt>> {
+    let start = pointer_operand(vm, start)?;
+    let end = pointer_operand(vm, end)?;
+    vm.read_felts(start, (end - start)?)
+}
+
+/// Reads the array or span whose start and end pointers are stored at `address`.
+pub fn read_array(vm: &dyn VmAccess, address: Address) -> Option<Vec<Felt>> {
+    let start = vm.read_pointer(address)?;
+    let end = vm.read_pointer((address + 1)?)?;
+    vm.read_felts(start, (end - start)?)
+}
+
+/// Address of the argument at `index` of the function whose frame starts at `fp`.
+pub fn argument_address(fp: Address, params: &[FunctionParam], index: usize) -> Option<Address> {
+    // Cells of the arguments are followed by the caller's fp and the return pc.
+    let arguments_size: usize = params.iter().map(|param| param.size).sum();
+    let offset: usize = params.get(..index)?.iter().map(|param| param.size).sum();
+    fp - (arguments_size + 2 - offset)
+}
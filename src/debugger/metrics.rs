@@ -0,0 +1,128 @@
+//! Counters of the performance of the debugger in the session, dumped by the `:metrics` custom
+//! request, so that a session that feels slow can be told apart into the cost of the handlers,
+//! of the sync with the VM at each step, and of the execution itself.
+
+use std::collections::BTreeMap;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+
+/// One in this many syncs is timed, along with the execution of the step after it,
+/// so that reading the clock does not add up to the cost of every step.
+const SAMPLING_INTERVAL: u64 = 64;
+
+#[derive(Default)]
+pub struct RuntimeMetrics {
+    /// Latency of the handlers of the requests, by the command of the request.
+    requests: BTreeMap<String, Latency>,
+    /// Number of steps executed while the execution was running, not counting the ones it
+    /// stopped at, the ones executed without debugging, nor the ones skipped while nothing
+    /// needed the attention of the debugger.
+    steps: u64,
+    /// Number of the [`Self::steps`] whose sync and execution were timed.
+    sampled_steps: u64,
+    /// Time spent executing the instructions of [`Self::sampled_steps`], between the syncs.
+    execution: Duration,
+    /// Time spent by the debugger syncing with the VM before [`Self::sampled_steps`].
+    sync: Duration,
+    /// Number of the syncs started, which chooses the ones timed.
+    syncs: u64,
+    /// Whether the previous sync let the execution go on, so that the current one ends a step.
+    step_started: bool,
+    /// End and duration of the previous sync, if it was timed.
+    sample: Option<(Instant, Duration)>,
+    /// Whether the execution stopped during the current sync, which then measures the user.
+    stopped_in_sync: bool,
+}
+
+#[derive(Default)]
+struct Latency {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl RuntimeMetrics {
+    pub fn record_request(&mut self, command: &str, latency: Duration) {
+        let stats = self.requests.entry(command.to_string()).or_default();
+        stats.count += 1;
+        stats.total += latency;
+        stats.max = stats.max.max(latency);
+    }
+
+    /// Called at the start of the sync before each step, which ends the execution
+    /// of the previous one, unless steps were skipped since the previous sync.
+    /// Returns the start of the sync if it is timed.
+    pub fn start_sync(&mut self, steps_skipped: bool) -> Option<Instant> {
+        let sample = self.sample.take();
+        if mem::take(&mut self.step_started) && !steps_skipped {
+            self.steps += 1;
+            if let Some((sync_end, sync)) = sample {
+                self.execution += sync_end.elapsed();
+                self.sync += sync;
+                self.sampled_steps += 1;
+            }
+        }
+        self.stopped_in_sync = false;
+        self.syncs += 1;
+        self.syncs.is_multiple_of(SAMPLING_INTERVAL).then(Instant::now)
+    }
+
+    pub fn record_stop(&mut self) {
+        self.stopped_in_sync = true;
+    }
+
+    /// Called once the sync that started at `started`, if timed, let the execution go on.
+    pub fn finish_sync(&mut self, started: Option<Instant>) {
+        if self.stopped_in_sync {
+            return;
+        }
+        self.step_started = true;
+        if let Some(started) = started {
+            let now = Instant::now();
+            self.sample = Some((now, now - started));
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let requests: serde_json::Map<String, Value> = self
+            .requests
+            .iter()
+            .map(|(command, latency)| {
+                let mean = latency.total / u32::try_from(latency.count).unwrap_or(u32::MAX);
+                let stats = json!({
+                    "count": latency.count,
+                    "meanMs": as_millis(mean),
+                    "maxMs": as_millis(latency.max),
+                    "totalMs": as_millis(latency.total),
+                });
+                (command.clone(), stats)
+            })
+            .collect();
+        let sampled = self.execution + self.sync;
+        let steps_per_second =
+            (!sampled.is_zero()).then(|| self.sampled_steps as f64 / sampled.as_secs_f64());
+        let sync_overhead =
+            (!sampled.is_zero()).then(|| self.sync.as_secs_f64() / sampled.as_secs_f64());
+        // Times of all the steps are estimated from the sampled ones.
+        let scale = if self.sampled_steps == 0 {
+            0.0
+        } else {
+            self.steps as f64 / self.sampled_steps as f64
+        };
+        json!({
+            "requests": requests,
+            "steps": self.steps,
+            "sampledSteps": self.sampled_steps,
+            "stepsPerSecond": steps_per_second,
+            "executionMs": as_millis(self.execution) * scale,
+            "syncMs": as_millis(self.sync) * scale,
+            "syncOverhead": sync_overhead,
+        })
+    }
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
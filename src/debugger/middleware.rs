@@ -0,0 +1,76 @@
+//! Concerns shared by the handling of all requests, wrapped around [`handler::handle_request`]:
+//! logging correlated by the sequence number of the request, timing, cancellation,
+//! validation of the state that the request needs and containing panics of the handlers.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+use anyhow::{Result, anyhow, ensure};
+use dap::prelude::{Command, Request};
+use tracing::{error, trace};
+
+use crate::debugger::command_name;
+use crate::debugger::context::registry::ContextRegistry;
+use crate::debugger::errors::DebuggerError;
+use crate::debugger::handler::{self, HandlerResponse};
+use crate::debugger::state::State;
+use crate::debugger::vm_access::VmAccess;
+
+/// Message of the error response to a cancelled request, as required by DAP.
+const CANCELLED_MESSAGE: &str = "cancelled";
+
+/// Handles the request, unless the client cancelled it while it was waiting to be handled.
+/// `vm` is `None` if the request is processed before the execution has started.
+pub fn handle_request(
+    request: &Request,
+    cancelled: bool,
+    state: &mut State,
+    contexts: &mut ContextRegistry,
+    vm: Option<&mut dyn VmAccess>,
+) -> Result<HandlerResponse> {
+    let command = command_name(&request.command);
+    if cancelled {
+        trace!("Request {} ({command}) was cancelled", request.seq);
+        return Err(anyhow!(CANCELLED_MESSAGE));
+    }
+
+    trace!("Handling request {} ({command})", request.seq);
+    let started = Instant::now();
+    let result = check_preconditions(&request.command, state, vm.is_some())
+        .and_then(|()| contain_panics(|| handler::handle_request(request, state, contexts, vm)));
+    let latency = started.elapsed();
+    state.metrics.record_request(&command, latency);
+    match &result {
+        Ok(_) => trace!("Request {} ({command}) handled in {latency:?}", request.seq),
+        // The client decides whether the session can go on without the request.
+        Err(err) => error!("Request {} ({command}) failed: {err:#}", request.seq),
+    }
+    result
+}
+
+/// Rejects requests sent out of the phase of the session they are meant for, see [`crate::debugger::lifecycle::Phase`].
+/// Moving back through the recording needs the VM, whose registers are moved.
+fn check_preconditions(command: &Command, state: &State, has_vm: bool) -> Result<()> {
+    state.phase().check_request(command)?;
+    if let Command::StepBack(_) | Command::ReverseContinue(_) = command {
+        ensure!(has_vm, "The execution has not started yet");
+    }
+    Ok(())
+}
+
+/// A bug in handling a single request, e.g. evaluating an unusual expression,
+/// should not abort the whole run, so it fails only the request.
+fn contain_panics(handle: impl FnOnce() -> Result<HandlerResponse>) -> Result<HandlerResponse> {
+    panic::catch_unwind(AssertUnwindSafe(handle)).unwrap_or_else(|payload| {
+        Err(DebuggerError::Internal { details: panic_message(&*payload).to_string() }.into())
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
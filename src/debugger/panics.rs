@@ -0,0 +1,177 @@
+//! Panics of the debugged program, which are expected in `#[should_panic]` tests.
+
+use cairo_lang_sierra::program::StatementIdx;
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::Context;
+use crate::debugger::vm_access::VmAccess;
+use crate::debugger::{abi, memory};
+
+/// Filter of the exception breakpoint stopping where the program starts panicking.
+pub const PANIC_FILTER: &str = "panic";
+
+/// Functions of the corelib through which programs panic, with the data of the panic
+/// passed as their only argument, if any.
+const PANIC_FUNCTIONS: &[&str] = &[
+    "core::panic_with_felt252",
+    "core::panic_with_const_felt252",
+    "core::panics::panic_with_byte_array",
+    "core::panics::panic",
+];
+
+/// Prepended to the panic data of `ByteArray` messages, e.g. from `panic!("...")`.
+const BYTE_ARRAY_MAGIC: &str = "0x46a6158a16a947e5916b2a2ca68501a45e93d7110e81aa2d6438b1c57c879a3";
+
+/// Panic expected by a `#[should_panic]` test, see [`crate::Debugger::expect_panic`].
+pub enum ExpectedPanic {
+    Any,
+    /// Exact panic data, e.g. a single short string for `expected: 'error'`.
+    Data(Vec<Felt>),
+}
+
+pub struct Panic {
+    /// Data of the panic, unknown if the panic function was inlined.
+    pub data: Option<Vec<Felt>>,
+    /// Whether the panic is expected by the test.
+    pub expected: bool,
+}
+
+/// Outcome of the execution, with regard to the panics expected by the test.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Succeeded,
+    PanickedAsExpected,
+    Panicked,
+    /// A `#[should_panic]` test did not panic.
+    DidNotPanic,
+}
+
+impl ExpectedPanic {
+    pub fn matches(&self, data: Option<&[Felt]>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Data(expected) => data == Some(expected.as_slice()),
+        }
+    }
+}
+
+impl Panic {
+    pub fn describe(&self) -> String {
+        let data = self.data.as_ref().map_or_else(
+            || "unknown data".to_string(),
+            |data| {
+                let data = data.iter().map(Felt::to_hex_string).collect::<Vec<_>>().join(", ");
+                format!("data [{data}]")
+            },
+        );
+        let expected = if self.expected { " as expected" } else { "" };
+        format!("Panicked{expected} with {data}")
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "data": self.data.as_ref().map(|data| data.iter().map(Felt::to_hex_string).collect::<Vec<_>>()),
+            "expected": self.expected,
+        })
+    }
+}
+
+impl Outcome {
+    pub fn new(panic: Option<&Panic>, expected_panic: Option<&ExpectedPanic>) -> Self {
+        match (panic, expected_panic) {
+            (None, None) => Self::Succeeded,
+            (None, Some(_)) => Self::DidNotPanic,
+            (Some(panic), _) if panic.expected => Self::PanickedAsExpected,
+            (Some(_), _) => Self::Panicked,
+        }
+    }
+
+    /// Exit code reported to the client. Expected panics are not failures.
+    pub fn exit_code(self) -> i64 {
+        match self {
+            Self::Succeeded | Self::PanickedAsExpected => 0,
+            Self::Panicked | Self::DidNotPanic => 1,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Succeeded => "Execution succeeded",
+            Self::PanickedAsExpected => "Execution panicked as expected",
+            Self::Panicked => "Execution failed with a panic",
+            Self::DidNotPanic => "Execution failed: a panic was expected",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::PanickedAsExpected => "panickedAsExpected",
+            Self::Panicked => "panicked",
+            Self::DidNotPanic => "didNotPanic",
+        }
+    }
+}
+
+/// Returns the panic function the statement belongs to, possibly inlined.
+pub fn panic_function(ctx: &Context, statement_idx: StatementIdx) -> Option<&'static str> {
+    let names = ctx.function_names_for_statement_idx(statement_idx)?;
+    PANIC_FUNCTIONS
+        .iter()
+        .find(|panic_function| names.iter().any(|name| name.0 == **panic_function))
+        .copied()
+}
+
+/// Reads the data of the panic from the argument of the panic function, if the statement
+/// belongs to its own frame. Data of `ByteArray` messages is serialized like in the panic.
+pub fn read_panic_data(
+    vm: &dyn VmAccess,
+    ctx: &Context,
+    statement_idx: StatementIdx,
+    panic_function: &str,
+) -> Option<Vec<Felt>> {
+    if ctx.sierra_function_name_for_statement_idx(statement_idx)? != panic_function {
+        return None;
+    }
+    let params = ctx.function_params_for_statement_idx(statement_idx)?;
+    let argument = memory::argument_address(vm.fp(), params, 0)?;
+    match panic_function {
+        "core::panic_with_felt252" => Some(vec![vm.read_felt(argument)?]),
+        "core::panics::panic" => memory::read_array(vm, argument),
+        "core::panics::panic_with_byte_array" => {
+            // The snapshot of the `ByteArray` consists of the array of full words,
+            // the pending word and its length.
+            let words = memory::read_array(vm, argument)?;
+            let pending_word = vm.read_felt((argument + 2)?)?;
+            let pending_word_len = vm.read_felt((argument + 3)?)?;
+            let mut data = vec![Felt::from_hex(BYTE_ARRAY_MAGIC).ok()?, Felt::from(words.len())];
+            data.extend(words);
+            data.extend([pending_word, pending_word_len]);
+            Some(data)
+        }
+        _ => None,
+    }
+}
+
+/// Decodes the panic data into a readable message: the text of a `ByteArray` message,
+/// or the felts, shown as short strings where they are ones, e.g. `'Insufficient balance'`.
+pub fn decode_panic_data(data: &[Felt]) -> String {
+    if let Some((magic, byte_array)) = data.split_first()
+        && Felt::from_hex(BYTE_ARRAY_MAGIC).is_ok_and(|byte_array_magic| *magic == byte_array_magic)
+        && let Some(message) = abi::decode_byte_array(&mut byte_array.iter())
+    {
+        return message;
+    }
+    data.iter().map(display_panic_felt).collect::<Vec<_>>().join(", ")
+}
+
+fn display_panic_felt(felt: &Felt) -> String {
+    let bytes = felt.to_bytes_be();
+    let text = bytes.iter().skip_while(|byte| **byte == 0).copied().collect::<Vec<_>>();
+    if !text.is_empty() && text.iter().all(|byte| byte.is_ascii_graphic() || *byte == b' ') {
+        format!("'{}'", String::from_utf8_lossy(&text))
+    } else {
+        felt.to_hex_string()
+    }
+}
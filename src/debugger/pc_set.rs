@@ -0,0 +1,73 @@
+//! Sets of the pcs of a program checked at each step, e.g. the ones breakpoints are set at.
+
+use std::ops::Range;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Bit vector over the pcs of a program, so that checking a pc is a single load.
+/// Its last pc stands for all the later ones too, since the last statement
+/// of the program extends to its end.
+#[derive(Default)]
+pub struct PcSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl PcSet {
+    /// Empty set of the pcs up to `last_pc`, usually the start of the last statement.
+    pub fn new(last_pc: usize) -> Self {
+        let len = last_pc + 1;
+        Self { words: vec![0; len.div_ceil(WORD_BITS)], len }
+    }
+
+    /// Whether the set covers no pcs, e.g. since no breakpoints are set.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, pcs: Range<usize>) {
+        let pcs = pcs.start.min(self.len)..pcs.end.min(self.len);
+        for pc in pcs {
+            self.words[pc / WORD_BITS] |= 1 << (pc % WORD_BITS);
+        }
+    }
+
+    pub fn contains(&self, pc: usize) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let pc = pc.min(self.len - 1);
+        self.words[pc / WORD_BITS] & (1 << (pc % WORD_BITS)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PcSet;
+
+    #[test]
+    fn contains_inserted_pcs_across_words() {
+        let mut pcs = PcSet::new(200);
+        pcs.insert(62..66);
+        assert!(!pcs.contains(61));
+        assert!((62..66).all(|pc| pcs.contains(pc)));
+        assert!(!pcs.contains(66));
+        assert!(!pcs.is_empty());
+    }
+
+    #[test]
+    fn last_pc_stands_for_the_later_ones() {
+        let mut pcs = PcSet::new(10);
+        pcs.insert(10..usize::MAX);
+        assert!(pcs.contains(10));
+        assert!(pcs.contains(1000));
+        assert!(!pcs.contains(9));
+    }
+
+    #[test]
+    fn default_set_is_empty() {
+        let pcs = PcSet::default();
+        assert!(pcs.is_empty());
+        assert!(!pcs.contains(0));
+    }
+}
@@ -0,0 +1,303 @@
+//! Finished executions loaded from the trace and memory files written by cairo-vm, e.g. in
+//! proof-mode runs, which are navigated like a recording, without executing anything.
+//!
+//! Both files hold relocated values, with the program segment starting at address 1 and the other
+//! segments following it. The memory is loaded both into the program segment, shifted by its base,
+//! and into the execution segment as it is, so that the relocated registers address it directly.
+//! Relocated pointers are plain felts, apart from the ones saved in the frames of the trace,
+//! which are restored so that the call stack can be rebuilt.
+//!
+//! Recorded executions are exported in the same format, see [`export`], with the inputs needed
+//! to replay them saved next to the trace, see [`crate::debugger::replay_metadata`].
+//!
+//! Traces saved by snforge for cairo-profiler hold the registers of each step, but not
+//! the memory. Only the frames are rebuilt from the trace then, so values of variables are unknown.
+
+// Finished executions are loaded by the standalone runners only.
+#![cfg_attr(not(feature = "standalone"), allow(dead_code))]
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, ensure};
+use cairo_annotations::trace_data::VersionedCallTrace;
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use cairo_vm::vm::vm_core::VirtualMachine;
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::recording::{self, ExecutionRecording};
+use crate::debugger::vm_access::{Address, Value, VmAccess};
+
+/// Address the program segment is relocated to.
+const PROGRAM_BASE: usize = 1;
+
+/// Each entry of the trace holds ap, fp and pc, as 64-bit little-endian integers.
+const TRACE_ENTRY_SIZE: usize = 3 * 8;
+
+/// Each entry of the memory holds the address, as a 64-bit little-endian integer,
+/// and the value, as a 256-bit little-endian integer.
+const MEMORY_ENTRY_SIZE: usize = 8 + 32;
+
+/// Registers of a step, with the pc unrelocated and ap and fp relocated.
+pub struct TraceEntry {
+    pub pc: usize,
+    pub ap: usize,
+    pub fp: usize,
+}
+
+/// Execution of a test saved by snforge with `--save-trace-data`.
+pub struct ProfilerTrace {
+    /// Sierra program of the test, as saved by snforge.
+    pub sierra_path: PathBuf,
+    /// Name of the test, if snforge saved it.
+    pub function: Option<String>,
+    /// Whether the execution started from the entry code calling the function.
+    pub run_with_call_header: bool,
+    pub trace: Vec<TraceEntry>,
+}
+
+pub fn read_trace(path: &Path) -> Result<Vec<TraceEntry>> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    ensure!(
+        !bytes.is_empty() && bytes.len() % TRACE_ENTRY_SIZE == 0,
+        "{} is not a trace file",
+        path.display()
+    );
+
+    bytes
+        .chunks_exact(TRACE_ENTRY_SIZE)
+        .map(|entry| {
+            let [ap, fp, pc] = [0, 1, 2].map(|index| read_u64(&entry[index * 8..]));
+            let pc = pc
+                .checked_sub(PROGRAM_BASE)
+                .ok_or_else(|| anyhow!("Invalid pc in {}: {pc}", path.display()))?;
+            Ok(TraceEntry { pc, ap, fp })
+        })
+        .collect()
+}
+
+/// Reads the trace of the test itself, calls to contracts made by the test having traces of their
+/// own, which are not replayed.
+pub fn read_profiler_trace(path: &Path) -> Result<ProfilerTrace> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let VersionedCallTrace::V1(call_trace) = serde_json::from_str(&contents)
+        .with_context(|| format!("{} is not a trace saved by snforge", path.display()))?;
+    let execution_info = call_trace.cairo_execution_info.ok_or_else(|| {
+        anyhow!("{} has no VM trace, save it with a newer version of snforge", path.display())
+    })?;
+
+    let trace = execution_info
+        .casm_level_info
+        .vm_trace
+        .iter()
+        .map(|entry| {
+            let pc = entry
+                .pc
+                .checked_sub(PROGRAM_BASE)
+                .ok_or_else(|| anyhow!("Invalid pc in {}: {}", path.display(), entry.pc))?;
+            Ok(TraceEntry { pc, ap: entry.ap, fp: entry.fp })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ensure!(!trace.is_empty(), "The trace in {} is empty", path.display());
+
+    Ok(ProfilerTrace {
+        sierra_path: execution_info.source_sierra_path.into(),
+        function: call_trace.entry_point.function_name,
+        run_with_call_header: execution_info.casm_level_info.run_with_call_header,
+        trace,
+    })
+}
+
+/// Builds a VM holding only the frames of the calls in the trace, which is all that can be told
+/// about the memory from the registers. Its registers are set to the last step of the trace.
+pub fn rebuild_frames(trace: &[TraceEntry]) -> Result<VirtualMachine> {
+    let last_step = trace.last().ok_or_else(|| anyhow!("The trace is empty"))?;
+    let mut vm = VirtualMachine::new(false, false);
+    let program_segment = vm.add_memory_segment();
+    let execution_segment = vm.add_memory_segment();
+
+    // Caller steps of the frames not returned from yet, the innermost being the last one.
+    let mut callers: Vec<&TraceEntry> = Vec::new();
+    for (entry, next) in trace.iter().zip(&trace[1..]) {
+        if next.fp != entry.fp && next.fp == entry.ap + 2 {
+            vm.insert_value(
+                Relocatable::from((execution_segment.segment_index, next.fp - 2)),
+                Relocatable::from((execution_segment.segment_index, entry.fp)),
+            )?;
+            callers.push(entry);
+        } else if let Some(caller) = callers.last()
+            && next.fp == caller.fp
+        {
+            // The return pc is the one the execution returned to.
+            vm.insert_value(
+                Relocatable::from((execution_segment.segment_index, entry.fp - 1)),
+                Relocatable::from((program_segment.segment_index, next.pc)),
+            )?;
+            callers.pop();
+        }
+    }
+    // Calls of compiled code are `call rel` instructions with an immediate, two cells long.
+    for caller in callers {
+        let callee_fp = caller.ap + 2;
+        vm.insert_value(
+            Relocatable::from((execution_segment.segment_index, callee_fp - 1)),
+            Relocatable::from((program_segment.segment_index, caller.pc + 2)),
+        )?;
+    }
+
+    set_registers(&mut vm, program_segment, last_step);
+    Ok(vm)
+}
+
+/// Loads the memory into a new VM, whose registers are set to the last step of the trace.
+pub fn load_vm(memory_path: &Path, trace: &[TraceEntry]) -> Result<VirtualMachine> {
+    let bytes = fs::read(memory_path)
+        .with_context(|| format!("Failed to read {}", memory_path.display()))?;
+    ensure!(bytes.len() % MEMORY_ENTRY_SIZE == 0, "{} is not a memory file", memory_path.display());
+    let last_step = trace.last().ok_or_else(|| anyhow!("The trace is empty"))?;
+
+    // Each frame starts with the fp of the caller and the return pc.
+    let frame_pointers: HashSet<usize> = trace.iter().map(|entry| entry.fp).collect();
+    let mut vm = VirtualMachine::new(false, false);
+    let program_segment = vm.add_memory_segment();
+    let execution_segment = vm.add_memory_segment();
+
+    for entry in bytes.chunks_exact(MEMORY_ENTRY_SIZE) {
+        let address = read_u64(entry);
+        let value = Felt::from_bytes_le(entry[8..].try_into()?);
+        if let Some(offset) = address.checked_sub(PROGRAM_BASE) {
+            vm.insert_value((program_segment + offset)?, value)?;
+        }
+
+        let pointer = u64::try_from(value).ok().map(|pointer| pointer as usize);
+        let value = match pointer {
+            Some(caller_fp) if frame_pointers.contains(&(address + 2)) => MaybeRelocatable::from(
+                Relocatable::from((execution_segment.segment_index, caller_fp)),
+            ),
+            Some(return_pc)
+                if frame_pointers.contains(&(address + 1)) && return_pc >= PROGRAM_BASE =>
+            {
+                MaybeRelocatable::from(Relocatable::from((
+                    program_segment.segment_index,
+                    return_pc - PROGRAM_BASE,
+                )))
+            }
+            _ => MaybeRelocatable::from(value),
+        };
+        vm.insert_value(Relocatable::from((execution_segment.segment_index, address)), value)?;
+    }
+
+    set_registers(&mut vm, program_segment, last_step);
+    Ok(vm)
+}
+
+/// First step at which two finished executions of the same program differ.
+pub struct Divergence {
+    pub step: usize,
+    pub reason: String,
+    /// Pcs of both executions at the step, unless an execution ended before it.
+    pub pcs: [Option<usize>; 2],
+}
+
+/// Compares two finished executions step by step, by their registers and by the values
+/// of the operands of their instructions, which include the values the instructions wrote.
+pub fn first_divergence(traces: [&[TraceEntry]; 2], vms: [&dyn VmAccess; 2]) -> Option<Divergence> {
+    let steps = traces[0].len().max(traces[1].len());
+    (0..steps).find_map(|step| {
+        let entries = traces.map(|trace| trace.get(step));
+        let reason = match entries {
+            [Some(first), Some(second)] if first.pc != second.pc => "the pcs differ".to_string(),
+            [Some(first), Some(second)] if (first.ap, first.fp) != (second.ap, second.fp) => {
+                "ap or fp differs".to_string()
+            }
+            [Some(first), Some(second)] => {
+                let values = [(vms[0], first), (vms[1], second)]
+                    .map(|(vm, entry)| recording::operand_values(vm, entry));
+                if values[0] == values[1] {
+                    return None;
+                }
+                let [first, second] = values.map(|values| {
+                    values
+                        .map(|value| {
+                            value.map_or_else(|| "?".to_string(), |value| value.to_string())
+                        })
+                        .join(", ")
+                });
+                format!("the operands differ: [{first}] and [{second}]")
+            }
+            [None, _] => "the first execution ended".to_string(),
+            [_, None] => "the second execution ended".to_string(),
+        };
+        Some(Divergence { step, reason, pcs: entries.map(|entry| entry.map(|entry| entry.pc)) })
+    })
+}
+
+/// Writes the recorded steps to `trace_path`, and the memory as it is now to `memory_path`,
+/// with the segments relocated one after another, like cairo-vm does.
+/// Returns the number of steps written.
+pub fn export(
+    vm: &mut dyn VmAccess,
+    recording: &ExecutionRecording,
+    trace_path: &Path,
+    memory_path: Option<&Path>,
+) -> Result<usize> {
+    let sizes = vm.segment_sizes();
+    let bases: Vec<usize> = sizes
+        .iter()
+        .scan(PROGRAM_BASE, |base, size| {
+            let segment_base = *base;
+            *base += size;
+            Some(segment_base)
+        })
+        .collect();
+    let relocate = |address: Address| {
+        usize::try_from(address.segment_index)
+            .ok()
+            .and_then(|segment_index| bases.get(segment_index))
+            .map(|base| base + address.offset)
+            .ok_or_else(|| anyhow!("Cannot relocate {address}"))
+    };
+
+    let execution_base = relocate(Address::from((vm.ap().segment_index, 0)))?;
+    let trace = recording.trace(vm, execution_base)?;
+    let mut bytes = Vec::with_capacity(trace.len() * TRACE_ENTRY_SIZE);
+    for entry in &trace {
+        for value in [entry.ap, entry.fp, entry.pc + PROGRAM_BASE] {
+            bytes.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+    fs::write(trace_path, bytes)
+        .with_context(|| format!("Failed to write {}", trace_path.display()))?;
+
+    let Some(memory_path) = memory_path else {
+        return Ok(trace.len());
+    };
+    let mut bytes = Vec::new();
+    for (segment_index, (base, size)) in bases.iter().zip(&sizes).enumerate() {
+        let segment_start = Address::from((segment_index as isize, 0));
+        for (offset, cell) in vm.read_range(segment_start, *size).into_iter().enumerate() {
+            let value = match cell {
+                None => continue,
+                Some(Value::Felt(value)) => value,
+                Some(Value::Address(address)) => Felt::from(relocate(address)?),
+            };
+            bytes.extend_from_slice(&((base + offset) as u64).to_le_bytes());
+            bytes.extend_from_slice(&value.to_bytes_le());
+        }
+    }
+    fs::write(memory_path, bytes)
+        .with_context(|| format!("Failed to write {}", memory_path.display()))?;
+    Ok(trace.len())
+}
+
+fn set_registers(vm: &mut dyn VmAccess, program_segment: Relocatable, entry: &TraceEntry) {
+    vm.set_registers(Address::from((program_segment.segment_index, entry.pc)), entry.ap, entry.fp);
+}
+
+fn read_u64(bytes: &[u8]) -> usize {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word) as usize
+}
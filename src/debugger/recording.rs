@@ -0,0 +1,538 @@
+//! Recording of the execution, which lets the client step back through the steps executed
+//! so far, enabled with the `recordExecution` launch argument.
+//!
+//! The VM cannot be rewound, but its memory is write-once, so every value seen at a past step
+//! is still there. Moving back to a step is a matter of moving the registers back, which is done
+//! on the VM itself while the execution is stopped, and undone before it resumes.
+//! The cells written by each step are recorded too, to tell the values that did not exist
+//! yet at the step apart from the ones that did, and to find the step that wrote a cell.
+//!
+//! Only the pc of each step is recorded, which is enough to map the steps to the code.
+//! All registers are recorded in checkpoints every [`CHECKPOINT_INTERVAL`] steps, and the ones
+//! of the steps in between are recomputed by re-executing the instructions from the nearest
+//! checkpoint. Since every operand the instructions read is still in memory, this needs neither
+//! copies of the memory nor the hints, whose effects are already there.
+
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use anyhow::{Result, anyhow};
+use cairo_lang_sierra::program::StatementIdx;
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::call_stack::CallStack;
+use crate::debugger::context::Context;
+use crate::debugger::hints::HintInfo;
+use crate::debugger::post_mortem::TraceEntry;
+use crate::debugger::vm_access::{Address, Value, VmAccess};
+
+/// Number of steps between the checkpoints, i.e. the most instructions re-executed
+/// to move the execution to any recorded step.
+const CHECKPOINT_INTERVAL: usize = 1024;
+
+/// Steps kept unless the client sets `maxRecordedSteps`, taking about 40 MB.
+pub const DEFAULT_MAX_RECORDED_STEPS: usize = 10_000_000;
+
+/// Parts of an encoded instruction, see the Cairo whitepaper.
+const OFFSET_BITS: u32 = 16;
+const OFFSET_BIAS: isize = 1 << 15;
+const FLAGS_OFFSET: u32 = 48;
+const DST_REG_BIT: u32 = 0;
+const OP0_REG_BIT: u32 = 1;
+const OP1_IMM_BIT: u32 = 2;
+const OP1_FP_BIT: u32 = 3;
+const OP1_AP_BIT: u32 = 4;
+const RES_ADD_BIT: u32 = 5;
+const RES_MUL_BIT: u32 = 6;
+const PC_JUMP_ABS_BIT: u32 = 7;
+const PC_JUMP_REL_BIT: u32 = 8;
+const PC_JNZ_BIT: u32 = 9;
+const AP_ADD_BIT: u32 = 10;
+const AP_ADD1_BIT: u32 = 11;
+const OPCODE_CALL_BIT: u32 = 12;
+const OPCODE_RET_BIT: u32 = 13;
+
+pub struct ExecutionRecording {
+    /// Pc offset of each kept step, in the order of execution. The last one is the step
+    /// the execution is stopped at, unless it was moved back.
+    pcs: VecDeque<u32>,
+    /// Registers of every [`Self::checkpoint_interval`]-th kept step, starting from the first one.
+    checkpoints: VecDeque<Registers>,
+    /// [`CHECKPOINT_INTERVAL`], unless the registers of every step are known up front.
+    checkpoint_interval: usize,
+    /// Index of the first kept step, the earlier ones being dropped once there are more than
+    /// [`Self::max_steps`].
+    first_step: usize,
+    max_steps: usize,
+    /// Cells written by the steps, with what wrote them, in the order of execution. Cells written
+    /// by hints are recorded only if the hints refer to them, see [`Self::record_hint_writes`].
+    writes: VecDeque<(usize, Address, Writer)>,
+    /// Cells the step being executed may write, which were not written before it.
+    pending_writes: Vec<(Address, Writer)>,
+    /// Step the registers were moved back to, with the state of the execution to return to.
+    past: Option<(usize, PresentState)>,
+}
+
+/// Offsets of the registers, whose segments never change within a program.
+#[derive(Clone, Copy)]
+struct Registers {
+    pc: usize,
+    ap: usize,
+    fp: usize,
+}
+
+/// What wrote a cell at a step.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Writer {
+    Instruction,
+    /// Hint executed before the instruction.
+    Hint,
+}
+
+/// Cell whose values are looked up across the recorded steps, see
+/// [`ExecutionRecording::history`].
+#[derive(Clone, Copy)]
+pub enum TrackedCell {
+    Fixed(Address),
+    /// Cell at the offset from ap, which is a different one at each step.
+    Ap(isize),
+    /// Cell at the offset from fp, which is the same one within a frame.
+    Fp(isize),
+}
+
+/// State of the execution at the last recorded step, replaced while the execution is moved back.
+pub struct PresentState {
+    pub statement_idx: StatementIdx,
+    pub call_stack: CallStack,
+    registers: Registers,
+}
+
+impl ExecutionRecording {
+    /// Keeps at most `max_steps` steps, rounded up to a multiple of the checkpoint interval.
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            pcs: VecDeque::new(),
+            checkpoints: VecDeque::new(),
+            checkpoint_interval: CHECKPOINT_INTERVAL,
+            first_step: 0,
+            max_steps: max_steps.max(1),
+            writes: VecDeque::new(),
+            pending_writes: Vec::new(),
+            past: None,
+        }
+    }
+
+    /// Recording of the steps of a finished execution, the last one being the present.
+    /// Every step is a checkpoint, so nothing is re-executed, as the memory may be incomplete.
+    pub fn from_trace(trace: &[TraceEntry]) -> Result<Self> {
+        let mut recording = Self::new(trace.len());
+        recording.checkpoint_interval = 1;
+        for (step, entry) in trace.iter().enumerate() {
+            let pc = u32::try_from(entry.pc)
+                .map_err(|_| anyhow!("Pc of step {step} is out of range: {}", entry.pc))?;
+            recording.checkpoints.push_back(Registers { pc: entry.pc, ap: entry.ap, fp: entry.fp });
+            recording.pcs.push_back(pc);
+        }
+        Ok(recording)
+    }
+
+    /// Takes the recorded steps, leaving an empty recording with the same limit,
+    /// e.g. for a called contract.
+    pub fn take(&mut self) -> Self {
+        mem::replace(self, Self::new(self.max_steps))
+    }
+
+    /// Records the pc of the step about to be executed, and its registers if it starts
+    /// a checkpoint, as well as the operand cells its instruction may write.
+    pub fn record_step(&mut self, vm: &dyn VmAccess) {
+        let pc = vm.pc();
+        // Same as in `State::update_state` - such steps are not a part of the program.
+        let (0, Ok(pc_offset)) = (pc.segment_index, u32::try_from(pc.offset)) else {
+            return;
+        };
+        let registers = registers_of(vm);
+        if self.pcs.len() % self.checkpoint_interval == 0 {
+            if self.pcs.len() >= self.max_steps {
+                self.drop_oldest_checkpoint();
+            }
+            self.checkpoints.push_back(registers);
+        }
+        self.pcs.push_back(pc_offset);
+
+        self.pending_writes = Instruction::at(vm, registers)
+            .map(|instruction| instruction.operand_cells(vm, registers))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|cell| vm.read(*cell).is_none())
+            .map(|cell| (cell, Writer::Instruction))
+            .collect();
+    }
+
+    /// Records the cells the hints of the step refer to which are not written yet, as the ones
+    /// the hints may write. Writes of hints through pointers are not recorded.
+    pub fn record_hint_writes(&mut self, hints: &[HintInfo]) {
+        let hint_cells = hints
+            .iter()
+            .flat_map(|hint| &hint.cells)
+            .filter(|cell| cell.value.is_none())
+            .map(|cell| cell.address);
+        for cell in hint_cells {
+            // Hints are executed first, so the instruction only reads what they wrote.
+            self.pending_writes.retain(|(pending, _)| *pending != cell);
+            self.pending_writes.push((cell, Writer::Hint));
+        }
+    }
+
+    /// Records which of the pending cells were written by the step that was just executed.
+    pub fn finish_step(&mut self, vm: &dyn VmAccess) {
+        let Some(step) = self.last_step() else {
+            return;
+        };
+        for (cell, writer) in mem::take(&mut self.pending_writes) {
+            if vm.read(cell).is_some() {
+                self.writes.push_back((step, cell, writer));
+            }
+        }
+    }
+
+    fn drop_oldest_checkpoint(&mut self) {
+        self.checkpoints.pop_front();
+        self.pcs.drain(..self.checkpoint_interval);
+        self.first_step += self.checkpoint_interval;
+        while self.writes.front().is_some_and(|(step, ..)| *step < self.first_step) {
+            self.writes.pop_front();
+        }
+    }
+
+    /// Forgets the recorded steps, e.g. once the program is executed again.
+    pub fn clear(&mut self) {
+        *self = Self::new(self.max_steps);
+    }
+
+    /// Index of the step the execution is stopped at, `None` if nothing was recorded yet.
+    pub fn position(&self) -> Option<usize> {
+        match &self.past {
+            Some((step, _)) => Some(*step),
+            None => self.last_step(),
+        }
+    }
+
+    /// Index of the earliest step kept, which is the first one of the program unless
+    /// the execution went past the limit of the steps.
+    pub fn first_step(&self) -> usize {
+        self.first_step
+    }
+
+    /// Index of the last recorded step, the one the execution actually is at.
+    pub fn last_step(&self) -> Option<usize> {
+        if self.pcs.is_empty() { None } else { Some(self.first_step + self.pcs.len() - 1) }
+    }
+
+    pub fn is_in_past(&self) -> bool {
+        self.past.is_some()
+    }
+
+    pub fn statement_idx(&self, step: usize, ctx: &Context) -> StatementIdx {
+        ctx.statement_idx_for_pc(self.pc(step))
+    }
+
+    pub fn pc(&self, step: usize) -> usize {
+        self.pcs[step - self.first_step] as usize
+    }
+
+    /// Whether the cell was written by a step executed after the current position.
+    pub fn is_written_later(&self, cell: Address) -> bool {
+        let Some(position) = self.position() else {
+            return false;
+        };
+        self.writes
+            .iter()
+            .rev()
+            .take_while(|(step, ..)| *step > position)
+            .any(|(_, written, _)| *written == cell)
+    }
+
+    /// Step that wrote the cell, and what wrote it, unless it was written before the kept steps
+    /// or by a hint that does not refer to it. Memory is write-once, so there is one such step.
+    pub fn last_write(&self, cell: Address) -> Option<(usize, Writer)> {
+        self.writes
+            .iter()
+            .rev()
+            .find(|(_, written, _)| *written == cell)
+            .map(|(step, _, writer)| (*step, *writer))
+    }
+
+    /// Moves the registers of `vm` back to `step`, re-executing the instructions from the nearest
+    /// checkpoint. `present` is called the first time the execution leaves the last step,
+    /// to keep its state until it returns there.
+    pub fn move_to_past(
+        &mut self,
+        step: usize,
+        vm: &mut dyn VmAccess,
+        present: impl FnOnce() -> (StatementIdx, CallStack),
+    ) -> Result<()> {
+        let registers = self.registers_at(step, vm)?;
+        let present = match self.past.take() {
+            Some((_, present_state)) => present_state,
+            None => {
+                let (statement_idx, call_stack) = present();
+                PresentState { statement_idx, call_stack, registers: registers_of(vm) }
+            }
+        };
+        set_registers(vm, registers);
+        self.past = Some((step, present));
+        Ok(())
+    }
+
+    /// Moves the registers of `vm` back to the last step. Returns the state of the execution
+    /// there, unless it was not moved back.
+    pub fn return_to_present(&mut self, vm: &mut dyn VmAccess) -> Option<PresentState> {
+        let (_, present) = self.past.take()?;
+        set_registers(vm, present.registers);
+        Some(present)
+    }
+
+    /// Registers of all kept steps, re-executed one after another, with ap and fp relocated
+    /// by `execution_base`, see [`crate::debugger::post_mortem`].
+    pub fn trace(&self, vm: &dyn VmAccess, execution_base: usize) -> Result<Vec<TraceEntry>> {
+        Ok(self
+            .all_registers(vm)?
+            .into_iter()
+            .map(|registers| TraceEntry {
+                pc: registers.pc,
+                ap: execution_base + registers.ap,
+                fp: execution_base + registers.fp,
+            })
+            .collect())
+    }
+
+    /// Values of `cell` at the kept steps it changed at, starting from the first one, `None`
+    /// standing for a cell not written yet. Cells written by hints that do not refer to them
+    /// are not recorded, so they are seen as written since the first step.
+    pub fn history(
+        &self,
+        vm: &dyn VmAccess,
+        cell: TrackedCell,
+    ) -> Result<Vec<(usize, Option<Value>)>> {
+        let write_steps: HashMap<Address, usize> =
+            self.writes.iter().map(|(step, written, _)| (*written, *step)).collect();
+        let segment_index = vm.ap().segment_index;
+
+        let mut history: Vec<(usize, Option<Value>)> = Vec::new();
+        for (index, registers) in self.all_registers(vm)?.into_iter().enumerate() {
+            let step = self.first_step + index;
+            let relative = |register: usize, offset| {
+                register
+                    .checked_add_signed(offset)
+                    .map(|offset| Address::from((segment_index, offset)))
+            };
+            let address = match cell {
+                TrackedCell::Fixed(address) => Some(address),
+                TrackedCell::Ap(offset) => relative(registers.ap, offset),
+                TrackedCell::Fp(offset) => relative(registers.fp, offset),
+            };
+            // The step is the state before its instruction is executed.
+            let value = address
+                .filter(|address| write_steps.get(address).is_none_or(|written| *written < step))
+                .and_then(|address| vm.read(address));
+            if history.last().is_none_or(|(_, last)| *last != value) {
+                history.push((step, value));
+            }
+        }
+        Ok(history)
+    }
+
+    /// Call stack of the execution at the last step, while it is moved back.
+    pub fn present_call_stack(&self) -> Option<&CallStack> {
+        self.past.as_ref().map(|(_, present)| &present.call_stack)
+    }
+
+    /// Registers of all kept steps, re-executed one after another.
+    fn all_registers(&self, vm: &dyn VmAccess) -> Result<Vec<Registers>> {
+        let mut all_registers: Vec<Registers> = Vec::with_capacity(self.pcs.len());
+        for index in 0..self.pcs.len() {
+            let registers = match all_registers.last() {
+                Some(previous) if index % self.checkpoint_interval != 0 => {
+                    self.re_execute(index, vm, *previous)?
+                }
+                _ => self.checkpoints[index / self.checkpoint_interval],
+            };
+            all_registers.push(registers);
+        }
+        Ok(all_registers)
+    }
+
+    /// Registers at `step`, re-executed from the checkpoint before it. Each re-executed step
+    /// is checked against the recorded pc, in case a hint moved the registers.
+    fn registers_at(&self, step: usize, vm: &dyn VmAccess) -> Result<Registers> {
+        let index = step - self.first_step;
+        let checkpoint = index / self.checkpoint_interval;
+        let mut registers = self.checkpoints[checkpoint];
+        for index in checkpoint * self.checkpoint_interval + 1..=index {
+            registers = self.re_execute(index, vm, registers)?;
+        }
+        Ok(registers)
+    }
+
+    /// Registers of the kept step at `index`, re-executed from the ones of the step before it.
+    fn re_execute(
+        &self,
+        index: usize,
+        vm: &dyn VmAccess,
+        previous: Registers,
+    ) -> Result<Registers> {
+        Instruction::at(vm, previous)
+            .and_then(|instruction| instruction.next_registers(vm, previous))
+            .filter(|registers| registers.pc == self.pcs[index] as usize)
+            .ok_or_else(|| {
+                anyhow!("Failed to re-execute step {} of the recording", self.first_step + index)
+            })
+    }
+}
+
+/// Values of the destination and the operands of the instruction of a step of a finished
+/// execution, loaded like in [`crate::debugger::post_mortem`].
+pub fn operand_values(vm: &dyn VmAccess, entry: &TraceEntry) -> [Option<Value>; 3] {
+    let registers = Registers { pc: entry.pc, ap: entry.ap, fp: entry.fp };
+    match Instruction::at(vm, registers) {
+        Some(instruction) => {
+            instruction.operand_cells(vm, registers).map(|cell| cell.and_then(|cell| vm.read(cell)))
+        }
+        None => [None, None, None],
+    }
+}
+
+fn registers_of(vm: &dyn VmAccess) -> Registers {
+    Registers { pc: vm.pc().offset, ap: vm.ap().offset, fp: vm.fp().offset }
+}
+
+fn set_registers(vm: &mut dyn VmAccess, registers: Registers) {
+    vm.set_registers(Address::from((0, registers.pc)), registers.ap, registers.fp);
+}
+
+/// Encoded instruction, whose flags are read with [`Self::flag`].
+struct Instruction(u64);
+
+impl Instruction {
+    fn at(vm: &dyn VmAccess, registers: Registers) -> Option<Self> {
+        let encoded = vm.read_felt(Address::from((0, registers.pc)))?;
+        u64::try_from(encoded).ok().map(Self)
+    }
+
+    fn offset(&self, index: u32) -> isize {
+        ((self.0 >> (index * OFFSET_BITS)) & 0xffff) as isize - OFFSET_BIAS
+    }
+
+    fn flag(&self, bit: u32) -> bool {
+        (self.0 >> (FLAGS_OFFSET + bit)) & 1 == 1
+    }
+
+    fn size(&self) -> usize {
+        if self.flag(OP1_IMM_BIT) { 2 } else { 1 }
+    }
+
+    /// Cells of the destination and the operands, which are the ones the instruction may write.
+    /// The second operand relative to the first one is unknown if the first one is unknown.
+    fn operand_cells(&self, vm: &dyn VmAccess, registers: Registers) -> [Option<Address>; 3] {
+        let segment = vm.ap().segment_index;
+        let register = |is_fp: bool| if is_fp { registers.fp } else { registers.ap };
+        let cell = |segment: isize, base: usize, offset: isize| {
+            Some(Address::from((segment, base.checked_add_signed(offset)?)))
+        };
+
+        let dst = cell(segment, register(self.flag(DST_REG_BIT)), self.offset(0));
+        let op0 = cell(segment, register(self.flag(OP0_REG_BIT)), self.offset(1));
+        let op1 = if self.flag(OP1_IMM_BIT) {
+            cell(0, registers.pc, 1)
+        } else if self.flag(OP1_FP_BIT) {
+            cell(segment, registers.fp, self.offset(2))
+        } else if self.flag(OP1_AP_BIT) {
+            cell(segment, registers.ap, self.offset(2))
+        } else {
+            op0.and_then(|op0| vm.read_pointer(op0))
+                .and_then(|base| cell(base.segment_index, base.offset, self.offset(2)))
+        };
+        [dst, op0, op1]
+    }
+
+    /// Registers after the instruction, computed from the operands in memory.
+    fn next_registers(&self, vm: &dyn VmAccess, registers: Registers) -> Option<Registers> {
+        let [dst, op0, op1] =
+            self.operand_cells(vm, registers).map(|cell| cell.and_then(|cell| vm.read(cell)));
+        let res = || {
+            let op1 = op1.clone()?;
+            if self.flag(RES_ADD_BIT) {
+                add(op0.clone()?, op1)
+            } else if self.flag(RES_MUL_BIT) {
+                match (op0.clone()?, op1) {
+                    (Value::Felt(lhs), Value::Felt(rhs)) => Some(Value::Felt(lhs * rhs)),
+                    _ => None,
+                }
+            } else {
+                Some(op1)
+            }
+        };
+
+        let pc = if self.flag(PC_JUMP_ABS_BIT) {
+            address_offset(&res()?)?
+        } else if self.flag(PC_JUMP_REL_BIT) {
+            registers.pc.checked_add_signed(signed(&res()?)?)?
+        } else if self.flag(PC_JNZ_BIT) {
+            if *dst.as_ref()? == Value::Felt(Felt::ZERO) {
+                registers.pc + self.size()
+            } else {
+                registers.pc.checked_add_signed(signed(op1.as_ref()?)?)?
+            }
+        } else {
+            registers.pc + self.size()
+        };
+        let ap = if self.flag(OPCODE_CALL_BIT) {
+            registers.ap + 2
+        } else if self.flag(AP_ADD_BIT) {
+            registers.ap.checked_add_signed(signed(&res()?)?)?
+        } else if self.flag(AP_ADD1_BIT) {
+            registers.ap + 1
+        } else {
+            registers.ap
+        };
+        let fp = if self.flag(OPCODE_CALL_BIT) {
+            registers.ap + 2
+        } else if self.flag(OPCODE_RET_BIT) {
+            address_offset(dst.as_ref()?)?
+        } else {
+            registers.fp
+        };
+        Some(Registers { pc, ap, fp })
+    }
+}
+
+fn add(lhs: Value, rhs: Value) -> Option<Value> {
+    match (lhs, rhs) {
+        (Value::Felt(lhs), Value::Felt(rhs)) => Some(Value::Felt(lhs + rhs)),
+        (Value::Address(base), offset @ Value::Felt(_))
+        | (offset @ Value::Felt(_), Value::Address(base)) => {
+            let offset = base.offset.checked_add_signed(signed(&offset)?)?;
+            Some(Value::Address(Address::from((base.segment_index, offset))))
+        }
+        _ => None,
+    }
+}
+
+/// Felts used as offsets are negative if they are closer to the prime than to zero.
+fn signed(value: &Value) -> Option<isize> {
+    let Value::Felt(felt) = value else {
+        return None;
+    };
+    match u64::try_from(*felt) {
+        Ok(value) => isize::try_from(value).ok(),
+        Err(_) => isize::try_from(u64::try_from(-*felt).ok()?).ok().map(|value| -value),
+    }
+}
+
+fn address_offset(value: &Value) -> Option<usize> {
+    match value {
+        Value::Address(address) => Some(address.offset),
+        Value::Felt(_) => None,
+    }
+}
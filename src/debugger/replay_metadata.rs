@@ -0,0 +1,158 @@
+//! Inputs of an execution that its trace does not contain, e.g. the seed of the fuzzer or
+//! the forked block, saved next to the exported trace so that a post-mortem session can tell
+//! whether it debugs the same execution as the one that failed.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result, bail};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::debugger::context::Context;
+use crate::debugger::state::State;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayMetadata {
+    /// SHA-256 of the program file, which has to be the same to replay the execution.
+    pub program_checksum: String,
+    pub test_name: Option<String>,
+    pub fuzz_iteration: Option<FuzzMetadata>,
+    pub fork: Option<ForkMetadata>,
+    /// Block and transaction seen by the contract, including the values set by cheatcodes,
+    /// see [`crate::debugger::execution_info::ExecutionInfo::to_json`].
+    pub execution_info: Option<Value>,
+}
+
+#[derive(Deserialize)]
+pub struct FuzzMetadata {
+    pub iteration: usize,
+    pub seed: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkMetadata {
+    pub url: String,
+    pub block_number: u64,
+}
+
+impl ReplayMetadata {
+    /// Collects the inputs of the execution of the program `ctx` describes.
+    pub fn collect(state: &State, ctx: &Context) -> Result<Self> {
+        Ok(Self {
+            program_checksum: program_checksum(&ctx.sierra_path)?,
+            test_name: Some(state.thread_name()).filter(|name| !name.is_empty()),
+            fuzz_iteration: state.fuzz_iteration.as_ref().map(|iteration| FuzzMetadata {
+                iteration: iteration.number,
+                seed: iteration.seed,
+            }),
+            fork: state.fork_origin.as_ref().map(|origin| ForkMetadata {
+                url: origin.url.clone(),
+                block_number: origin.block_number,
+            }),
+            execution_info: state.call_trace.active_execution_info().map(|info| info.to_json()),
+        })
+    }
+
+    /// Saves the metadata next to the trace exported to `trace_path`.
+    pub fn write(&self, trace_path: &Path) -> Result<()> {
+        let path = path_for(trace_path);
+        fs::write(&path, serde_json::to_string_pretty(&self.to_json())?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Reads the metadata saved next to the trace at `trace_path`, if there is any,
+    /// which is not the case for traces not exported by the debugger.
+    #[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+    pub fn read(trace_path: &Path) -> Result<Option<Self>> {
+        let path = path_for(trace_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&content)
+            .map(Some)
+            .with_context(|| format!("Invalid replay metadata in {}", path.display()))
+    }
+
+    /// Fails if the program at `program_path` is not the one that was executed,
+    /// in which case the trace would silently point at the wrong code.
+    #[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+    pub fn verify(&self, program_path: &Path) -> Result<()> {
+        if program_checksum(program_path)? != self.program_checksum {
+            bail!(
+                "{} is not the program the trace was recorded with, it may have been compiled \
+                 again since",
+                program_path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// Human-readable summary, e.g. `Recorded execution of test_transfer, fuzz iteration 3
+    /// with seed 42, forked from https://rpc.example at block 100`.
+    pub fn describe(&self) -> String {
+        let mut description = match &self.test_name {
+            Some(test) => format!("Recorded execution of {test}"),
+            None => "Recorded execution".to_string(),
+        };
+        if let Some(FuzzMetadata { iteration, seed }) = &self.fuzz_iteration {
+            description += &format!(", fuzz iteration {iteration} with seed {seed}");
+        }
+        if let Some(ForkMetadata { url, block_number }) = &self.fork {
+            description += &format!(", forked from {url} at block {block_number}");
+        }
+        description
+    }
+
+    /// Describes each input the two executions did not share, with its values in both of them,
+    /// e.g. `testName: "test_a" != "test_b"`.
+    #[cfg_attr(not(feature = "standalone"), allow(dead_code))]
+    pub fn differences(&self, other: &Self) -> Vec<String> {
+        let (first, second) = (self.to_json(), other.to_json());
+        let (Value::Object(first), Value::Object(second)) = (first, second) else {
+            return Vec::new();
+        };
+        first
+            .iter()
+            .filter(|(key, value)| second.get(*key) != Some(*value))
+            .map(|(key, value)| {
+                format!("{key}: {value} != {}", second.get(key).unwrap_or(&Value::Null))
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "programChecksum": self.program_checksum,
+            "testName": self.test_name,
+            "fuzzIteration": self.fuzz_iteration.as_ref().map(|fuzz| json!({
+                "iteration": fuzz.iteration,
+                "seed": fuzz.seed,
+            })),
+            "fork": self.fork.as_ref().map(|fork| json!({
+                "url": fork.url,
+                "blockNumber": fork.block_number,
+            })),
+            "executionInfo": self.execution_info,
+        })
+    }
+}
+
+/// The metadata of `trace.bin` is saved in `trace.bin.json`.
+fn path_for(trace_path: &Path) -> PathBuf {
+    let mut path = OsString::from(trace_path);
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+fn program_checksum(path: &Path) -> Result<String> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let digest = Sha256::digest(content);
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
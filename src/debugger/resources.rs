@@ -0,0 +1,125 @@
+//! Resources used by the frames of the call stack, which make the debugger a lightweight profiler.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Value, json};
+
+use crate::debugger::context::FunctionParam;
+use crate::debugger::memory;
+use crate::debugger::syscall_trace::SyscallTrace;
+use crate::debugger::vm_access::{Address, VmAccess};
+
+/// Resources used by the execution at some point, subtracted from the ones at a later point
+/// to get the resources used in between.
+#[derive(Clone)]
+pub struct ResourceSnapshot {
+    steps: usize,
+    /// Number of applications of each builtin, by the name of the builtin.
+    builtins: BTreeMap<String, usize>,
+    /// Remaining Sierra gas, known only where it is passed to a function.
+    gas: Option<u64>,
+}
+
+/// Point of the execution marked with the `:resources mark` custom request,
+/// which the resources used later are compared with.
+pub struct ResourceMark {
+    snapshot: ResourceSnapshot,
+    memory_holes: usize,
+    /// Number of syscalls executed before the mark.
+    syscalls: usize,
+}
+
+/// Resources used by a frame, including the frames of the functions it called.
+pub struct FrameResources {
+    pub steps: usize,
+    pub builtins: BTreeMap<String, usize>,
+    pub gas_consumed: Option<u64>,
+}
+
+impl ResourceSnapshot {
+    pub fn take(vm: &dyn VmAccess, gas: Option<u64>) -> Self {
+        Self { steps: vm.current_step(), builtins: vm.builtin_instances(), gas }
+    }
+
+    /// Snapshot at the entry of the function whose frame starts at `fp`, with the gas passed to it.
+    pub fn at_function_entry(vm: &dyn VmAccess, fp: Address, params: &[FunctionParam]) -> Self {
+        Self::take(vm, read_gas_argument(vm, fp, params))
+    }
+
+    /// Resources used from this snapshot until the `later` one.
+    pub fn usage_until(&self, later: &Self) -> FrameResources {
+        let builtins = later
+            .builtins
+            .iter()
+            .map(|(name, instances)| {
+                let before = self.builtins.get(name).copied().unwrap_or_default();
+                (name.clone(), instances.saturating_sub(before))
+            })
+            .filter(|(_, applications)| *applications > 0)
+            .collect();
+        FrameResources {
+            steps: later.steps.saturating_sub(self.steps),
+            builtins,
+            gas_consumed: self
+                .gas
+                .zip(later.gas)
+                .map(|(before, after)| before.saturating_sub(after)),
+        }
+    }
+
+    pub fn gas(&self) -> Option<u64> {
+        self.gas
+    }
+}
+
+impl ResourceMark {
+    pub fn take(vm: &mut dyn VmAccess, syscall_trace: &SyscallTrace) -> Self {
+        Self {
+            snapshot: ResourceSnapshot::take(vm, None),
+            memory_holes: memory::memory_holes(vm),
+            syscalls: syscall_trace.count(),
+        }
+    }
+
+    /// Resources used since the mark, with the change in the number of memory holes,
+    /// which may be filled later, and the syscalls executed in between.
+    pub fn diff(&self, vm: &mut dyn VmAccess, syscall_trace: &SyscallTrace) -> Value {
+        let usage = self.snapshot.usage_until(&ResourceSnapshot::take(vm, None));
+        let memory_holes = memory::memory_holes(vm) as i64 - self.memory_holes as i64;
+        json!({
+            "steps": usage.steps,
+            "builtins": usage.builtins,
+            "memoryHoles": memory_holes,
+            "syscalls": syscall_trace.counts_since(self.syscalls),
+        })
+    }
+}
+
+impl FrameResources {
+    /// Short summary appended to frame names, e.g. `120 steps, 4500 gas, range_check: 3`.
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("{} steps", self.steps)];
+        if let Some(gas_consumed) = self.gas_consumed {
+            parts.push(format!("{gas_consumed} gas"));
+        }
+        parts.extend(
+            self.builtins.iter().map(|(name, applications)| format!("{name}: {applications}")),
+        );
+        parts.join(", ")
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "steps": self.steps,
+            "builtins": self.builtins,
+            "gasConsumed": self.gas_consumed,
+        })
+    }
+}
+
+/// Reads the gas argument of the function whose frame starts at `fp`, if it takes one.
+fn read_gas_argument(vm: &dyn VmAccess, fp: Address, params: &[FunctionParam]) -> Option<u64> {
+    let index = params.iter().position(|param| param.is_gas)?;
+    let gas = vm.read_felt(memory::argument_address(fp, params, index)?)?;
+    u64::try_from(gas).ok()
+}
@@ -0,0 +1,76 @@
+//! Settings the client can change mid-session with the `:settings` custom request, e.g. from
+//! toggles of the editor, without launching the debuggee again. They start as set by the launch
+//! arguments, if these have them, and only the ones present in the request are changed.
+
+use std::sync::OnceLock;
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::debugger::launch_args::ValueFormat;
+use crate::debugger::state::State;
+
+/// Sets the filter of the logs of the process, e.g. `debug` or `cairo_debugger=trace`.
+type LogLevelHandler = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+/// Logs are global to the process, like the `tracing` subscriber, so the handler is shared
+/// by all the debuggers of the process.
+static LOG_LEVEL_HANDLER: OnceLock<LogLevelHandler> = OnceLock::new();
+
+/// Lets clients change the log level with the `logLevel` setting. Called by whoever installed
+/// the `tracing` subscriber, i.e. the `cairo-debugger` binary or the execution tool, at most once.
+pub fn set_log_level_handler(handler: impl Fn(&str) -> Result<()> + Send + Sync + 'static) {
+    if LOG_LEVEL_HANDLER.set(Box::new(handler)).is_err() {
+        warn!("The log level handler is already set");
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct SettingsUpdate {
+    log_level: Option<String>,
+    just_my_code: Option<bool>,
+    value_format: Option<ValueFormat>,
+    /// Prefixes of the paths of functions that stepping does not stop in, e.g. `core::`,
+    /// on top of the code outside of the user's workspace if `justMyCode` is set.
+    skip_functions: Option<Vec<String>>,
+}
+
+impl SettingsUpdate {
+    pub fn parse(args: &str) -> Result<Self> {
+        serde_json::from_str(args).map_err(|err| {
+            anyhow!(
+                "Usage: :settings {{\"justMyCode\": false, \"logLevel\": \"debug\", ...}}: {err}"
+            )
+        })
+    }
+
+    /// Applies the settings and returns the names of the ones changed.
+    pub fn apply(self, state: &mut State) -> Result<Vec<&'static str>> {
+        // Applied first, since it is the only one that can fail.
+        let mut changed = Vec::new();
+        if let Some(log_level) = self.log_level {
+            let handler = LOG_LEVEL_HANDLER.get().ok_or_else(|| {
+                anyhow!("The log level is set by the execution tool, which does not change it")
+            })?;
+            handler(&log_level)?;
+            changed.push("logLevel");
+        }
+        if let Some(just_my_code) = self.just_my_code {
+            // Breakpoints outside of the user's code that were rejected are verified
+            // by the caller, while the ones already verified stay set.
+            state.just_my_code = just_my_code;
+            changed.push("justMyCode");
+        }
+        if let Some(value_format) = self.value_format {
+            state.call_stack.value_format = value_format;
+            changed.push("valueFormat");
+        }
+        if let Some(skip_functions) = self.skip_functions {
+            state.skip_functions = skip_functions;
+            changed.push("skipFunctions");
+        }
+        Ok(changed)
+    }
+}
@@ -1,44 +1,439 @@
 use std::cmp::PartialEq;
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use anyhow::{Result, anyhow, bail, ensure};
 use cairo_annotations::annotations::coverage::CodeLocation;
+use cairo_annotations::annotations::profiler::FunctionName;
 use cairo_lang_sierra::program::StatementIdx;
-use cairo_vm::vm::vm_core::VirtualMachine;
-use dap::types::StackFrame;
+use dap::types::{Breakpoint, Source};
+use serde_json::{Map, Value};
 use tracing::{debug, trace};
 
 use crate::debugger::call_stack::CallStack;
-use crate::debugger::context::{Context, Line};
+use crate::debugger::call_trace::CallTrace;
+use crate::debugger::context::registry::ContextRegistry;
+use crate::debugger::context::{Context, Line, StatementKind};
+use crate::debugger::deployments::{Deployments, PendingDeclaration};
+use crate::debugger::errors::DebuggerError;
+use crate::debugger::fork::ForkOrigin;
+use crate::debugger::fuzzer::{FuzzIteration, FuzzerOptions};
 use crate::debugger::handler::StepAction;
+use crate::debugger::hints::{HintBreakpoint, HintInfo};
+use crate::debugger::launch_args::{Console, DisconnectAction, ProgramArg};
+use crate::debugger::lifecycle::Phase;
+use crate::debugger::memory::SegmentGrowthMonitor;
+use crate::debugger::metrics::RuntimeMetrics;
+use crate::debugger::panics::{self, ExpectedPanic, Outcome, Panic};
+use crate::debugger::pc_set::PcSet;
+use crate::debugger::recording::{DEFAULT_MAX_RECORDED_STEPS, ExecutionRecording};
+use crate::debugger::resources::ResourceMark;
+use crate::debugger::storage::StorageTracker;
+use crate::debugger::syscall_trace::SyscallTrace;
+use crate::debugger::view_call::ViewCaller;
+use crate::debugger::vm_access::{Address, VmAccess};
+use crate::debugger::{MAX_OBJECT_REFERENCE, MIN_OBJECT_REFERENCE};
 
 type SourcePath = String;
 
 pub struct State {
-    configuration_done: bool,
-    execution_stopped: bool,
+    phase: Phase,
+    /// Breakpoints requested by the client, including the ones not valid in any of the programs
+    /// yet, which may become valid once the class of a called contract is registered.
+    requested_breakpoints: HashMap<SourcePath, Vec<RequestedBreakpoint>>,
+    next_breakpoint_id: i64,
+    /// Statements of the breakpoints in the active program.
     breakpoints: HashMap<SourcePath, HashSet<StatementIdx>>,
+    /// Function breakpoints requested by the client, resolved like [`Self::requested_breakpoints`].
+    requested_function_breakpoints: Vec<RequestedFunctionBreakpoint>,
+    /// Statements of the function breakpoints in the active program.
+    function_breakpoints: HashSet<StatementIdx>,
+    /// Pcs of the statements of the line and function breakpoints in the active program,
+    /// so that checking them at each instruction is a single load. Empty without breakpoints.
+    breakpoint_pcs: PcSet,
     pub current_statement_idx: StatementIdx,
     pub call_stack: CallStack,
+    /// Set once the VM ran instructions the debugger did not handle, see
+    /// [`Self::needs_every_step`], until the state is caught up with it.
+    pub steps_skipped: bool,
     last_breakpoint_hit: Option<BreakpointHit>,
+    /// State of the execution of programs that called contracts being executed,
+    /// the innermost one last.
+    suspended_programs: Vec<ProgramState>,
+    /// Contract calls made so far, reported by the execution tool.
+    pub call_trace: CallTrace,
+    pub storage: StorageTracker,
+    /// Executes view calls requested in the debug console, if the execution tool supports them.
+    pub view_caller: Option<Box<dyn ViewCaller>>,
+    pub syscall_trace: SyscallTrace,
+    /// Point of the execution the resources are compared with, see [`ResourceMark`].
+    pub resource_mark: Option<ResourceMark>,
+    pub metrics: RuntimeMetrics,
+    pub deployments: Deployments,
+    /// Whether each syscall should be reported in the output once it was executed.
+    pub stream_syscalls: bool,
+    /// Network the test runs against, if it is a fork test.
+    pub fork_origin: Option<ForkOrigin>,
+    /// Number of reads served from the forked network so far, in fork tests.
+    pub fork_reads: usize,
+    /// Whether the execution should stop at the first read from the forked network.
+    pub break_on_fork_read: bool,
+    /// Set once the read the execution should stop at happened, until it stops.
+    pub fork_read_break_pending: bool,
+    /// Whether the execution should stop in the caller of a contract call that failed.
+    pub break_on_failed_call: bool,
+    /// Set once a contract call failed, until the execution stops in its caller.
+    pub failed_call_break_pending: bool,
+    /// Whether the execution should stop once a class is declared.
+    pub break_on_declare: bool,
+    /// Whether the execution should stop at each deployment of a contract.
+    pub break_on_deploy: bool,
+    /// Cell the syscall executed by the current instruction writes the execution info pointer to.
+    pub pending_execution_info: Option<Address>,
+    /// Class being declared by the instruction being executed.
+    pub pending_declaration: Option<PendingDeclaration>,
+    /// Description of the declaration or deployment the execution should stop at, until it stops.
+    pub deployment_break_pending: Option<String>,
+    /// Whether the execution should stop where the program starts panicking,
+    /// unless the panic is expected by the test.
+    pub break_on_panic: bool,
+    /// Panic expected by the test being executed, if it is a `#[should_panic]` test.
+    pub expected_panic: Option<ExpectedPanic>,
+    /// First panic of the program, not counting the ones in called contracts,
+    /// which the caller may recover from.
+    pub panic: Option<Panic>,
+    /// Step at which the first panic of the program started, if the steps are recorded,
+    /// until the execution moves back to it once the program returns with the panic.
+    pub panic_step: Option<usize>,
+    /// Whether the current statement belongs to a panic function, so that each panic
+    /// is detected once.
+    in_panic_function: bool,
+    /// Hints the execution should stop at, before they are executed.
+    pub hint_breakpoints: Vec<HintBreakpoint>,
+    /// Hints of the instruction about to be executed.
+    pub hints: Vec<HintInfo>,
+    /// Iteration being executed, if the test is a fuzz test.
+    pub fuzz_iteration: Option<FuzzIteration>,
+    pub fuzzer_options: FuzzerOptions,
+    /// Set once a failing iteration started being replayed, until the execution stops at its start.
+    pub fuzz_replay_stop_pending: bool,
     pub step_action: Option<StepAction>,
+    /// Steps of the active program executed so far, if the client enabled the recording
+    /// with the `recordExecution` launch argument, or since it started it with `:record start`.
+    pub recording: Option<ExecutionRecording>,
+    pub max_recorded_steps: usize,
+    pub segment_growth_monitor: Option<SegmentGrowthMonitor>,
+    /// Whether code outside of the user's workspace should be skipped when stepping,
+    /// ignored when setting breakpoints and deemphasized in stack traces.
+    pub just_my_code: bool,
+    /// Prefixes of the paths of functions that stepping does not stop in, see [`Self::skips_statement`].
+    pub skip_functions: Vec<String>,
+    /// Whether the execution should stop at the first statement of the program.
+    pub stop_on_entry: bool,
+    /// Name of the debugged test or function, displayed as the name of the thread.
+    pub test_name: Option<String>,
+    /// Test to run in the standalone mode, chosen with the `testFilter` launch argument.
+    pub selected_test: Option<String>,
+    /// Thread of the test being executed, `None` between the tests of a session covering
+    /// multiple tests, see [`crate::Debugger::start_test`].
+    pub thread: Option<TestThread>,
+    next_thread_id: i64,
+    /// Whether the program is run without debugging, in which case the debugger does nothing
+    /// until the end of the execution.
+    pub no_debug: bool,
+    /// Arguments of the run function in the standalone mode.
+    pub program_args: Vec<ProgramArg>,
+    /// Where the output of the program is shown in the standalone mode.
+    pub console: Console,
+    /// Working directory of the program, set in the launch configuration.
+    pub cwd: Option<PathBuf>,
+    /// Whether the client supports the `runInTerminal` reverse request.
+    pub supports_run_in_terminal: bool,
+    /// Whether the client supports the `startDebugging` reverse request.
+    pub supports_start_debugging: bool,
+    /// Whether contracts called by the program should be debugged in child sessions.
+    pub debug_contract_calls: bool,
+    /// Launch or attach arguments of the session, inherited by its child sessions.
+    pub configuration: Map<String, Value>,
+    /// Whether the client attached to the debuggee instead of launching it.
+    pub attached: bool,
+    /// See [`crate::debugger::watchdog::Watchdog`].
+    pub watchdog_timeout: Option<Duration>,
+    pub pause_on_watchdog_timeout: bool,
+    /// See [`Self::disconnect_action`].
+    pub on_disconnect: Option<DisconnectAction>,
+    /// Whether the execution tool re-executes the current test on the client's request,
+    /// see [`crate::Debugger::enable_test_rerun`].
+    pub supports_test_rerun: bool,
+    rerun_requested: bool,
+    /// Whether the client sent the auth token, if one is required.
+    pub authenticated: bool,
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
-            configuration_done: false,
-            execution_stopped: false,
+            phase: Phase::Uninitialized,
+            requested_breakpoints: HashMap::default(),
+            next_breakpoint_id: MIN_OBJECT_REFERENCE,
             breakpoints: HashMap::default(),
+            requested_function_breakpoints: Vec::new(),
+            function_breakpoints: HashSet::new(),
+            breakpoint_pcs: PcSet::default(),
             current_statement_idx: StatementIdx(0),
             call_stack: CallStack::default(),
             last_breakpoint_hit: None,
+            suspended_programs: Vec::new(),
+            call_trace: CallTrace::default(),
+            storage: StorageTracker::default(),
+            view_caller: None,
+            syscall_trace: SyscallTrace::default(),
+            resource_mark: None,
+            metrics: RuntimeMetrics::default(),
+            deployments: Deployments::default(),
+            stream_syscalls: false,
+            fork_origin: None,
+            fork_reads: 0,
+            break_on_fork_read: false,
+            fork_read_break_pending: false,
+            break_on_failed_call: false,
+            failed_call_break_pending: false,
+            break_on_declare: false,
+            break_on_deploy: false,
+            pending_declaration: None,
+            pending_execution_info: None,
+            deployment_break_pending: None,
+            break_on_panic: false,
+            expected_panic: None,
+            panic: None,
+            panic_step: None,
+            in_panic_function: false,
+            steps_skipped: false,
+            hint_breakpoints: Vec::new(),
+            hints: Vec::new(),
+            fuzz_iteration: None,
+            fuzzer_options: FuzzerOptions::default(),
+            fuzz_replay_stop_pending: false,
             step_action: None,
+            recording: None,
+            max_recorded_steps: DEFAULT_MAX_RECORDED_STEPS,
+            segment_growth_monitor: None,
+            just_my_code: false,
+            skip_functions: Vec::new(),
+            stop_on_entry: false,
+            test_name: None,
+            selected_test: None,
+            thread: Some(TestThread { id: MAX_OBJECT_REFERENCE, name: None }),
+            next_thread_id: MIN_OBJECT_REFERENCE,
+            no_debug: false,
+            program_args: Vec::new(),
+            console: Console::default(),
+            cwd: None,
+            supports_run_in_terminal: false,
+            supports_start_debugging: false,
+            debug_contract_calls: false,
+            configuration: Map::new(),
+            attached: false,
+            watchdog_timeout: None,
+            pause_on_watchdog_timeout: false,
+            on_disconnect: None,
+            supports_test_rerun: false,
+            rerun_requested: false,
+            authenticated: false,
         }
     }
 
-    pub fn update_state(&mut self, vm: &VirtualMachine, ctx: &Context) {
-        let current_pc = vm.get_pc();
+    /// Resets everything set up by the client, keeping the state of the execution,
+    /// so that the next client starts with a clean session.
+    pub fn reset_session(&mut self) {
+        let mut call_stack = mem::take(&mut self.call_stack);
+        call_stack.reset_session();
+        *self = Self {
+            current_statement_idx: self.current_statement_idx,
+            call_stack,
+            suspended_programs: mem::take(&mut self.suspended_programs),
+            call_trace: mem::take(&mut self.call_trace),
+            storage: mem::take(&mut self.storage),
+            view_caller: self.view_caller.take(),
+            syscall_trace: mem::take(&mut self.syscall_trace),
+            resource_mark: self.resource_mark.take(),
+            deployments: mem::take(&mut self.deployments),
+            fork_origin: self.fork_origin.take(),
+            fork_reads: self.fork_reads,
+            thread: self.thread.take(),
+            fuzz_iteration: self.fuzz_iteration.take(),
+            expected_panic: self.expected_panic.take(),
+            panic: self.panic.take(),
+            panic_step: self.panic_step,
+            in_panic_function: self.in_panic_function,
+            hints: mem::take(&mut self.hints),
+            // The registers may be moved back to a past step until the execution resumes.
+            recording: self.recording.take(),
+            next_thread_id: self.next_thread_id,
+            supports_test_rerun: self.supports_test_rerun,
+            ..Self::new()
+        };
+    }
+
+    /// Suspends the execution of the active program, once a contract it called starts
+    /// being executed, whose context is `ctx`.
+    pub fn enter_program(&mut self, ctx: &Context) {
+        let call_stack = self.call_stack.for_nested_program();
+        self.suspended_programs.push(ProgramState {
+            current_statement_idx: mem::replace(&mut self.current_statement_idx, StatementIdx(0)),
+            call_stack: mem::replace(&mut self.call_stack, call_stack),
+            last_breakpoint_hit: self.last_breakpoint_hit.take(),
+            recording: self.recording.as_mut().map(ExecutionRecording::take),
+        });
+        self.steps_skipped = true;
+        self.resolve_breakpoints(ctx);
+    }
+
+    /// Resumes the execution of the program that called the contract which finished,
+    /// whose context is `ctx`.
+    pub fn exit_program(&mut self, ctx: &Context) {
+        let Some(program_state) = self.suspended_programs.pop() else {
+            return;
+        };
+        self.current_statement_idx = program_state.current_statement_idx;
+        self.call_stack = program_state.call_stack;
+        // The caller may have been suspended in the middle of skipped steps.
+        self.steps_skipped = true;
+        self.last_breakpoint_hit = program_state.last_breakpoint_hit;
+        if let Some(recording) = &mut self.recording {
+            match program_state.recording {
+                Some(caller_recording) => *recording = caller_recording,
+                None => recording.clear(),
+            }
+        }
+        self.resolve_breakpoints(ctx);
+
+        // Depths of steps started in the contract do not apply to the caller. Any step ends
+        // once the contract returns, since the caller is shallower than every frame of it.
+        if self.step_action.is_some() {
+            self.step_action = Some(StepAction::StepOut { depth: usize::MAX });
+        }
+    }
+
+    /// Whether the active program is a contract called by another program.
+    pub fn is_in_nested_program(&self) -> bool {
+        !self.suspended_programs.is_empty()
+    }
+
+    /// Id of the thread the execution is reported in.
+    pub fn thread_id(&self) -> i64 {
+        self.thread.as_ref().map_or(MAX_OBJECT_REFERENCE, |thread| thread.id)
+    }
+
+    /// Name of the thread, the full path of the test being executed.
+    pub fn thread_name(&self) -> String {
+        self.thread
+            .as_ref()
+            .and_then(|thread| thread.name.clone())
+            .or_else(|| self.test_name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Starts the thread of the next test. Returns the id of the thread that exited, if any,
+    /// and the id of the new one.
+    pub fn start_test(&mut self, name: String) -> (Option<i64>, i64) {
+        let exited = self.finish_test();
+        let id = self.next_thread_id;
+        self.next_thread_id += 1;
+        self.thread = Some(TestThread { id, name: Some(name) });
+        self.fuzz_iteration = None;
+        self.expected_panic = None;
+        self.deployments.clear();
+        self.reset_execution();
+        (exited, id)
+    }
+
+    /// Starts the next iteration of the fuzz test, stopping at its start if it replays a failing one.
+    pub fn start_fuzz_iteration(&mut self, iteration: FuzzIteration) {
+        self.fuzz_replay_stop_pending = iteration.is_failure_replay;
+        self.fuzz_iteration = Some(iteration);
+        self.reset_execution();
+    }
+
+    /// Nothing tracked during the previous run of the program applies to the next one.
+    fn reset_execution(&mut self) {
+        self.call_stack = self.call_stack.for_nested_program();
+        self.last_breakpoint_hit = None;
+        self.step_action = None;
+        self.panic = None;
+        self.panic_step = None;
+        self.in_panic_function = false;
+        if let Some(recording) = &mut self.recording {
+            recording.clear();
+        }
+    }
+
+    /// Returns the panic if the program starts panicking at the current statement.
+    pub fn detect_panic(&mut self, vm: &dyn VmAccess, ctx: &Context) -> Option<Panic> {
+        let panic_function = panics::panic_function(ctx, self.current_statement_idx);
+        let started = panic_function.is_some() && !self.in_panic_function;
+        self.in_panic_function = panic_function.is_some();
+        let panic_function = panic_function.filter(|_| started)?;
+
+        let data = panics::read_panic_data(vm, ctx, self.current_statement_idx, panic_function);
+        let expected = self
+            .expected_panic
+            .as_ref()
+            .is_some_and(|expected_panic| expected_panic.matches(data.as_deref()));
+        Some(Panic { data, expected })
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        Outcome::new(self.panic.as_ref(), self.expected_panic.as_ref())
+    }
+
+    /// Returns the id of the thread of the finished test.
+    pub fn finish_test(&mut self) -> Option<i64> {
+        self.thread.take().map(|thread| thread.id)
+    }
+
+    /// Number of programs suspended by the contract calls they made.
+    pub fn program_depth(&self) -> usize {
+        self.suspended_programs.len()
+    }
+
+    /// Whether the debugger must handle every instruction, since the execution may stop
+    /// at any of them or they are recorded. Otherwise, the VM runs freely until a request
+    /// arrives or a panic starts.
+    pub fn needs_every_step(&self) -> bool {
+        !self.no_debug
+            && (!self.breakpoint_pcs.is_empty()
+                || !self.hint_breakpoints.is_empty()
+                || self.break_on_panic
+                || self.break_on_fork_read
+                || self.break_on_failed_call
+                || self.break_on_declare
+                || self.break_on_deploy
+                || self.stop_on_entry
+                || self.step_action.is_some()
+                || self.fuzz_replay_stop_pending
+                || self.fork_read_break_pending
+                || self.failed_call_break_pending
+                || self.deployment_break_pending.is_some()
+                || self.call_stack.return_value.is_some()
+                || self.recording.is_some()
+                || self.segment_growth_monitor.is_some()
+                || self.watchdog_timeout.is_some())
+    }
+
+    /// Brings the state up to date once the debugger handles an instruction again,
+    /// after [`Self::steps_skipped`].
+    pub fn catch_up(&mut self, vm: &dyn VmAccess, ctx: &Context) {
+        self.steps_skipped = false;
+        // Instructions of panic functions are never skipped.
+        self.in_panic_function = false;
+        self.call_stack.catch_up(vm, ctx);
+    }
+
+    pub fn update_state(&mut self, vm: &dyn VmAccess, ctx: &Context) {
+        let current_pc = vm.pc();
 
         if current_pc.segment_index != 0 {
             // We cannot map pc to a sierra statement in such a case since we are before relocation.
@@ -48,71 +443,550 @@ impl State {
         }
 
         self.current_statement_idx = ctx.statement_idx_for_pc(current_pc.offset);
-        self.call_stack.update(self.current_statement_idx, ctx);
+        self.call_stack.update(self.current_statement_idx, vm, ctx);
+    }
+
+    /// Whether the stopped execution was moved back to a past step.
+    pub fn is_in_past(&self) -> bool {
+        self.recording.as_ref().is_some_and(ExecutionRecording::is_in_past)
+    }
+
+    /// Moves the stopped execution back to the first step of the previous line,
+    /// the first recorded step at the latest.
+    pub fn step_back(&mut self, vm: &mut dyn VmAccess, ctx: &Context) -> Result<()> {
+        let recording =
+            self.recording.as_ref().ok_or_else(|| requires_recording("Stepping back"))?;
+        let Some(position) = recording.position() else {
+            bail!("No steps were recorded yet");
+        };
+        let line = |step| Line::create_from_statement_idx(recording.statement_idx(step, ctx), ctx);
+        // Same as when stepping forward, the skipped steps are not stopped at.
+        let can_stop_at = |step| !self.skips_statement(recording.statement_idx(step, ctx), ctx);
+
+        let first_step = recording.first_step();
+        let current_line = line(position);
+        let mut target = (first_step..position)
+            .rev()
+            .find(|&step| line(step) != current_line && can_stop_at(step))
+            .unwrap_or(first_step);
+        let target_line = line(target);
+        while target > first_step && line(target - 1) == target_line {
+            target -= 1;
+        }
+        self.travel_to(target, vm, ctx)
+    }
+
+    /// Moves the stopped execution back to the most recent recorded hit of any breakpoint,
+    /// or to the first recorded step if there is none. Returns whether a breakpoint was hit.
+    pub fn reverse_continue(&mut self, vm: &mut dyn VmAccess, ctx: &Context) -> Result<bool> {
+        let recording =
+            self.recording.as_ref().ok_or_else(|| requires_recording("Continuing backwards"))?;
+        let Some(position) = recording.position() else {
+            bail!("No steps were recorded yet");
+        };
+        let line = |step| Line::create_from_statement_idx(recording.statement_idx(step, ctx), ctx);
+        let is_breakpoint = |step| self.has_breakpoint_at(recording.pc(step));
+
+        // Earlier steps of the current line belong to the hit the execution is stopped at.
+        let first_step = recording.first_step();
+        let current_line = line(position);
+        let mut start = position;
+        while start > first_step && line(start - 1) == current_line {
+            start -= 1;
+        }
+        let hit = (first_step..start).rev().find(|&step| is_breakpoint(step)).map(|mut hit| {
+            // Stop where the execution stopped going forward, at the first step of the line.
+            let hit_line = line(hit);
+            while hit > first_step && line(hit - 1) == hit_line && is_breakpoint(hit - 1) {
+                hit -= 1;
+            }
+            hit
+        });
+        self.travel_to(hit.unwrap_or(first_step), vm, ctx)?;
+        Ok(hit.is_some())
+    }
+
+    /// Moves the execution that was moved back forward to the next recorded hit of any
+    /// breakpoint. Returns whether there was one, otherwise the execution returns to the present,
+    /// to resume from there.
+    pub fn continue_in_past(&mut self, vm: &mut dyn VmAccess, ctx: &Context) -> Result<bool> {
+        let Some(recording) = &self.recording else {
+            return Ok(false);
+        };
+        let (Some(position), Some(last_step)) = (recording.position(), recording.last_step())
+        else {
+            return Ok(false);
+        };
+        let line = |step| Line::create_from_statement_idx(recording.statement_idx(step, ctx), ctx);
+        let is_breakpoint = |step| self.has_breakpoint_at(recording.pc(step));
+
+        // Later steps of the current line belong to the hit the execution is stopped at.
+        let current_line = line(position);
+        let mut end = position;
+        while end < last_step && line(end + 1) == current_line {
+            end += 1;
+        }
+        match (end + 1..=last_step).find(|&step| is_breakpoint(step)) {
+            Some(hit) => {
+                self.travel_to(hit, vm, ctx)?;
+                Ok(true)
+            }
+            None => {
+                self.return_to_present(vm);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Starts at the first step of a finished execution, whose last step `vm` is at,
+    /// see [`crate::Debugger::debug_post_mortem`].
+    pub fn start_post_mortem(
+        &mut self,
+        recording: ExecutionRecording,
+        vm: &mut dyn VmAccess,
+        ctx: &Context,
+    ) -> Result<()> {
+        self.current_statement_idx = ctx.statement_idx_for_pc(vm.pc().offset);
+        self.call_stack = self.call_stack.at_past_step(vm, ctx);
+        let first_step = recording.first_step();
+        self.recording = Some(recording);
+        self.travel_to(first_step, vm, ctx)
+    }
+
+    /// Moves the execution that was moved back forward to the next recorded line, without
+    /// skipping calls, and returns to the present once there is none.
+    pub fn step_forward_in_past(&mut self, vm: &mut dyn VmAccess, ctx: &Context) -> Result<()> {
+        let Some(recording) = &self.recording else {
+            return Ok(());
+        };
+        let (Some(position), Some(last_step)) = (recording.position(), recording.last_step())
+        else {
+            return Ok(());
+        };
+        let line = |step| Line::create_from_statement_idx(recording.statement_idx(step, ctx), ctx);
+        let can_stop_at = |step| !self.skips_statement(recording.statement_idx(step, ctx), ctx);
+
+        let current_line = line(position);
+        let target = (position + 1..last_step)
+            .find(|&step| line(step) != current_line && can_stop_at(step))
+            .unwrap_or(last_step);
+        self.travel_to(target, vm, ctx)
+    }
+
+    /// Starts recording the steps of the active program, from the current one on.
+    pub fn start_recording(&mut self, vm: &dyn VmAccess) -> Result<()> {
+        ensure!(self.recording.is_none(), "The execution is already being recorded");
+        let mut recording = ExecutionRecording::new(self.max_recorded_steps);
+        recording.record_step(vm);
+        self.recording = Some(recording);
+        Ok(())
+    }
+
+    /// Stops recording the steps, forgetting the ones recorded so far, also for the programs
+    /// that called the active one, which frees their memory.
+    pub fn stop_recording(&mut self, vm: &mut dyn VmAccess) -> Result<()> {
+        ensure!(self.recording.is_some(), "The execution is not being recorded");
+        self.return_to_present(vm);
+        self.recording = None;
+        for program in &mut self.suspended_programs {
+            program.recording = None;
+        }
+        Ok(())
+    }
+
+    pub fn enabled_recording(&self) -> Result<&ExecutionRecording> {
+        self.recording.as_ref().ok_or_else(|| {
+            anyhow!("Steps are recorded only with `recordExecution` in the launch configuration")
+        })
+    }
+
+    /// Moves the stopped execution to the state after `step` steps, backward or forward,
+    /// as long as the step is recorded.
+    pub fn go_to_step(&mut self, step: usize, vm: &mut dyn VmAccess, ctx: &Context) -> Result<()> {
+        let recording = self.enabled_recording()?;
+        // The registers of a running execution must not be moved.
+        ensure!(self.is_execution_stopped(), "The execution must be stopped first");
+        let last_step = recording.last_step().ok_or_else(|| anyhow!("No steps recorded yet"))?;
+        ensure!(
+            step >= recording.first_step(),
+            "Step {step} is no longer recorded, the first one kept is {}",
+            recording.first_step()
+        );
+        ensure!(step <= last_step, "Step {step} was not executed yet, the last one is {last_step}");
+        self.travel_to(step, vm, ctx)
+    }
+
+    /// Moves the execution back to the last recorded step of user code before the program started
+    /// panicking, once the program returns with the panic, so that the user lands where things
+    /// went wrong rather than in the code propagating the panic. Returns whether it moved.
+    pub fn move_to_uncaught_panic(&mut self, vm: &mut dyn VmAccess, ctx: &Context) -> Result<bool> {
+        let returns_from_program = self.call_stack.depth() == 0
+            && !self.is_in_nested_program()
+            && matches!(ctx.statement_kind(self.current_statement_idx), StatementKind::Return);
+        if !returns_from_program || self.panic.as_ref().is_none_or(|panic| panic.expected) {
+            return Ok(false);
+        }
+        // The entry code following the program may map to a return statement too.
+        let (Some(recording), Some(panic_step)) = (&self.recording, self.panic_step.take()) else {
+            return Ok(false);
+        };
+        let origin = (recording.first_step()..panic_step).rev().find(|&step| {
+            let statement_idx = recording.statement_idx(step, ctx);
+            ctx.is_user_code_statement(statement_idx)
+                && panics::panic_function(ctx, statement_idx).is_none()
+        });
+        let Some(origin) = origin else {
+            return Ok(false);
+        };
+        self.travel_to(origin, vm, ctx)?;
+        Ok(true)
+    }
+
+    /// Moves the stopped execution to the recorded `step`, the last one being the present.
+    fn travel_to(&mut self, step: usize, vm: &mut dyn VmAccess, ctx: &Context) -> Result<()> {
+        let Some(recording) = &mut self.recording else {
+            return Ok(());
+        };
+        if Some(step) == recording.last_step() {
+            self.return_to_present(vm);
+            return Ok(());
+        }
+
+        recording.move_to_past(step, vm, || {
+            (self.current_statement_idx, mem::take(&mut self.call_stack))
+        })?;
+        self.current_statement_idx = recording.statement_idx(step, ctx);
+        if let Some(present_call_stack) = recording.present_call_stack() {
+            self.call_stack = present_call_stack.at_past_step(vm, ctx);
+        }
+        Ok(())
+    }
+
+    /// Moves the execution moved back to a past step back to the present, which must happen
+    /// before it resumes.
+    pub fn return_to_present(&mut self, vm: &mut dyn VmAccess) {
+        if let Some(present) =
+            self.recording.as_mut().and_then(|recording| recording.return_to_present(vm))
+        {
+            self.current_statement_idx = present.statement_idx;
+            self.call_stack = present.call_stack;
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn set_initialized(&mut self) {
+        trace!("Session initialized");
+        self.phase = Phase::Initialized;
+    }
+
+    pub fn set_launched(&mut self) {
+        trace!("Debuggee launched");
+        self.phase = Phase::Configuring;
     }
 
     pub fn is_configuration_done(&self) -> bool {
-        self.configuration_done
+        self.phase.is_configuration_done()
     }
 
     pub fn set_configuration_done(&mut self) {
         trace!("Configuration done");
-        self.configuration_done = true;
+        self.phase = Phase::Running;
+    }
+
+    /// Whether stepping skips the statement, since it is outside of the user's code
+    /// with `justMyCode` set, or in one of [`Self::skip_functions`].
+    pub fn skips_statement(&self, statement_idx: StatementIdx, ctx: &Context) -> bool {
+        if self.just_my_code && !ctx.is_user_code_statement(statement_idx) {
+            return true;
+        }
+        // The innermost function, which the statement may be inlined into the others from.
+        let function =
+            ctx.function_names_for_statement_idx(statement_idx).and_then(|names| names.last());
+        function.is_some_and(|FunctionName(name)| {
+            self.skip_functions.iter().any(|prefix| name.starts_with(prefix.as_str()))
+        })
     }
 
     pub fn is_execution_stopped(&self) -> bool {
-        self.execution_stopped
+        self.phase == Phase::Stopped
     }
 
+    /// Only a running execution can be stopped, e.g. not one being terminated.
     pub fn stop_execution(&mut self) {
-        trace!("Execution stopped");
-        self.execution_stopped = true;
+        if self.phase == Phase::Running {
+            trace!("Execution stopped");
+            self.phase = Phase::Stopped;
+        }
     }
 
     pub fn resume_execution(&mut self) {
-        trace!("Execution resumed");
-        self.execution_stopped = false;
+        if self.phase == Phase::Stopped {
+            trace!("Execution resumed");
+            self.phase = Phase::Running;
+        }
+    }
+
+    pub fn request_termination(&mut self) {
+        trace!("Termination requested");
+        self.phase = Phase::Terminating;
+        self.step_action = None;
     }
 
+    /// Action taken when the client is gone without a disconnect request.
+    pub fn disconnect_action(&self) -> DisconnectAction {
+        self.on_disconnect.unwrap_or(if self.attached {
+            DisconnectAction::WaitForClient
+        } else {
+            DisconnectAction::Terminate
+        })
+    }
+
+    pub fn is_termination_requested(&self) -> bool {
+        self.phase == Phase::Terminating
+    }
+
+    /// Aborts the execution like [`Self::request_termination`], but the session goes on
+    /// once the execution tool executes the test again.
+    pub fn request_rerun(&mut self) -> Result<()> {
+        if !self.supports_test_rerun {
+            bail!("The execution tool does not support re-running the test");
+        }
+        trace!("Re-run requested");
+        self.rerun_requested = true;
+        self.step_action = None;
+        self.resume_execution();
+        Ok(())
+    }
+
+    pub fn is_rerun_requested(&self) -> bool {
+        self.rerun_requested
+    }
+
+    /// Prepares the re-run of the current test, forgetting everything tracked during
+    /// the aborted one, which might have been aborted inside a called contract.
+    pub fn restart_test(&mut self, root: &Context) {
+        self.rerun_requested = false;
+        if let Some(program_state) = self.suspended_programs.drain(..).next() {
+            self.call_stack = program_state.call_stack;
+        }
+        self.current_statement_idx = StatementIdx(0);
+        self.call_trace = CallTrace::default();
+        self.syscall_trace = SyscallTrace::default();
+        self.resource_mark = None;
+        self.deployments.clear();
+        self.fork_reads = 0;
+        self.hints.clear();
+        self.reset_execution();
+        self.resolve_breakpoints(root);
+    }
+
+    /// Sets the breakpoint, which is verified if it is valid in any of the programs,
+    /// e.g. in a called contract. Returns its id and whether it is verified.
     pub fn verify_and_set_breakpoint(
         &mut self,
         source: SourcePath,
         line: Line,
-        ctx: &Context,
-    ) -> bool {
-        let indexes = ctx.statement_idxs_for_breakpoint(Path::new(&source), line);
+        contexts: &ContextRegistry,
+    ) -> (i64, bool) {
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        let verified = is_breakpoint_valid(self.just_my_code, &source, line, contexts);
 
-        if let Some(indexes) = indexes {
+        if verified
+            && let Some(indexes) =
+                contexts.active().statement_idxs_for_breakpoint(Path::new(&source), line)
+        {
             debug!(
                 "Setting breakpoint for file: {:?}, line: {:?}, idxs: {:?}",
                 source, line, indexes
             );
-            self.breakpoints.entry(source).or_default().extend(indexes);
+            self.breakpoints.entry(source.clone()).or_default().extend(indexes);
+            self.index_breakpoints(contexts.active());
+        }
+        self.requested_breakpoints.entry(source).or_default().push(RequestedBreakpoint {
+            id,
+            line,
+            verified,
+        });
 
-            return true;
+        (id, verified)
+    }
+
+    /// Replaces all function breakpoints, each of which is verified if a function of that name
+    /// exists in any of the programs. Returns the id of each breakpoint and whether it is verified.
+    pub fn set_function_breakpoints(
+        &mut self,
+        names: Vec<String>,
+        contexts: &ContextRegistry,
+    ) -> Vec<(i64, bool)> {
+        self.requested_function_breakpoints = names
+            .into_iter()
+            .map(|name| {
+                let id = self.next_breakpoint_id;
+                self.next_breakpoint_id += 1;
+                let verified = is_function_breakpoint_valid(&name, contexts);
+                RequestedFunctionBreakpoint { id, name, verified }
+            })
+            .collect();
+        self.resolve_breakpoints(contexts.active());
+
+        self.requested_function_breakpoints
+            .iter()
+            .map(|breakpoint| (breakpoint.id, breakpoint.verified))
+            .collect()
+    }
+
+    /// Verifies all the breakpoints again, e.g. once a program was reloaded and the lines
+    /// of its code moved, and returns the ones whose verification changed.
+    pub fn reverify_breakpoints(&mut self, contexts: &ContextRegistry) -> Vec<Breakpoint> {
+        let mut changed = Vec::new();
+        for (source, breakpoints) in &mut self.requested_breakpoints {
+            for breakpoint in breakpoints.iter_mut() {
+                let verified =
+                    is_breakpoint_valid(self.just_my_code, source, breakpoint.line, contexts);
+                if verified == breakpoint.verified {
+                    continue;
+                }
+                breakpoint.verified = verified;
+                let message = (!verified).then(|| {
+                    DebuggerError::UnmappedBreakpoint {
+                        source: source.clone(),
+                        line: breakpoint.line.ui_number() as usize,
+                    }
+                    .to_string()
+                });
+                changed.push(Breakpoint {
+                    id: Some(breakpoint.id),
+                    verified,
+                    message,
+                    source: Some(Source { path: Some(source.clone()), ..Default::default() }),
+                    line: Some(breakpoint.line.ui_number()),
+                    ..Default::default()
+                });
+            }
+        }
+        for breakpoint in &mut self.requested_function_breakpoints {
+            let verified = is_function_breakpoint_valid(&breakpoint.name, contexts);
+            if verified != breakpoint.verified {
+                breakpoint.verified = verified;
+                changed.push(Breakpoint {
+                    id: Some(breakpoint.id),
+                    verified,
+                    ..Default::default()
+                });
+            }
         }
+        self.resolve_breakpoints(contexts.active());
+        changed
+    }
 
-        false
+    /// Verifies the breakpoints that became valid, e.g. once a new class was registered.
+    pub fn verify_pending_breakpoints(&mut self, contexts: &ContextRegistry) -> Vec<Breakpoint> {
+        let mut newly_verified = Vec::new();
+        for (source, breakpoints) in &mut self.requested_breakpoints {
+            for breakpoint in breakpoints.iter_mut().filter(|breakpoint| !breakpoint.verified) {
+                if is_breakpoint_valid(self.just_my_code, source, breakpoint.line, contexts) {
+                    breakpoint.verified = true;
+                    newly_verified.push(Breakpoint {
+                        id: Some(breakpoint.id),
+                        verified: true,
+                        source: Some(Source { path: Some(source.clone()), ..Default::default() }),
+                        line: Some(breakpoint.line.ui_number()),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        for breakpoint in
+            self.requested_function_breakpoints.iter_mut().filter(|breakpoint| !breakpoint.verified)
+        {
+            if is_function_breakpoint_valid(&breakpoint.name, contexts) {
+                breakpoint.verified = true;
+                newly_verified.push(Breakpoint {
+                    id: Some(breakpoint.id),
+                    verified: true,
+                    ..Default::default()
+                });
+            }
+        }
+        self.resolve_breakpoints(contexts.active());
+        newly_verified
     }
 
-    pub fn clear_breakpoints(&mut self, source: &SourcePath) {
+    pub fn clear_breakpoints(&mut self, source: &SourcePath, ctx: &Context) {
+        self.requested_breakpoints.remove(source);
         self.breakpoints.remove(source);
+        self.index_breakpoints(ctx);
     }
 
-    pub fn was_breakpoint_hit(&mut self, ctx: &Context) -> bool {
-        if self
-            .breakpoints
-            .values()
-            .flatten()
-            .all(|statement_idx| *statement_idx != self.current_statement_idx)
-        {
+    /// Maps the verified breakpoints to the statements of the program that became active.
+    fn resolve_breakpoints(&mut self, ctx: &Context) {
+        self.breakpoints = self
+            .requested_breakpoints
+            .iter()
+            .map(|(source, breakpoints)| {
+                let indexes = breakpoints
+                    .iter()
+                    .filter(|breakpoint| breakpoint.verified)
+                    .filter_map(|breakpoint| {
+                        ctx.statement_idxs_for_breakpoint(Path::new(source), breakpoint.line)
+                    })
+                    .flatten()
+                    .copied()
+                    .collect();
+                (source.clone(), indexes)
+            })
+            .collect();
+        self.function_breakpoints = self
+            .requested_function_breakpoints
+            .iter()
+            .filter(|breakpoint| breakpoint.verified)
+            .flat_map(|breakpoint| ctx.statement_idxs_for_function_breakpoint(&breakpoint.name))
+            .collect();
+        self.index_breakpoints(ctx);
+    }
+
+    /// Rebuilds [`Self::breakpoint_pcs`] once the breakpoints changed, or the pcs
+    /// of the program did, see [`Context::offset_pcs`].
+    pub fn index_breakpoints(&mut self, ctx: &Context) {
+        let mut statements =
+            self.breakpoints.values().flatten().chain(&self.function_breakpoints).peekable();
+        if statements.peek().is_none() {
+            self.breakpoint_pcs = PcSet::default();
+            return;
+        }
+        let mut breakpoint_pcs = PcSet::new(ctx.last_statement_pc());
+        for &statement_idx in statements {
+            breakpoint_pcs.insert(ctx.statement_pcs(statement_idx));
+        }
+        self.breakpoint_pcs = breakpoint_pcs;
+    }
+
+    /// Whether any line or function breakpoint is set at the pc, in the current fuzz iteration.
+    fn has_breakpoint_at(&self, pc: usize) -> bool {
+        self.breakpoint_pcs.contains(pc)
+            // Breakpoints apply only in the fuzz iterations chosen in the launch configuration.
+            && self
+                .fuzz_iteration
+                .as_ref()
+                .is_none_or(|iteration| iteration.matches(self.fuzzer_options))
+    }
+
+    /// Whether the execution at `pc`, of [`Self::current_statement_idx`], hit a breakpoint
+    /// it did not stop at already.
+    pub fn was_breakpoint_hit(&mut self, pc: usize, ctx: &Context) -> bool {
+        if !self.has_breakpoint_at(pc) {
             return false;
         }
 
         let location = ctx
             .code_location_for_statement_idx(self.current_statement_idx)
-            .expect("Breakpoint statement was expected to have corresponding code location");
-        let ui_state = UiState::build(self, ctx);
+            .expect("Breakpoint statement was expected to have corresponding code location")
+            .clone();
+        let ui_state = UiState::build(self);
         let breakpoint_hit = Some(BreakpointHit { location, ui_state });
 
         // If we hit the same breakpoint and the ui state is the same,
@@ -126,6 +1000,59 @@ impl State {
     }
 }
 
+/// A breakpoint is valid if it maps to statements of any of the programs.
+fn is_breakpoint_valid(
+    just_my_code: bool,
+    source: &str,
+    line: Line,
+    contexts: &ContextRegistry,
+) -> bool {
+    let path = Path::new(source);
+    if just_my_code && !contexts.iter().any(|ctx| ctx.is_user_code(path)) {
+        debug!("Ignoring breakpoint outside of user code: {source:?}");
+        return false;
+    }
+    contexts.iter().any(|ctx| ctx.statement_idxs_for_breakpoint(path, line).is_some())
+}
+
+fn is_function_breakpoint_valid(name: &str, contexts: &ContextRegistry) -> bool {
+    contexts.iter().any(|ctx| !ctx.statement_idxs_for_function_breakpoint(name).is_empty())
+}
+
+pub struct TestThread {
+    pub id: i64,
+    /// Full path of the test, `None` for the thread of the whole session,
+    /// named after [`State::test_name`].
+    pub name: Option<String>,
+}
+
+struct RequestedBreakpoint {
+    id: i64,
+    line: Line,
+    verified: bool,
+}
+
+struct RequestedFunctionBreakpoint {
+    id: i64,
+    name: String,
+    verified: bool,
+}
+
+fn requires_recording(feature: &str) -> DebuggerError {
+    DebuggerError::Unsupported {
+        feature: feature.to_string(),
+        requirement: Some("the `recordExecution` launch argument".to_string()),
+    }
+}
+
+/// State of the execution of a program, specific to its Sierra program.
+struct ProgramState {
+    current_statement_idx: StatementIdx,
+    call_stack: CallStack,
+    last_breakpoint_hit: Option<BreakpointHit>,
+    recording: Option<ExecutionRecording>,
+}
+
 #[derive(PartialEq)]
 struct BreakpointHit {
     location: CodeLocation,
@@ -134,15 +1061,15 @@ struct BreakpointHit {
 }
 
 /// Represents the state of the debugger from the user's point of view.
-/// E.g. `stack_trace` is visible to a user through [`dap::prelude::Command::StackTrace`] request.
+/// E.g. the stack trace, visible to a user through [`dap::prelude::Command::StackTrace`] request,
+/// which is told apart by the statements of its frames rather than built at every hit.
 #[derive(PartialEq)]
 struct UiState {
-    stack_trace: Vec<StackFrame>,
+    frame_statements: Vec<StatementIdx>,
 }
 
 impl UiState {
-    fn build(state: &State, ctx: &Context) -> Self {
-        let stack_trace = state.call_stack.get_frames(state.current_statement_idx, ctx);
-        UiState { stack_trace }
+    fn build(state: &State) -> Self {
+        UiState { frame_statements: state.call_stack.frame_statements(state.current_statement_idx) }
     }
 }
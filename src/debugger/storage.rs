@@ -0,0 +1,239 @@
+//! Storage of the contracts being executed, as far as it is known to the debugger:
+//! the values read and written by storage syscalls, and the ones read on demand
+//! with a [`StorageReader`] provided by the execution tool.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Result, bail};
+use dap::types::{Scope, Variable, VariablePresentationHint};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::abi;
+use crate::debugger::launch_args::ValueFormat;
+use crate::debugger::syscalls::Syscall;
+use crate::debugger::vm_access::{Address, VmAccess};
+
+/// Set in the variables references of the Storage scope and of its lazily read variables.
+/// Lower than the flag of entrypoint arguments, higher than the references of most frames.
+const STORAGE_REFERENCE_FLAG: i64 = 1 << 29;
+
+/// Storage variables of a contract are declared in the `Storage` struct, for each member
+/// of which the compiler generates a module named with this prefix.
+const MEMBER_MODULE_PREFIX: &str = "__member_module_";
+
+/// Offsets in the buffer of a syscall, see [`Syscall`]. The request consists of the address
+/// domain and the key, followed by the value for writes.
+const REQUEST_KEY_OFFSET: usize = 3;
+const WRITE_REQUEST_VALUE_OFFSET: usize = 4;
+/// The read response consists of the gas, the failure flag and the value.
+const READ_RESPONSE_VALUE_OFFSET: usize = 6;
+
+/// Reads storage of deployed contracts. Implemented by execution tools, which own the state,
+/// to let users inspect storage variables the contract has not accessed yet.
+pub trait StorageReader {
+    fn read_storage(&self, contract_address: Felt, key: Felt) -> Option<Felt>;
+}
+
+/// Storage variable declared by a contract, whose value is stored at the base address
+/// unless it is a mapping or a collection.
+pub struct StorageVariable {
+    pub name: String,
+    pub address: Felt,
+}
+
+pub struct ContractStorageDiff {
+    pub contract_address: Felt,
+    pub changes: Vec<StorageChange>,
+}
+
+pub struct StorageChange {
+    pub key: Felt,
+    pub old: Option<Felt>,
+    pub new: Felt,
+}
+
+/// Read whose value is known only once the syscall was executed.
+struct PendingRead {
+    contract_address: Felt,
+    key: Felt,
+    value_address: Address,
+}
+
+#[derive(Default)]
+pub struct StorageTracker {
+    /// Last known values of storage keys accessed so far, by contract address.
+    accesses: HashMap<Felt, BTreeMap<Felt, Felt>>,
+    pending_read: Option<PendingRead>,
+    /// Values of the accessed keys at the stop where the snapshot was taken,
+    /// see [`Self::take_snapshot`].
+    snapshot: Option<HashMap<Felt, BTreeMap<Felt, Felt>>>,
+    pub reader: Option<Box<dyn StorageReader>>,
+}
+
+impl StorageTracker {
+    /// Records the syscall about to be executed by the contract at `contract_address`,
+    /// if it accesses storage. The value of a read is recorded by [`Self::finish_read`],
+    /// once the instruction was executed.
+    pub fn record_syscall(&mut self, vm: &dyn VmAccess, syscall: &Syscall, contract_address: Felt) {
+        let Some(key) = syscall.read(vm, REQUEST_KEY_OFFSET) else {
+            return;
+        };
+
+        if syscall.is("StorageWrite")
+            && let Some(value) = syscall.read(vm, WRITE_REQUEST_VALUE_OFFSET)
+        {
+            self.accesses.entry(contract_address).or_default().insert(key, value);
+        } else if syscall.is("StorageRead")
+            && let Some(value_address) = syscall.address(READ_RESPONSE_VALUE_OFFSET)
+        {
+            self.pending_read = Some(PendingRead { contract_address, key, value_address });
+        }
+    }
+
+    pub fn finish_read(&mut self, vm: &dyn VmAccess) {
+        let Some(PendingRead { contract_address, key, value_address }) = self.pending_read.take()
+        else {
+            return;
+        };
+        // The value is missing if the read failed.
+        let Some(value) = vm.read_felt(value_address) else {
+            return;
+        };
+        self.accesses.entry(contract_address).or_default().insert(key, value);
+    }
+
+    /// Remembers the values accessed so far, to be compared with later ones by [`Self::diff`].
+    pub fn take_snapshot(&mut self) {
+        self.snapshot = Some(self.accesses.clone());
+    }
+
+    /// Returns the keys whose values changed since the snapshot, grouped by contract address,
+    /// optionally only of a single contract. The old value is `None` for keys first accessed
+    /// after the snapshot, whose previous value is unknown.
+    pub fn diff(&self, contract_address: Option<Felt>) -> Result<Vec<ContractStorageDiff>> {
+        let Some(snapshot) = &self.snapshot else {
+            bail!("No storage snapshot was taken");
+        };
+
+        let mut diffs: Vec<ContractStorageDiff> = self
+            .accesses
+            .iter()
+            .filter(|(address, _)| contract_address.is_none_or(|filter| filter == **address))
+            .map(|(&address, accesses)| {
+                let old_accesses = snapshot.get(&address);
+                let changes = accesses
+                    .iter()
+                    .filter_map(|(&key, &new)| {
+                        let old = old_accesses.and_then(|old_accesses| old_accesses.get(&key));
+                        (old != Some(&new)).then(|| StorageChange { key, old: old.copied(), new })
+                    })
+                    .collect();
+                ContractStorageDiff { contract_address: address, changes }
+            })
+            .filter(|diff| !diff.changes.is_empty())
+            .collect();
+        diffs.sort_by_key(|diff| diff.contract_address);
+        Ok(diffs)
+    }
+
+    pub fn scope(&self) -> Scope {
+        Scope {
+            name: "Storage".to_string(),
+            variables_reference: STORAGE_REFERENCE_FLAG,
+            expensive: true,
+            ..Default::default()
+        }
+    }
+
+    /// Variables of the Storage scope, or the value of a single lazily read variable.
+    pub fn variables(
+        &self,
+        variables_reference: i64,
+        contract_address: Felt,
+        storage_variables: &[StorageVariable],
+        value_format: ValueFormat,
+    ) -> Vec<Variable> {
+        let display = |value: Felt| {
+            if value_format.hex { value.to_hex_string() } else { value.to_string() }
+        };
+
+        let index = variables_reference & !STORAGE_REFERENCE_FLAG;
+        if index > 0 {
+            let Some(variable) = storage_variables.get(index as usize - 1) else {
+                return vec![];
+            };
+            let value = self
+                .reader
+                .as_ref()
+                .and_then(|reader| reader.read_storage(contract_address, variable.address));
+            return vec![Variable {
+                name: variable.name.clone(),
+                value: value.map_or_else(|| "<unavailable>".to_string(), display),
+                variables_reference: 0,
+                ..Default::default()
+            }];
+        }
+
+        let accesses = self.accesses.get(&contract_address);
+        let mut variables: Vec<Variable> = accesses
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| {
+                let name = storage_variables
+                    .iter()
+                    .find(|variable| variable.address == *key)
+                    .map_or_else(|| key.to_hex_string(), |variable| variable.name.clone());
+                Variable {
+                    name,
+                    value: display(*value),
+                    variables_reference: 0,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        // The remaining variables are read only once the user asks for them.
+        if self.reader.is_some() {
+            variables.extend(
+                storage_variables
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, variable)| {
+                        !accesses.is_some_and(|accesses| accesses.contains_key(&variable.address))
+                    })
+                    .map(|(index, variable)| Variable {
+                        name: variable.name.clone(),
+                        value: String::new(),
+                        presentation_hint: Some(VariablePresentationHint {
+                            lazy: Some(true),
+                            ..Default::default()
+                        }),
+                        variables_reference: STORAGE_REFERENCE_FLAG | (index as i64 + 1),
+                        ..Default::default()
+                    }),
+            );
+        }
+        variables
+    }
+}
+
+pub fn is_storage_reference(variables_reference: i64) -> bool {
+    variables_reference & STORAGE_REFERENCE_FLAG != 0
+}
+
+/// Finds the storage variables among the debug names of a Sierra program.
+pub fn storage_variables<'a>(debug_names: impl Iterator<Item = &'a str>) -> Vec<StorageVariable> {
+    let mut names: Vec<&str> = debug_names
+        .filter_map(|debug_name| {
+            let (_, member) = debug_name.split_once(MEMBER_MODULE_PREFIX)?;
+            member.split("::").next()
+        })
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| StorageVariable { name: name.to_string(), address: abi::starknet_keccak(name) })
+        .collect()
+}
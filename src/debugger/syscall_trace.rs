@@ -0,0 +1,216 @@
+//! Trace of the syscalls executed by contracts, with the resources used by each of them.
+
+use std::collections::BTreeMap;
+
+use serde_json::{Value, json};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::syscalls::Syscall;
+use crate::debugger::vm_access::{self, Address, VmAccess};
+
+/// Offsets in the buffer of a syscall, see [`Syscall`].
+const GAS_OFFSET: usize = 1;
+const REQUEST_OFFSET: usize = 2;
+
+/// Sizes of the requests of the syscalls, by their names. The response, which starts with
+/// the remaining gas, follows the request, so its gas is unknown for the syscalls not listed here.
+const REQUEST_SIZES: &[(&str, usize)] = &[
+    ("StorageRead", 2),
+    ("StorageWrite", 3),
+    ("EmitEvent", 4),
+    ("CallContract", 4),
+    ("LibraryCall", 4),
+    ("Deploy", 5),
+    ("GetBlockHash", 1),
+    ("GetExecutionInfo", 0),
+    ("GetClassHashAt", 1),
+    ("SendMessageToL1", 3),
+    ("ReplaceClass", 1),
+    ("Keccak", 2),
+    ("Sha256ProcessBlock", 2),
+    ("MetaTxV0", 6),
+];
+
+pub struct SyscallRecord {
+    pub name: String,
+    /// Request of the syscall, with spans shown as their lengths, e.g. `0x1, 0x2, [3 felts]`.
+    pub arguments: String,
+    /// Address of the contract that executed the syscall, if known.
+    pub contract_address: Option<Felt>,
+    pub gas_consumed: Option<u64>,
+    /// Steps of the contract call made by the syscall, if it made one.
+    pub steps: Option<usize>,
+}
+
+/// Syscall whose gas is known only once it was executed.
+struct PendingSyscall {
+    index: usize,
+    /// Number of programs suspended when the syscall started, the execution of calls made
+    /// by the syscall itself happening in between.
+    depth: usize,
+    gas_before: Option<u64>,
+    gas_after_address: Option<Address>,
+}
+
+#[derive(Default)]
+pub struct SyscallTrace {
+    records: Vec<SyscallRecord>,
+    /// Syscalls being executed, the innermost one last.
+    pending: Vec<PendingSyscall>,
+}
+
+impl SyscallRecord {
+    /// Human-readable summary, e.g. `StorageRead(0x0, 0x1) used 100 gas`.
+    pub fn describe(&self) -> String {
+        let mut result = format!("{}({})", self.name, self.arguments);
+        if let Some(gas_consumed) = self.gas_consumed {
+            result.push_str(&format!(" used {gas_consumed} gas"));
+        }
+        if let Some(steps) = self.steps {
+            result.push_str(&format!(", {steps} steps"));
+        }
+        result
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "arguments": self.arguments,
+            "contractAddress": self.contract_address.as_ref().map(Felt::to_hex_string),
+            "gasConsumed": self.gas_consumed,
+            "steps": self.steps,
+        })
+    }
+}
+
+impl SyscallTrace {
+    /// Records the syscall about to be executed. Its resources are known once
+    /// [`Self::finish`] is called at the same `depth`.
+    pub fn start(
+        &mut self,
+        vm: &dyn VmAccess,
+        syscall: &Syscall,
+        contract_address: Option<Felt>,
+        depth: usize,
+    ) {
+        let name = syscall.name();
+        let request_size =
+            REQUEST_SIZES.iter().find(|(syscall_name, _)| *syscall_name == name).map(|(_, s)| *s);
+
+        let arguments =
+            request_size.map(|size| summarize_request(vm, syscall, size)).unwrap_or_default();
+        let gas_before = syscall.read(vm, GAS_OFFSET).and_then(|gas| u64::try_from(gas).ok());
+        let gas_after_address =
+            request_size.and_then(|size| syscall.address(REQUEST_OFFSET + size));
+
+        self.pending.push(PendingSyscall {
+            index: self.records.len(),
+            depth,
+            gas_before,
+            gas_after_address,
+        });
+        self.records.push(SyscallRecord {
+            name,
+            arguments,
+            contract_address,
+            gas_consumed: None,
+            steps: None,
+        });
+    }
+
+    /// Attributes the steps of a contract call that finished to the syscall which made it.
+    pub fn record_call_steps(&mut self, steps: usize) {
+        if let Some(pending) = self.pending.last() {
+            self.records[pending.index].steps = Some(steps);
+        }
+    }
+
+    /// Completes the innermost syscall, if it was started at `depth`, once its instruction
+    /// was executed. Returns the completed record.
+    pub fn finish(&mut self, vm: &dyn VmAccess, depth: usize) -> Option<&SyscallRecord> {
+        if self.pending.last()?.depth != depth {
+            return None;
+        }
+        let pending = self.pending.pop()?;
+        let gas_after = pending
+            .gas_after_address
+            .and_then(|address| vm.read_felt(address))
+            .and_then(|gas| u64::try_from(gas).ok());
+
+        let record = &mut self.records[pending.index];
+        record.gas_consumed = pending.gas_before.zip(gas_after).map(|(before, after)| {
+            // Gas consumed by the syscall is deducted from the remaining gas it responds with.
+            before.saturating_sub(after)
+        });
+        Some(record)
+    }
+
+    /// Number of syscalls executed so far.
+    pub fn count(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Number of executions of each syscall, since the first `skipped` syscalls.
+    pub fn counts_since(&self, skipped: usize) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for record in self.records.iter().skip(skipped) {
+            *counts.entry(record.name.clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Serializes the syscalls executed so far, with the totals for each syscall,
+    /// so that the expensive ones stand out.
+    pub fn to_json(&self) -> Value {
+        let mut totals: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+        for record in &self.records {
+            let (count, gas_consumed) = totals.entry(&record.name).or_default();
+            *count += 1;
+            *gas_consumed += record.gas_consumed.unwrap_or_default();
+        }
+        let totals: BTreeMap<&str, Value> = totals
+            .into_iter()
+            .map(|(name, (count, gas_consumed))| {
+                (name, json!({ "count": count, "gasConsumed": gas_consumed }))
+            })
+            .collect();
+
+        json!({
+            "syscalls": self.records.iter().map(SyscallRecord::to_json).collect::<Vec<_>>(),
+            "totals": totals,
+        })
+    }
+}
+
+/// Shows the felts of the request in hex, and each span, a pair of pointers, as its length.
+fn summarize_request(vm: &dyn VmAccess, syscall: &Syscall, size: usize) -> String {
+    let cells: Vec<Option<vm_access::Value>> = (REQUEST_OFFSET..REQUEST_OFFSET + size)
+        .map(|offset| vm.read(syscall.address(offset)?))
+        .collect();
+
+    let mut arguments = Vec::new();
+    let mut rest = cells.as_slice();
+    loop {
+        let argument = match rest {
+            [] => break,
+            [
+                Some(vm_access::Value::Address(start)),
+                Some(vm_access::Value::Address(end)),
+                tail @ ..,
+            ] => {
+                rest = tail;
+                (*end - *start).map_or_else(|| start.to_string(), |len| format!("[{len} felts]"))
+            }
+            [cell, tail @ ..] => {
+                rest = tail;
+                match cell {
+                    Some(vm_access::Value::Felt(felt)) => felt.to_hex_string(),
+                    Some(vm_access::Value::Address(pointer)) => pointer.to_string(),
+                    None => "?".to_string(),
+                }
+            }
+        };
+        arguments.push(argument);
+    }
+    arguments.join(", ")
+}
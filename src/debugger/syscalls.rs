@@ -0,0 +1,82 @@
+//! Starknet syscalls invoked by contracts with the `SystemCall` hint.
+
+use std::any::Any;
+
+use cairo_lang_casm::hints::{Hint, StarknetHint};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::memory;
+use crate::debugger::vm_access::{Address, VmAccess};
+
+/// Offset of the request in the buffer of a syscall, after the selector and the gas.
+const REQUEST_OFFSET: usize = 2;
+
+/// Syscall about to be executed, whose buffer starts with the selector and the gas,
+/// followed by the request and then the response.
+pub struct Syscall {
+    pub selector: Felt,
+    buffer: Address,
+}
+
+impl Syscall {
+    /// Returns the syscall invoked by the hints of the instruction about to be executed.
+    pub fn find(vm: &dyn VmAccess, hints_data: &[Box<dyn Any>]) -> Option<Self> {
+        let system = hints_data
+            .iter()
+            .filter_map(|hint_data| hint_data.downcast_ref::<Vec<Hint>>())
+            .flatten()
+            .find_map(|hint| match hint {
+                Hint::Starknet(StarknetHint::SystemCall { system }) => Some(system),
+                _ => None,
+            })?;
+        let buffer = memory::pointer_operand(vm, system)?;
+        let selector = vm.read_felt(buffer)?;
+        Some(Self { selector, buffer })
+    }
+
+    /// Selectors are names of the syscalls, encoded as short strings.
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.selector.to_bytes_be()).trim_start_matches('\0').to_string()
+    }
+
+    pub fn is(&self, name: &str) -> bool {
+        self.selector == Felt::from_bytes_be_slice(name.as_bytes())
+    }
+
+    /// Address of the cell at `offset` in the buffer.
+    pub fn address(&self, offset: usize) -> Option<Address> {
+        self.buffer + offset
+    }
+
+    pub fn read(&self, vm: &dyn VmAccess, offset: usize) -> Option<Felt> {
+        vm.read_felt(self.address(offset)?)
+    }
+
+    /// Keys and data of the event emitted with the `EmitEvent` syscall.
+    pub fn read_event(&self, vm: &dyn VmAccess) -> Option<(Vec<Felt>, Vec<Felt>)> {
+        let keys = self.read_span(vm, REQUEST_OFFSET)?;
+        let data = self.read_span(vm, REQUEST_OFFSET + 2)?;
+        Some((keys, data))
+    }
+
+    /// Class hash and constructor calldata of the contract deployed with the `Deploy` syscall.
+    pub fn read_deploy(&self, vm: &dyn VmAccess) -> Option<(Felt, Vec<Felt>)> {
+        let class_hash = self.read(vm, REQUEST_OFFSET)?;
+        // The calldata follows the salt.
+        let calldata = self.read_span(vm, REQUEST_OFFSET + 2)?;
+        Some((class_hash, calldata))
+    }
+
+    /// Address of the cell the `GetExecutionInfo` syscall writes the pointer to the execution info
+    /// to, after the remaining gas and the failure flag of the response.
+    pub fn execution_info_address(&self) -> Option<Address> {
+        self.address(REQUEST_OFFSET + 2)
+    }
+
+    /// Reads the span whose start and end pointers are at `offset`.
+    pub fn read_span(&self, vm: &dyn VmAccess, offset: usize) -> Option<Vec<Felt>> {
+        let start = vm.read_pointer(self.address(offset)?)?;
+        let end = vm.read_pointer(self.address(offset + 1)?)?;
+        vm.read_felts(start, (end - start)?)
+    }
+}
@@ -0,0 +1,134 @@
+//! Calls of read-only entrypoints from the debug console, e.g. `0x1234.balance_of(0x5678)`,
+//! executed by the execution tool against the current state.
+
+use anyhow::{Context as AnyhowContext, Result, anyhow, bail};
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::context::registry::ContextRegistry;
+use crate::debugger::state::State;
+use crate::debugger::{abi, memory, panics};
+
+/// Target of view calls referring to the contract being executed.
+const SELF_TARGET: &str = "self";
+
+/// Type whose values are passed as two felts, the low and the high 128 bits.
+const U256_TYPE: &str = "core::integer::u256";
+
+/// Executes view calls requested in the debug console. Implemented by execution tools,
+/// which own the state, e.g. the one of cheatnet.
+pub trait ViewCaller {
+    /// Class of the contract deployed at the address, if there is one.
+    fn class_hash_at(&self, contract_address: Felt) -> Option<Felt>;
+
+    /// Calls the entrypoint in a scratch execution context, so that the state seen by
+    /// the debugged program is not changed. Returns the retdata, or the panic data
+    /// if the call failed.
+    fn call(
+        &self,
+        contract_address: Felt,
+        entry_point_selector: Felt,
+        calldata: Vec<Felt>,
+    ) -> Result<Vec<Felt>, Vec<Felt>>;
+}
+
+/// Call expression, e.g. `self.get_balance()` or `0x1234.balance_of(0x5678)`.
+pub struct ViewCall {
+    /// Address of the called contract, `None` for the contract being executed.
+    contract_address: Option<Felt>,
+    entrypoint: String,
+    arguments: Vec<Felt>,
+}
+
+impl ViewCall {
+    /// Returns `None` if the expression is not a call.
+    pub fn parse(expression: &str) -> Option<Result<Self>> {
+        let (callee, arguments) = expression.trim().strip_suffix(')')?.split_once('(')?;
+        let (target, entrypoint) = callee.trim().rsplit_once('.')?;
+        if entrypoint.is_empty()
+            || !entrypoint.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return None;
+        }
+
+        let parse = || -> Result<Self> {
+            let contract_address = match target.trim() {
+                SELF_TARGET => None,
+                address => Some(
+                    memory::parse_felt(address)
+                        .context("The contract must be `self` or its address")?,
+                ),
+            };
+            let arguments = arguments
+                .split(',')
+                .map(str::trim)
+                .filter(|argument| !argument.is_empty())
+                .map(memory::parse_felt)
+                .collect::<Result<_>>()?;
+            Ok(Self { contract_address, entrypoint: entrypoint.to_string(), arguments })
+        };
+        Some(parse())
+    }
+
+    /// Encodes the arguments with the ABI of the class, calls the entrypoint
+    /// and decodes the result.
+    pub fn execute(self, state: &State, contexts: &ContextRegistry) -> Result<String> {
+        let caller = state
+            .view_caller
+            .as_deref()
+            .ok_or_else(|| anyhow!("The execution tool does not support view calls"))?;
+        let contract_address = match self.contract_address {
+            Some(contract_address) => contract_address,
+            None => {
+                state
+                    .call_trace
+                    .active_call()
+                    .ok_or_else(|| anyhow!("No contract is being executed"))?
+                    .contract_address
+            }
+        };
+        let class_hash = caller
+            .class_hash_at(contract_address)
+            .ok_or_else(|| anyhow!("No contract is deployed at {contract_address:#x}"))?;
+        let abi = contexts
+            .class(&class_hash)
+            .and_then(|ctx| ctx.abi.as_ref())
+            .ok_or_else(|| anyhow!("ABI of the class {class_hash:#x} is unknown"))?;
+        let function = abi
+            .function(&self.entrypoint)
+            .ok_or_else(|| anyhow!("The contract has no entrypoint {}", self.entrypoint))?;
+
+        if self.arguments.len() != function.inputs.len() {
+            bail!(
+                "{} takes {} arguments, {} were given",
+                function.name,
+                function.inputs.len(),
+                self.arguments.len()
+            );
+        }
+        let mut calldata = Vec::new();
+        for (argument, input) in self.arguments.iter().zip(&function.inputs) {
+            if input.ty == U256_TYPE {
+                let [high, low] = split_u256(argument);
+                calldata.extend([low, high]);
+            } else {
+                calldata.push(*argument);
+            }
+        }
+
+        let selector = abi::starknet_keccak(&function.name);
+        match caller.call(contract_address, selector, calldata) {
+            Ok(retdata) => abi
+                .decode_outputs(function, &retdata, state.call_stack.value_format)
+                .ok_or_else(|| anyhow!("Failed to decode the result of {}", function.name)),
+            Err(panic_data) => {
+                Ok(format!("Panicked with {}", panics::decode_panic_data(&panic_data)))
+            }
+        }
+    }
+}
+
+/// Splits the value into its high and low 128 bits.
+fn split_u256(value: &Felt) -> [Felt; 2] {
+    let bytes = value.to_bytes_be();
+    [Felt::from_bytes_be_slice(&bytes[..16]), Felt::from_bytes_be_slice(&bytes[16..])]
+}
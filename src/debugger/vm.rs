@@ -7,7 +7,7 @@ use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
 use cairo_vm::vm::hooks::StepHooks;
 use cairo_vm::vm::vm_core::VirtualMachine;
 
-use crate::CairoDebugger;
+use crate::debugger::CairoDebugger;
 
 impl StepHooks for CairoDebugger {
     fn before_first_step(
@@ -15,11 +15,12 @@ impl StepHooks for CairoDebugger {
         _vm: &mut VirtualMachine,
         _hints_data: &[Box<dyn Any>],
     ) -> Result<(), VirtualMachineError> {
+        self.request_attention();
         Ok(())
     }
 
     #[tracing::instrument(
-        skip(self, vm, _hint_processor, _exec_scopes, _hints_data, _constants),
+        skip(self, vm, _hint_processor, _exec_scopes, hints_data, _constants),
         err
     )]
     fn pre_step_instruction(
@@ -27,20 +28,37 @@ impl StepHooks for CairoDebugger {
         vm: &mut VirtualMachine,
         _hint_processor: &mut dyn HintProcessor,
         _exec_scopes: &mut ExecutionScopes,
-        _hints_data: &[Box<dyn Any>],
+        hints_data: &[Box<dyn Any>],
         _constants: &HashMap<String, starknet_types_core::felt::Felt>,
     ) -> Result<(), VirtualMachineError> {
-        self.sync_with_vm(vm).map_err(VirtualMachineError::Other)
+        self.sync_with_vm(vm, hints_data).map_err(VirtualMachineError::Other)?;
+        // Cheatcodes and syscalls are executed by hints, so instructions without any
+        // are not inspected.
+        if hints_data.is_empty() {
+            return Ok(());
+        }
+        self.inspect_hints(vm, hints_data).map_err(VirtualMachineError::Other)
     }
 
     fn post_step_instruction(
         &mut self,
-        _vm: &mut VirtualMachine,
+        vm: &mut VirtualMachine,
         _hint_processor: &mut dyn HintProcessor,
         _exec_scopes: &mut ExecutionScopes,
-        _hints_data: &[Box<dyn Any>],
+        hints_data: &[Box<dyn Any>],
         _constants: &HashMap<String, starknet_types_core::felt::Felt>,
     ) -> Result<(), VirtualMachineError> {
-        Ok(())
+        // Only hints leave work to finish, unless the steps are recorded.
+        if hints_data.is_empty() && self.state.recording.is_none() {
+            return Ok(());
+        }
+        // Values read from storage are written to memory by the hint of the instruction.
+        self.state.storage.finish_read(vm);
+        self.finish_execution_info(vm);
+        if let Some(recording) = &mut self.state.recording {
+            recording.finish_step(vm);
+        }
+        self.finish_declaration(vm).map_err(VirtualMachineError::Other)?;
+        self.finish_syscall(vm).map_err(VirtualMachineError::Other)
     }
 }
@@ -0,0 +1,311 @@
+//! Access of the debugger to the VM running the program. The debugger depends only on
+//! [`VmAccess`], rather than on the internals of `cairo-vm`, so that a change of its API
+//! is handled in the implementation below, and other runners only have to implement the trait.
+//!
+//! Aborting the execution is not part of the access: the step hooks abort it by returning
+//! an error to the VM, once the client terminates the session.
+//!
+//! The access speaks in the types below rather than in the ones of `cairo-vm`,
+//! which the implementation converts from and to.
+
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::ops::{Add, Sub};
+
+use cairo_vm::hint_processor::builtin_hint_processor::builtin_hint_processor_definition::HintProcessorData;
+use cairo_vm::hint_processor::hint_processor_utils::compute_addr_from_reference;
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use cairo_vm::vm::vm_core::VirtualMachine;
+use starknet_types_core::felt::Felt;
+
+/// Address of a memory cell: the index of its segment and the offset in it, shown as `1:5`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    pub segment_index: isize,
+    pub offset: usize,
+}
+
+/// Value of a memory cell.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Value {
+    Felt(Felt),
+    Address(Address),
+}
+
+/// Cairo 0 hint about to be executed, with the variables it refers to resolved by the runner.
+pub struct Cairo0Hint {
+    /// Python code of the hint.
+    pub code: String,
+    pub ap_tracking: ApTracking,
+    /// Addresses of the `ids` of the hint, by their names.
+    pub ids: Vec<(String, Address)>,
+}
+
+/// Tracking of the `ap` register at a Cairo 0 hint, used to find its `ids`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ApTracking {
+    pub group: usize,
+    pub offset: usize,
+}
+
+pub trait VmAccess {
+    fn pc(&self) -> Address;
+    fn ap(&self) -> Address;
+    fn fp(&self) -> Address;
+
+    /// Moves the registers, e.g. to show the state of the execution at a past step.
+    /// `ap` and `fp` are offsets in the segment they point to.
+    fn set_registers(&mut self, pc: Address, ap: usize, fp: usize);
+
+    /// Number of steps executed so far.
+    fn current_step(&self) -> usize;
+
+    /// Value of the cell, or `None` if it was not written yet.
+    fn read(&self, address: Address) -> Option<Value>;
+
+    /// Value of the cell, or `None` if it is not a written felt.
+    fn read_felt(&self, address: Address) -> Option<Felt>;
+
+    /// Value of the cell, or `None` if it is not a written pointer.
+    fn read_pointer(&self, address: Address) -> Option<Address>;
+
+    /// Values of `len` cells starting at `start`, `None` for the ones not written yet.
+    fn read_range(&self, start: Address, len: usize) -> Vec<Option<Value>>;
+
+    /// Values of `len` cells starting at `start`, or `None` if any of them is not a written felt.
+    fn read_felts(&self, start: Address, len: usize) -> Option<Vec<Felt>>;
+
+    /// Current sizes of all memory segments.
+    fn segment_sizes(&mut self) -> Vec<usize>;
+
+    /// Number of cells of the segment accessed so far.
+    fn accessed_cells(&self, segment_index: usize) -> usize;
+
+    /// Indices of the segments of the builtins.
+    fn builtin_segments(&self) -> HashSet<usize>;
+
+    /// Number of applications of each builtin so far, by the name of the builtin.
+    fn builtin_instances(&self) -> BTreeMap<String, usize>;
+
+    /// Cairo 0 hint of the data the runner passed to the step hooks,
+    /// or `None` if the runner does not execute Cairo 0 hints.
+    fn cairo_0_hint(&self, hint_data: &dyn Any) -> Option<Cairo0Hint>;
+}
+
+impl Address {
+    /// Address `offset` cells away, or `None` if its offset would be negative.
+    pub fn add_signed(self, offset: isize) -> Option<Self> {
+        Some(Self { offset: self.offset.checked_add_signed(offset)?, ..self })
+    }
+}
+
+impl From<(isize, usize)> for Address {
+    fn from((segment_index, offset): (isize, usize)) -> Self {
+        Self { segment_index, offset }
+    }
+}
+
+impl Add<usize> for Address {
+    /// `None` on an overflow of the offset.
+    type Output = Option<Address>;
+
+    fn add(self, offset: usize) -> Self::Output {
+        Some(Self { offset: self.offset.checked_add(offset)?, ..self })
+    }
+}
+
+impl Sub<usize> for Address {
+    /// `None` if the offset would be negative.
+    type Output = Option<Address>;
+
+    fn sub(self, offset: usize) -> Self::Output {
+        Some(Self { offset: self.offset.checked_sub(offset)?, ..self })
+    }
+}
+
+impl Sub for Address {
+    /// Distance between the cells, or `None` if they are in different segments
+    /// or `other` is after `self`.
+    type Output = Option<usize>;
+
+    fn sub(self, other: Address) -> Self::Output {
+        if self.segment_index != other.segment_index {
+            return None;
+        }
+        self.offset.checked_sub(other.offset)
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.segment_index, self.offset)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Felt(felt) => write!(f, "{felt}"),
+            Self::Address(address) => write!(f, "{address}"),
+        }
+    }
+}
+
+impl From<Felt> for Value {
+    fn from(felt: Felt) -> Self {
+        Self::Felt(felt)
+    }
+}
+
+impl From<Address> for Value {
+    fn from(address: Address) -> Self {
+        Self::Address(address)
+    }
+}
+
+impl From<Relocatable> for Address {
+    fn from(relocatable: Relocatable) -> Self {
+        Self { segment_index: relocatable.segment_index, offset: relocatable.offset }
+    }
+}
+
+impl From<Address> for Relocatable {
+    fn from(address: Address) -> Self {
+        Self { segment_index: address.segment_index, offset: address.offset }
+    }
+}
+
+impl From<MaybeRelocatable> for Value {
+    fn from(value: MaybeRelocatable) -> Self {
+        match value {
+            MaybeRelocatable::Int(felt) => Self::Felt(felt),
+            MaybeRelocatable::RelocatableValue(relocatable) => Self::Address(relocatable.into()),
+        }
+    }
+}
+
+impl From<Value> for MaybeRelocatable {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Felt(felt) => Self::Int(felt),
+            Value::Address(address) => Self::RelocatableValue(address.into()),
+        }
+    }
+}
+
+impl VmAccess for VirtualMachine {
+    fn pc(&self) -> Address {
+        self.get_pc().into()
+    }
+
+    fn ap(&self) -> Address {
+        self.get_ap().into()
+    }
+
+    fn fp(&self) -> Address {
+        self.get_fp().into()
+    }
+
+    fn set_registers(&mut self, pc: Address, ap: usize, fp: usize) {
+        self.set_pc(pc.into());
+        self.set_ap(ap);
+        self.set_fp(fp);
+    }
+
+    fn current_step(&self) -> usize {
+        self.get_current_step()
+    }
+
+    fn read(&self, address: Address) -> Option<Value> {
+        self.get_maybe(&Relocatable::from(address)).map(Value::from)
+    }
+
+    fn read_felt(&self, address: Address) -> Option<Felt> {
+        self.get_integer(address.into()).ok().map(Cow::into_owned)
+    }
+
+    fn read_pointer(&self, address: Address) -> Option<Address> {
+        self.get_relocatable(address.into()).ok().map(Address::from)
+    }
+
+    fn read_range(&self, start: Address, len: usize) -> Vec<Option<Value>> {
+        self.get_range(start.into(), len)
+            .into_iter()
+            .map(|cell| cell.map(|value| value.into_owned().into()))
+            .collect()
+    }
+
+    fn read_felts(&self, start: Address, len: usize) -> Option<Vec<Felt>> {
+        let felts = self.get_integer_range(start.into(), len).ok()?;
+        Some(felts.into_iter().map(Cow::into_owned).collect())
+    }
+
+    /// `cairo-vm` only exposes segment sizes once they are computed and cached at the end of the run.
+    /// We fill the cache here and restore its previous value right away so that the run is unaffected.
+    fn segment_sizes(&mut self) -> Vec<usize> {
+        let previous_sizes = self.segments.segment_used_sizes.take();
+        let sizes = self.segments.compute_effective_sizes().clone();
+        self.segments.segment_used_sizes = previous_sizes;
+        sizes
+    }
+
+    fn accessed_cells(&self, segment_index: usize) -> usize {
+        self.segments
+            .memory
+            .get_amount_of_accessed_addresses_for_segment(segment_index)
+            .unwrap_or_default()
+    }
+
+    fn builtin_segments(&self) -> HashSet<usize> {
+        self.get_builtin_runners().iter().map(|runner| runner.base()).collect()
+    }
+
+    fn builtin_instances(&self) -> BTreeMap<String, usize> {
+        self.get_builtin_runners()
+            .iter()
+            .filter_map(|runner| {
+                let instances = runner.get_used_instances(&self.segments).ok()?;
+                Some((runner.name().to_str().to_string(), instances))
+            })
+            .collect()
+    }
+
+    fn cairo_0_hint(&self, hint_data: &dyn Any) -> Option<Cairo0Hint> {
+        let data = hint_data.downcast_ref::<HintProcessorData>()?;
+        let mut ids: Vec<_> = data
+            .ids_data
+            .iter()
+            .filter_map(|(name, reference)| {
+                let address = compute_addr_from_reference(reference, self, &data.ap_tracking)?;
+                Some((name.clone(), address.into()))
+            })
+            .collect();
+        ids.sort();
+        let ap_tracking =
+            ApTracking { group: data.ap_tracking.group, offset: data.ap_tracking.offset };
+        Some(Cairo0Hint { code: data.code.clone(), ap_tracking, ids })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Address;
+
+    #[test]
+    fn arithmetic_stays_in_the_segment() {
+        let address = Address::from((1, 5));
+        assert_eq!(address + 2, Some(Address::from((1, 7))));
+        assert_eq!(address - 5, Some(Address::from((1, 0))));
+        assert_eq!(address - 6, None);
+        assert_eq!(address.add_signed(-3), Some(Address::from((1, 2))));
+        assert_eq!(Address::from((1, 9)) - address, Some(4));
+        assert_eq!(Address::from((2, 9)) - address, None);
+    }
+
+    #[test]
+    fn address_is_shown_as_segment_and_offset() {
+        assert_eq!(Address::from((-1, 3)).to_string(), "-1:3");
+    }
+}
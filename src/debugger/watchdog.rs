@@ -0,0 +1,174 @@
+//! Diagnostics for sessions that stopped making progress, e.g. because of a handler stuck
+//! on a huge value or a hint that never returns.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dap::events::{Event, OutputEventBody};
+use dap::types::OutputEventCategory;
+use serde_json::json;
+use tracing::warn;
+
+use crate::connection::EventSender;
+
+/// Upper bound on how often the watchdog checks for progress.
+const MAX_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the debugger is busy with, reported to the watchdog on every change.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Activity {
+    /// Waiting for the client, which may legitimately take forever.
+    Idle,
+    /// Handling the request with the given command.
+    Request(String),
+    /// Running the VM until the next instruction.
+    Running,
+}
+
+/// Reports activities that take longer than the timeout to the client.
+/// Stops checking once dropped.
+pub struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    timeout: Duration,
+    /// Whether the execution should be paused once the VM makes no progress for too long.
+    pause_on_timeout: bool,
+    progress: Mutex<Progress>,
+    /// Whether the recorded activity is [`Activity::Running`], in which case the steps are
+    /// only counted, so that no lock is taken for every instruction.
+    running: AtomicBool,
+    /// Steps run by the VM, compared by the watchdog thread with [`Progress::steps`].
+    steps: AtomicU64,
+    pause_requested: AtomicBool,
+    events: EventSender,
+    dropped: AtomicBool,
+}
+
+struct Progress {
+    activity: Activity,
+    since: Instant,
+    /// Value of [`Shared::steps`] at [`Self::since`].
+    steps: u64,
+    /// Whether the current activity was reported already, so that it is reported only once.
+    reported: bool,
+}
+
+impl Watchdog {
+    pub fn start(timeout: Duration, pause_on_timeout: bool, events: EventSender) -> Self {
+        let shared = Arc::new(Shared {
+            timeout,
+            pause_on_timeout,
+            progress: Mutex::new(Progress {
+                activity: Activity::Idle,
+                since: Instant::now(),
+                steps: 0,
+                reported: false,
+            }),
+            running: AtomicBool::new(false),
+            steps: AtomicU64::new(0),
+            pause_requested: AtomicBool::new(false),
+            events,
+            dropped: AtomicBool::new(false),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            let interval = (thread_shared.timeout / 4).min(MAX_CHECK_INTERVAL);
+            while !thread_shared.dropped.load(Ordering::Relaxed) {
+                thread::sleep(interval);
+                thread_shared.check();
+            }
+        });
+
+        Self { shared }
+    }
+
+    /// Records progress of the debugger. Even the same activity counts as progress,
+    /// e.g. the VM running the next instruction.
+    pub fn record(&self, activity: Activity) {
+        let running = activity == Activity::Running;
+        if running && self.shared.running.load(Ordering::Relaxed) {
+            self.shared.steps.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let mut progress = self.shared.progress.lock().expect("Watchdog lock poisoned");
+        *progress = Progress {
+            activity,
+            since: Instant::now(),
+            steps: self.shared.steps.load(Ordering::Relaxed),
+            reported: false,
+        };
+        self.shared.running.store(running, Ordering::Relaxed);
+    }
+
+    /// Whether the watchdog asked to pause the execution since the last call.
+    pub fn take_pause_request(&self) -> bool {
+        self.shared.pause_requested.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.dropped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Shared {
+    fn check(&self) {
+        let mut progress = self.progress.lock().expect("Watchdog lock poisoned");
+        let steps = self.steps.load(Ordering::Relaxed);
+        if steps != progress.steps {
+            // The VM made progress since the previous check.
+            progress.since = Instant::now();
+            progress.steps = steps;
+            progress.reported = false;
+            return;
+        }
+        if progress.activity == Activity::Idle
+            || progress.reported
+            || progress.since.elapsed() < self.timeout
+        {
+            return;
+        }
+        progress.reported = true;
+
+        let what = match &progress.activity {
+            Activity::Request(command) => format!("handling the {command} request"),
+            Activity::Running => "running the program".to_string(),
+            Activity::Idle => unreachable!("Idle debugger is never reported"),
+        };
+        // The VM cannot be interrupted, so the execution is paused once it gets to the next
+        // instruction, if ever.
+        let pause = self.pause_on_timeout && progress.activity == Activity::Running;
+        if pause {
+            self.pause_requested.store(true, Ordering::Relaxed);
+        }
+        warn!("No progress for {}s while {what}", self.timeout.as_secs_f64());
+
+        let output = format!(
+            "Warning: the debugger made no progress for {}s while {what}{}\n",
+            self.timeout.as_secs_f64(),
+            if pause { ", the execution will be paused at the next instruction" } else { "" },
+        );
+        let event = Event::Output(OutputEventBody {
+            category: Some(OutputEventCategory::Stderr),
+            output,
+            group: None,
+            variables_reference: None,
+            source: None,
+            line: None,
+            column: None,
+            data: Some(json!({
+                "event": "watchdogTimeout",
+                "timeoutSecs": self.timeout.as_secs_f64(),
+            })),
+        });
+        if let Err(err) = self.events.send(event) {
+            warn!("Failed to report the watchdog timeout: {err:#}");
+        }
+    }
+}
@@ -1,5 +1,27 @@
 mod connection;
 mod debugger;
+mod session;
+#[cfg(feature = "standalone")]
+mod standalone;
 
-pub use debugger::CairoDebugger;
+pub use connection::in_memory::{InMemoryClient, InMemoryListener};
+pub use connection::protocol_trace::replay_protocol_trace;
+pub use connection::{ClientListener, ClientStreams, DebugServer, Transport};
+pub use debugger::call_trace::{CallResources, CallResult, ContractCall};
 pub use debugger::context::CasmDebugInfo;
+pub use debugger::deployments::DeployedContract;
+pub use debugger::errors::DebuggerError;
+pub use debugger::execution_info::ExecutionInfo;
+pub use debugger::fork::{ForkOrigin, ForkRead, ForkReadKind};
+pub use debugger::fuzzer::{FuzzArgument, FuzzIteration};
+pub use debugger::panics::ExpectedPanic;
+pub use debugger::settings::set_log_level_handler;
+pub use debugger::storage::StorageReader;
+pub use debugger::view_call::ViewCaller;
+pub use session::{Debugger, DebuggerBuilder, PreloadedClass};
+#[cfg(feature = "standalone")]
+pub use standalone::output::forward_program_output;
+#[cfg(feature = "standalone")]
+pub use standalone::{
+    diff_runs, run_contract_call, run_post_mortem, run_profiler_trace, run_standalone,
+};
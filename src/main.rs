@@ -0,0 +1,234 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use cairo_debugger::{
+    DebugServer, Transport, diff_runs, forward_program_output, replay_protocol_trace,
+    run_contract_call, run_post_mortem, run_profiler_trace, run_standalone, set_log_level_handler,
+};
+use clap::{Args, Parser, Subcommand};
+use starknet_types_core::felt::Felt;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, reload};
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a Cairo program under the debugger.
+    Run {
+        /// Path to the Sierra program, compiled with debug info, to an executable built
+        /// by Scarb with `sierra = true`, or to a Cairo 0 program compiled with debug info.
+        sierra_path: PathBuf,
+        /// Name of the function to run, or a suffix of its full path. For executables,
+        /// the `#[executable]` function. For Cairo 0 programs, a function of the main scope.
+        #[arg(long, default_value = "::main")]
+        function: String,
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+    /// Call an entrypoint of a contract class under the debugger, with the calldata
+    /// from the launch configuration.
+    Call {
+        /// Path to the contract class, e.g. `target/dev/hello_HelloStarknet.contract_class.json`.
+        contract_class_path: PathBuf,
+        /// Name or selector of the entrypoint.
+        #[arg(long)]
+        entrypoint: String,
+        /// Address of the called contract.
+        #[arg(long, default_value = "0x1", value_parser = parse_felt)]
+        contract_address: Felt,
+        /// Address of the caller, which is also the account sending the transaction.
+        #[arg(long, default_value = "0x0", value_parser = parse_felt)]
+        caller_address: Felt,
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+    /// Debug a finished run of a Cairo program from the trace and memory files written by cairo-vm,
+    /// without running it again.
+    PostMortem {
+        /// Path to the program that was run, like for `run`.
+        program_path: PathBuf,
+        /// Name of the function that was run, like for `run`.
+        #[arg(long, default_value = "::main")]
+        function: String,
+        /// Path to the trace file, e.g. written with `--trace_file`.
+        #[arg(long)]
+        trace: PathBuf,
+        /// Path to the memory file, e.g. written with `--memory_file`.
+        #[arg(long)]
+        memory: PathBuf,
+        /// The files were exported with `:export` from a session of `run`, instead of being
+        /// written by a proof-mode run.
+        #[arg(long)]
+        exported: bool,
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+    /// Compare two finished runs of a Cairo program, each from its trace and memory files like for
+    /// `post-mortem`, and print the first step at which they differ.
+    Diff {
+        /// Path to the program that was run, like for `run`.
+        program_path: PathBuf,
+        /// Name of the function that was run, like for `run`.
+        #[arg(long, default_value = "::main")]
+        function: String,
+        /// Paths to the trace files of both runs.
+        #[arg(long, num_args = 2, required = true)]
+        trace: Vec<PathBuf>,
+        /// Paths to the memory files of both runs.
+        #[arg(long, num_args = 2, required = true)]
+        memory: Vec<PathBuf>,
+        /// Like for `post-mortem`.
+        #[arg(long)]
+        exported: bool,
+    },
+    /// Replay a test from the trace saved by `snforge test --save-trace-data`, e.g. in CI,
+    /// without running it again. Values of variables are not saved in the trace.
+    Replay {
+        /// Path to the trace, e.g. `snfoundry_trace/hello::tests::test_increase_balance.json`.
+        trace: PathBuf,
+        /// Path to the Sierra program of the test, if it is not where the trace says.
+        #[arg(long)]
+        sierra_path: Option<PathBuf>,
+        /// Name of the test, if the trace does not name it.
+        #[arg(long)]
+        function: Option<String>,
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+    /// Print the output of a program run by the debugger. Started by the debugger in a terminal
+    /// of the client, when requested in the launch configuration.
+    #[command(hide = true)]
+    Output {
+        /// Name of the pipe the debugger sends the output to.
+        #[arg(long)]
+        pipe: String,
+    },
+}
+
+/// Where and how the debugger waits for the client.
+#[derive(Args)]
+struct ServerArgs {
+    /// Port to wait for the client on, so that it can attach. Assigned by the OS if not set.
+    #[arg(long, visible_alias = "port", conflicts_with_all = ["socket", "pipe"])]
+    debug_port: Option<u16>,
+    /// Address to wait for the client on. Use with `--auth-token` if it is not a loopback one.
+    #[arg(long, default_value = "127.0.0.1", conflicts_with_all = ["socket", "pipe"])]
+    bind_address: IpAddr,
+    /// Token the client has to send in the `authToken` field of the arguments of its first request.
+    /// Only the first client that sends it is accepted, unless `--reuse-auth-token` is set.
+    #[arg(long, env = "CAIRO_DEBUGGER_AUTH_TOKEN")]
+    auth_token: Option<String>,
+    /// Accepts any number of clients with `--auth-token`, e.g. the next client of an attached
+    /// program once the current one disconnects.
+    #[arg(long, requires = "auth_token")]
+    reuse_auth_token: bool,
+    /// Seconds to wait for a client before failing. Waits forever if not set.
+    #[arg(long)]
+    accept_timeout: Option<u64>,
+    /// How many more times to wait for `--accept-timeout` seconds before failing.
+    #[arg(long, default_value_t = 0, requires = "accept_timeout")]
+    accept_retries: u32,
+    /// Path of a Unix domain socket to wait for the client on, instead of a TCP port.
+    #[arg(long, conflicts_with = "pipe")]
+    socket: Option<PathBuf>,
+    /// Name of a named pipe (a socket in the abstract namespace on Linux) to wait
+    /// for the client on, instead of a TCP port.
+    #[arg(long)]
+    pipe: Option<String>,
+    /// Path of a file to capture every message exchanged with the client to, one JSON per line,
+    /// e.g. to attach to a bug report. Auth tokens are left out.
+    #[arg(long)]
+    protocol_trace: Option<PathBuf>,
+    /// Path of a file captured with `--protocol-trace`, whose requests are sent to the debugger
+    /// instead of waiting for a client, to reproduce the captured session.
+    #[arg(long, conflicts_with_all = ["debug_port", "socket", "pipe", "auth_token"])]
+    replay_protocol_trace: Option<PathBuf>,
+}
+
+impl ServerArgs {
+    fn bind(self) -> Result<DebugServer> {
+        let mut server = match self.replay_protocol_trace {
+            Some(trace) => DebugServer::with_listener(replay_protocol_trace(&trace)?),
+            None => {
+                let transport = match (self.socket, self.pipe) {
+                    (Some(path), _) => Transport::UnixSocket(path),
+                    (_, Some(name)) => Transport::NamedPipe(name),
+                    (None, None) => {
+                        Transport::Tcp { address: self.bind_address, port: self.debug_port }
+                    }
+                };
+                DebugServer::bind(&transport)?
+            }
+        }
+        .with_accept_retries(self.accept_retries);
+        if let Some(protocol_trace) = &self.protocol_trace {
+            server = server.with_protocol_trace(protocol_trace)?;
+        }
+        if let Some(accept_timeout) = self.accept_timeout {
+            server = server.with_accept_timeout(Duration::from_secs(accept_timeout));
+        }
+        if let Some(auth_token) = self.auth_token {
+            server = if self.reuse_auth_token {
+                server.with_reusable_auth_token(auth_token)
+            } else {
+                server.with_auth_token(auth_token)
+            };
+        }
+        Ok(server)
+    }
+}
+
+fn parse_felt(value: &str) -> Result<Felt, String> {
+    if value.starts_with("0x") { Felt::from_hex(value) } else { Felt::from_dec_str(value) }
+        .map_err(|_| format!("Invalid felt: {value}"))
+}
+
+fn main() -> Result<()> {
+    // Stdout is reserved for the communication with the client.
+    let (filter, filter_handle) = reload::Layer::new(EnvFilter::from_default_env());
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .init();
+    // Clients change the filter with the `logLevel` setting.
+    set_log_level_handler(move |level| Ok(filter_handle.reload(EnvFilter::try_new(level)?)?));
+
+    match Cli::parse().command {
+        Command::Run { sierra_path, function, server } => {
+            run_standalone(&sierra_path, &function, &server.bind()?)
+        }
+        Command::Call {
+            contract_class_path,
+            entrypoint,
+            contract_address,
+            caller_address,
+            server,
+        } => run_contract_call(
+            &contract_class_path,
+            &entrypoint,
+            contract_address,
+            caller_address,
+            &server.bind()?,
+        ),
+        Command::PostMortem { program_path, function, trace, memory, exported, server } => {
+            run_post_mortem(&program_path, &function, &trace, &memory, !exported, &server.bind()?)
+        }
+        Command::Diff { program_path, function, trace, memory, exported } => {
+            let runs = [0, 1].map(|run| (trace[run].as_path(), memory[run].as_path()));
+            println!("{}", diff_runs(&program_path, &function, runs, !exported)?);
+            Ok(())
+        }
+        Command::Replay { trace, sierra_path, function, server } => {
+            run_profiler_trace(&trace, sierra_path.as_deref(), function.as_deref(), &server.bind()?)
+        }
+        Command::Output { pipe } => forward_program_output(&pipe),
+    }
+}
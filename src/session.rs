@@ -0,0 +1,313 @@
+//! Facade for execution tools embedding the debugger, e.g. test runners, so that they do not
+//! depend on how the debugger is set up internally.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use cairo_vm::hint_processor::hint_processor_definition::HintProcessor;
+use cairo_vm::types::exec_scope::ExecutionScopes;
+use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
+use cairo_vm::vm::hooks::StepHooks;
+use cairo_vm::vm::vm_core::VirtualMachine;
+use dap::events::Event;
+use serde_json::Value;
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::CairoDebugger;
+use crate::{
+    CallResult, CasmDebugInfo, ContractCall, DebugServer, DeployedContract, ExecutionInfo,
+    ExpectedPanic, ForkOrigin, ForkRead, FuzzIteration, StorageReader, Transport, ViewCaller,
+};
+
+/// Debug session of a single program, installed as the step hooks of the VM running it.
+///
+/// Execution tools configure it with [`Debugger::builder`], report what happens
+/// around the VM, e.g. contract calls, through its methods, and call [`Self::shut_down`]
+/// once the VM is done with it.
+pub struct Debugger {
+    debugger: CairoDebugger,
+}
+
+/// Contract class known to the tool before the execution starts, e.g. a declared one.
+pub struct PreloadedClass {
+    pub class_hash: Felt,
+    /// Sierra program of the class, with debug info.
+    pub sierra_path: PathBuf,
+    pub casm_debug_info: CasmDebugInfo,
+    /// ABI of the class, if the tool has it rather than the Sierra program.
+    pub abi: Option<Value>,
+}
+
+/// Configuration of a [`Debugger`], created with [`Debugger::builder`]
+/// and started with [`Self::start`], once the execution tool knows the classes it may run.
+pub struct DebuggerBuilder {
+    sierra_path: PathBuf,
+    casm_debug_info: CasmDebugInfo,
+    transport: Transport,
+    server: Option<DebugServer>,
+    classes: Vec<PreloadedClass>,
+    /// Sources and 1-indexed lines.
+    breakpoints: Vec<(PathBuf, usize)>,
+    event_listeners: Vec<Box<dyn Fn(&Event) + Send>>,
+}
+
+impl Debugger {
+    /// Starts configuring the session of the program loaded from `sierra_path`.
+    pub fn builder(sierra_path: &Path, casm_debug_info: CasmDebugInfo) -> DebuggerBuilder {
+        DebuggerBuilder {
+            sierra_path: sierra_path.to_path_buf(),
+            casm_debug_info,
+            transport: Transport::default(),
+            server: None,
+            classes: Vec::new(),
+            breakpoints: Vec::new(),
+            event_listeners: Vec::new(),
+        }
+    }
+
+    /// Registers the Sierra program of a contract class, so that the contract can be debugged
+    /// once it is executed, see [`Self::enter_class`]. Meant for execution tools,
+    /// which know the programs of the declared classes.
+    ///
+    /// With the `abi` of the class, arguments of its entrypoints are decoded into typed values.
+    pub fn register_class(&mut self, class: PreloadedClass) -> Result<()> {
+        self.debugger.register_class(
+            class.class_hash,
+            &class.sierra_path,
+            class.casm_debug_info,
+            class.abi.as_ref(),
+        )
+    }
+
+    /// Whether the class was registered, so that it is registered once per session.
+    pub fn is_class_registered(&self, class_hash: &Felt) -> bool {
+        self.debugger.is_class_registered(class_hash)
+    }
+
+    /// Ends the session once the execution is over, i.e. the VM stopped, on its own or aborted
+    /// at the client's request. The client gets the outcome of the run, then the terminated
+    /// and exited events, and its disconnect request sent in response is answered, before all
+    /// of these are delivered and the connection is closed.
+    ///
+    /// Execution tools should call it once they take the debugger back from the VM, so that
+    /// failures are not only logged, as they are when the debugger is dropped instead.
+    pub fn shut_down(self) -> Result<()> {
+        self.debugger.shut_down()
+    }
+
+    /// Switches to a registered class once a contract of that class starts being executed,
+    /// e.g. on a contract call. Execution tools running the contract in a separate VM
+    /// should move the debugger to its hooks, and call [`Self::exit_class`] once it finishes.
+    ///
+    /// Until then, stack traces, breakpoints and variables refer to the contract.
+    /// The call is recorded in the call trace, available with the `:calls` custom request.
+    pub fn enter_class(&mut self, call: ContractCall) -> Result<()> {
+        self.debugger.enter_class(call)
+    }
+
+    /// Switches back to the caller of the contract that finished executing.
+    ///
+    /// The retdata of the call is decoded with the ABI of the class and reported in the output,
+    /// as well as shown in the caller until it moves on. Failures are reported with the decoded
+    /// panic data, and the execution stops in the caller if the "Failed contract calls"
+    /// exception breakpoint is enabled.
+    pub fn exit_class(&mut self, result: CallResult) -> Result<()> {
+        self.debugger.exit_class(result)
+    }
+
+    /// Starts debugging the next test of a session covering multiple tests, e.g. all tests
+    /// of a package run one after another with this debugger. Each test is shown as a separate
+    /// thread, named after the full path of the test, e.g. `hello::tests::test_increase_balance`.
+    pub fn start_test(&mut self, test_path: &str) -> Result<()> {
+        self.debugger.start_test(test_path)
+    }
+
+    /// Finishes the test started with [`Self::start_test`], whose thread exits.
+    pub fn finish_test(&mut self) -> Result<()> {
+        self.debugger.finish_test()
+    }
+
+    /// Lets the client re-run the current test with a restart request or by evaluating `:rerun`,
+    /// without restarting the execution tool. The execution is aborted then, and the tool,
+    /// seeing [`Self::take_rerun_request`] return `true`, executes the same test again
+    /// with this debugger, without calling [`Self::start_test`] or [`Self::finish_test`].
+    pub fn enable_test_rerun(&mut self) -> Result<()> {
+        self.debugger.enable_test_rerun()
+    }
+
+    /// Returns whether the client asked for a re-run of the current test, once its execution
+    /// was aborted, and prepares the debugger for the re-run. Breakpoints, registered classes
+    /// and the session are kept.
+    pub fn take_rerun_request(&mut self) -> Result<bool> {
+        self.debugger.take_rerun_request()
+    }
+
+    /// Marks the test being executed as a `#[should_panic]` test. Its expected panics do not stop
+    /// the execution with the panic exception breakpoint, and count as a success in the exit code.
+    pub fn expect_panic(&mut self, expected: ExpectedPanic) {
+        self.debugger.expect_panic(expected);
+    }
+
+    /// Reports the start of an iteration of a fuzz test, right before the test is executed
+    /// with the generated arguments, which are shown in the Fuzzer scope. Tools that replay
+    /// a failing iteration report it with [`FuzzIteration::is_failure_replay`] set,
+    /// which makes the execution stop at the start of the replay.
+    pub fn start_fuzz_iteration(&mut self, iteration: FuzzIteration) -> Result<()> {
+        self.debugger.start_fuzz_iteration(iteration)
+    }
+
+    /// Reports the block and transaction seen by the contract being executed, e.g. once it was
+    /// entered with [`Self::enter_class`], including the changes made by cheatcodes. They are shown
+    /// in the Execution info scope, and learned from the contract itself once it asks for them.
+    pub fn report_execution_info(&mut self, info: ExecutionInfo) {
+        self.debugger.report_execution_info(info);
+    }
+
+    /// Reports a contract deployed during the execution, listed with the `:contracts` custom
+    /// request together with the contracts called so far.
+    pub fn report_deployment(&mut self, contract: DeployedContract) -> Result<()> {
+        self.debugger.report_deployment(contract)
+    }
+
+    /// Reports the network a fork test runs against, saved with the exported trace
+    /// so that the execution can be replayed against the same state.
+    pub fn report_fork_origin(&mut self, origin: ForkOrigin) {
+        self.debugger.report_fork_origin(origin);
+    }
+
+    /// Reports a read of the state served from the forked network in a fork test, e.g. by a state
+    /// reader of the execution tool. Such reads are shown in the output with their timing,
+    /// and the execution stops after the first one if the client asked for it.
+    pub fn report_fork_read(&mut self, read: ForkRead) -> Result<()> {
+        self.debugger.report_fork_read(read)
+    }
+
+    /// Lets users inspect storage variables of the contracts being executed, which they have not
+    /// accessed yet. Without it, the Storage scope lists only the values read and written so far.
+    pub fn set_storage_reader(&mut self, reader: impl StorageReader + 'static) {
+        self.debugger.set_storage_reader(reader);
+    }
+
+    /// Lets users call read-only entrypoints from the debug console, e.g. `self.get_balance()`
+    /// or `0x1234.balance_of(0x5678)`, with the results decoded with the ABIs of registered classes.
+    pub fn set_view_caller(&mut self, caller: impl ViewCaller + 'static) {
+        self.debugger.set_view_caller(caller);
+    }
+
+    /// Opens a child session for a contract called by the debugged program, e.g. by a test.
+    /// Meant for execution tools, which run the contract with its own Sierra program
+    /// and should use the returned debugger for that run instead of this one.
+    ///
+    /// The client is asked to attach to the child debugger with the `startDebugging`
+    /// reverse request, inheriting the configuration of this session. Returns `None` if
+    /// `debugContractCalls` is not set in the launch configuration or the client does not support
+    /// child sessions, in which case the contract is run without debugging.
+    pub fn start_child_session(
+        &self,
+        contract_name: &str,
+        sierra_path: &Path,
+        casm_debug_info: CasmDebugInfo,
+    ) -> Result<Option<Self>> {
+        let debugger =
+            self.debugger.start_child_session(contract_name, sierra_path, casm_debug_info)?;
+        Ok(debugger.map(|debugger| Self { debugger }))
+    }
+}
+
+impl DebuggerBuilder {
+    /// Channel the debugger waits for a client on. Ignored if a [`Self::server`] is set.
+    #[must_use]
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Listener shared with the sessions of other programs, e.g. of tests run in parallel.
+    #[must_use]
+    pub fn server(mut self, server: DebugServer) -> Self {
+        self.server = Some(server);
+        self
+    }
+
+    /// Registers the class once the client configured the session, so that breakpoints
+    /// in its files are verified right away.
+    #[must_use]
+    pub fn class(mut self, class: PreloadedClass) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    /// Breakpoint set on behalf of the user, e.g. from the command line of the tool,
+    /// in addition to the ones set by the client. `line` is 1-indexed.
+    #[must_use]
+    pub fn breakpoint(mut self, source: impl Into<PathBuf>, line: usize) -> Self {
+        self.breakpoints.push((source.into(), line));
+        self
+    }
+
+    /// Called with each event sent to the client, e.g. each stop of the execution.
+    #[must_use]
+    pub fn on_event(mut self, listener: impl Fn(&Event) + Send + 'static) -> Self {
+        self.event_listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Waits for a client and lets it configure the session.
+    pub fn start(self) -> Result<Debugger> {
+        let server = match self.server {
+            Some(server) => server,
+            None => DebugServer::bind(&self.transport)?,
+        };
+        let mut debugger = CairoDebugger::connect_and_initialize_with_server(
+            &self.sierra_path,
+            self.casm_debug_info,
+            &server,
+        )?;
+        for listener in self.event_listeners {
+            debugger.add_event_listener(listener);
+        }
+
+        let mut session = Debugger { debugger };
+        for class in self.classes {
+            session.register_class(class)?;
+        }
+        for (source, line) in &self.breakpoints {
+            session.debugger.set_breakpoint(source, *line)?;
+        }
+        Ok(session)
+    }
+}
+
+impl StepHooks for Debugger {
+    fn before_first_step(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hints_data: &[Box<dyn Any>],
+    ) -> Result<(), VirtualMachineError> {
+        self.debugger.before_first_step(vm, hints_data)
+    }
+
+    fn pre_step_instruction(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_processor: &mut dyn HintProcessor,
+        exec_scopes: &mut ExecutionScopes,
+        hints_data: &[Box<dyn Any>],
+        constants: &HashMap<String, Felt>,
+    ) -> Result<(), VirtualMachineError> {
+        self.debugger.pre_step_instruction(vm, hint_processor, exec_scopes, hints_data, constants)
+    }
+
+    fn post_step_instruction(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_processor: &mut dyn HintProcessor,
+        exec_scopes: &mut ExecutionScopes,
+        hints_data: &[Box<dyn Any>],
+        constants: &HashMap<String, Felt>,
+    ) -> Result<(), VirtualMachineError> {
+        self.debugger.post_step_instruction(vm, hint_processor, exec_scopes, hints_data, constants)
+    }
+}
@@ -0,0 +1,456 @@
+//! Standalone mode, in which the debugger runs the Cairo program itself,
+//! instead of being embedded in an execution tool.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::{Context, Result, anyhow};
+use cairo_lang_runner::RunnableBuilder;
+use cairo_lang_runner::casm_run::EntryCodeConfig;
+use cairo_lang_sierra::program::{Function, Program as SierraProgram};
+use cairo_lang_sierra_to_casm::metadata::MetadataComputationConfig;
+use cairo_vm::hint_processor::builtin_hint_processor::builtin_hint_processor_definition::BuiltinHintProcessor;
+use cairo_vm::hint_processor::hint_processor_definition::HintProcessor;
+use cairo_vm::serde::deserialize_program::{
+    ApTracking, FlowTrackingData, HintParams, ReferenceManager,
+};
+use cairo_vm::types::builtin_name::BuiltinName;
+use cairo_vm::types::exec_scope::ExecutionScopes;
+use cairo_vm::types::layout_name::LayoutName;
+use cairo_vm::types::program::Program;
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
+use cairo_vm::vm::hooks::StepHooks;
+use cairo_vm::vm::runners::cairo_runner::CairoRunner;
+use cairo_vm::vm::vm_core::VirtualMachine;
+use starknet_types_core::felt::Felt;
+use tracing::trace;
+
+use crate::debugger::CairoDebugger;
+use crate::debugger::context::artifact::{self, ArtifactKind, LoadedProgram};
+use crate::debugger::context::{Context as ProgramContext, cairo0};
+use crate::debugger::post_mortem::{self, Divergence, ProfilerTrace};
+use crate::debugger::replay_metadata::ReplayMetadata;
+use crate::standalone::hint_processor::StandaloneHintProcessor;
+use crate::standalone::output::program_output;
+use crate::standalone::starknet::StarknetEnvironment;
+use crate::{CasmDebugInfo, DebugServer};
+
+mod hint_processor;
+pub mod output;
+mod starknet;
+
+/// Runs `function` of the Sierra program under the debugger.
+///
+/// The program is compiled to CASM here, so only the Sierra program with debug info is needed.
+/// For executables built by Scarb, the Sierra program built next to them is run instead,
+/// starting from the wrapper of the executable function, like `scarb execute` does.
+/// Arguments of the function are taken from the launch configuration. When the program contains
+/// multiple tests, the `testFilter` launch argument chooses the one run instead of `function`.
+///
+/// Cairo 0 programs are run by the VM as they are, starting from `function`, which takes
+/// no arguments, with hints executed by the VM's builtin hint processor.
+pub fn run_standalone(sierra_path: &Path, function: &str, server: &DebugServer) -> Result<()> {
+    if artifact::kind_of(sierra_path)? == ArtifactKind::Cairo0 {
+        return run_cairo0_program(sierra_path, function, server);
+    }
+    let LoadedProgram { program: sierra_program, kind, .. } = artifact::load_program(sierra_path)?;
+    let function = entry_function(kind, function);
+    run_function(sierra_path, sierra_program.program, &function, None, server)
+}
+
+/// Calls `entrypoint` of the contract class under the debugger, given either its name
+/// or its selector, like a transaction sent by `caller_address` to the contract deployed
+/// at `contract_address` would. The calldata is taken from the launch configuration.
+///
+/// Only the called contract is executed, in a minimal environment with empty storage,
+/// so calls to other contracts fail.
+pub fn run_contract_call(
+    contract_class_path: &Path,
+    entrypoint: &str,
+    contract_address: Felt,
+    caller_address: Felt,
+    server: &DebugServer,
+) -> Result<()> {
+    let LoadedProgram { program: sierra_program, entry_points, .. } =
+        artifact::load_program(contract_class_path)?;
+    let entry_points = entry_points
+        .ok_or_else(|| anyhow!("{} is not a contract class", contract_class_path.display()))?;
+    let (selector, function_idx) = artifact::find_entry_point(&entry_points, entrypoint)?;
+    let function = sierra_program
+        .program
+        .funcs
+        .get(function_idx)
+        .and_then(|function| function.id.debug_name.as_ref())
+        .ok_or_else(|| anyhow!("Function of the entrypoint {entrypoint} has no debug name"))?
+        .to_string();
+
+    let starknet = StarknetEnvironment::new(contract_address, caller_address, selector);
+    run_function(contract_class_path, sierra_program.program, &function, Some(starknet), server)
+}
+
+/// Debugs a finished run of `function` of the program, loaded from the trace and memory files
+/// written by cairo-vm, e.g. with `--trace_file` and `--memory_file` in proof mode,
+/// without executing it again. Sierra programs are compiled here like in [`run_standalone`],
+/// which has to give the same code as the one that was run. Unless `proof_mode` is set,
+/// the files are expected to be exported from [`run_standalone`], whose entry code differs.
+/// The inputs saved next to exported traces are checked to match, e.g. the program.
+pub fn run_post_mortem(
+    program_path: &Path,
+    function: &str,
+    trace_path: &Path,
+    memory_path: &Path,
+    proof_mode: bool,
+    server: &DebugServer,
+) -> Result<()> {
+    let trace = post_mortem::read_trace(trace_path)?;
+    let vm = post_mortem::load_vm(memory_path, &trace)?;
+    let metadata = ReplayMetadata::read(trace_path)?;
+    if let Some(metadata) = &metadata {
+        metadata.verify(program_path)?;
+    }
+
+    let debugger = if artifact::kind_of(program_path)? == ArtifactKind::Cairo0 {
+        let casm_debug_info = cairo0::load(program_path)?.casm_debug_info;
+        CairoDebugger::connect_and_initialize_with_server(program_path, casm_debug_info, server)?
+    } else {
+        let LoadedProgram { program: sierra_program, kind, .. } =
+            artifact::load_program(program_path)?;
+        connect_to_compiled(
+            program_path,
+            sierra_program.program,
+            &entry_function(kind, function),
+            Some(post_mortem_entry_code(proof_mode)),
+            server,
+        )?
+    };
+
+    debugger.debug_post_mortem(&trace, vm, metadata)
+}
+
+/// Compares two finished runs of `function` of the program, each loaded from its trace and memory
+/// files like in [`run_post_mortem`], and describes the first step at which they differ,
+/// e.g. to find the source of nondeterminism or of a regression between compiler versions.
+pub fn diff_runs(
+    program_path: &Path,
+    function: &str,
+    runs: [(&Path, &Path); 2],
+    proof_mode: bool,
+) -> Result<String> {
+    let ctx = if artifact::kind_of(program_path)? == ArtifactKind::Cairo0 {
+        ProgramContext::new(program_path, cairo0::load(program_path)?.casm_debug_info)?
+    } else {
+        let LoadedProgram { program: sierra_program, kind, .. } =
+            artifact::load_program(program_path)?;
+        let builder = compile(sierra_program.program)?;
+        let mut ctx = ProgramContext::new(program_path, casm_debug_info(&builder))?;
+        let func = builder
+            .find_function(&entry_function(kind, function))
+            .map_err(|err| anyhow!("{err}"))?;
+        ctx.offset_pcs(header_size(&builder, func, post_mortem_entry_code(proof_mode))?);
+        ctx
+    };
+
+    let [(first_trace, first_memory), (second_trace, second_memory)] = runs;
+    let traces = [post_mortem::read_trace(first_trace)?, post_mortem::read_trace(second_trace)?];
+    let vms = [
+        post_mortem::load_vm(first_memory, &traces[0])?,
+        post_mortem::load_vm(second_memory, &traces[1])?,
+    ];
+    let Some(Divergence { step, reason, pcs }) =
+        post_mortem::first_divergence([&traces[0], &traces[1]], [&vms[0], &vms[1]])
+    else {
+        return Ok(format!("The runs are the same, {} steps long", traces[0].len()));
+    };
+
+    let mut report = format!("The runs diverge at step {step}: {reason}");
+    for (run, pc) in ["First", "Second"].iter().zip(pcs) {
+        if let Some(pc) = pc {
+            let location = ctx.describe_location(ctx.statement_idx_for_pc(pc));
+            write!(report, "\n{run} run: {location} (pc {pc})")?;
+        }
+    }
+    // Different inputs, e.g. another fuzz seed, explain the divergence without any bug.
+    if let (Some(first), Some(second)) =
+        (ReplayMetadata::read(first_trace)?, ReplayMetadata::read(second_trace)?)
+    {
+        for difference in first.differences(&second) {
+            write!(report, "\nDifferent input {difference}")?;
+        }
+    }
+    Ok(report)
+}
+
+/// Replays the execution of a test saved by snforge for cairo-profiler, with `--save-trace-data`,
+/// without executing it again, e.g. to debug a test that failed in CI. Only the steps are known,
+/// so the debugger can go through the code and the calls, but values of variables are unknown.
+///
+/// The Sierra program saved in the trace, or `sierra_path` if set, is compiled here, which has to
+/// give the same code as the one snforge ran.
+pub fn run_profiler_trace(
+    trace_path: &Path,
+    sierra_path: Option<&Path>,
+    function: Option<&str>,
+    server: &DebugServer,
+) -> Result<()> {
+    let ProfilerTrace {
+        sierra_path: saved_sierra_path,
+        function: test,
+        run_with_call_header,
+        trace,
+    } = post_mortem::read_profiler_trace(trace_path)?;
+    let sierra_path = sierra_path.unwrap_or(&saved_sierra_path);
+    let function = function.or(test.as_deref()).ok_or_else(|| {
+        anyhow!("{} does not name the test, choose it with --function", trace_path.display())
+    })?;
+    let vm = post_mortem::rebuild_frames(&trace)?;
+
+    let LoadedProgram { program: sierra_program, .. } = artifact::load_program(sierra_path)?;
+    // Tests are run from the entry code calling them, unless the trace says otherwise.
+    let entry_code_config = run_with_call_header.then(EntryCodeConfig::testing);
+    let debugger = connect_to_compiled(
+        sierra_path,
+        sierra_program.program,
+        function,
+        entry_code_config,
+        server,
+    )?;
+
+    debugger.debug_post_mortem(&trace, vm, None)
+}
+
+fn run_cairo0_program(program_path: &Path, function: &str, server: &DebugServer) -> Result<()> {
+    let bytes = fs::read(program_path)
+        .with_context(|| format!("Failed to read {}", program_path.display()))?;
+    // Functions of Cairo 0 programs are looked up in the main scope, e.g. `__main__`.
+    let program = Program::from_bytes(&bytes, Some(function.trim_start_matches("::")))?;
+    let casm_debug_info = cairo0::load(program_path)?.casm_debug_info;
+
+    let debugger =
+        CairoDebugger::connect_and_initialize_with_server(program_path, casm_debug_info, server)?;
+    let mut hint_processor = BuiltinHintProcessor::new_empty();
+
+    let mut runner = CairoRunner::new(&program, LayoutName::all_cairo, None, false, false, false)?;
+    let end = runner.initialize(false)?;
+    run_until_pc(&mut runner, end, &mut hint_processor, debugger)
+}
+
+fn run_function(
+    sierra_path: &Path,
+    sierra_program: SierraProgram,
+    function: &str,
+    starknet: Option<StarknetEnvironment>,
+    server: &DebugServer,
+) -> Result<()> {
+    let builder = compile(sierra_program)?;
+    let casm_debug_info = casm_debug_info(&builder);
+
+    // The test to run may be chosen in the launch configuration, which is known only once
+    // the client configured the session.
+    let mut debugger =
+        CairoDebugger::connect_and_initialize_with_server(sierra_path, casm_debug_info, server)?;
+    let function = debugger.selected_test().unwrap_or(function).to_string();
+
+    let func = builder.find_function(&function).map_err(|err| anyhow!("{err}"))?;
+    let (assembled_program, builtins) =
+        builder.assemble_function_program(func).map_err(|err| anyhow!("{err}"))?;
+
+    debugger.offset_program(header_size(&builder, func, EntryCodeConfig::testing())?);
+
+    // Builtin names come from the VM used by the compiler, which is not necessarily ours.
+    let builtins = builtins
+        .iter()
+        .map(|builtin| {
+            BuiltinName::from_str(builtin.to_str())
+                .ok_or_else(|| anyhow!("Unsupported builtin: {}", builtin.to_str()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let data: Vec<MaybeRelocatable> = assembled_program
+        .bytecode
+        .iter()
+        .map(|value| MaybeRelocatable::from(Felt::from(value)))
+        .collect();
+    // The hint processor finds hints to execute by their pc.
+    let hints = assembled_program
+        .hints
+        .iter()
+        .map(|(pc, _)| {
+            let hint_params = HintParams {
+                code: pc.to_string(),
+                accessible_scopes: Vec::new(),
+                flow_tracking_data: FlowTrackingData {
+                    ap_tracking: ApTracking::default(),
+                    reference_ids: HashMap::new(),
+                },
+            };
+            (*pc, vec![hint_params])
+        })
+        .collect();
+    let program = Program::new(
+        builtins,
+        data,
+        Some(0),
+        hints,
+        ReferenceManager { references: Vec::new() },
+        HashMap::new(),
+        Vec::new(),
+        None,
+    )?;
+
+    // Arguments and the destination of the output are known only once the client sent
+    // the launch configuration.
+    let output = program_output(&debugger)?;
+    let mut hint_processor = StandaloneHintProcessor::new(
+        &assembled_program.hints,
+        debugger.program_args().to_vec(),
+        output,
+        starknet,
+    );
+
+    let mut runner = CairoRunner::new(&program, LayoutName::all_cairo, None, false, false, false)?;
+    let end = runner.initialize(false)?;
+    run_until_pc(&mut runner, end, &mut hint_processor, debugger)
+}
+
+/// Runs the program until `end` with the debugger as the step hooks of the VM,
+/// and shuts the debugger down once the VM is done with it.
+fn run_until_pc(
+    runner: &mut CairoRunner,
+    end: Relocatable,
+    hint_processor: &mut dyn HintProcessor,
+    debugger: CairoDebugger,
+) -> Result<()> {
+    let debugger = Rc::new(RefCell::new(debugger));
+    runner.vm.hooks = Some(Box::new(SharedDebugger(Rc::clone(&debugger))));
+    let run = runner.run_until_pc(end, hint_processor);
+    runner.vm.hooks = None;
+    let debugger = Rc::into_inner(debugger).expect("VM kept the debugger").into_inner();
+    let shut_down = debugger.shut_down();
+
+    run?;
+    trace!("Program finished");
+    shut_down
+}
+
+/// Step hooks sharing the debugger with [`run_until_pc`], which takes it back from the VM.
+struct SharedDebugger(Rc<RefCell<CairoDebugger>>);
+
+impl StepHooks for SharedDebugger {
+    fn before_first_step(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hints_data: &[Box<dyn Any>],
+    ) -> Result<(), VirtualMachineError> {
+        self.0.borrow_mut().before_first_step(vm, hints_data)
+    }
+
+    fn pre_step_instruction(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_processor: &mut dyn HintProcessor,
+        exec_scopes: &mut ExecutionScopes,
+        hints_data: &[Box<dyn Any>],
+        constants: &HashMap<String, Felt>,
+    ) -> Result<(), VirtualMachineError> {
+        self.0.borrow_mut().pre_step_instruction(
+            vm,
+            hint_processor,
+            exec_scopes,
+            hints_data,
+            constants,
+        )
+    }
+
+    fn post_step_instruction(
+        &mut self,
+        vm: &mut VirtualMachine,
+        hint_processor: &mut dyn HintProcessor,
+        exec_scopes: &mut ExecutionScopes,
+        hints_data: &[Box<dyn Any>],
+        constants: &HashMap<String, Felt>,
+    ) -> Result<(), VirtualMachineError> {
+        self.0.borrow_mut().post_step_instruction(
+            vm,
+            hint_processor,
+            exec_scopes,
+            hints_data,
+            constants,
+        )
+    }
+}
+
+/// Executables are run from the wrapper of their function, like `scarb execute` does.
+fn entry_function(kind: ArtifactKind, function: &str) -> String {
+    if kind == ArtifactKind::Executable {
+        artifact::executable_wrapper_name(function)
+    } else {
+        function.to_string()
+    }
+}
+
+/// Proof-mode runs start from the entry code with the builtins of the function, while runs
+/// exported from [`run_standalone`] start from the one of tests.
+fn post_mortem_entry_code(proof_mode: bool) -> EntryCodeConfig {
+    if proof_mode { EntryCodeConfig::provable() } else { EntryCodeConfig::testing() }
+}
+
+fn compile(sierra_program: SierraProgram) -> Result<RunnableBuilder> {
+    RunnableBuilder::new(sierra_program, Some(MetadataComputationConfig::default()))
+        .map_err(|err| anyhow!("Failed to compile the program: {err}"))
+}
+
+fn casm_debug_info(builder: &RunnableBuilder) -> CasmDebugInfo {
+    CasmDebugInfo {
+        statement_to_pc: builder
+            .casm_program()
+            .debug_info
+            .sierra_statement_info
+            .iter()
+            .map(|statement_info| statement_info.start_offset)
+            .collect(),
+    }
+}
+
+/// The compiled functions are preceded by the entry code calling `func`.
+fn header_size(
+    builder: &RunnableBuilder,
+    func: &Function,
+    config: EntryCodeConfig,
+) -> Result<usize> {
+    Ok(builder
+        .create_wrapper_info(func, config)
+        .map_err(|err| anyhow!("{err}"))?
+        .header
+        .iter()
+        .map(|instruction| instruction.body.op_size())
+        .sum())
+}
+
+/// Compiles the Sierra program and connects the debugger to it. If the execution started from
+/// the entry code calling `function`, or the test chosen in the launch configuration,
+/// the program is offset by its size.
+fn connect_to_compiled(
+    sierra_path: &Path,
+    sierra_program: SierraProgram,
+    function: &str,
+    entry_code_config: Option<EntryCodeConfig>,
+    server: &DebugServer,
+) -> Result<CairoDebugger> {
+    let builder = compile(sierra_program)?;
+    let mut debugger = CairoDebugger::connect_and_initialize_with_server(
+        sierra_path,
+        casm_debug_info(&builder),
+        server,
+    )?;
+    if let Some(entry_code_config) = entry_code_config {
+        let function = debugger.selected_test().unwrap_or(function).to_string();
+        let func = builder.find_function(&function).map_err(|err| anyhow!("{err}"))?;
+        debugger.offset_program(header_size(&builder, func, entry_code_config)?);
+    }
+    Ok(debugger)
+}
@@ -0,0 +1,195 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use cairo_lang_casm::hints::{CoreHint, CoreHintBase, ExternalHint, Hint, StarknetHint};
+use cairo_lang_casm::operand::{CellRef, Register, ResOperand};
+use cairo_lang_runner::casm_run::format_for_debug;
+use cairo_vm::hint_processor::cairo_1_hint_processor::hint_processor::Cairo1HintProcessor;
+use cairo_vm::hint_processor::hint_processor_definition::{HintProcessorLogic, HintReference};
+use cairo_vm::serde::deserialize_program::ApTracking;
+use cairo_vm::types::exec_scope::ExecutionScopes;
+use cairo_vm::types::relocatable::Relocatable;
+use cairo_vm::vm::errors::hint_errors::HintError;
+use cairo_vm::vm::errors::vm_errors::VirtualMachineError;
+use cairo_vm::vm::runners::cairo_runner::{ResourceTracker, RunResources};
+use cairo_vm::vm::vm_core::VirtualMachine;
+use starknet_types_core::felt::Felt;
+
+use crate::debugger::launch_args::ProgramArg;
+use crate::debugger::memory;
+use crate::standalone::starknet::StarknetEnvironment;
+
+/// Processor of Cairo 1 hints, which also provides arguments of the run function
+/// and redirects the output of the program.
+///
+/// The entry code calling the function fetches its arguments with [`ExternalHint::WriteRunParam`],
+/// which is not supported by [`Cairo1HintProcessor`].
+/// [`CoreHint::DebugPrint`] is handled here, since [`Cairo1HintProcessor`] prints to stdout.
+/// Syscalls of a contract call are executed in its [`StarknetEnvironment`].
+pub struct StandaloneHintProcessor {
+    inner: Cairo1HintProcessor,
+    /// One argument for each parameter of the run function, apart from the implicit ones.
+    args: Vec<ProgramArg>,
+    output: Box<dyn Write + Send>,
+    /// Environment of the contract call, if a contract entrypoint is run.
+    starknet: Option<StarknetEnvironment>,
+}
+
+impl StandaloneHintProcessor {
+    pub fn new(
+        hints: &[(usize, Vec<Hint>)],
+        args: Vec<ProgramArg>,
+        output: Box<dyn Write + Send>,
+        starknet: Option<StarknetEnvironment>,
+    ) -> Self {
+        Self {
+            inner: Cairo1HintProcessor::new(hints, RunResources::default(), false),
+            args,
+            output,
+            starknet,
+        }
+    }
+
+    fn debug_print(
+        &mut self,
+        vm: &VirtualMachine,
+        start: &ResOperand,
+        end: &ResOperand,
+    ) -> Result<(), HintError> {
+        let start = pointer_operand(vm, start)?;
+        let end = pointer_operand(vm, end)?;
+        let felts: Vec<Felt> = vm
+            .get_integer_range(start, (end - start)?)?
+            .into_iter()
+            .map(|felt| felt.into_owned())
+            .collect();
+
+        let to_hint_error =
+            |err: io::Error| HintError::CustomHint(err.to_string().into_boxed_str());
+        write!(self.output, "{}", format_for_debug(felts.into_iter())).map_err(to_hint_error)?;
+        self.output.flush().map_err(to_hint_error)
+    }
+
+    fn write_run_param(
+        &self,
+        vm: &mut VirtualMachine,
+        index: &ResOperand,
+        dst: &CellRef,
+    ) -> Result<(), HintError> {
+        let index = match index {
+            ResOperand::Immediate(value) => usize::try_from(&value.value).ok(),
+            ResOperand::Deref(cell) => {
+                vm.get_integer(cell_address(vm, cell)?)?.to_biguint().try_into().ok()
+            }
+            _ => None,
+        }
+        .ok_or_else(|| HintError::CustomHint("Invalid index of a run param".into()))?;
+        let arg = self.args.get(index).ok_or_else(|| {
+            HintError::CustomHint(
+                format!("Missing argument {index}, only {} were provided", self.args.len())
+                    .into_boxed_str(),
+            )
+        })?;
+
+        write_args(vm, cell_address(vm, dst)?, std::slice::from_ref(arg))?;
+        Ok(())
+    }
+}
+
+impl HintProcessorLogic for StandaloneHintProcessor {
+    fn compile_hint(
+        &self,
+        hint_code: &str,
+        ap_tracking_data: &ApTracking,
+        reference_ids: &HashMap<String, usize>,
+        references: &[HintReference],
+    ) -> Result<Box<dyn Any>, VirtualMachineError> {
+        self.inner.compile_hint(hint_code, ap_tracking_data, reference_ids, references)
+    }
+
+    fn execute_hint(
+        &mut self,
+        vm: &mut VirtualMachine,
+        exec_scopes: &mut ExecutionScopes,
+        hint_data: &Box<dyn Any>,
+        _constants: &HashMap<String, Felt>,
+    ) -> Result<(), HintError> {
+        let hints = hint_data.downcast_ref::<Vec<Hint>>().ok_or(HintError::WrongHintData)?;
+        for hint in hints {
+            match hint {
+                Hint::External(ExternalHint::WriteRunParam { index, dst }) => {
+                    self.write_run_param(vm, index, dst)?;
+                }
+                Hint::Core(CoreHintBase::Core(CoreHint::DebugPrint { start, end })) => {
+                    self.debug_print(vm, start, end)?;
+                }
+                Hint::Starknet(StarknetHint::SystemCall { system }) if self.starknet.is_some() => {
+                    let buffer = pointer_operand(vm, system)?;
+                    if let Some(starknet) = &mut self.starknet {
+                        starknet.execute(vm, buffer)?;
+                    }
+                }
+                hint => self.inner.execute(vm, exec_scopes, hint)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ResourceTracker for StandaloneHintProcessor {
+    fn consumed(&self) -> bool {
+        self.inner.consumed()
+    }
+
+    fn consume_step(&mut self) {
+        self.inner.consume_step()
+    }
+
+    fn get_n_steps(&self) -> Option<usize> {
+        self.inner.get_n_steps()
+    }
+
+    fn run_resources(&self) -> &RunResources {
+        self.inner.run_resources()
+    }
+}
+
+fn cell_address(vm: &VirtualMachine, cell: &CellRef) -> Result<Relocatable, HintError> {
+    let base = match cell.register {
+        Register::AP => vm.get_ap(),
+        Register::FP => vm.get_fp(),
+    };
+    Ok((base + i32::from(cell.offset))?)
+}
+
+fn pointer_operand(vm: &VirtualMachine, operand: &ResOperand) -> Result<Relocatable, HintError> {
+    memory::pointer_operand(vm, operand)
+        .map(Relocatable::from)
+        .ok_or_else(|| HintError::CustomHint("Expected a pointer operand".into()))
+}
+
+/// Writes the arguments starting at `address`. Arrays are written to new segments,
+/// and represented by their start and end pointers. Returns the address after the arguments.
+fn write_args(
+    vm: &mut VirtualMachine,
+    mut address: Relocatable,
+    args: &[ProgramArg],
+) -> Result<Relocatable, HintError> {
+    for arg in args {
+        match arg {
+            ProgramArg::Value(value) => {
+                vm.insert_value(address, *value)?;
+                address = (address + 1)?;
+            }
+            ProgramArg::Array(items) => {
+                let start = vm.add_memory_segment();
+                let end = write_args(vm, start, items)?;
+                vm.insert_value(address, start)?;
+                vm.insert_value((address + 1)?, end)?;
+                address = (address + 2)?;
+            }
+        }
+    }
+    Ok(address)
+}
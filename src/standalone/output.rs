@@ -0,0 +1,72 @@
+use std::io::{self, ErrorKind, Write};
+use std::time::{Duration, Instant};
+use std::{env, process, thread};
+
+use anyhow::{Context, Result, bail};
+use interprocess::local_socket::prelude::*;
+use interprocess::local_socket::{
+    GenericNamespaced, ListenerNonblockingMode, ListenerOptions, Stream,
+};
+use tracing::{trace, warn};
+
+use crate::debugger::CairoDebugger;
+
+/// How long to wait for the terminal of the client to start the forwarder.
+const TERMINAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens the destination of the output of the program, chosen in the launch configuration.
+///
+/// Output shown in a terminal of the client goes through a local socket, to a forwarder
+/// run there with the `runInTerminal` reverse request, see [`forward_program_output`].
+/// This way it is kept separate from both the DAP channel and the output of the debugger.
+pub fn program_output(debugger: &CairoDebugger) -> Result<Box<dyn Write + Send>> {
+    if !debugger.uses_terminal() {
+        return Ok(Box::new(io::stdout()));
+    }
+
+    let pipe_name = format!("cairo-debugger-output-{}.sock", process::id());
+    let listener = ListenerOptions::new()
+        .name(pipe_name.as_str().to_ns_name::<GenericNamespaced>()?)
+        .nonblocking(ListenerNonblockingMode::Accept)
+        .create_sync()
+        .with_context(|| format!("Failed to listen on pipe {pipe_name}"))?;
+
+    let executable = env::current_exe().context("Failed to get the path of the debugger")?;
+    debugger.run_in_terminal(vec![
+        executable.display().to_string(),
+        "output".to_string(),
+        "--pipe".to_string(),
+        pipe_name,
+    ])?;
+
+    // The client does not report whether the command was started, and its response
+    // is not read anyway, so the debugger falls back to its own output after a while.
+    let deadline = Instant::now() + TERMINAL_TIMEOUT;
+    loop {
+        match listener.accept() {
+            Ok(stream) => {
+                trace!("Terminal of the client connected");
+                stream.set_nonblocking(false)?;
+                return Ok(Box::new(stream));
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock && Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                warn!("Terminal of the client did not connect, printing the output here instead");
+                return Ok(Box::new(io::stdout()));
+            }
+            Err(err) => bail!("Failed to connect to the terminal of the client: {err}"),
+        }
+    }
+}
+
+/// Prints the output of the program received from the debugger, until the program finishes.
+/// Run in a terminal of the client, see [`program_output`].
+pub fn forward_program_output(pipe_name: &str) -> Result<()> {
+    let name = pipe_name.to_ns_name::<GenericNamespaced>()?;
+    let mut stream = Stream::connect(name)
+        .with_context(|| format!("Failed to connect to the debugger on pipe {pipe_name}"))?;
+    io::copy(&mut stream, &mut io::stdout().lock())?;
+    Ok(())
+}
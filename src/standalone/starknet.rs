@@ -0,0 +1,160 @@
+//! Minimal Starknet environment of a single contract call, run in the standalone mode.
+//!
+//! The called contract sees empty storage, a block with all values set to zero,
+//! and a transaction sent by the caller. Syscalls reaching other contracts fail.
+
+use std::collections::HashMap;
+
+use cairo_vm::types::relocatable::{MaybeRelocatable, Relocatable};
+use cairo_vm::vm::errors::hint_errors::HintError;
+use cairo_vm::vm::vm_core::VirtualMachine;
+use starknet_types_core::felt::Felt;
+
+/// Offset of the request in the buffer of a syscall, after the selector and the gas.
+const REQUEST_OFFSET: usize = 2;
+
+/// Chain id seen by the contract.
+const CHAIN_ID: &str = "SN_SEPOLIA";
+
+/// Version of the transaction seen by the contract.
+const TRANSACTION_VERSION: u8 = 3;
+
+pub struct StarknetEnvironment {
+    contract_address: Felt,
+    caller_address: Felt,
+    selector: Felt,
+    /// Storage of the called contract, by addresses.
+    storage: HashMap<Felt, Felt>,
+}
+
+impl StarknetEnvironment {
+    pub fn new(contract_address: Felt, caller_address: Felt, selector: Felt) -> Self {
+        Self { contract_address, caller_address, selector, storage: HashMap::new() }
+    }
+
+    /// Executes the syscall whose buffer starts at `buffer`. The buffer consists of
+    /// the selector and the gas, followed by the request, after which the response is written.
+    pub fn execute(
+        &mut self,
+        vm: &mut VirtualMachine,
+        buffer: Relocatable,
+    ) -> Result<(), HintError> {
+        let selector = read_felt(vm, buffer)?;
+        let gas = read_felt(vm, (buffer + 1)?)?;
+        let request = (buffer + REQUEST_OFFSET)?;
+        let name =
+            String::from_utf8_lossy(&selector.to_bytes_be()).trim_start_matches('\0').to_string();
+
+        let (request_size, response) = match name.as_str() {
+            "StorageRead" => {
+                let key = read_felt(vm, (request + 1)?)?;
+                let value = self.storage.get(&key).copied().unwrap_or_default();
+                (2, Ok(vec![value.into()]))
+            }
+            "StorageWrite" => {
+                let key = read_felt(vm, (request + 1)?)?;
+                let value = read_felt(vm, (request + 2)?)?;
+                self.storage.insert(key, value);
+                (3, Ok(Vec::new()))
+            }
+            // Events and messages are reported by the debugger itself.
+            "EmitEvent" => (4, Ok(Vec::new())),
+            "SendMessageToL1" => (3, Ok(Vec::new())),
+            "GetBlockHash" => (1, Ok(vec![Felt::ZERO.into()])),
+            "GetExecutionInfo" => (0, Ok(vec![self.write_execution_info(vm)?.into()])),
+            "CallContract" | "LibraryCall" => (4, Err("Calls are not supported")),
+            "Deploy" => (5, Err("Deploy is not supported")),
+            "GetClassHashAt" | "ReplaceClass" => (1, Err("Classes are not supported")),
+            "Keccak" | "Sha256ProcessBlock" => (2, Err("Hashing syscalls are not supported")),
+            "MetaTxV0" => (6, Err("Calls are not supported")),
+            _ => {
+                return Err(HintError::CustomHint(
+                    format!("Unknown syscall: {name}").into_boxed_str(),
+                ));
+            }
+        };
+
+        let mut address = (request + request_size)?;
+        vm.insert_value(address, gas)?;
+        address = (address + 1)?;
+        match response {
+            Ok(values) => {
+                vm.insert_value(address, Felt::ZERO)?;
+                for value in values {
+                    address = (address + 1)?;
+                    vm.insert_value(address, value)?;
+                }
+            }
+            // Failed syscalls respond with the revert reason, an array of short strings.
+            Err(reason) => {
+                vm.insert_value(address, Felt::ONE)?;
+                let reason_start = vm.add_memory_segment();
+                vm.insert_value(reason_start, Felt::from_bytes_be_slice(reason.as_bytes()))?;
+                vm.insert_value((address + 1)?, reason_start)?;
+                vm.insert_value((address + 2)?, (reason_start + 1)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the `ExecutionInfo` struct of the call, returning its address.
+    fn write_execution_info(&self, vm: &mut VirtualMachine) -> Result<Relocatable, HintError> {
+        let empty_span = vm.add_memory_segment();
+        let block_info = write_struct(vm, vec![Felt::ZERO.into(); 3])?;
+        let tx_info = write_struct(
+            vm,
+            vec![
+                Felt::from(TRANSACTION_VERSION).into(),
+                self.caller_address.into(),
+                // Max fee.
+                Felt::ZERO.into(),
+                // Signature.
+                empty_span.into(),
+                empty_span.into(),
+                // Transaction hash.
+                Felt::ZERO.into(),
+                Felt::from_bytes_be_slice(CHAIN_ID.as_bytes()).into(),
+                // Nonce.
+                Felt::ZERO.into(),
+                // Resource bounds.
+                empty_span.into(),
+                empty_span.into(),
+                // Tip.
+                Felt::ZERO.into(),
+                // Paymaster data.
+                empty_span.into(),
+                empty_span.into(),
+                // Data availability modes of the nonce and the fee.
+                Felt::ZERO.into(),
+                Felt::ZERO.into(),
+                // Account deployment data.
+                empty_span.into(),
+                empty_span.into(),
+            ],
+        )?;
+        write_struct(
+            vm,
+            vec![
+                block_info.into(),
+                tx_info.into(),
+                self.caller_address.into(),
+                self.contract_address.into(),
+                self.selector.into(),
+            ],
+        )
+    }
+}
+
+/// Writes the members of a struct to a new segment, returning its start.
+fn write_struct(
+    vm: &mut VirtualMachine,
+    members: Vec<MaybeRelocatable>,
+) -> Result<Relocatable, HintError> {
+    let start = vm.add_memory_segment();
+    vm.load_data(start, &members)?;
+    Ok(start)
+}
+
+fn read_felt(vm: &VirtualMachine, address: Relocatable) -> Result<Felt, HintError> {
+    Ok(*vm.get_integer(address)?)
+}
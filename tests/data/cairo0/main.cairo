@@ -0,0 +1,5 @@
+func main() {
+    [ap] = 5, ap++;
+    [ap] = [ap - 1] + 2, ap++;
+    ret;
+}
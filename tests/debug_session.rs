@@ -0,0 +1,239 @@
+//! Whole debug sessions driven through an [`InMemoryListener`], from the first request
+//! of the client to the end of the execution, checking the responses and events it gets.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread::{self, JoinHandle};
+
+use anyhow::{Result, bail};
+use cairo_debugger::{
+    DebugServer, InMemoryClient, InMemoryListener, replay_protocol_trace, run_standalone,
+};
+use serde_json::{Value, json};
+
+/// Line of the second instruction of `tests/data/cairo0/main.cairo`.
+const BREAKPOINT_LINE: i64 = 3;
+
+fn cairo0_program() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/cairo0/main.json")
+}
+
+fn cairo0_source() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/cairo0/main.cairo")
+}
+
+/// Capture of a session stopping at the breakpoint on [`BREAKPOINT_LINE`].
+fn cairo0_session_capture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/cairo0/session.jsonl")
+}
+
+/// Runs `main` of the program under the debugger, which waits for the returned client.
+fn start_debuggee(program: PathBuf) -> (InMemoryClient, JoinHandle<Result<()>>) {
+    let listener = InMemoryListener::new();
+    let client = listener.connect();
+    let server = DebugServer::with_listener(listener);
+    let debuggee = thread::spawn(move || run_standalone(&program, "main", &server));
+    (client, debuggee)
+}
+
+/// Name of a message from the debugger, e.g. `response:initialize` or `event:stopped`.
+fn message_name(message: &Value) -> String {
+    let name = match message["type"].as_str() {
+        Some("response") => &message["command"],
+        Some("event") => &message["event"],
+        _ => &message["command"],
+    };
+    format!(
+        "{}:{}",
+        message["type"].as_str().unwrap_or_default(),
+        name.as_str().unwrap_or_default()
+    )
+}
+
+/// Receives the messages up to the one named `last`, which must be among them.
+fn receive_until(client: &mut InMemoryClient, last: &str) -> Result<Vec<Value>> {
+    let mut messages = Vec::new();
+    loop {
+        let message = client.next_message()?;
+        let done = message_name(&message) == last;
+        if message["type"] == "response" && message["success"] != true {
+            bail!("{} failed: {}", message_name(&message), message["message"]);
+        }
+        messages.push(message);
+        if done {
+            return Ok(messages);
+        }
+    }
+}
+
+fn find<'a>(messages: &'a [Value], name: &str) -> &'a Value {
+    messages
+        .iter()
+        .find(|message| message_name(message) == name)
+        .unwrap_or_else(|| panic!("{name} was not received"))
+}
+
+/// Messages sent by the debugger in a protocol capture.
+fn captured_debugger_messages(capture: &Path) -> Result<Vec<Value>> {
+    fs::read_to_string(capture)?
+        .lines()
+        .map(|line| Ok(serde_json::from_str::<Value>(line)?))
+        .filter(|entry| !matches!(entry, Ok(entry) if entry["from"] != "debugger"))
+        .map(|entry| entry.map(|mut entry| entry["message"].take()))
+        .collect()
+}
+
+/// Names of the messages, leaving out the ones not relevant to the flow, e.g. outputs.
+fn flow(messages: &[Value]) -> Vec<String> {
+    messages
+        .iter()
+        .map(message_name)
+        .filter(|name| {
+            !matches!(
+                name.as_str(),
+                "event:output" | "event:module" | "event:capabilities" | "event:loadedSource"
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn breakpoint_stops_the_execution_until_continued() -> Result<()> {
+    let (mut client, debuggee) = start_debuggee(cairo0_program());
+
+    client.send_request(
+        "initialize",
+        json!({ "adapterID": "cairo", "linesStartAt1": true, "pathFormat": "path" }),
+    )?;
+    let messages = receive_until(&mut client, "response:initialize")?;
+    assert_eq!(flow(&messages), ["event:initialized", "response:initialize"]);
+    assert_eq!(
+        find(&messages, "response:initialize")["body"]["supportsConfigurationDoneRequest"],
+        true
+    );
+
+    client.send_request("launch", json!({ "program": cairo0_program() }))?;
+    receive_until(&mut client, "response:launch")?;
+
+    client.send_request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": cairo0_source() },
+            "breakpoints": [{ "line": BREAKPOINT_LINE }],
+        }),
+    )?;
+    let messages = receive_until(&mut client, "response:setBreakpoints")?;
+    let breakpoints = &find(&messages, "response:setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[0]["line"], BREAKPOINT_LINE);
+
+    client.send_request("configurationDone", Value::Null)?;
+    let messages = receive_until(&mut client, "event:stopped")?;
+    assert_eq!(flow(&messages), ["response:configurationDone", "event:stopped"]);
+    let stopped = &find(&messages, "event:stopped")["body"];
+    assert_eq!(stopped["reason"], "breakpoint");
+    let thread_id = stopped["threadId"].clone();
+
+    let seq = client.send_request("stackTrace", json!({ "threadId": thread_id }))?;
+    let stack_trace = client.response_to(seq)?;
+    assert_eq!(stack_trace["stackFrames"][0]["line"], BREAKPOINT_LINE);
+
+    client.send_request("continue", json!({ "threadId": thread_id }))?;
+    let messages = receive_until(&mut client, "event:exited")?;
+    assert_eq!(flow(&messages), ["response:continue", "event:terminated", "event:exited"]);
+    assert_eq!(find(&messages, "event:exited")["body"]["exitCode"], 0);
+
+    client.send_request("disconnect", json!({}))?;
+    receive_until(&mut client, "response:disconnect")?;
+    debuggee.join().expect("Debuggee panicked")
+}
+
+#[test]
+fn execution_without_breakpoints_runs_to_the_end() -> Result<()> {
+    let (mut client, debuggee) = start_debuggee(cairo0_program());
+
+    client.send_request("initialize", json!({ "adapterID": "cairo" }))?;
+    receive_until(&mut client, "response:initialize")?;
+    client.send_request("launch", json!({ "program": cairo0_program() }))?;
+    receive_until(&mut client, "response:launch")?;
+
+    client.send_request("configurationDone", Value::Null)?;
+    let messages = receive_until(&mut client, "event:exited")?;
+    assert_eq!(flow(&messages), ["response:configurationDone", "event:terminated", "event:exited"]);
+
+    client.send_request("disconnect", json!({}))?;
+    receive_until(&mut client, "response:disconnect")?;
+    debuggee.join().expect("Debuggee panicked")
+}
+
+#[test]
+fn replayed_capture_gets_the_captured_responses_and_events() -> Result<()> {
+    let capture = cairo0_session_capture();
+    let replay =
+        std::env::temp_dir().join(format!("cairo-debugger-replay-{}.jsonl", process::id()));
+    let server = DebugServer::with_listener(replay_protocol_trace(&capture)?)
+        .with_protocol_trace(&replay)?;
+
+    // The captured client disconnects while stopped, which terminates the debuggee.
+    let result = run_standalone(&cairo0_program(), "main", &server);
+    let replayed = captured_debugger_messages(&replay);
+    fs::remove_file(&replay)?;
+    let err = result.expect_err("Debuggee was not terminated by the disconnect request");
+    assert!(format!("{err:#}").contains("Disconnect request received"), "{err:#}");
+
+    let replayed = replayed?;
+    let captured = captured_debugger_messages(&capture)?;
+    assert_eq!(flow(&replayed), flow(&captured));
+    for response in replayed.iter().filter(|message| message["type"] == "response") {
+        assert_eq!(response["success"], true, "{} failed", message_name(response));
+    }
+    assert_eq!(find(&replayed, "event:stopped")["body"]["reason"], "breakpoint");
+    Ok(())
+}
+
+/// Breaks on [`BREAKPOINT_LINE`] and steps over it to the next line.
+fn break_and_step(launch_arguments: Value) -> Result<()> {
+    let (mut client, debuggee) = start_debuggee(cairo0_program());
+
+    client.send_request("initialize", json!({ "adapterID": "cairo" }))?;
+    receive_until(&mut client, "response:initialize")?;
+    client.send_request("launch", launch_arguments)?;
+    receive_until(&mut client, "response:launch")?;
+
+    client.send_request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": cairo0_source() },
+            "breakpoints": [{ "line": BREAKPOINT_LINE }],
+        }),
+    )?;
+    let messages = receive_until(&mut client, "response:setBreakpoints")?;
+    let breakpoints = &find(&messages, "response:setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(breakpoints[0]["verified"], true);
+
+    client.send_request("configurationDone", Value::Null)?;
+    let messages = receive_until(&mut client, "event:stopped")?;
+    let thread_id = find(&messages, "event:stopped")["body"]["threadId"].clone();
+
+    client.send_request("next", json!({ "threadId": thread_id }))?;
+    let messages = receive_until(&mut client, "event:stopped")?;
+    assert_eq!(flow(&messages), ["response:next", "event:stopped"]);
+    assert_eq!(find(&messages, "event:stopped")["body"]["reason"], "step");
+    let seq = client.send_request("stackTrace", json!({ "threadId": thread_id }))?;
+    let stack_trace = client.response_to(seq)?;
+    assert_eq!(stack_trace["stackFrames"][0]["line"], BREAKPOINT_LINE + 1);
+
+    client.send_request("continue", json!({ "threadId": thread_id }))?;
+    receive_until(&mut client, "event:exited")?;
+    client.send_request("disconnect", json!({}))?;
+    receive_until(&mut client, "response:disconnect")?;
+    debuggee.join().expect("Debuggee panicked")
+}
+
+#[test]
+fn breakpoints_and_stepping_work_outside_of_a_scarb_project() -> Result<()> {
+    break_and_step(json!({ "program": cairo0_program() }))?;
+    // Without the roots of the user's code, all code is the user's code.
+    break_and_step(json!({ "program": cairo0_program(), "justMyCode": true }))
+}